@@ -1,88 +1,330 @@
-use anyhow::Result;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
 use tokio::sync::Barrier;
-use porcupine::{*, history::{Operation, Invocation, Response}};
-use serin_multidc::{MemoryStore, ReplicationClient, ReplicationServer, DcId};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use serin_multidc::{MemoryStore, ReplicationClient, ReplicationServer};
+
+pub mod linearizability;
+
+use linearizability::{check_history, Op, Operation, Response};
+
+/// How long a writer waits for its entry to show up on the replica.
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Shape of a consistency run.
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    /// Writer tasks; writer `i` replicates from DC `i % 2` into the other DC.
+    pub writers: usize,
+    /// Reader tasks; reader `i` reads DC `i % 2`.
+    pub readers: usize,
+    /// Writes issued by each writer.
+    pub writes_per_writer: u64,
+    /// Reads issued by each reader.
+    pub reads_per_reader: u64,
+    /// Pause between two reads of the same reader.
+    pub read_interval: Duration,
+    /// Offsets from the start at which both replication links are dropped.
+    pub partitions: Vec<Duration>,
+    /// Workers stop issuing operations after this long.
+    pub duration: Duration,
+    /// Seed for every random choice (values and pauses); rerunning with the
+    /// same seed replays the same schedule.
+    pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            writers: 2,
+            readers: 2,
+            writes_per_writer: 100,
+            reads_per_reader: 100,
+            read_interval: Duration::from_millis(5),
+            partitions: (0..10).map(|i| Duration::from_millis(20 * i)).collect(),
+            duration: Duration::from_secs(3),
+            seed: rand::random(),
+        }
+    }
+}
+
+/// One write of a writer's schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    /// Payload replicated with the write.
+    pub value: Vec<u8>,
+    /// Pause after the write completes.
+    pub pause: Duration,
+}
+
+/// RNG of worker `worker`, derived from the run seed.
+fn worker_rng(seed: u64, worker: usize) -> StdRng {
+    StdRng::seed_from_u64(seed ^ (worker as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+/// The writes writer `writer` issues in a run seeded with `seed`.
+pub fn plan_writes(seed: u64, writer: usize, count: u64) -> Vec<PlannedWrite> {
+    let mut rng = worker_rng(seed, writer);
+    (0..count)
+        .map(|_| PlannedWrite { value: rng.gen::<[u8; 8]>().to_vec(), pause: Duration::from_millis(rng.gen_range(0..3)) })
+        .collect()
+}
+
+/// Latency summary over completed operations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+    /// Operations measured.
+    pub count: usize,
+    /// Mean latency.
+    pub mean: Duration,
+    /// Median latency.
+    pub p50: Duration,
+    /// 99th percentile latency.
+    pub p99: Duration,
+    /// Slowest operation.
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    fn from_nanos(mut samples: Vec<u64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let pick = |q: f64| Duration::from_nanos(samples[((samples.len() - 1) as f64 * q).round() as usize]);
+        let total: u128 = samples.iter().map(|&s| s as u128).sum();
+        Self {
+            count: samples.len(),
+            mean: Duration::from_nanos((total / samples.len() as u128) as u64),
+            p50: pick(0.5),
+            p99: pick(0.99),
+            max: Duration::from_nanos(*samples.last().unwrap()),
+        }
+    }
+}
+
+/// Outcome of [`run_consistency_test`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsistencyReport {
+    /// Whether the recorded history is linearizable.
+    pub linearizable: bool,
+    /// Writes acknowledged by the replica.
+    pub writes_ok: usize,
+    /// Writes that failed or timed out; they may or may not have applied.
+    pub writes_failed: usize,
+    /// Reads performed.
+    pub reads: usize,
+    /// Latency of acknowledged writes.
+    pub write_latency: LatencyStats,
+    /// Latency of reads.
+    pub read_latency: LatencyStats,
+    /// Wall-clock time of the run.
+    pub elapsed: Duration,
+    /// Seed the run used.
+    pub seed: u64,
+}
+
+/// Replication server tasks of a run, aborted when the run ends.
+struct Servers(Vec<JoinHandle<anyhow::Result<()>>>);
+
+impl Drop for Servers {
+    fn drop(&mut self) {
+        for server in &self.0 {
+            server.abort();
+        }
+    }
+}
 
 /// Generate Jepsen-like test on in-memory replicated stores.
 /// Runs concurrent read/write and verifies linearizability.
-pub async fn run_consistency_test() -> Result<()> {
-    let store_a = Arc::new(MemoryStore::new());
-    let store_b = Arc::new(MemoryStore::new());
+///
+/// Each store is a register holding its highest applied LSN. A write counts
+/// as acknowledged once the replica has applied it; readers record the LSN
+/// they observed, so a stale read makes the history non-linearizable.
+/// Fails if the replication servers cannot bind their loopback ports.
+pub async fn run_consistency_test(cfg: WorkloadConfig) -> anyhow::Result<ConsistencyReport> {
+    println!("consistency run seed: {}", cfg.seed);
+    let stores = [Arc::new(MemoryStore::new()), Arc::new(MemoryStore::new())];
 
-    // Start servers (loopback ports).
-    tokio::spawn(ReplicationServer::new("127.0.0.1:7001", 1, store_a.clone()).run());
-    tokio::spawn(ReplicationServer::new("127.0.0.1:7002", 2, store_b.clone()).run());
+    // Start servers on ephemeral loopback ports.
+    let mut addrs = Vec::new();
+    let mut servers = Servers(Vec::new());
+    for (dc, store) in stores.iter().enumerate() {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        let server = ReplicationServer::new(addr.clone(), dc as u8 + 1, store.clone());
+        servers.0.push(tokio::spawn(server.serve(listener)));
+        addrs.push(addr);
+    }
 
-    // Clients.
-    let client_a = Arc::new(ReplicationClient::new("127.0.0.1:7002", 1));
-    let client_b = Arc::new(ReplicationClient::new("127.0.0.1:7001", 2));
+    // Clients; client `dc` pushes from that DC to the other one.
+    let clients = [
+        Arc::new(ReplicationClient::new(addrs[1].clone(), 1)),
+        Arc::new(ReplicationClient::new(addrs[0].clone(), 2)),
+    ];
 
     // History collector.
     let history = Arc::new(tokio::sync::Mutex::new(Vec::new()));
-    let barrier = Arc::new(Barrier::new(6)); // 2 writers + 2 readers + main + partition task
+    let barrier = Arc::new(Barrier::new(cfg.writers + cfg.readers + 2)); // workers + main + partition task
+    let origin = Instant::now();
+    let now = move || origin.elapsed().as_nanos() as u64;
+    let running = move || origin.elapsed() < cfg.duration;
+    let mut tasks = Vec::new();
 
     // Writers.
-    for i in 0..2 {
+    for i in 0..cfg.writers {
         let hist = history.clone();
-        let client = if i == 0 { client_a.clone() } else { client_b.clone() };
+        let client = clients[i % 2].clone();
+        let key = 1 - i % 2;
+        let replica = stores[key].clone();
         let b = barrier.clone();
-        tokio::spawn(async move {
+        let plan = plan_writes(cfg.seed, i, cfg.writes_per_writer);
+        tasks.push(tokio::spawn(async move {
             b.wait().await;
-            let mut rng = rand::thread_rng();
-            for seq in 0..100u64 {
-                let value: Vec<u8> = vec![i as u8, (seq & 0xFF) as u8];
+            for (seq, PlannedWrite { value, pause }) in (0u64..).zip(plan) {
+                if !running() {
+                    break;
+                }
                 let lsn = (i as u64) << 32 | seq;
-                let inv = Invocation { process: i, time: 0, operation: Operation::Write(lsn) };
-                hist.lock().await.push(Event::Invocation(inv.clone()));
-                let _ = client.send(lsn, &value).await;
-                let resp = Response::Ok;
-                hist.lock().await.push(Event::Response(inv, resp));
+                let call = now();
+                let acked = client.send(lsn, &value).await.is_ok() && wait_applied(&replica, lsn).await;
+                let output = if acked { Response::Ok } else { Response::Failed };
+                let op = Op { process: i, key, input: Operation::Write(lsn), output, call, ret: now() };
+                hist.lock().await.push(op);
+                tokio::time::sleep(pause).await;
             }
-        });
+        }));
     }
 
     // Readers.
-    for i in 0..2 {
+    for i in 0..cfg.readers {
         let hist = history.clone();
-        let store = if i == 0 { store_a.clone() } else { store_b.clone() };
+        let key = i % 2;
+        let store = stores[key].clone();
         let b = barrier.clone();
-        tokio::spawn(async move {
+        let (reads, interval) = (cfg.reads_per_reader, cfg.read_interval);
+        tasks.push(tokio::spawn(async move {
             b.wait().await;
-            for _ in 0..100u64 {
-                let inv = Invocation { process: i + 2, time: 0, operation: Operation::Read };
-                hist.lock().await.push(Event::Invocation(inv.clone()));
-                let entries = store.entries.lock().await; // access internal map
-                let _ = entries.len();
-                let resp = Response::Ok;
-                hist.lock().await.push(Event::Response(inv, resp));
-                tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+            for _ in 0..reads {
+                if !running() {
+                    break;
+                }
+                let call = now();
+                let observed = store.last_lsn().await;
+                let op = Op {
+                    process: cfg.writers + i,
+                    key,
+                    input: Operation::Read,
+                    output: Response::Read(observed),
+                    call,
+                    ret: now(),
+                };
+                hist.lock().await.push(op);
+                tokio::time::sleep(interval).await;
             }
-        });
+        }));
     }
 
-    // Partition task (toggle connectivity).
+    // Partition task (drop connectivity on schedule).
     let b = barrier.clone();
-    tokio::spawn(async move {
+    let partitions = cfg.partitions.clone();
+    tasks.push(tokio::spawn(async move {
         b.wait().await;
-        for _ in 0..10 {
+        let start = tokio::time::Instant::now();
+        for at in partitions {
+            tokio::time::sleep_until(start + at).await;
             // Simulate partition by dropping client streams.
-            *client_a.stream.lock().await = None;
-            *client_b.stream.lock().await = None;
-            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+            for client in &clients {
+                client.disconnect().await;
+            }
         }
-    });
+    }));
 
     barrier.wait().await;
-    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+    for task in tasks {
+        task.await.expect("workload task panicked");
+    }
 
-    // Linearizability check using Porcupine.
     let h = history.lock().await.clone();
-    let res = check_history(h, LinearizabilityChecker::new(|op| match op {
-        Operation::Read => ModelStep::Read(None),
-        Operation::Write(_) => ModelStep::Write,
-    }));
-    assert!(res.is_ok(), "History is not linearizable");
-    Ok(())
-} 
\ No newline at end of file
+    let mut report = ConsistencyReport {
+        linearizable: check_history(&h),
+        writes_ok: 0,
+        writes_failed: 0,
+        reads: 0,
+        write_latency: LatencyStats::default(),
+        read_latency: LatencyStats::default(),
+        elapsed: origin.elapsed(),
+        seed: cfg.seed,
+    };
+    let (mut write_ns, mut read_ns) = (Vec::new(), Vec::new());
+    for op in &h {
+        match (op.input, op.output) {
+            (Operation::Write(_), Response::Failed) => report.writes_failed += 1,
+            (Operation::Write(_), _) => {
+                report.writes_ok += 1;
+                write_ns.push(op.ret - op.call);
+            }
+            (Operation::Read, _) => {
+                report.reads += 1;
+                read_ns.push(op.ret - op.call);
+            }
+        }
+    }
+    report.write_latency = LatencyStats::from_nanos(write_ns);
+    report.read_latency = LatencyStats::from_nanos(read_ns);
+    Ok(report)
+}
+
+/// Wait until `store` has applied `lsn`; false on timeout.
+async fn wait_applied(store: &MemoryStore, lsn: u64) -> bool {
+    let deadline = tokio::time::Instant::now() + ACK_TIMEOUT;
+    while !store.contains(lsn).await {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn small_workload_reports_counts() {
+        let cfg = WorkloadConfig {
+            writers: 3,
+            readers: 2,
+            writes_per_writer: 20,
+            reads_per_reader: 10,
+            read_interval: Duration::from_millis(1),
+            partitions: vec![Duration::from_millis(10)],
+            duration: Duration::from_secs(10),
+            seed: 7,
+        };
+        let report = run_consistency_test(cfg).await.unwrap();
+        assert!(report.linearizable, "{report:?}");
+        assert_eq!(report.writes_ok + report.writes_failed, 60);
+        assert_eq!(report.reads, 20);
+        assert_eq!(report.write_latency.count, report.writes_ok);
+        assert_eq!(report.read_latency.count, 20);
+        assert!(report.read_latency.p50 <= report.read_latency.p99);
+        assert!(report.read_latency.p99 <= report.read_latency.max);
+        assert_eq!(report.seed, 7);
+    }
+
+    #[test]
+    fn same_seed_replays_write_schedule() {
+        let run = |seed| (0..3).map(|w| plan_writes(seed, w, 50)).collect::<Vec<_>>();
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+        // Workers of one run do not share a stream.
+        let plans = run(42);
+        assert_ne!(plans[0], plans[1]);
+    }
+}
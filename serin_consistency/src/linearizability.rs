@@ -0,0 +1,158 @@
+//! Linearizability checking for a set of independent LSN registers.
+//!
+//! Each key is a register holding the highest LSN written to it, matching the
+//! last-writer-wins-by-LSN rule of the replicated stores. Histories are
+//! split per key and every key is checked separately with the Wing & Gong
+//! search (as in Porcupine), memoizing `(linearized ops, register state)`.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serin_multidc::Lsn;
+
+/// What a client asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Read the register.
+    Read,
+    /// Write an LSN; the register keeps the maximum.
+    Write(Lsn),
+}
+
+/// What the client observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// Write acknowledged.
+    Ok,
+    /// Read returned this LSN (`None` before any write).
+    Read(Option<Lsn>),
+    /// No answer; the operation may or may not have taken effect.
+    Failed,
+}
+
+/// One completed (or failed) operation with its real-time interval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Op {
+    /// Client that issued the operation.
+    pub process: usize,
+    /// Register the operation targets.
+    pub key: usize,
+    /// Request.
+    pub input: Operation,
+    /// Observed result.
+    pub output: Response,
+    /// Invocation time.
+    pub call: u64,
+    /// Completion time; ignored for [`Response::Failed`].
+    pub ret: u64,
+}
+
+/// Whether `history` is linearizable: every read returns the highest LSN
+/// among the writes ordered before it, respecting real-time order.
+pub fn check_history(history: &[Op]) -> bool {
+    let mut by_key: BTreeMap<usize, Vec<&Op>> = BTreeMap::new();
+    for op in history {
+        // A failed read has no effect and observed nothing.
+        if op.input == Operation::Read && op.output == Response::Failed {
+            continue;
+        }
+        by_key.entry(op.key).or_default().push(op);
+    }
+    by_key.values().all(|ops| check_register(ops))
+}
+
+fn check_register(ops: &[&Op]) -> bool {
+    let mut search = Search {
+        ops,
+        // A failed write may take effect at any point after its call, or never.
+        ret: ops.iter().map(|op| if op.output == Response::Failed { u64::MAX } else { op.ret }).collect(),
+        done: vec![false; ops.len()],
+        remaining: ops.iter().filter(|op| op.output != Response::Failed).count(),
+        seen: HashSet::new(),
+    };
+    search.run(None)
+}
+
+struct Search<'a> {
+    ops: &'a [&'a Op],
+    ret: Vec<u64>,
+    done: Vec<bool>,
+    /// Operations that still have to be linearized.
+    remaining: usize,
+    /// Configurations already shown to be dead ends.
+    seen: HashSet<(Vec<bool>, Option<Lsn>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, state: Option<Lsn>) -> bool {
+        if self.remaining == 0 {
+            return true;
+        }
+        if !self.seen.insert((self.done.clone(), state)) {
+            return false;
+        }
+        // Only operations not preceded by a pending one may go next.
+        let horizon = (0..self.ops.len()).filter(|&i| !self.done[i]).map(|i| self.ret[i]).min().unwrap_or(u64::MAX);
+        for i in 0..self.ops.len() {
+            if self.done[i] || self.ops[i].call > horizon {
+                continue;
+            }
+            let next = match (self.ops[i].input, self.ops[i].output) {
+                (Operation::Write(lsn), _) => state.max(Some(lsn)),
+                (Operation::Read, Response::Read(observed)) if observed == state => state,
+                _ => continue,
+            };
+            let required = self.ops[i].output != Response::Failed;
+            self.done[i] = true;
+            self.remaining -= required as usize;
+            if self.run(next) {
+                return true;
+            }
+            self.done[i] = false;
+            self.remaining += required as usize;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(process: usize, input: Operation, output: Response, call: u64, ret: u64) -> Op {
+        Op { process, key: 0, input, output, call, ret }
+    }
+
+    #[test]
+    fn stale_read_is_not_linearizable() {
+        let write = op(0, Operation::Write(7), Response::Ok, 0, 10);
+        let fresh = op(1, Operation::Read, Response::Read(Some(7)), 20, 30);
+        assert!(check_history(&[write.clone(), fresh.clone()]));
+
+        // Reading the initial value after the write completed is stale.
+        let stale = op(1, Operation::Read, Response::Read(None), 20, 30);
+        assert!(!check_history(&[write.clone(), stale.clone()]));
+
+        // Overlapping the write, either value is fine.
+        let concurrent = op(1, Operation::Read, Response::Read(None), 5, 30);
+        assert!(check_history(&[write.clone(), concurrent, fresh.clone()]));
+
+        // Once a read saw the write, a later read may not go back.
+        let back = op(2, Operation::Read, Response::Read(None), 40, 50);
+        assert!(!check_history(&[write.clone(), fresh.clone(), back]));
+
+        // A lower LSN written later does not replace a higher one.
+        let lower = op(3, Operation::Write(3), Response::Ok, 12, 15);
+        assert!(check_history(&[write.clone(), lower.clone(), fresh]));
+        let lowered = op(1, Operation::Read, Response::Read(Some(3)), 20, 30);
+        assert!(!check_history(&[write, lower, lowered]));
+    }
+
+    #[test]
+    fn failed_writes_are_optional() {
+        let failed = op(0, Operation::Write(1), Response::Failed, 0, 0);
+        let none = op(1, Operation::Read, Response::Read(None), 10, 20);
+        let one = op(1, Operation::Read, Response::Read(Some(1)), 30, 40);
+        assert!(check_history(&[failed.clone(), none.clone()]));
+        assert!(check_history(&[failed, none, one]));
+    }
+}
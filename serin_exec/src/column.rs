@@ -0,0 +1,101 @@
+//! Typed columns for the non-`i64` cases.
+use bitvec::slice::BitSlice;
+use bitvec::vec::BitVec;
+use serde::{Deserialize, Serialize};
+
+/// A column of one SQL type. [`crate::ColumnBatch`] remains the nullable
+/// `i64` case.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Column {
+    /// 64-bit integers.
+    Int64(Vec<i64>),
+    /// 64-bit floats.
+    Float64(Vec<f64>),
+    /// UTF-8 strings.
+    Utf8(Vec<String>),
+    /// Booleans, one bit each.
+    Bool(BitVec),
+}
+
+impl Column {
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Int64(v) => v.len(),
+            Column::Float64(v) => v.len(),
+            Column::Utf8(v) => v.len(),
+            Column::Bool(v) => v.len(),
+        }
+    }
+
+    /// True if the column has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keep the rows whose bit in `selection` is set.
+    pub fn select(&self, selection: &BitSlice) -> Column {
+        fn pick<T: Clone>(values: &[T], selection: &BitSlice) -> Vec<T> {
+            selection.iter_ones().filter_map(|i| values.get(i).cloned()).collect()
+        }
+        match self {
+            Column::Int64(v) => Column::Int64(pick(v, selection)),
+            Column::Float64(v) => Column::Float64(pick(v, selection)),
+            Column::Utf8(v) => Column::Utf8(pick(v, selection)),
+            Column::Bool(v) => Column::Bool(selection.iter_ones().filter_map(|i| v.get(i).map(|b| *b)).collect()),
+        }
+    }
+
+    /// Rows of an `Int64` column matching `pred`, or `None` for another type.
+    pub fn filter_int64(&self, pred: impl Fn(i64) -> bool) -> Option<Column> {
+        match self {
+            Column::Int64(v) => Some(Column::Int64(v.iter().copied().filter(|&x| pred(x)).collect())),
+            _ => None,
+        }
+    }
+
+    /// Rows of a `Float64` column matching `pred`, or `None` for another type.
+    pub fn filter_float64(&self, pred: impl Fn(f64) -> bool) -> Option<Column> {
+        match self {
+            Column::Float64(v) => Some(Column::Float64(v.iter().copied().filter(|&x| pred(x)).collect())),
+            _ => None,
+        }
+    }
+
+    /// Rows of a `Utf8` column matching `pred`, or `None` for another type.
+    pub fn filter_utf8(&self, pred: impl Fn(&str) -> bool) -> Option<Column> {
+        match self {
+            Column::Utf8(v) => Some(Column::Utf8(v.iter().filter(|s| pred(s)).cloned().collect())),
+            _ => None,
+        }
+    }
+
+    /// Rows of a `Bool` column matching `pred`, or `None` for another type.
+    pub fn filter_bool(&self, pred: impl Fn(bool) -> bool) -> Option<Column> {
+        match self {
+            Column::Bool(v) => Some(Column::Bool(v.iter().map(|b| *b).filter(|&b| pred(b)).collect())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::prelude::*;
+
+    #[test]
+    fn typed_filters_and_lengths() {
+        let floats = Column::Float64(vec![0.5, 2.25, -1.0, 3.5]);
+        assert_eq!(floats.filter_float64(|x| x > 1.0), Some(Column::Float64(vec![2.25, 3.5])));
+        assert_eq!(floats.filter_int64(|_| true), None);
+
+        let names = Column::Utf8(vec!["ada".into(), "grace".into(), "edsger".into()]);
+        assert_eq!(names.len(), 3);
+        assert_eq!(names.filter_utf8(|s| s.len() > 3).map(|c| c.len()), Some(2));
+
+        let flags = Column::Bool(bitvec![1, 0, 1]);
+        assert_eq!(flags.select(bits![0, 1, 1]), Column::Bool(bitvec![0, 1]));
+        assert!(Column::Int64(Vec::new()).is_empty());
+    }
+}
@@ -1,25 +1,51 @@
+//! Cranelift JIT for generated expression and filter code.
 #[cfg(feature = "jit")]
-use cranelift_codegen::ir::types;
+use cranelift_codegen::ir::condcodes::IntCC;
 #[cfg(feature = "jit")]
-use cranelift_codegen::{Context, settings};
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags};
+#[cfg(feature = "jit")]
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 #[cfg(feature = "jit")]
 use cranelift_jit::{JITBuilder, JITModule};
 #[cfg(feature = "jit")]
-use cranelift_module::{FuncId, Linkage, Module};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+#[cfg(feature = "jit")]
+use std::collections::HashMap;
+
+#[cfg(feature = "jit")]
+use crate::CmpOp;
+
+/// Compiled filter: `f(values, len, mask, constant)` writes `mask[i] = 1`
+/// where `values[i] <op> constant` and `0` otherwise.
+///
+/// # Safety
+/// `values` must be valid for reading `len` values and `mask` for writing
+/// `len` bytes.
+#[cfg(feature = "jit")]
+pub type FilterFn = unsafe extern "C" fn(*const i64, usize, *mut u8, i64);
 
 /// JIT engine wrapper.
 #[cfg(feature = "jit")]
 pub struct JitEngine {
     module: JITModule,
+    next_fn: usize,
+}
+
+#[cfg(feature = "jit")]
+impl Default for JitEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(feature = "jit")]
 impl JitEngine {
     /// Create new engine.
     pub fn new() -> Self {
-        let builder = JITBuilder::with_builder(settings::builder());
+        let builder = JITBuilder::new(default_libcall_names()).expect("host ISA is not supported by cranelift");
         let module = JITModule::new(builder);
-        Self { module }
+        Self { module, next_fn: 0 }
     }
 
     /// Compile constant returning function `fn() -> i64`.
@@ -27,9 +53,7 @@ impl JitEngine {
         let mut ctx = self.module.make_context();
         let sig = self.module.make_signature();
         ctx.func.signature = sig;
-        use cranelift_codegen::ir::AbiParam;
         ctx.func.signature.returns.push(AbiParam::new(types::I64));
-        use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
         let mut fb_ctx = FunctionBuilderContext::new();
         let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fb_ctx);
         let block = builder.create_block();
@@ -38,15 +62,116 @@ impl JitEngine {
         let val = builder.ins().iconst(types::I64, value);
         builder.ins().return_(&[val]);
         builder.finalize();
-        let id: FuncId = self
-            .module
-            .declare_function("const_fn", Linkage::Export, &ctx.func.signature)
-            .unwrap();
+        let name = self.fn_name("const_fn");
+        let id: FuncId = self.module.declare_function(&name, Linkage::Export, &ctx.func.signature).unwrap();
         self.module.define_function(id, &mut ctx).unwrap();
         self.module.clear_context(&mut ctx);
-        self.module.finalize_definitions();
+        self.module.finalize_definitions().unwrap();
         self.module.get_finalized_function(id)
     }
+
+    /// Compile a selection loop for `value <op> constant` over an `i64` slice,
+    /// with the constant passed at call time. The mask gets one byte per
+    /// value, `1` for a match.
+    pub fn compile_filter(&mut self, op: CmpOp) -> FilterFn {
+        let ptr = self.module.target_config().pointer_type();
+        let mut ctx = self.module.make_context();
+        ctx.func.signature.params.extend([AbiParam::new(ptr), AbiParam::new(ptr), AbiParam::new(ptr), AbiParam::new(types::I64)]);
+
+        let mut fb_ctx = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut ctx.func, &mut fb_ctx);
+        let entry = b.create_block();
+        let header = b.create_block();
+        let body = b.create_block();
+        let exit = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.append_block_param(header, ptr);
+
+        b.switch_to_block(entry);
+        b.seal_block(entry);
+        let (values, len, mask, constant) = {
+            let p = b.block_params(entry);
+            (p[0], p[1], p[2], p[3])
+        };
+        let zero = b.ins().iconst(ptr, 0);
+        b.ins().jump(header, &[zero]);
+
+        // header(i): i < len ? body : exit
+        b.switch_to_block(header);
+        let i = b.block_params(header)[0];
+        let more = b.ins().icmp(IntCC::UnsignedLessThan, i, len);
+        b.ins().brif(more, body, &[], exit, &[]);
+        b.seal_block(body);
+
+        // body: mask[i] = values[i] <op> constant; i += 1
+        b.switch_to_block(body);
+        let offset = b.ins().ishl_imm(i, 3);
+        let addr = b.ins().iadd(values, offset);
+        let v = b.ins().load(types::I64, MemFlags::trusted(), addr, 0);
+        let cc = match op {
+            CmpOp::Gt => IntCC::SignedGreaterThan,
+            CmpOp::Lt => IntCC::SignedLessThan,
+            CmpOp::Eq => IntCC::Equal,
+        };
+        let hit = b.ins().icmp(cc, v, constant);
+        let out = b.ins().iadd(mask, i);
+        b.ins().store(MemFlags::trusted(), hit, out, 0);
+        let next = b.ins().iadd_imm(i, 1);
+        b.ins().jump(header, &[next]);
+        b.seal_block(header);
+
+        b.switch_to_block(exit);
+        b.seal_block(exit);
+        b.ins().return_(&[]);
+        b.finalize();
+
+        let name = self.fn_name("filter_fn");
+        let id = self.module.declare_function(&name, Linkage::Local, &ctx.func.signature).unwrap();
+        self.module.define_function(id, &mut ctx).unwrap();
+        self.module.clear_context(&mut ctx);
+        self.module.finalize_definitions().unwrap();
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the function was built with exactly this signature and the
+        // module (and so the code) lives as long as the engine.
+        unsafe { std::mem::transmute::<*const u8, FilterFn>(code) }
+    }
+
+    /// Each compiled function needs a distinct symbol within the module.
+    fn fn_name(&mut self, prefix: &str) -> String {
+        self.next_fn += 1;
+        format!("{prefix}_{}", self.next_fn)
+    }
+}
+
+/// Per-thread engine plus the filters it has already compiled: one per
+/// operator, so the JIT memory stays bounded whatever constants are used.
+#[cfg(feature = "jit")]
+#[derive(Default)]
+struct FilterCache {
+    engine: JitEngine,
+    compiled: HashMap<CmpOp, FilterFn>,
+}
+
+#[cfg(feature = "jit")]
+thread_local! {
+    static FILTERS: std::cell::RefCell<FilterCache> = std::cell::RefCell::new(FilterCache::default());
+}
+
+/// Write `mask[i] = values[i] <op> rhs` with the filter compiled for `op`,
+/// compiling it on first use on this thread.
+///
+/// # Panics
+/// If `mask` is shorter than `values`.
+#[cfg(feature = "jit")]
+pub(crate) fn run_filter(op: CmpOp, rhs: i64, values: &[i64], mask: &mut [u8]) {
+    assert!(mask.len() >= values.len(), "mask shorter than values");
+    FILTERS.with(|cell| {
+        let FilterCache { engine, compiled } = &mut *cell.borrow_mut();
+        let filter = *compiled.entry(op).or_insert_with(|| engine.compile_filter(op));
+        // SAFETY: `values` is readable for `values.len()` elements and `mask`
+        // is writable for at least as many bytes, checked above.
+        unsafe { filter(values.as_ptr(), values.len(), mask.as_mut_ptr(), rhs) }
+    })
 }
 
 #[cfg(test)]
@@ -61,4 +186,18 @@ mod tests {
         let compiled: extern "C" fn() -> i64 = unsafe { std::mem::transmute(func) };
         assert_eq!(compiled(), 42);
     }
+
+    #[test]
+    fn jit_filter_matches_scalar() {
+        let values: Vec<i64> = vec![-3, 5, 6, 100, 0, 7, 5, i64::MAX, i64::MIN];
+        let mut eng = JitEngine::new();
+        let filter = eng.compile_filter(CmpOp::Gt);
+        for rhs in [5, i64::MIN, -3] {
+            let mut mask = vec![0xffu8; values.len()];
+            // SAFETY: both buffers hold `values.len()` elements.
+            unsafe { filter(values.as_ptr(), values.len(), mask.as_mut_ptr(), rhs) };
+            let expected: Vec<u8> = values.iter().map(|&v| CmpOp::Gt.eval(v, rhs) as u8).collect();
+            assert_eq!(mask, expected);
+        }
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,54 @@
+//! Join operators.
+use std::collections::HashMap;
+
+use crate::ColumnBatch;
+
+/// Equi-join two batches on their `i64` values, returning every matching
+/// `(left_row, right_row)` pair. The hash table is built on the smaller side
+/// and probed with the larger; NULLs match nothing.
+pub fn hash_join(left: &ColumnBatch, right: &ColumnBatch) -> Vec<(usize, usize)> {
+    let build_left = left.count() <= right.count();
+    let (build, probe) = if build_left { (left, right) } else { (right, left) };
+    let mut table: HashMap<i64, Vec<usize>> = HashMap::with_capacity(build.count());
+    for i in build.validity().iter_ones() {
+        table.entry(build.values()[i]).or_default().push(i);
+    }
+    let mut out = Vec::new();
+    for j in probe.validity().iter_ones() {
+        if let Some(rows) = table.get(&probe.values()[j]) {
+            out.extend(rows.iter().map(|&i| if build_left { (i, j) } else { (j, i) }));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(values: &[Option<i64>]) -> ColumnBatch {
+        let mut b = ColumnBatch::new();
+        for v in values {
+            match v {
+                Some(v) => b.push(*v),
+                None => b.push_null(),
+            };
+        }
+        b
+    }
+
+    #[test]
+    fn joins_duplicates_on_both_sides() {
+        let left = batch(&[Some(1), Some(2), Some(2), None, Some(5)]);
+        let right = batch(&[Some(2), Some(7), Some(1), Some(2), None, Some(2)]);
+        let mut pairs = hash_join(&left, &right);
+        pairs.sort();
+        let want = vec![(0, 2), (1, 0), (1, 3), (1, 5), (2, 0), (2, 3), (2, 5)];
+        assert_eq!(pairs, want);
+
+        // Same pairs, mirrored, when the sides swap (and so does the build side).
+        let mut swapped: Vec<_> = hash_join(&right, &left).into_iter().map(|(r, l)| (l, r)).collect();
+        swapped.sort();
+        assert_eq!(swapped, want);
+    }
+}
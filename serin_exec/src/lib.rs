@@ -1,49 +1,256 @@
 //! SerinDB vectorized execution primitives (MVP).
 #![deny(missing_docs)]
 
+use bitvec::slice::BitSlice;
+use bitvec::vec::BitVec;
 use serde::{Deserialize, Serialize};
 
 /// Number of rows per column batch (MVP value).
 pub const BATCH_CAPACITY: usize = 4096;
 
+/// Comparison of a column against a constant; these predicates have a SIMD
+/// fast path in [`ColumnBatch::filter_cmp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    /// `value > rhs`
+    Gt,
+    /// `value < rhs`
+    Lt,
+    /// `value == rhs`
+    Eq,
+}
+
+impl CmpOp {
+    /// Evaluate `lhs <op> rhs`.
+    pub fn eval(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Eq => lhs == rhs,
+        }
+    }
+}
+
 /// Column batch storing homogeneous type `i64` for MVP.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawColumnBatch")]
 pub struct ColumnBatch {
-    /// Values buffer (length <= BATCH_CAPACITY).
-    pub values: Vec<i64>,
+    /// Values buffer (length <= BATCH_CAPACITY). Null slots hold 0.
+    values: Vec<i64>,
+    /// Validity bitmap, one bit per slot; unset means NULL. Always as long as `values`.
+    validity: BitVec,
+}
+
+/// Serialized form of a [`ColumnBatch`], checked before it becomes one.
+#[derive(Deserialize)]
+struct RawColumnBatch {
+    values: Vec<i64>,
+    validity: BitVec,
+}
+
+impl TryFrom<RawColumnBatch> for ColumnBatch {
+    type Error = String;
+
+    fn try_from(raw: RawColumnBatch) -> Result<Self, String> {
+        if raw.values.len() > BATCH_CAPACITY || raw.validity.len() != raw.values.len() {
+            return Err(format!("column batch with {} values and {} validity bits", raw.values.len(), raw.validity.len()));
+        }
+        Ok(Self { values: raw.values, validity: raw.validity })
+    }
+}
+
+impl Default for ColumnBatch {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ColumnBatch {
     /// Create empty batch.
     pub fn new() -> Self {
-        Self { values: Vec::with_capacity(BATCH_CAPACITY) }
+        Self { values: Vec::with_capacity(BATCH_CAPACITY), validity: BitVec::with_capacity(BATCH_CAPACITY) }
     }
 
     /// Push a value, returning false if batch full.
     pub fn push(&mut self, v: i64) -> bool {
+        self.push_slot(v, true)
+    }
+
+    /// Push a NULL, returning false if batch full.
+    pub fn push_null(&mut self) -> bool {
+        self.push_slot(0, false)
+    }
+
+    fn push_slot(&mut self, v: i64, valid: bool) -> bool {
         if self.values.len() >= BATCH_CAPACITY {
             return false;
         }
         self.values.push(v);
+        self.validity.push(valid);
         true
     }
 
-    /// Simple vectorized filter using predicate closure.
+    /// Number of slots, NULLs included.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// True if the batch has no slots.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Raw slot values, NULLs included as 0; see [`validity`](Self::validity).
+    pub fn values(&self) -> &[i64] {
+        &self.values
+    }
+
+    /// Validity bitmap, one bit per slot; unset means NULL.
+    pub fn validity(&self) -> &BitSlice {
+        &self.validity
+    }
+
+    /// Value at slot `i`, or `None` if it is NULL or out of range.
+    pub fn get(&self, i: usize) -> Option<i64> {
+        self.validity.get(i).filter(|valid| **valid).map(|_| self.values[i])
+    }
+
+    /// Non-NULL values in slot order.
+    pub fn valid_values(&self) -> impl Iterator<Item = i64> + '_ {
+        self.values.iter().zip(self.validity.iter()).filter(|(_, valid)| **valid).map(|(&v, _)| v)
+    }
+
+    /// Scalar filter using predicate closure. NULLs never match.
+    /// Prefer [`filter_cmp`](Self::filter_cmp) for comparisons with a constant.
     pub fn filter(&self, pred: impl Fn(i64) -> bool) -> ColumnBatch {
         let mut out = ColumnBatch::new();
-        // naive loop; placeholder for SIMD acceleration.
-        for &v in &self.values {
+        for v in self.valid_values() {
             if pred(v) {
-                out.values.push(v);
+                out.push(v);
             }
         }
         out
     }
+
+    /// Keep values where `value <op> rhs`; NULLs never match. With the `jit`
+    /// feature the selection loop is compiled once per predicate; with `simd`
+    /// the mask is computed several lanes at a time.
+    pub fn filter_cmp(&self, op: CmpOp, rhs: i64) -> ColumnBatch {
+        #[cfg(feature = "jit")]
+        {
+            let mut mask = vec![0u8; self.values.len()];
+            jit::run_filter(op, rhs, &self.values, &mut mask);
+            let mut out = ColumnBatch::new();
+            for (i, hit) in mask.into_iter().enumerate() {
+                if hit != 0 && self.validity[i] {
+                    out.push(self.values[i]);
+                }
+            }
+            out
+        }
+        #[cfg(all(feature = "simd", not(feature = "jit")))]
+        {
+            let mut out = ColumnBatch::new();
+            let mask = simd::select(&self.values, op, rhs) & &self.validity;
+            for i in mask.iter_ones() {
+                out.push(self.values[i]);
+            }
+            out
+        }
+        #[cfg(not(any(feature = "simd", feature = "jit")))]
+        self.filter(|v| op.eval(v, rhs))
+    }
+
+    /// Sum of non-NULL values, wrapping on overflow.
+    pub fn sum(&self) -> i64 {
+        self.valid_values().fold(0i64, |acc, v| acc.wrapping_add(v))
+    }
+
+    /// Smallest non-NULL value, or `None` if there is none.
+    pub fn min(&self) -> Option<i64> {
+        self.valid_values().min()
+    }
+
+    /// Largest non-NULL value, or `None` if there is none.
+    pub fn max(&self) -> Option<i64> {
+        self.valid_values().max()
+    }
+
+    /// Number of non-NULL values.
+    pub fn count(&self) -> usize {
+        self.validity.count_ones()
+    }
+
+    /// Mean of non-NULL values, or NaN if there are none.
+    pub fn avg(&self) -> f64 {
+        let sum: i128 = self.valid_values().map(|v| v as i128).sum();
+        sum as f64 / self.count() as f64
+    }
+}
+
+/// Running sum/min/max/count/avg folded over any number of batches,
+/// ignoring NULLs.
+#[derive(Debug, Clone, Default)]
+pub struct Aggregator {
+    sum: i128,
+    count: usize,
+    min: Option<i64>,
+    max: Option<i64>,
 }
 
+impl Aggregator {
+    /// Create an aggregator that has seen no values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold one batch into the running aggregates.
+    pub fn update(&mut self, batch: &ColumnBatch) {
+        self.sum += batch.valid_values().map(|v| v as i128).sum::<i128>();
+        self.count += batch.count();
+        self.min = self.min.into_iter().chain(batch.min()).min();
+        self.max = self.max.into_iter().chain(batch.max()).max();
+    }
+
+    /// Sum so far, wrapping on overflow.
+    pub fn sum(&self) -> i64 {
+        self.sum as i64
+    }
+
+    /// Smallest value so far, or `None` if none seen.
+    pub fn min(&self) -> Option<i64> {
+        self.min
+    }
+
+    /// Largest value so far, or `None` if none seen.
+    pub fn max(&self) -> Option<i64> {
+        self.max
+    }
+
+    /// Number of values seen.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Mean so far, or NaN if no values were seen.
+    pub fn avg(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+}
+
+pub mod column;
+pub use column::Column;
+
+pub mod join;
+pub use join::hash_join;
+
 #[cfg(feature = "jit")]
 pub mod jit;
 
+// The JIT path supersedes the SIMD one when both features are on.
+#[cfg(all(feature = "simd", not(feature = "jit")))]
+mod simd;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,6 +262,79 @@ mod tests {
             assert!(batch.push(i));
         }
         let even = batch.filter(|v| v % 2 == 0);
-        assert_eq!(even.values.len(), 50);
+        assert_eq!(even.values().len(), 50);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn batch_aggregates() {
+        let mut batch = ColumnBatch::new();
+        for v in [4, -2, 9, 1] {
+            batch.push(v);
+        }
+        assert_eq!((batch.sum(), batch.min(), batch.max(), batch.count()), (12, Some(-2), Some(9), 4));
+        assert_eq!(batch.avg(), 3.0);
+
+        let empty = ColumnBatch::new();
+        assert_eq!((empty.sum(), empty.min(), empty.max(), empty.count()), (0, None, None, 0));
+        assert!(empty.avg().is_nan());
+    }
+
+    #[test]
+    fn aggregator_folds_multiple_batches() {
+        let mut agg = Aggregator::new();
+        let mut expected = Vec::new();
+        for chunk in 0..3 {
+            let mut batch = ColumnBatch::new();
+            for i in 0..1000 {
+                let v = chunk * 1000 + i - 1500;
+                batch.push(v);
+                expected.push(v);
+            }
+            agg.update(&batch);
+        }
+        agg.update(&ColumnBatch::new());
+        assert_eq!(agg.count(), 3000);
+        assert_eq!(agg.sum(), expected.iter().sum::<i64>());
+        assert_eq!((agg.min(), agg.max()), (Some(-1500), Some(1499)));
+        assert_eq!(agg.avg(), -0.5);
+    }
+
+    #[test]
+    fn nulls_are_skipped_by_filter_and_aggregates() {
+        let mut batch = ColumnBatch::new();
+        for v in [Some(5), None, Some(-3), None, Some(10)] {
+            match v {
+                Some(v) => batch.push(v),
+                None => batch.push_null(),
+            };
+        }
+        assert_eq!(batch.len(), 5);
+        assert_eq!((0..6).map(|i| batch.get(i)).collect::<Vec<_>>(), [Some(5), None, Some(-3), None, Some(10), None]);
+        assert_eq!((batch.sum(), batch.min(), batch.max(), batch.count()), (12, Some(-3), Some(10), 3));
+        assert_eq!(batch.avg(), 4.0);
+        let mut agg = Aggregator::new();
+        agg.update(&batch);
+        assert_eq!((agg.count(), agg.sum()), (3, 12));
+
+        let kept = batch.filter(|v| v > 0);
+        assert_eq!(kept.values(), [5, 10]);
+        assert_eq!(kept.validity().len(), kept.values().len());
+        assert!(kept.validity().all());
+        // Even an always-true predicate drops NULLs.
+        assert_eq!(batch.filter(|_| true).len(), 3);
+    }
+
+    #[test]
+    fn deserializing_checks_the_validity_length() {
+        let mut batch = ColumnBatch::new();
+        batch.push(1);
+        batch.push_null();
+        let json = serde_json::to_string(&batch).unwrap();
+        let back: ColumnBatch = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.values(), back.validity()), (batch.values(), batch.validity()));
+
+        let mut short = serde_json::to_value(&batch).unwrap();
+        short["values"] = serde_json::json!([1, 2, 3]);
+        assert!(serde_json::from_value::<ColumnBatch>(short).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! SIMD selection masks for comparison predicates.
+use bitvec::vec::BitVec;
+use wide::{i64x4, CmpEq, CmpGt, CmpLt};
+
+use crate::CmpOp;
+
+const LANES: usize = 4;
+
+/// Mask with bit `i` set where `values[i] <op> rhs`, four lanes at a time.
+pub(crate) fn select(values: &[i64], op: CmpOp, rhs: i64) -> BitVec {
+    let mut mask = BitVec::with_capacity(values.len());
+    let rhs_v = i64x4::splat(rhs);
+    let chunks = values.chunks_exact(LANES);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let v = i64x4::new(chunk.try_into().unwrap());
+        let hits = match op {
+            CmpOp::Gt => v.cmp_gt(rhs_v),
+            CmpOp::Lt => v.cmp_lt(rhs_v),
+            CmpOp::Eq => v.cmp_eq(rhs_v),
+        }
+        .move_mask();
+        mask.extend((0..LANES).map(|lane| hits & (1 << lane) != 0));
+    }
+    mask.extend(tail.iter().map(|&v| op.eval(v, rhs)));
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CmpOp, ColumnBatch, BATCH_CAPACITY};
+
+    #[test]
+    fn simd_and_scalar_filters_agree() {
+        let mut batch = ColumnBatch::new();
+        let mut x: i64 = 0x2545_f491_4f6c_dd1d;
+        for i in 0..BATCH_CAPACITY - 3 {
+            // xorshift for a spread of values, with some NULLs mixed in.
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            if i % 97 == 0 {
+                batch.push_null();
+            } else {
+                batch.push(x % 1000);
+            }
+        }
+        for (op, rhs) in [(CmpOp::Gt, 0), (CmpOp::Gt, 999), (CmpOp::Lt, -500), (CmpOp::Eq, 7)] {
+            let simd = batch.filter_cmp(op, rhs);
+            let scalar = batch.filter(|v| op.eval(v, rhs));
+            assert_eq!(simd.values(), scalar.values(), "{op:?} {rhs}");
+            assert_eq!(simd.validity(), scalar.validity());
+        }
+    }
+}
@@ -2,7 +2,7 @@
 //! Designed for fast set membership tests with configurable false positive rate.
 
 use bitvec::prelude::*;
-use murmur3::murmur3_32::MurmurHasher;
+use murmur3::murmur3_32;
 use std::hash::{Hash, Hasher};
 
 /// BloomFilter structure.
@@ -20,29 +20,40 @@ impl BloomFilter {
         Self { bits, k }
     }
 
-    fn hash_with_seed<T: Hash>(&self, item: &T, seed: u32) -> usize {
-        let mut hasher = MurmurHasher::with_seed(seed);
-        item.hash(&mut hasher);
-        (hasher.finish() as usize) % self.bits.len()
+    /// Bit positions of `item`: its `Hash` bytes run through MurmurHash3 once per seed.
+    fn bit_indexes<T: Hash + ?Sized>(&self, item: &T) -> impl Iterator<Item = usize> + '_ {
+        let mut bytes = ByteSink::default();
+        item.hash(&mut bytes);
+        (0..self.k).map(move |seed| {
+            let hash = murmur3_32(&mut bytes.0.as_slice(), seed).expect("reading from a slice cannot fail");
+            hash as usize % self.bits.len()
+        })
     }
 
     /// Insert an item into the filter.
-    pub fn insert<T: Hash>(&mut self, item: &T) {
-        for i in 0..self.k {
-            let idx = self.hash_with_seed(item, i);
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        for idx in self.bit_indexes(item).collect::<Vec<_>>() {
             self.bits.set(idx, true);
         }
     }
 
     /// Check if an item is possibly in the set (false positives possible).
-    pub fn contains<T: Hash>(&self, item: &T) -> bool {
-        for i in 0..self.k {
-            let idx = self.hash_with_seed(item, i);
-            if !self.bits[idx] {
-                return false;
-            }
-        }
-        true
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.bit_indexes(item).all(|idx| self.bits[idx])
+    }
+}
+
+/// Hasher that records the bytes fed to it instead of hashing them.
+#[derive(Default)]
+struct ByteSink(Vec<u8>);
+
+impl Hasher for ByteSink {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        0
     }
 }
 
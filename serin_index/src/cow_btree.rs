@@ -0,0 +1,201 @@
+//! Copy-on-write B+Tree for lock-free reads.
+//!
+//! Writers copy the root-to-leaf path they modify and publish the new root with
+//! a single atomic swap, so a [`Snapshot`] taken by a reader keeps seeing the
+//! tree exactly as it was, without blocking or being blocked by writers.
+
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+
+use crate::{Key, Value};
+
+const ORDER: usize = 4; // max keys per node
+
+/// Immutable tree node shared between versions.
+#[derive(Debug)]
+enum Node {
+    /// Internal node with keys and child pointers.
+    Internal {
+        keys: Vec<Key>,
+        children: Vec<Arc<Node>>, // len = keys.len()+1
+    },
+    /// Leaf node with sorted key-value pairs.
+    Leaf { keys: Vec<Key>, values: Vec<Value> },
+}
+
+impl Node {
+    fn empty() -> Self {
+        Node::Leaf { keys: Vec::new(), values: Vec::new() }
+    }
+
+    /// Copy the path to `key`, returning the new node and, on overflow, the
+    /// separator key and right sibling to insert into the parent.
+    fn insert(&self, key: Key, value: Value) -> (Arc<Node>, Option<(Key, Arc<Node>)>) {
+        match self {
+            Node::Leaf { keys, values } => {
+                let (mut keys, mut values) = (keys.clone(), values.clone());
+                match keys.binary_search(&key) {
+                    Ok(i) => values[i] = value,
+                    Err(i) => {
+                        keys.insert(i, key);
+                        values.insert(i, value);
+                    }
+                }
+                if keys.len() > ORDER {
+                    let split_point = keys.len() / 2;
+                    let right_keys = keys.split_off(split_point);
+                    let right_values = values.split_off(split_point);
+                    let split_key = right_keys[0];
+                    let right = Arc::new(Node::Leaf { keys: right_keys, values: right_values });
+                    return (Arc::new(Node::Leaf { keys, values }), Some((split_key, right)));
+                }
+                (Arc::new(Node::Leaf { keys, values }), None)
+            }
+            Node::Internal { keys, children } => {
+                let idx = keys.partition_point(|&k| k <= key);
+                let (child, split) = children[idx].insert(key, value);
+                let (mut keys, mut children) = (keys.clone(), children.clone());
+                children[idx] = child;
+                if let Some((split_key, right)) = split {
+                    keys.insert(idx, split_key);
+                    children.insert(idx + 1, right);
+                    if keys.len() > ORDER {
+                        let split_point = keys.len() / 2;
+                        let right_keys = keys.split_off(split_point + 1);
+                        let promo_key = keys.pop().unwrap();
+                        let right_children = children.split_off(split_point + 1);
+                        let right = Arc::new(Node::Internal { keys: right_keys, children: right_children });
+                        return (Arc::new(Node::Internal { keys, children }), Some((promo_key, right)));
+                    }
+                }
+                (Arc::new(Node::Internal { keys, children }), None)
+            }
+        }
+    }
+}
+
+/// B+Tree whose readers never block: each write publishes a new root.
+#[derive(Debug)]
+pub struct CowBPlusTree {
+    root: ArcSwap<Node>,
+    /// Serializes writers so no concurrent update is lost.
+    writer: Mutex<()>,
+}
+
+impl Default for CowBPlusTree {
+    fn default() -> Self {
+        Self { root: ArcSwap::from_pointee(Node::empty()), writer: Mutex::new(()) }
+    }
+}
+
+impl CowBPlusTree {
+    /// Insert or overwrite `key`. Snapshots taken earlier are unaffected.
+    pub fn insert(&self, key: Key, value: Value) {
+        let _guard = self.writer.lock().unwrap();
+        let (root, split) = self.root.load().insert(key, value);
+        let root = match split {
+            Some((k, right)) => Arc::new(Node::Internal { keys: vec![k], children: vec![root, right] }),
+            None => root,
+        };
+        self.root.store(root);
+    }
+
+    /// Consistent read-only view of the current tree.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { root: self.root.load_full() }
+    }
+
+    /// Search the current tree for a key.
+    pub fn search(&self, key: Key) -> Option<Value> {
+        self.snapshot().search(key)
+    }
+}
+
+/// Point-in-time view of a [`CowBPlusTree`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    root: Arc<Node>,
+}
+
+impl Snapshot {
+    /// Search for a key, returning Option<Value>.
+    pub fn search(&self, key: Key) -> Option<Value> {
+        let mut node = &*self.root;
+        loop {
+            match node {
+                Node::Internal { keys, children } => node = &children[keys.partition_point(|&k| k <= key)],
+                Node::Leaf { keys, values } => return keys.binary_search(&key).ok().map(|i| values[i]),
+            }
+        }
+    }
+
+    /// Iterate all entries in key order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { stack: vec![&*self.root], keys: &[], values: &[], pos: 0 }
+    }
+}
+
+/// In-order iterator over a [`Snapshot`].
+pub struct Iter<'a> {
+    stack: Vec<&'a Node>,
+    keys: &'a [Key],
+    values: &'a [Value],
+    pos: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = (Key, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos < self.keys.len() {
+                self.pos += 1;
+                return Some((self.keys[self.pos - 1], self.values[self.pos - 1]));
+            }
+            match self.stack.pop()? {
+                Node::Internal { children, .. } => self.stack.extend(children.iter().rev().map(|c| &**c)),
+                Node::Leaf { keys, values } => {
+                    self.keys = keys;
+                    self.values = values;
+                    self.pos = 0;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_keep_snapshot_while_writer_inserts() {
+        let tree = CowBPlusTree::default();
+        for i in 0..100 {
+            tree.insert(i, i as i64);
+        }
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let snap = tree.snapshot();
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        let seen: Vec<_> = snap.iter().collect();
+                        assert_eq!(seen, (0..100).map(|i| (i, i as i64)).collect::<Vec<_>>());
+                        assert_eq!(snap.search(500), None);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for i in 100..5000 {
+                    tree.insert(i, i as i64);
+                }
+                tree.insert(0, -1);
+            });
+        });
+        let snap = tree.snapshot();
+        assert_eq!(snap.iter().count(), 5000);
+        assert_eq!(snap.search(0), Some(-1));
+        assert_eq!(tree.search(4999), Some(4999));
+    }
+}
@@ -1,4 +1,7 @@
+//! GIN-style key extraction for JSON documents.
+
 use serde_json::Value;
+use std::collections::BTreeSet;
 
 /// Extract (<path>, <scalar>) pairs for GIN indexing.
 pub fn extract_gin_keys(val: &Value, prefix: String, out: &mut Vec<(String, String)>) {
@@ -18,4 +21,27 @@ pub fn extract_gin_keys(val: &Value, prefix: String, out: &mut Vec<(String, Stri
             out.push((prefix, val.to_string()));
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// Inverted set of the (path, scalar) keys of every indexed document.
+#[derive(Debug, Clone, Default)]
+pub struct GinIndex {
+    keys: BTreeSet<(String, String)>,
+}
+
+impl GinIndex {
+    /// Index every (path, scalar) key of `doc`.
+    pub fn insert(&mut self, doc: &Value) {
+        let mut keys = Vec::new();
+        extract_gin_keys(doc, String::new(), &mut keys);
+        self.keys.extend(keys);
+    }
+
+    /// True if every key of `query` has been indexed. The keys may come from
+    /// different documents, so a match is a candidate to recheck, as with `@>`.
+    pub fn contains(&self, query: &Value) -> bool {
+        let mut keys = Vec::new();
+        extract_gin_keys(query, String::new(), &mut keys);
+        keys.iter().all(|k| self.keys.contains(k))
+    }
+}
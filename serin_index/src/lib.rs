@@ -1,6 +1,11 @@
 //! B+Tree implementation (minimal, in-memory, order 4).
 #![deny(missing_docs)]
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const ORDER: usize = 4; // max keys per node
 
@@ -35,23 +40,40 @@ impl Node {
 #[derive(Debug)]
 pub struct BPlusTree {
     root: Box<Node>,
+    /// Root-to-leaf descents performed by lookups and range scans.
+    #[cfg(test)]
+    descents: AtomicUsize,
 }
 
 impl Default for BPlusTree {
     fn default() -> Self {
-        Self {
-            root: Box::new(Node::Leaf {
-                keys: Vec::new(),
-                values: Vec::new(),
-                next: None,
-            }),
-        }
+        Self::with_root(Box::new(Node::Leaf {
+            keys: Vec::new(),
+            values: Vec::new(),
+            next: None,
+        }))
     }
 }
 
 impl BPlusTree {
+    fn with_root(root: Box<Node>) -> Self {
+        Self {
+            root,
+            #[cfg(test)]
+            descents: AtomicUsize::new(0),
+        }
+    }
+
+    /// Count a root-to-leaf descent; only tracked in tests.
+    #[inline]
+    fn note_descent(&self) {
+        #[cfg(test)]
+        self.descents.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Search for a key, returning Option<Value>.
     pub fn search(&self, key: Key) -> Option<Value> {
+        self.note_descent();
         let mut node = &self.root;
         loop {
             match &**node {
@@ -69,6 +91,142 @@ impl BPlusTree {
         }
     }
 
+    /// Build a tree bottom-up from pairs sorted by strictly ascending key, in O(n).
+    /// Leaves are packed full, then each internal level is built over the one below;
+    /// on every level the last two nodes share their entries evenly, so none is left
+    /// with a single entry.
+    /// Leaf `next` links stay unset: each leaf is owned by its parent, so [`BPlusTree::range`]
+    /// descends from the root instead of following the chain.
+    ///
+    /// # Panics
+    /// If the keys are not strictly ascending.
+    pub fn bulk_load(sorted_pairs: Vec<(Key, Value)>) -> BPlusTree {
+        assert!(
+            sorted_pairs.windows(2).all(|w| w[0].0 < w[1].0),
+            "bulk_load requires keys in strictly ascending order"
+        );
+        if sorted_pairs.is_empty() {
+            return BPlusTree::default();
+        }
+        // (min key of subtree, subtree) for the level being built.
+        let mut rest = &sorted_pairs[..];
+        let mut level: Vec<(Key, Box<Node>)> = run_lengths(sorted_pairs.len(), ORDER)
+            .into_iter()
+            .map(|len| {
+                let (chunk, tail) = rest.split_at(len);
+                rest = tail;
+                let (keys, values) = chunk.iter().copied().unzip();
+                (chunk[0].0, Box::new(Node::Leaf { keys, values, next: None }))
+            })
+            .collect();
+        while level.len() > 1 {
+            let runs = run_lengths(level.len(), ORDER + 1);
+            let mut parents = Vec::with_capacity(runs.len());
+            let mut nodes = level.into_iter();
+            for len in runs {
+                let group: Vec<_> = nodes.by_ref().take(len).collect();
+                let min_key = group[0].0;
+                let keys = group[1..].iter().map(|(k, _)| *k).collect();
+                let children = group.into_iter().map(|(_, n)| n).collect();
+                parents.push((min_key, Box::new(Node::Internal { keys, children })));
+            }
+            level = parents;
+        }
+        BPlusTree::with_root(level.pop().unwrap().1)
+    }
+
+    /// Number of levels from the root to the leaves (1 for a lone leaf).
+    pub fn height(&self) -> usize {
+        let mut node = &self.root;
+        let mut height = 1;
+        while let Node::Internal { children, .. } = &**node {
+            node = &children[0];
+            height += 1;
+        }
+        height
+    }
+
+    /// All pairs with `start <= key < end`, in key order.
+    pub fn range(&self, start: Key, end: Key) -> Vec<(Key, Value)> {
+        let mut out = Vec::new();
+        if start < end {
+            self.note_descent();
+            Self::collect_range(&self.root, start, end, &mut out);
+        }
+        out
+    }
+
+    /// Number of root-to-leaf descents performed so far.
+    #[cfg(test)]
+    fn descent_count(&self) -> usize {
+        self.descents.load(Ordering::Relaxed)
+    }
+
+    /// Build a [`LeafIndex`] sampling the min key of every `stride`-th leaf.
+    /// The borrow keeps the tree unchanged while the index is alive.
+    pub fn leaf_index(&self, stride: usize) -> LeafIndex<'_> {
+        assert!(stride > 0, "stride must be positive");
+        let mut leaves = Vec::new();
+        let mut stack = vec![&*self.root];
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Internal { children, .. } => stack.extend(children.iter().rev().map(|c| &**c)),
+                Node::Leaf { keys, values, .. } => leaves.push((&keys[..], &values[..])),
+            }
+        }
+        let sampled = leaves.iter().step_by(stride).map(|(keys, _)| keys.first().copied().unwrap_or(Key::MIN)).collect();
+        LeafIndex { leaves, sampled, stride }
+    }
+
+    /// Persist the whole tree to `path` as a CRC32C-prefixed bincode dump.
+    /// The file is written and synced as `<path>.tmp`, renamed over `path`,
+    /// and the directory synced, so a crash leaves either the old or the new
+    /// tree.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let body = bincode::serialize(&*self.root).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut bytes = crc32c::crc32c(&body).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&body);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(dir)?.sync_all()
+    }
+
+    /// Load a tree written by [`BPlusTree::save`].
+    /// Fails with `InvalidData` if the checksum does not match.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<BPlusTree> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < 4 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "index file truncated"));
+        }
+        let (crc, body) = bytes.split_at(4);
+        if crc32c::crc32c(body).to_le_bytes() != crc {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "index checksum mismatch"));
+        }
+        let root = bincode::deserialize(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(BPlusTree::with_root(Box::new(root)))
+    }
+
+    fn collect_range(node: &Node, start: Key, end: Key, out: &mut Vec<(Key, Value)>) {
+        match node {
+            Node::Internal { keys, children } => {
+                let first = keys.partition_point(|&k| k <= start);
+                let last = keys.partition_point(|&k| k < end);
+                for child in &children[first..=last] {
+                    Self::collect_range(child, start, end, out);
+                }
+            }
+            Node::Leaf { keys, values, .. } => {
+                out.extend(keys.iter().zip(values).filter(|(&k, _)| start <= k && k < end).map(|(&k, &v)| (k, v)));
+            }
+        }
+    }
+
     /// Insert key-value pair.
     pub fn insert(&mut self, key: Key, value: Value) {
         let (split_key, split_node) = Self::insert_inner(&mut self.root, key, value);
@@ -127,9 +285,85 @@ impl BPlusTree {
     }
 }
 
+/// Side index over the leaf level for wide range scans: all leaves in key
+/// order plus the min key of every `stride`-th leaf. A scan seeks its start
+/// leaf by binary search over the samples instead of descending the tree, then
+/// reads the following leaves sequentially, prefetching a few leaves ahead.
+#[derive(Debug)]
+pub struct LeafIndex<'a> {
+    leaves: Vec<(&'a [Key], &'a [Value])>,
+    sampled: Vec<Key>,
+    stride: usize,
+}
+
+impl LeafIndex<'_> {
+    /// All pairs with `start <= key < end`, in key order.
+    pub fn range(&self, start: Key, end: Key) -> Vec<(Key, Value)> {
+        let mut out = Vec::new();
+        if start >= end {
+            return out;
+        }
+        let group = self.sampled.partition_point(|&k| k <= start).saturating_sub(1);
+        let mut first = group * self.stride;
+        while self.leaves.get(first + 1).is_some_and(|(keys, _)| keys.first().is_some_and(|&k| k <= start)) {
+            first += 1;
+        }
+        for (i, (keys, values)) in self.leaves.iter().enumerate().skip(first) {
+            if keys.first().is_some_and(|&k| k >= end) {
+                break;
+            }
+            if let Some(ahead) = self.leaves.get(i + PREFETCH_LEAVES) {
+                prefetch(ahead);
+            }
+            out.extend(keys.iter().zip(*values).filter(|(&k, _)| start <= k && k < end).map(|(&k, &v)| (k, v)));
+        }
+        out
+    }
+}
+
+/// Split `len` entries into runs of at most `max`. A short last run is evened
+/// out with the one before it, so it never holds a lone entry.
+fn run_lengths(len: usize, max: usize) -> Vec<usize> {
+    let mut runs = vec![max; len / max];
+    if !len.is_multiple_of(max) {
+        runs.push(len % max);
+    }
+    if let [.., prev, last] = runs.as_mut_slice() {
+        if *last < max {
+            let total = *prev + *last;
+            *prev = total.div_ceil(2);
+            *last = total / 2;
+        }
+    }
+    runs
+}
+
+/// How many leaves ahead of the current one a [`LeafIndex`] scan prefetches.
+const PREFETCH_LEAVES: usize = 2;
+
+/// Hint the CPU to start loading `leaf` before the scan reaches it.
+#[inline]
+fn prefetch(leaf: &(&[Key], &[Value])) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: a prefetch is only a hint and never faults, whatever the address.
+    #[allow(unused_unsafe)]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(leaf.0.as_ptr() as *const i8);
+        _mm_prefetch::<_MM_HINT_T0>(leaf.1.as_ptr() as *const i8);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = leaf;
+}
+
 pub mod rtree;
 pub mod bloom;
 pub mod json_gin;
+pub mod cow_btree;
+pub mod prefix_btree;
+pub mod traits;
+
+pub use traits::{OrderedIndex, SetIndex, SpatialIndex};
 
 #[cfg(test)]
 mod tests {
@@ -145,4 +379,91 @@ mod tests {
             assert_eq!(tree.search(i), Some(i as i64 * 10));
         }
     }
+
+    #[test]
+    fn bulk_load_matches_incremental_and_is_shallower() {
+        let pairs: Vec<(Key, Value)> = (0..100_000).map(|i| (i * 2, i as i64)).collect();
+        let bulk = BPlusTree::bulk_load(pairs.clone());
+        let mut incremental = BPlusTree::default();
+        for &(k, v) in &pairs {
+            incremental.insert(k, v);
+        }
+        for &(k, v) in &pairs {
+            assert_eq!(bulk.search(k), Some(v));
+            assert_eq!(bulk.search(k + 1), None);
+        }
+        assert_eq!(bulk.range(1_000, 1_010), vec![(1_000, 500), (1_002, 501), (1_004, 502), (1_006, 503), (1_008, 504)]);
+        assert_eq!(bulk.range(-10, 200_000).len(), pairs.len());
+        assert_eq!(bulk.range(1_000, 1_010), incremental.range(1_000, 1_010));
+        assert!(bulk.height() < incremental.height(), "{} vs {}", bulk.height(), incremental.height());
+    }
+
+    #[test]
+    fn bulk_load_leaves_no_single_child_nodes() {
+        fn check(node: &Node) {
+            match node {
+                Node::Internal { keys, children } => {
+                    assert!(children.len() >= 2, "internal node with {} children", children.len());
+                    assert_eq!(keys.len() + 1, children.len());
+                    children.iter().for_each(|c| check(c));
+                }
+                Node::Leaf { keys, .. } => assert!(!keys.is_empty()),
+            }
+        }
+        for n in 1..300 {
+            let pairs: Vec<(Key, Value)> = (0..n).map(|i| (i, i as i64)).collect();
+            let tree = BPlusTree::bulk_load(pairs.clone());
+            check(&tree.root);
+            assert_eq!(tree.range(Key::MIN, Key::MAX), pairs, "n = {n}");
+        }
+        assert_eq!(run_lengths(21, 5), [5, 5, 5, 3, 3]);
+        assert_eq!(run_lengths(20, 5), [5, 5, 5, 5]);
+        assert_eq!(run_lengths(3, 5), [3]);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let path = dir.join("index.bpt");
+        let mut tree = BPlusTree::default();
+        for i in 0..1000 {
+            tree.insert(i, i as i64 * 7);
+        }
+        tree.save(&path).unwrap();
+        let loaded = BPlusTree::load(&path).unwrap();
+        for i in 0..1000 {
+            assert_eq!(loaded.search(i), Some(i as i64 * 7));
+        }
+        assert_eq!(loaded.height(), tree.height());
+
+        // A sibling with another extension gets its own temporary file.
+        BPlusTree::default().save(dir.join("index.idx")).unwrap();
+        assert_eq!(BPlusTree::load(&path).unwrap().search(999), Some(999 * 7));
+        assert!(!dir.join("index.bpt.tmp").exists());
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(BPlusTree::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn leaf_index_range_matches_and_skips_descent() {
+        let pairs: Vec<(Key, Value)> = (0..10_000).map(|i| (i * 3, i as i64)).collect();
+        let tree = BPlusTree::bulk_load(pairs.clone());
+        let index = tree.leaf_index(8);
+        for (start, end) in [(-5, 4), (0, 30), (1_000, 9_000), (29_990, 40_000), (100, 100), (7, 5)] {
+            let brute: Vec<_> = pairs.iter().copied().filter(|&(k, _)| start <= k && k < end).collect();
+            let descents = tree.descent_count();
+            let scanned = index.range(start, end);
+            assert_eq!(tree.descent_count(), descents, "seek must not descend the tree");
+            assert_eq!(scanned, brute);
+            assert_eq!(scanned, tree.range(start, end));
+        }
+
+        let empty = BPlusTree::default();
+        assert!(empty.leaf_index(4).range(Key::MIN, Key::MAX).is_empty());
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,221 @@
+//! B+Tree over byte-string keys with prefix-compressed leaves.
+//!
+//! Each leaf stores the longest prefix shared by all of its keys once and keeps
+//! only the remaining suffix per entry. Search, insert and range scans encode
+//! and decode keys transparently.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Value;
+
+const ORDER: usize = 32; // max keys per node
+
+/// Leaf entries sharing one stored prefix.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PrefixLeaf {
+    prefix: Vec<u8>,
+    suffixes: Vec<Vec<u8>>, // sorted
+    values: Vec<Value>,
+}
+
+impl PrefixLeaf {
+    /// Build a leaf from sorted full keys.
+    fn from_entries(keys: Vec<Vec<u8>>, values: Vec<Value>) -> Self {
+        let prefix_len = match (keys.first(), keys.last()) {
+            (Some(first), Some(last)) => common_prefix_len(first, last),
+            _ => 0,
+        };
+        let prefix = keys.first().map(|k| k[..prefix_len].to_vec()).unwrap_or_default();
+        let suffixes = keys.into_iter().map(|k| k[prefix_len..].to_vec()).collect();
+        Self { prefix, suffixes, values }
+    }
+
+    fn len(&self) -> usize {
+        self.suffixes.len()
+    }
+
+    fn key(&self, i: usize) -> Vec<u8> {
+        [&self.prefix[..], &self.suffixes[i][..]].concat()
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Value> {
+        let suffix = key.strip_prefix(&self.prefix[..])?;
+        self.suffixes.binary_search_by(|s| s[..].cmp(suffix)).ok().map(|i| self.values[i])
+    }
+
+    /// Insert or overwrite `key`, shortening the shared prefix if needed.
+    fn insert(&mut self, key: &[u8], value: Value) {
+        if self.suffixes.is_empty() {
+            self.prefix = key.to_vec();
+        }
+        let keep = common_prefix_len(&self.prefix, key);
+        if keep < self.prefix.len() {
+            let moved = self.prefix.split_off(keep);
+            for suffix in &mut self.suffixes {
+                suffix.splice(0..0, moved.iter().copied());
+            }
+        }
+        let suffix = &key[keep..];
+        match self.suffixes.binary_search_by(|s| s[..].cmp(suffix)) {
+            Ok(i) => self.values[i] = value,
+            Err(i) => {
+                self.suffixes.insert(i, suffix.to_vec());
+                self.values.insert(i, value);
+            }
+        }
+    }
+
+    /// Split off the upper half, re-deriving each half's (possibly longer) prefix.
+    fn split(&mut self) -> (Vec<u8>, PrefixLeaf) {
+        let split_point = self.len() / 2;
+        let keys: Vec<Vec<u8>> = (0..self.len()).map(|i| self.key(i)).collect();
+        let mut left_keys = keys;
+        let right_keys = left_keys.split_off(split_point);
+        let right_values = self.values.split_off(split_point);
+        let left_values = std::mem::take(&mut self.values);
+        let split_key = right_keys[0].clone();
+        *self = PrefixLeaf::from_entries(left_keys, left_values);
+        (split_key, PrefixLeaf::from_entries(right_keys, right_values))
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Tree node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Node {
+    /// Internal node with separator keys and child pointers.
+    Internal {
+        keys: Vec<Vec<u8>>,
+        children: Vec<Node>, // len = keys.len()+1
+    },
+    /// Prefix-compressed leaf.
+    Leaf(PrefixLeaf),
+}
+
+/// B+Tree keyed by byte strings with prefix-compressed leaves.
+#[derive(Debug)]
+pub struct PrefixBPlusTree {
+    root: Node,
+}
+
+impl Default for PrefixBPlusTree {
+    fn default() -> Self {
+        Self { root: Node::Leaf(PrefixLeaf::default()) }
+    }
+}
+
+impl PrefixBPlusTree {
+    /// Search for a key, returning Option<Value>.
+    pub fn search(&self, key: &[u8]) -> Option<Value> {
+        let mut node = &self.root;
+        loop {
+            match node {
+                Node::Internal { keys, children } => node = &children[keys.partition_point(|k| &k[..] <= key)],
+                Node::Leaf(leaf) => return leaf.get(key),
+            }
+        }
+    }
+
+    /// Insert or overwrite a key-value pair.
+    pub fn insert(&mut self, key: &[u8], value: Value) {
+        if let Some((k, right)) = Self::insert_inner(&mut self.root, key, value) {
+            let old_root = std::mem::replace(&mut self.root, Node::Leaf(PrefixLeaf::default()));
+            self.root = Node::Internal { keys: vec![k], children: vec![old_root, right] };
+        }
+    }
+
+    fn insert_inner(node: &mut Node, key: &[u8], value: Value) -> Option<(Vec<u8>, Node)> {
+        match node {
+            Node::Leaf(leaf) => {
+                leaf.insert(key, value);
+                if leaf.len() > ORDER {
+                    let (split_key, right) = leaf.split();
+                    return Some((split_key, Node::Leaf(right)));
+                }
+                None
+            }
+            Node::Internal { keys, children } => {
+                let idx = keys.partition_point(|k| &k[..] <= key);
+                let (split_key, child) = Self::insert_inner(&mut children[idx], key, value)?;
+                keys.insert(idx, split_key);
+                children.insert(idx + 1, child);
+                if keys.len() > ORDER {
+                    let split_point = keys.len() / 2;
+                    let right_keys = keys.split_off(split_point + 1);
+                    let promo_key = keys.pop().unwrap();
+                    let right_children = children.split_off(split_point + 1);
+                    return Some((promo_key, Node::Internal { keys: right_keys, children: right_children }));
+                }
+                None
+            }
+        }
+    }
+
+    /// All entries with `start <= key < end`, in key order.
+    pub fn range(&self, start: &[u8], end: &[u8]) -> Vec<(Vec<u8>, Value)> {
+        let mut out = Vec::new();
+        if start < end {
+            Self::collect_range(&self.root, start, end, &mut out);
+        }
+        out
+    }
+
+    fn collect_range(node: &Node, start: &[u8], end: &[u8], out: &mut Vec<(Vec<u8>, Value)>) {
+        match node {
+            Node::Internal { keys, children } => {
+                // Child i holds keys in [keys[i-1], keys[i]).
+                let first = keys.partition_point(|k| &k[..] <= start);
+                let last = keys.partition_point(|k| &k[..] < end);
+                for child in &children[first..=last] {
+                    Self::collect_range(child, start, end, out);
+                }
+            }
+            Node::Leaf(leaf) => {
+                for i in 0..leaf.len() {
+                    let key = leaf.key(i);
+                    if &key[..] >= start && &key[..] < end {
+                        out.push((key, leaf.values[i]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("tenant/acme-corporation/table/orders/partition/2024-06/row/{i:06}").into_bytes()
+    }
+
+    #[test]
+    fn shared_prefix_search_range_and_size() {
+        let mut tree = PrefixBPlusTree::default();
+        for i in (0..2000).rev() {
+            tree.insert(&key(i), i as i64);
+        }
+        tree.insert(b"other/key", -1);
+        for i in 0..2000 {
+            assert_eq!(tree.search(&key(i)), Some(i as i64));
+        }
+        assert_eq!(tree.search(b"other/key"), Some(-1));
+        assert_eq!(tree.search(b"tenant/acme-corporation"), None);
+
+        let got = tree.range(&key(500), &key(510));
+        let want: Vec<_> = (500..510).map(|i| (key(i), i as i64)).collect();
+        assert_eq!(got, want);
+
+        let keys: Vec<_> = (0..ORDER).map(key).collect();
+        let values: Vec<_> = (0..ORDER as i64).collect();
+        let plain: Vec<_> = keys.iter().cloned().zip(values.iter().copied()).collect();
+        let compressed = PrefixLeaf::from_entries(keys, values);
+        let plain_size = bincode::serialize(&plain).unwrap().len();
+        let compressed_size = bincode::serialize(&compressed).unwrap().len();
+        assert!(compressed_size * 2 < plain_size, "{compressed_size} vs {plain_size}");
+    }
+}
@@ -13,9 +13,13 @@ const MIN_ENTRIES: usize = MAX_ENTRIES / 2;
 /// A 2-D axis-aligned bounding rectangle.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct Rect {
+    /// Left edge.
     pub min_x: f64,
+    /// Bottom edge.
     pub min_y: f64,
+    /// Right edge.
     pub max_x: f64,
+    /// Top edge.
     pub max_y: f64,
 }
 
@@ -50,7 +54,9 @@ impl Rect {
 /// Entry in a leaf node.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LeafEntry<T> {
+    /// Bounding rectangle.
     pub rect: Rect,
+    /// Associated value.
     pub value: T,
 }
 
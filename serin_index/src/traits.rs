@@ -0,0 +1,119 @@
+//! Common surfaces over the index kinds, so planners can be generic over them.
+//!
+//! * [`OrderedIndex`] – point lookups and key ranges ([`BPlusTree`]).
+//! * [`SpatialIndex`] – rectangle intersection ([`RTree`]).
+//! * [`SetIndex`] – membership that may report false positives
+//!   ([`BloomFilter`], [`GinIndex`]).
+
+use crate::bloom::BloomFilter;
+use crate::json_gin::GinIndex;
+use crate::rtree::{RTree, Rect};
+use crate::{BPlusTree, Key, Value};
+use std::hash::Hash;
+
+/// Index over totally ordered keys.
+pub trait OrderedIndex {
+    /// Insert a key-value pair.
+    fn insert(&mut self, key: Key, value: Value);
+    /// Value stored under `key`, if any.
+    fn search(&self, key: Key) -> Option<Value>;
+    /// All pairs with `start <= key < end`, in key order.
+    fn range(&self, start: Key, end: Key) -> Vec<(Key, Value)>;
+}
+
+/// Index over 2-D rectangles carrying values of type `T`.
+pub trait SpatialIndex<T> {
+    /// Insert a rectangle with its value.
+    fn insert(&mut self, rect: Rect, value: T);
+    /// Values of all rectangles intersecting `query`.
+    fn search(&self, query: &Rect) -> Vec<T>;
+}
+
+/// Membership index over items of type `T`. `contains` may return false
+/// positives but never false negatives, so callers recheck matches.
+pub trait SetIndex<T: ?Sized> {
+    /// Add an item.
+    fn insert(&mut self, item: &T);
+    /// Whether the item may have been added.
+    fn contains(&self, item: &T) -> bool;
+}
+
+impl OrderedIndex for BPlusTree {
+    fn insert(&mut self, key: Key, value: Value) {
+        BPlusTree::insert(self, key, value)
+    }
+
+    fn search(&self, key: Key) -> Option<Value> {
+        BPlusTree::search(self, key)
+    }
+
+    fn range(&self, start: Key, end: Key) -> Vec<(Key, Value)> {
+        BPlusTree::range(self, start, end)
+    }
+}
+
+impl<T: Clone> SpatialIndex<T> for RTree<T> {
+    fn insert(&mut self, rect: Rect, value: T) {
+        RTree::insert(self, rect, value)
+    }
+
+    fn search(&self, query: &Rect) -> Vec<T> {
+        RTree::search(self, query)
+    }
+}
+
+impl<T: Hash + ?Sized> SetIndex<T> for BloomFilter {
+    fn insert(&mut self, item: &T) {
+        BloomFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        BloomFilter::contains(self, item)
+    }
+}
+
+impl SetIndex<serde_json::Value> for GinIndex {
+    fn insert(&mut self, doc: &serde_json::Value) {
+        GinIndex::insert(self, doc)
+    }
+
+    fn contains(&self, query: &serde_json::Value) -> bool {
+        GinIndex::contains(self, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(index: &mut dyn OrderedIndex) {
+        for i in 0..500 {
+            index.insert(i * 2, i as i64);
+        }
+    }
+
+    #[test]
+    fn bplus_tree_through_dyn_ordered_index() {
+        let mut tree = BPlusTree::default();
+        fill(&mut tree);
+        let index: &dyn OrderedIndex = &tree;
+        assert_eq!(index.search(100), Some(50));
+        assert_eq!(index.search(101), None);
+        assert_eq!(index.range(10, 17), vec![(10, 5), (12, 6), (14, 7), (16, 8)]);
+    }
+
+    #[test]
+    fn set_indexes_share_membership_surface() {
+        let mut bloom = BloomFilter::new(1024, 3);
+        SetIndex::<str>::insert(&mut bloom, "alice");
+        assert!(SetIndex::<str>::contains(&bloom, "alice"));
+
+        let mut gin = GinIndex::default();
+        let sets: [&mut dyn SetIndex<serde_json::Value>; 1] = [&mut gin];
+        for set in sets {
+            set.insert(&serde_json::json!({"name": "alice", "tags": ["admin"]}));
+            assert!(set.contains(&serde_json::json!({"tags": ["admin"]})));
+            assert!(!set.contains(&serde_json::json!({"name": "bob"})));
+        }
+    }
+}
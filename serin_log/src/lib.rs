@@ -1,24 +1,282 @@
 use anyhow::Result;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{filter, fmt, fmt::MakeWriter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::reload::{Handle, Layer as ReloadLayer, ReloadLayer as _};
-use tracing::Level;
-
-/// Initialize structured JSON logging with rolling files and runtime log-level reload.
-/// `dir` – log directory, `level` – initial log level.
-/// Returns a reload handle that can update filter at runtime.
-pub fn init(dir: &str, level: Level) -> Result<Handle<EnvFilter, impl tracing::Subscriber + Send + Sync>> {
-    let file_appender = RollingFileAppender::new(Rotation::HOURLY, dir, "serindb.log");
-    let (reload_env, layer) = ReloadLayer::new(EnvFilter::default().add_directive(level.into()));
+use tracing::{Level, Subscriber};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// File name of the regular log; rotated files get a date suffix.
+const LOG_FILE: &str = "serindb.log";
+
+/// How often the retention sweep started by [`init`] runs.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// When the regular log starts a new file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogRotation {
+    /// A new file every hour, suffixed `YYYY-MM-DD-HH`.
+    #[default]
+    Hourly,
+    /// A new file every day, suffixed `YYYY-MM-DD`.
+    Daily,
+    /// A single, ever-growing `serindb.log`.
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Where and how [`init`] writes logs.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// Directory of the regular log.
+    pub dir: String,
+    /// Initial level of the regular log; see [`set_level`].
+    pub level: Level,
+    /// Directory of the separate `audit.log` sink; `dir` when unset.
+    pub audit_dir: Option<String>,
+    /// When the regular log rolls over.
+    pub rotation: LogRotation,
+    /// Rotated regular log files to keep besides the one being written;
+    /// all are kept when unset. The audit log is never pruned.
+    pub retain: Option<usize>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { dir: "logs".into(), level: Level::INFO, audit_dir: None, rotation: LogRotation::default(), retain: None }
+    }
+}
+
+/// Controls the level filter of the regular (non-audit) log at runtime.
+pub type Handle = reload::Handle<EnvFilter, Registry>;
+
+/// Extra layer added next to the log sinks, e.g. trace export.
+pub type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Stops the retention sweep started by [`init`] when dropped. Keep it alive
+/// for as long as old log files should be pruned.
+#[must_use = "dropping the guard stops the retention sweep"]
+pub struct LogGuard {
+    sweeper: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some((stop, thread)) = self.sweeper.take() {
+            // Disconnecting wakes the thread, which then exits.
+            drop(stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Initialize structured JSON logging with rolling files and runtime log-level reload,
+/// as described by `config`.
+/// Events carrying an `audit` field go only to the audit sink and bypass the level filter.
+/// With `config.retain` set, old regular log files are pruned now and every
+/// few minutes by a background thread until the returned [`LogGuard`] is dropped.
+/// `extra` (such as `serin_telemetry::layer`) sees every span and event,
+/// unaffected by the level filter.
+/// Returns a reload handle that can update filter at runtime, see [`set_level`].
+pub fn init(config: LogConfig, extra: Option<BoxedLayer>) -> Result<(Handle, LogGuard)> {
+    let LogConfig { dir, level, audit_dir, rotation, retain } = config;
+    let file_appender = RollingFileAppender::new(rotation.into(), &dir, LOG_FILE);
+    let audit_appender = RollingFileAppender::new(Rotation::DAILY, audit_dir.as_deref().unwrap_or(&dir), "audit.log");
+    let (subscriber, handle) = subscriber(file_appender, audit_appender, level, extra);
+    subscriber.try_init()?;
+    let mut guard = LogGuard { sweeper: None };
+    if let Some(keep) = retain {
+        sweep(Path::new(&dir), keep)?;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::Builder::new().name("serin-log-sweep".into()).spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(SWEEP_INTERVAL) {
+                if let Err(e) = sweep(Path::new(&dir), keep) {
+                    tracing::warn!("log retention sweep in {dir} failed: {e}");
+                }
+            }
+        })?;
+        guard.sweeper = Some((stop, thread));
+    }
+    Ok((handle, guard))
+}
+
+/// Delete all but the newest `keep` rotated log files in `dir`, not counting
+/// the one being written; returns how many were deleted. Rotated names end in
+/// a sortable timestamp, so name order is age order and the newest is the
+/// live file. An unrotated `serindb.log` is always live and never deleted.
+pub fn sweep(dir: &Path, keep: usize) -> std::io::Result<usize> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_rotated = name.to_str().is_some_and(|n| n.starts_with(&format!("{LOG_FILE}.")));
+        if is_rotated && entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    files.pop();
+    let stale = files.len().saturating_sub(keep);
+    for path in &files[..stale] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(stale)
+}
+
+/// Change the level of the regular log installed by [`init`]. The audit sink
+/// is unaffected.
+pub fn set_level(handle: &Handle, level: Level) -> Result<()> {
+    handle.reload(level_filter(level))?;
+    Ok(())
+}
+
+fn level_filter(level: Level) -> EnvFilter {
+    EnvFilter::default().add_directive(level.into())
+}
+
+/// Subscriber writing regular events at `level` or above to `log` and audit
+/// events to `audit`, plus `extra`, and the handle reloading the regular
+/// log's filter.
+///
+/// All layers sit side by side directly on the registry, which is what makes
+/// the returned `Handle<_, Registry>` refer to the installed filter.
+fn subscriber<L, A>(log: L, audit: A, level: Level, extra: Option<BoxedLayer>) -> (impl Subscriber + Send + Sync, Handle)
+where
+    L: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    A: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let (reload_filter, handle) = reload::Layer::new(level_filter(level));
     let fmt_layer = fmt::layer()
-        .with_writer(file_appender)
+        .with_writer(log)
         .json()
         .with_current_span(false)
         .with_span_list(false)
-        .with_filter(layer);
+        .with_filter(filter::filter_fn(|meta| !is_audit(meta)))
+        .with_filter(reload_filter)
+        .boxed();
+
+    let audit_layer = fmt::layer()
+        .with_writer(audit)
+        .json()
+        .with_current_span(false)
+        .with_span_list(false)
+        .with_filter(filter::filter_fn(is_audit))
+        .boxed();
+
+    let layers: Vec<BoxedLayer> = [fmt_layer, audit_layer].into_iter().chain(extra).collect();
+    let subscriber = tracing_subscriber::registry().with(layers);
+    (subscriber, handle)
+}
+
+/// Audit events are recognised by the presence of an `audit` field.
+fn is_audit(meta: &tracing::Metadata<'_>) -> bool {
+    meta.is_event() && meta.fields().field("audit").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory writer shared with the test.
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buf {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Buf {
+        type Writer = Buf;
+
+        fn make_writer(&'w self) -> Buf {
+            self.clone()
+        }
+    }
+
+    impl Buf {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn set_level_changes_the_live_filter() {
+        let (log, audit) = (Buf::default(), Buf::default());
+        let extra = Buf::default();
+        let extra_layer = fmt::layer().with_writer(extra.clone()).boxed();
+        let (subscriber, handle) = subscriber(log.clone(), audit.clone(), Level::INFO, Some(extra_layer));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first");
+            tracing::debug!("hidden");
+            set_level(&handle, Level::DEBUG).unwrap();
+            tracing::debug!("shown");
+            tracing::debug!(audit = true, "audited");
+        });
+        let out = log.contents();
+        assert!(out.contains("first"), "{out}");
+        assert!(!out.contains("hidden"), "{out}");
+        assert!(out.contains("shown"), "{out}");
+        assert!(!out.contains("audited"), "{out}");
+        assert!(audit.contents().contains("audited"));
+        // The extra layer is not behind the regular log's level filter.
+        assert!(extra.contents().contains("hidden"));
+    }
+
+    #[test]
+    fn sweep_keeps_the_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        for hour in ["08", "09", "10", "11", "12"] {
+            std::fs::write(dir.join(format!("serindb.log.2026-10-17-{hour}")), "{}\n").unwrap();
+        }
+        std::fs::write(dir.join("audit.log.2026-10-17"), "{}\n").unwrap();
+        std::fs::write(dir.join("serindb.log"), "{}\n").unwrap();
+
+        // The live file (hour 12) does not count toward `keep`.
+        assert_eq!(sweep(dir, 2).unwrap(), 2);
+        let mut left: Vec<String> =
+            std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "audit.log.2026-10-17",
+                "serindb.log",
+                "serindb.log.2026-10-17-10",
+                "serindb.log.2026-10-17-11",
+                "serindb.log.2026-10-17-12"
+            ]
+        );
+        assert_eq!(sweep(dir, 2).unwrap(), 0);
+        assert_eq!(sweep(dir, 0).unwrap(), 2);
+        assert!(dir.join("serindb.log.2026-10-17-12").exists());
+    }
 
-    tracing_subscriber::registry()
-        .with(fmt_layer)
-        .init();
-    Ok(reload_env)
-} 
\ No newline at end of file
+    #[test]
+    fn dropping_the_guard_stops_the_sweep() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig { dir: dir.path().to_str().unwrap().into(), retain: Some(1), ..Default::default() };
+        let (_handle, guard) = init(config, None).unwrap();
+        let start = std::time::Instant::now();
+        drop(guard);
+        assert!(start.elapsed() < SWEEP_INTERVAL / 2, "sweep thread outlived its guard");
+    }
+}
@@ -1,9 +1,15 @@
 //! Cluster metadata service with ShardMap gRPC API.
 use async_trait::async_trait;
-use openraft::Raft;
+use openraft::error::ClientWriteError;
 use serde::{Deserialize, Serialize};
+use serin_raft::{Machine, SerinRaft};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
 use tonic::{Request, Response, Status};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,14 +18,113 @@ pub struct ShardMapEntry {
     pub node: String,
 }
 
+/// Change batches buffered per watcher before it lags and its stream ends.
+const WATCH_BUFFER: usize = 1024;
+
+/// Assignments changed by one write, in the order they were applied.
+pub type ShardMapChange = Arc<[ShardMapEntry]>;
+
+/// Shard assignments. Standalone it is changed directly; when replicated it
+/// is the Raft state machine, changed only by applying committed commands.
+#[derive(Debug)]
+pub struct ShardAssignments {
+    map: RwLock<HashMap<u64, String>>,
+    updates: broadcast::Sender<ShardMapChange>,
+}
+
+impl Default for ShardAssignments {
+    fn default() -> Self {
+        Self { map: Default::default(), updates: broadcast::channel(WATCH_BUFFER).0 }
+    }
+}
+
+impl ShardAssignments {
+    fn assign(&self, entries: Vec<ShardMapEntry>) {
+        let mut map = self.map.write().unwrap();
+        let mut changed = Vec::new();
+        for entry in entries {
+            if map.get(&entry.shard_id) != Some(&entry.node) {
+                map.insert(entry.shard_id, entry.node.clone());
+                changed.push(entry);
+            }
+        }
+        // One message per write, however large, so a batch cannot lag
+        // watchers; sent under the lock so `subscribe` sees it exactly once.
+        if !changed.is_empty() {
+            let _ = self.updates.send(changed.into());
+        }
+    }
+}
+
+impl Machine for ShardAssignments {
+    fn apply(&self, command: &[u8]) -> Vec<u8> {
+        match serde_json::from_slice(command) {
+            Ok(entries) => self.assign(entries),
+            Err(e) => tracing::warn!("skipping undecodable shard map command: {e}"),
+        }
+        Vec::new()
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        serde_json::to_vec(&*self.map.read().unwrap()).expect("shard map serializes")
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        let restored: HashMap<u64, String> = serde_json::from_slice(snapshot)?;
+        self.assign(restored.into_iter().map(|(shard_id, node)| ShardMapEntry { shard_id, node }).collect());
+        Ok(())
+    }
+}
+
+/// Shard map. Standalone it lives only in memory; when replicated, every
+/// change is a Raft command applied to the shared [`ShardAssignments`].
 #[derive(Default)]
 pub struct ShardMapStore {
-    inner: tokio::sync::RwLock<HashMap<u64, String>>,
+    state: Arc<ShardAssignments>,
+    raft: Option<Arc<SerinRaft>>,
 }
 
 impl ShardMapStore {
-    pub async fn get(&self, id: u64) -> Option<String> { self.inner.read().await.get(&id).cloned() }
-    pub async fn set(&self, id: u64, node: String) { self.inner.write().await.insert(id, node); }
+    /// Store replicated through `raft`, whose storage applies commands to `state`.
+    pub fn replicated(raft: Arc<SerinRaft>, state: Arc<ShardAssignments>) -> Self {
+        Self { state, raft: Some(raft) }
+    }
+
+    pub async fn get(&self, id: u64) -> Option<String> {
+        self.state.map.read().unwrap().get(&id).cloned()
+    }
+
+    /// Assign shard `id` to `node`, notifying watchers if the mapping changed.
+    pub async fn set(&self, id: u64, node: String) -> Result<(), ClientWriteError> {
+        self.set_many(vec![ShardMapEntry { shard_id: id, node }]).await
+    }
+
+    /// Every assignment, in no particular order.
+    pub async fn all(&self) -> Vec<ShardMapEntry> {
+        let map = self.state.map.read().unwrap();
+        map.iter().map(|(&shard_id, node)| ShardMapEntry { shard_id, node: node.clone() }).collect()
+    }
+
+    /// Apply all `entries` as one change, so readers see none or all of them.
+    /// When replicated this fails with `ForwardToLeader` on a follower.
+    pub async fn set_many(&self, entries: Vec<ShardMapEntry>) -> Result<(), ClientWriteError> {
+        match &self.raft {
+            Some(raft) => {
+                let command = serde_json::to_vec(&entries).expect("shard map entries serialize");
+                serin_raft::propose(raft, command).await?;
+            }
+            None => self.state.assign(entries),
+        }
+        Ok(())
+    }
+
+    /// Current map plus a receiver for every later change.
+    pub async fn subscribe(&self) -> (Vec<ShardMapEntry>, broadcast::Receiver<ShardMapChange>) {
+        let map = self.state.map.read().unwrap();
+        let rx = self.state.updates.subscribe();
+        let entries = map.iter().map(|(&shard_id, node)| ShardMapEntry { shard_id, node: node.clone() }).collect();
+        (entries, rx)
+    }
 }
 
 pub mod proto {
@@ -27,7 +132,9 @@ pub mod proto {
 }
 
 use proto::shard_map_server::{ShardMap, ShardMapServer};
-use proto::{GetRequest, GetResponse, UpdateRequest, UpdateResponse};
+use proto::{
+    BatchUpdateRequest, GetAllRequest, GetAllResponse, GetRequest, GetResponse, UpdateRequest, UpdateResponse, WatchRequest,
+};
 
 pub fn service(store: Arc<ShardMapStore>) -> ShardMapServer<MyService> { ShardMapServer::new(MyService { store }) }
 
@@ -35,8 +142,29 @@ pub struct MyService {
     store: Arc<ShardMapStore>,
 }
 
+impl From<ShardMapEntry> for proto::ShardMapEntry {
+    fn from(e: ShardMapEntry) -> Self { Self { shard_id: e.shard_id, node: e.node } }
+}
+
+impl From<proto::ShardMapEntry> for ShardMapEntry {
+    fn from(e: proto::ShardMapEntry) -> Self { Self { shard_id: e.shard_id, node: e.node } }
+}
+
+/// Map a failed write to a status; a follower names the leader to retry against.
+fn write_status(e: ClientWriteError) -> Status {
+    match e {
+        ClientWriteError::ForwardToLeader(f) => match f.leader_id {
+            Some(leader) => Status::failed_precondition(format!("not the leader; retry on node {leader}")),
+            None => Status::unavailable("no leader elected"),
+        },
+        e => Status::internal(e.to_string()),
+    }
+}
+
 #[async_trait]
 impl ShardMap for MyService {
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<proto::ShardMapEntry, Status>> + Send>>;
+
     async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
         let id = request.into_inner().shard_id;
         let node = self.store.get(id).await.unwrap_or_default();
@@ -45,7 +173,141 @@ impl ShardMap for MyService {
 
     async fn update(&self, request: Request<UpdateRequest>) -> Result<Response<UpdateResponse>, Status> {
         let req = request.into_inner();
-        self.store.set(req.shard_id, req.node).await;
+        self.store.set(req.shard_id, req.node).await.map_err(write_status)?;
         Ok(Response::new(UpdateResponse {}))
     }
-} 
\ No newline at end of file
+
+    async fn get_all(&self, _request: Request<GetAllRequest>) -> Result<Response<GetAllResponse>, Status> {
+        let entries = self.store.all().await.into_iter().map(Into::into).collect();
+        Ok(Response::new(GetAllResponse { entries }))
+    }
+
+    async fn batch_update(&self, request: Request<BatchUpdateRequest>) -> Result<Response<UpdateResponse>, Status> {
+        let entries = request.into_inner().entries.into_iter().map(Into::into).collect();
+        self.store.set_many(entries).await.map_err(write_status)?;
+        Ok(Response::new(UpdateResponse {}))
+    }
+
+    #[allow(clippy::result_large_err)] // the stream item type is fixed by tonic
+    async fn watch(&self, _request: Request<WatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let (current, mut changes) = self.store.subscribe().await;
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(async move {
+            for entry in current {
+                if tx.send(Ok(entry.into())).await.is_err() {
+                    return;
+                }
+            }
+            loop {
+                match changes.recv().await {
+                    Ok(change) => {
+                        for entry in change.iter() {
+                            if tx.send(Ok(entry.clone().into())).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    // A watcher that falls too far behind gets an error and must resubscribe.
+                    Err(e @ broadcast::error::RecvError::Lagged(_)) => {
+                        let _ = tx.send(Err(Status::data_loss(e.to_string()))).await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn watch_sends_current_map_then_updates() {
+        let store = Arc::new(ShardMapStore::default());
+        store.set(1, "node-a".into()).await.unwrap();
+        let svc = MyService { store: store.clone() };
+        let mut stream = svc.watch(Request::new(WatchRequest {})).await.unwrap().into_inner();
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!((first.shard_id, first.node.as_str()), (1, "node-a"));
+
+        store.set(1, "node-a".into()).await.unwrap(); // unchanged: not sent
+        store.set(2, "node-b".into()).await.unwrap();
+        store.set(1, "node-c".into()).await.unwrap();
+        for (shard_id, node) in [(2, "node-b"), (1, "node-c")] {
+            let update = stream.next().await.unwrap().unwrap();
+            assert_eq!((update.shard_id, update.node.as_str()), (shard_id, node));
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_larger_than_watch_buffer_does_not_lag() {
+        let store = Arc::new(ShardMapStore::default());
+        let svc = MyService { store: store.clone() };
+        let mut stream = svc.watch(Request::new(WatchRequest {})).await.unwrap().into_inner();
+        let n = WATCH_BUFFER as u64 * 3;
+        store.set_many((0..n).map(|i| ShardMapEntry { shard_id: i, node: "node-a".into() }).collect()).await.unwrap();
+        for i in 0..n {
+            assert_eq!(stream.next().await.unwrap().unwrap().shard_id, i);
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_update_then_get_all_round_trips() {
+        let svc = MyService { store: Arc::new(ShardMapStore::default()) };
+        let entries: Vec<_> = (0..500).map(|i| proto::ShardMapEntry { shard_id: i, node: format!("node-{}", i % 7) }).collect();
+        svc.batch_update(Request::new(BatchUpdateRequest { entries: entries.clone() })).await.unwrap();
+        svc.update(Request::new(UpdateRequest { shard_id: 500, node: "node-x".into() })).await.unwrap();
+
+        let mut got = svc.get_all(Request::new(GetAllRequest {})).await.unwrap().into_inner().entries;
+        got.sort_by_key(|e| e.shard_id);
+        let mut want = entries;
+        want.push(proto::ShardMapEntry { shard_id: 500, node: "node-x".into() });
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn snapshot_restores_map() {
+        let state = ShardAssignments::default();
+        state.assign((0..3).map(|i| ShardMapEntry { shard_id: i, node: format!("node-{i}") }).collect());
+        let restored = ShardAssignments::default();
+        restored.restore(&state.snapshot()).unwrap();
+        assert_eq!(*restored.map.read().unwrap(), *state.map.read().unwrap());
+    }
+
+    #[tokio::test]
+    async fn replicated_map_survives_restart() {
+        use std::collections::BTreeSet;
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let start = || async {
+            let state = Arc::new(ShardAssignments::default());
+            let storage = Arc::new(serin_raft::Storage::open(dir.path(), state.clone()).unwrap());
+            let raft = Arc::new(serin_raft::new_raft(1, Arc::new(serin_raft::Network::new()), storage));
+            (raft, state)
+        };
+
+        let (raft, state) = start().await;
+        raft.initialize(BTreeSet::from([1])).await.unwrap();
+        raft.wait(Some(Duration::from_secs(5))).current_leader(1, "single node leads").await.unwrap();
+        let store = ShardMapStore::replicated(raft.clone(), state);
+        store.set(1, "node-a".into()).await.unwrap();
+        store.set_many((2..10).map(|i| ShardMapEntry { shard_id: i, node: format!("node-{i}") }).collect()).await.unwrap();
+        store.set(1, "node-b".into()).await.unwrap();
+        raft.shutdown().await.unwrap();
+        drop((store, raft));
+
+        let (raft, state) = start().await;
+        let store = ShardMapStore::replicated(raft.clone(), state);
+        assert_eq!(store.get(1).await.as_deref(), Some("node-b"));
+        let mut all = store.all().await;
+        all.sort_by_key(|e| e.shard_id);
+        let want: Vec<_> = (1..10).map(|i| (i, if i == 1 { "node-b".to_string() } else { format!("node-{i}") })).collect();
+        assert_eq!(all.into_iter().map(|e| (e.shard_id, e.node)).collect::<Vec<_>>(), want);
+        raft.shutdown().await.unwrap();
+    }
+}
@@ -1,25 +1,234 @@
 use anyhow::Result;
 use hyper::{service::{make_service_fn, service_fn}, Body, Request, Response, Server, StatusCode};
-use prometheus::{Encoder, TextEncoder, IntCounter, Histogram, HistogramOpts};
-use std::sync::Arc;
+use prometheus::{Encoder, TextEncoder, IntCounter, IntCounterVec, IntGauge, HistogramOpts, HistogramVec, Opts, Registry};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Instant;
 use base64::Engine as _;
 use base64::engine::general_purpose::STANDARD as B64;
 use once_cell::sync::Lazy;
+use subtle::ConstantTimeEq;
 
-pub static CONNECTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| prometheus::register_int_counter!("serin_connections_total", "Total client connections").unwrap());
-pub static QUERIES_TOTAL: Lazy<IntCounter> = Lazy::new(|| prometheus::register_int_counter!("serin_queries_total", "Total queries processed").unwrap());
-pub static QUERY_LATENCY_SECS: Lazy<Histogram> = Lazy::new(|| {
-    let opts = HistogramOpts::new("serin_query_latency_seconds", "Query latency in seconds").buckets(vec![0.0005,0.001,0.005,0.01,0.05,0.1,0.5,1.0]);
-    prometheus::register_histogram!(opts).unwrap()
-});
+/// One server instance's metrics, registered on their own [`Registry`] so
+/// several instances can coexist in a process.
+pub struct Metrics {
+    /// Registry the metrics below are registered on; pass it to [`serve`].
+    pub registry: Registry,
+    pub connections_total: IntCounter,
+    /// Currently connected, authenticated clients.
+    pub connections_active: IntGauge,
+    pub queries_total: IntCounterVec,
+    pub query_latency_secs: HistogramVec,
+    /// Exact-quantile alternative to `query_latency_secs`, enabled by the `summary` feature.
+    #[cfg(feature = "summary")]
+    pub query_latency_summary: summary::LatencySummary,
+    pub metrics_dropped_total: IntCounter,
+    /// Bounds the label combinations of `queries_total`.
+    pub queries_total_labels: CardinalityGuard,
+    /// Bounds the `database` label values of `query_latency_secs`.
+    pub query_latency_labels: CardinalityGuard,
+}
+
+impl Metrics {
+    /// Create metrics on a fresh, private registry.
+    pub fn new() -> prometheus::Result<Self> {
+        Self::with_registry(Registry::new())
+    }
+
+    /// Create metrics and register them on `registry`, allowing
+    /// [`DEFAULT_MAX_SERIES`] label combinations per labeled metric.
+    pub fn with_registry(registry: Registry) -> prometheus::Result<Self> {
+        Self::with_max_series(registry, DEFAULT_MAX_SERIES)
+    }
+
+    /// Create metrics on `registry`, allowing at most `max_series` label
+    /// combinations on each labeled metric.
+    pub fn with_max_series(registry: Registry, max_series: usize) -> prometheus::Result<Self> {
+        let connections_total = IntCounter::new("serin_connections_total", "Total client connections")?;
+        let connections_active = IntGauge::new("serin_connections_active", "Currently connected clients")?;
+        let queries_total = IntCounterVec::new(Opts::new("serin_queries_total", "Total queries processed"), &["database", "kind", "user"])?;
+        let opts = HistogramOpts::new("serin_query_latency_seconds", "Query latency in seconds").buckets(vec![0.0005,0.001,0.005,0.01,0.05,0.1,0.5,1.0]);
+        let query_latency_secs = HistogramVec::new(opts, &["database"])?;
+        #[cfg(feature = "summary")]
+        let query_latency_summary = summary::LatencySummary::new("serin_query_latency_summary_seconds", "Query latency quantiles in seconds")?;
+        let metrics_dropped_total = IntCounter::new("serin_metrics_dropped_total", "Label combinations folded into the overflow series")?;
+        registry.register(Box::new(connections_total.clone()))?;
+        registry.register(Box::new(connections_active.clone()))?;
+        registry.register(Box::new(queries_total.clone()))?;
+        registry.register(Box::new(query_latency_secs.clone()))?;
+        #[cfg(feature = "summary")]
+        registry.register(Box::new(query_latency_summary.clone()))?;
+        registry.register(Box::new(metrics_dropped_total.clone()))?;
+        let queries_total_labels = CardinalityGuard::with_dropped_counter(max_series, metrics_dropped_total.clone());
+        let query_latency_labels = CardinalityGuard::with_dropped_counter(max_series, metrics_dropped_total.clone());
+        Ok(Self {
+            registry,
+            connections_total,
+            connections_active,
+            queries_total,
+            query_latency_secs,
+            #[cfg(feature = "summary")]
+            query_latency_summary,
+            metrics_dropped_total,
+            queries_total_labels,
+            query_latency_labels,
+        })
+    }
+
+    /// Record a query latency in the configured latency metric: the exact-quantile
+    /// summary when built with the `summary` feature, the per-database bucketed
+    /// histogram otherwise.
+    pub fn observe_query_latency(&self, database: &str, secs: f64) {
+        #[cfg(feature = "summary")]
+        {
+            let _ = database;
+            self.query_latency_summary.observe(secs);
+        }
+        #[cfg(not(feature = "summary"))]
+        self.query_latency_secs.with_label_values(&self.query_latency_labels.admit(&[database])).observe(secs);
+    }
+
+    /// Count one query of statement `kind` (e.g. `select`, `copy`) run by `user`
+    /// against `database`. The overall total is the sum over all label combinations.
+    pub fn count_query(&self, database: &str, kind: &str, user: &str) {
+        self.queries_total.with_label_values(&self.queries_total_labels.admit(&[database, kind, user])).inc();
+    }
+
+    /// Count one completed query and record its latency.
+    pub fn record_query(&self, database: &str, kind: &str, user: &str, secs: f64) {
+        self.count_query(database, kind, user);
+        self.observe_query_latency(database, secs);
+    }
+
+    /// Start timing a query against `database`; the latency is recorded when
+    /// the returned timer is dropped, whichever way the scope exits.
+    pub fn start_timer(&self, database: &str) -> QueryTimer<'_> {
+        QueryTimer { metrics: self, database: database.to_string(), start: Instant::now() }
+    }
+}
+
+/// Records the time since its creation via [`Metrics::observe_query_latency`] on drop.
+pub struct QueryTimer<'a> {
+    metrics: &'a Metrics,
+    database: String,
+    start: Instant,
+}
+
+impl Drop for QueryTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics.observe_query_latency(&self.database, self.start.elapsed().as_secs_f64());
+    }
+}
+
+/// Process-wide metrics on the prometheus default registry, backing the statics and free functions below.
+pub static DEFAULT_METRICS: Lazy<Metrics> = Lazy::new(|| Metrics::with_registry(prometheus::default_registry().clone()).unwrap());
+
+pub static CONNECTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| DEFAULT_METRICS.connections_total.clone());
+pub static CONNECTIONS_ACTIVE: Lazy<IntGauge> = Lazy::new(|| DEFAULT_METRICS.connections_active.clone());
+pub static QUERIES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| DEFAULT_METRICS.queries_total.clone());
+pub static QUERY_LATENCY_SECS: Lazy<HistogramVec> = Lazy::new(|| DEFAULT_METRICS.query_latency_secs.clone());
+/// Exact-quantile alternative to [`QUERY_LATENCY_SECS`], enabled by the `summary` feature.
+#[cfg(feature = "summary")]
+pub static QUERY_LATENCY_SUMMARY: Lazy<summary::LatencySummary> = Lazy::new(|| DEFAULT_METRICS.query_latency_summary.clone());
+pub static METRICS_DROPPED_TOTAL: Lazy<IntCounter> = Lazy::new(|| DEFAULT_METRICS.metrics_dropped_total.clone());
+
+#[cfg(feature = "summary")]
+pub mod summary;
+
+/// [`Metrics::observe_query_latency`] on [`DEFAULT_METRICS`].
+pub fn observe_query_latency(database: &str, secs: f64) {
+    DEFAULT_METRICS.observe_query_latency(database, secs);
+}
+
+/// [`Metrics::count_query`] on [`DEFAULT_METRICS`].
+pub fn count_query(database: &str, kind: &str, user: &str) {
+    DEFAULT_METRICS.count_query(database, kind, user);
+}
+
+/// [`Metrics::start_timer`] on [`DEFAULT_METRICS`].
+pub fn start_timer(database: &str) -> QueryTimer<'static> {
+    DEFAULT_METRICS.start_timer(database)
+}
+
+/// [`Metrics::record_query`] on [`DEFAULT_METRICS`].
+pub fn record_query(database: &str, kind: &str, user: &str, secs: f64) {
+    DEFAULT_METRICS.record_query(database, kind, user, secs);
+}
+
+/// Holds a gauge incremented for as long as the guard lives, so every exit
+/// path (including `?` and panics) decrements it again.
+pub struct GaugeGuard {
+    gauge: IntGauge,
+}
 
-/// Launch Prometheus exporter HTTP server on given address.
+impl GaugeGuard {
+    /// Increment `gauge` until the guard is dropped.
+    pub fn new(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self { gauge: gauge.clone() }
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
+/// Label value substituted once a metric exceeds its cardinality limit.
+pub const OVERFLOW_LABEL: &str = "other";
+
+/// Default number of distinct label-value combinations allowed per metric.
+pub const DEFAULT_MAX_SERIES: usize = 1000;
+
+/// Caps the number of distinct label-value combinations a labeled metric exposes.
+/// Combinations beyond the limit are folded into a single `other` series and
+/// counted in `serin_metrics_dropped_total`.
+pub struct CardinalityGuard {
+    max_series: usize,
+    seen: Mutex<HashSet<Vec<String>>>,
+    dropped: IntCounter,
+}
+
+impl Default for CardinalityGuard {
+    fn default() -> Self { Self::new(DEFAULT_MAX_SERIES) }
+}
+
+impl CardinalityGuard {
+    /// Create a guard allowing at most `max_series` distinct label combinations,
+    /// counting overflow in the default [`METRICS_DROPPED_TOTAL`].
+    pub fn new(max_series: usize) -> Self {
+        Self::with_dropped_counter(max_series, METRICS_DROPPED_TOTAL.clone())
+    }
+
+    /// Create a guard counting overflow in `dropped`.
+    pub fn with_dropped_counter(max_series: usize, dropped: IntCounter) -> Self {
+        Self { max_series, seen: Mutex::new(HashSet::new()), dropped }
+    }
+
+    /// Return the label values to record: `values` itself if the combination is
+    /// already known or there is room for it, otherwise [`OVERFLOW_LABEL`] for every label.
+    pub fn admit<'a>(&self, values: &[&'a str]) -> Vec<&'a str> {
+        let key: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        let mut seen = self.seen.lock().unwrap();
+        if seen.contains(&key) || seen.len() < self.max_series {
+            seen.insert(key);
+            return values.to_vec();
+        }
+        self.dropped.inc();
+        vec![OVERFLOW_LABEL; values.len()]
+    }
+}
+
+/// Launch Prometheus exporter HTTP server on given address, exposing `registry`
+/// (e.g. `DEFAULT_METRICS.registry.clone()`).
 /// When `basic_auth` is Some((user, pass)), requires Authorization header.
-pub async fn serve(addr: &str, basic_auth: Option<(String, String)>) -> Result<()> {
+pub async fn serve(addr: &str, registry: Registry, basic_auth: Option<(String, String)>) -> Result<()> {
     let make_svc = make_service_fn(move |_| {
         let auth = basic_auth.clone();
+        let registry = registry.clone();
         async move {
-            Ok::<_, hyper::Error>(service_fn(move |req| metrics_handler(req, auth.clone())))
+            Ok::<_, hyper::Error>(service_fn(move |req| metrics_handler(req, registry.clone(), auth.clone())))
         }
     });
     let server = Server::bind(&addr.parse()?).serve(make_svc);
@@ -27,23 +236,128 @@ pub async fn serve(addr: &str, basic_auth: Option<(String, String)>) -> Result<(
     Ok(())
 }
 
-async fn metrics_handler(req: Request<Body>, auth: Option<(String, String)>) -> Result<Response<Body>, hyper::Error> {
+/// Check the Authorization header against the configured credentials. The
+/// raw header bytes are compared in constant time, so malformed or non-UTF8
+/// headers are simply rejected.
+fn authorized(req: &Request<Body>, auth: Option<&(String, String)>) -> bool {
+    let Some((u, p)) = auth else { return true };
+    let expected = format!("Basic {}", B64.encode(format!("{}:{}", u, p)));
+    req.headers().get("Authorization").is_some_and(|header| header.as_bytes().ct_eq(expected.as_bytes()).into())
+}
+
+async fn metrics_handler(req: Request<Body>, registry: Registry, auth: Option<(String, String)>) -> Result<Response<Body>, hyper::Error> {
     if req.uri().path() != "/metrics" {
         return Ok(Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap());
     }
-    if let Some((u, p)) = auth {
-        if let Some(header) = req.headers().get("Authorization") {
-            let expected = format!("Basic {}", B64.encode(format!("{}:{}", u, p)));
-            if header.to_str().unwrap_or("") != expected {
-                return Ok(Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty()).unwrap());
-            }
-        } else {
-            return Ok(Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty()).unwrap());
-        }
+    if !authorized(&req, auth.as_ref()) {
+        return Ok(Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty()).unwrap());
     }
     let encoder = TextEncoder::new();
-    let metric_families = prometheus::gather();
+    let metric_families = registry.gather();
     let mut buffer = Vec::new();
     encoder.encode(&metric_families, &mut buffer).unwrap();
     Ok(Response::builder().status(StatusCode::OK).body(Body::from(buffer)).unwrap())
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{IntCounterVec, Opts, Registry};
+
+    #[test]
+    fn cardinality_cap_routes_to_other() {
+        let registry = Registry::new();
+        let counter = IntCounterVec::new(Opts::new("test_queries_total", "test"), &["user"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        let guard = CardinalityGuard::new(2);
+        let dropped_before = METRICS_DROPPED_TOTAL.get();
+        for user in ["alice", "bob", "carol", "dave", "alice"] {
+            counter.with_label_values(&guard.admit(&[user])).inc();
+        }
+        assert_eq!(counter.with_label_values(&["alice"]).get(), 2);
+        assert_eq!(counter.with_label_values(&["bob"]).get(), 1);
+        assert_eq!(counter.with_label_values(&[OVERFLOW_LABEL]).get(), 2);
+        let series = registry.gather()[0].get_metric().len();
+        assert_eq!(series, 3);
+        assert!(METRICS_DROPPED_TOTAL.get() >= dropped_before + 2);
+    }
+
+    #[test]
+    fn queries_labeled_per_database() {
+        record_query("test_db_sales", "select", "alice", 0.002);
+        record_query("test_db_sales", "select", "alice", 0.004);
+        record_query("test_db_hr", "insert", "bob", 0.001);
+        assert_eq!(QUERIES_TOTAL.with_label_values(&["test_db_sales", "select", "alice"]).get(), 2);
+        assert_eq!(QUERIES_TOTAL.with_label_values(&["test_db_hr", "insert", "bob"]).get(), 1);
+        let family = prometheus::gather().into_iter().find(|f| f.get_name() == "serin_queries_total").unwrap();
+        let labels: Vec<&str> = family.get_metric().iter().map(|m| m.get_label()[0].get_value()).collect();
+        assert!(labels.contains(&"test_db_sales") && labels.contains(&"test_db_hr"));
+    }
+
+    #[test]
+    fn independent_instances_do_not_collide() {
+        let a = Metrics::new().unwrap();
+        let b = Metrics::new().unwrap();
+        a.connections_total.inc();
+        a.record_query("shared_db", "select", "alice", 0.01);
+        assert_eq!(b.connections_total.get(), 0);
+        assert_eq!(b.queries_total.with_label_values(&["shared_db", "select", "alice"]).get(), 0);
+        assert_eq!(a.queries_total.with_label_values(&["shared_db", "select", "alice"]).get(), 1);
+    }
+
+    #[cfg(not(feature = "summary"))]
+    #[test]
+    fn each_metric_has_its_own_series_limit() {
+        let metrics = Metrics::with_max_series(Registry::new(), 2).unwrap();
+        // Two query label combinations fill the counter's budget only.
+        metrics.record_query("sales", "select", "alice", 0.01);
+        metrics.record_query("sales", "insert", "alice", 0.01);
+        metrics.record_query("hr", "select", "bob", 0.01);
+        let latency = |db: &str| metrics.query_latency_secs.with_label_values(&[db]).get_sample_count();
+        assert_eq!(latency("sales"), 2);
+        assert_eq!(latency("hr"), 1);
+        assert_eq!(metrics.queries_total.with_label_values(&["hr", "select", "bob"]).get(), 0);
+        assert_eq!(metrics.queries_total.with_label_values(&[OVERFLOW_LABEL; 3]).get(), 1);
+        metrics.observe_query_latency("ops", 0.01);
+        assert_eq!(latency(OVERFLOW_LABEL), 1);
+        assert_eq!(metrics.metrics_dropped_total.get(), 2);
+    }
+
+    async fn status(path: &str, header: Option<&[u8]>) -> StatusCode {
+        let mut req = Request::builder().uri(path);
+        if let Some(value) = header {
+            req = req.header("Authorization", hyper::header::HeaderValue::from_bytes(value).unwrap());
+        }
+        let auth = Some(("admin".to_string(), "s3cret".to_string()));
+        metrics_handler(req.body(Body::empty()).unwrap(), Registry::new(), auth).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn basic_auth_rejects_wrong_and_malformed_credentials() {
+        let good = format!("Basic {}", B64.encode("admin:s3cret"));
+        let wrong = format!("Basic {}", B64.encode("admin:guess"));
+        assert_eq!(status("/metrics", Some(good.as_bytes())).await, StatusCode::OK);
+        assert_eq!(status("/metrics", Some(wrong.as_bytes())).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/metrics", Some(b"Basic \xff\xfe")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/metrics", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/other", Some(good.as_bytes())).await, StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(not(feature = "summary"))]
+    #[test]
+    fn timer_records_once_per_scope_including_early_return() {
+        fn timed(metrics: &Metrics, fail: bool) -> Result<(), ()> {
+            let _timer = metrics.start_timer("timed_db");
+            if fail {
+                return Err(());
+            }
+            Ok(())
+        }
+        let metrics = Metrics::new().unwrap();
+        let samples = || metrics.query_latency_secs.with_label_values(&["timed_db"]).get_sample_count();
+        assert_eq!(timed(&metrics, false), Ok(()));
+        assert_eq!(samples(), 1);
+        assert_eq!(timed(&metrics, true), Err(()));
+        assert_eq!(samples(), 2);
+    }
+}
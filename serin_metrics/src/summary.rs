@@ -0,0 +1,103 @@
+//! HdrHistogram-backed summary exposing exact latency quantiles.
+//!
+//! Unlike [`prometheus::Histogram`] this needs no server-side bucket layout:
+//! quantiles are computed from the full recorded distribution at scrape time.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use hdrhistogram::Histogram;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{Metric, MetricFamily, MetricType, Quantile, Summary};
+
+/// Quantiles exported for every summary.
+pub const QUANTILES: [f64; 4] = [0.5, 0.95, 0.99, 0.999];
+
+/// Highest trackable latency (1 hour, in microseconds).
+const MAX_MICROS: u64 = 3_600_000_000;
+
+/// Summary metric recording latencies in seconds with microsecond resolution.
+/// Clones share the same underlying distribution.
+#[derive(Clone)]
+pub struct LatencySummary {
+    desc: Desc,
+    /// Distribution in microseconds plus the exact sum in seconds.
+    state: Arc<Mutex<(Histogram<u64>, f64)>>,
+}
+
+impl LatencySummary {
+    /// Create an unregistered summary with the given metric name and help text.
+    pub fn new(name: &str, help: &str) -> prometheus::Result<Self> {
+        let desc = Desc::new(name.to_string(), help.to_string(), vec![], HashMap::new())?;
+        let hist = Histogram::new_with_bounds(1, MAX_MICROS, 3).expect("valid histogram bounds");
+        Ok(Self { desc, state: Arc::new(Mutex::new((hist, 0.0))) })
+    }
+
+    /// Record one observation in seconds.
+    pub fn observe(&self, secs: f64) {
+        let micros = ((secs * 1e6) as u64).clamp(1, MAX_MICROS);
+        let mut state = self.state.lock().unwrap();
+        state.0.saturating_record(micros);
+        state.1 += secs;
+    }
+
+    /// Current value of quantile `q` in seconds.
+    pub fn quantile(&self, q: f64) -> f64 {
+        self.state.lock().unwrap().0.value_at_quantile(q) as f64 / 1e6
+    }
+}
+
+impl Collector for LatencySummary {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let state = self.state.lock().unwrap();
+        let quantiles: Vec<Quantile> = QUANTILES
+            .iter()
+            .map(|&q| {
+                let mut quantile = Quantile::default();
+                quantile.set_quantile(q);
+                quantile.set_value(state.0.value_at_quantile(q) as f64 / 1e6);
+                quantile
+            })
+            .collect();
+        let mut summary = Summary::default();
+        summary.set_sample_count(state.0.len());
+        summary.set_sample_sum(state.1);
+        summary.set_quantile(quantiles.into());
+        let mut metric = Metric::default();
+        metric.set_summary(summary);
+        let mut family = MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(MetricType::SUMMARY);
+        family.set_metric(vec![metric].into());
+        vec![family]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Registry;
+
+    #[test]
+    fn exported_p99_matches_distribution() {
+        let summary = LatencySummary::new("test_latency_seconds", "test").unwrap();
+        // Uniform 1ms..=100ms: true p99 is 99ms.
+        for ms in 1..=100u64 {
+            for _ in 0..100 {
+                summary.observe(ms as f64 / 1e3);
+            }
+        }
+        let registry = Registry::new();
+        registry.register(Box::new(summary)).unwrap();
+        let families = registry.gather();
+        let exported = families[0].get_metric()[0].get_summary();
+        assert_eq!(exported.get_sample_count(), 10_000);
+        let p99 = exported.get_quantile().iter().find(|q| q.get_quantile() == 0.99).unwrap().get_value();
+        assert!((p99 - 0.099).abs() / 0.099 < 0.01, "p99 = {p99}");
+    }
+}
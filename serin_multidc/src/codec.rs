@@ -0,0 +1,112 @@
+//! Serialization of [`LogEntry`] frames.
+//!
+//! A client opens every link with a preface of [`MAGIC`], the protocol
+//! version and a byte naming its codec. The server answers with one status
+//! byte and, if it accepted the preface, a random 8-byte link id that
+//! authenticated frames are bound to (see [`crate::auth`]); it then decodes
+//! all frames on that link with the codec. Otherwise it closes the link, and
+//! the client reports why.
+
+use anyhow::{anyhow, bail, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::LogEntry;
+
+/// First bytes of every replication link.
+pub const MAGIC: [u8; 4] = *b"SRPL";
+/// Link protocol version, sent after [`MAGIC`].
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// Handshake status: preface accepted.
+const ACCEPTED: u8 = 0;
+/// Handshake status: bad magic or unsupported protocol version.
+const BAD_PROTOCOL: u8 = 1;
+/// Handshake status: unknown codec.
+const UNKNOWN_CODEC: u8 = 2;
+
+/// Send the preface for `codec` and wait for the server to accept it;
+/// returns the link id.
+pub async fn client_handshake<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, codec: Codec) -> Result<u64> {
+    let mut preface = MAGIC.to_vec();
+    preface.extend([PROTOCOL_VERSION, codec.id()]);
+    stream.write_all(&preface).await?;
+    stream.flush().await?;
+    let status = match stream.read_u8().await {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            bail!("replication peer closed the link during the handshake; it may not speak protocol version {PROTOCOL_VERSION}")
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match status {
+        ACCEPTED => Ok(stream.read_u64().await?),
+        BAD_PROTOCOL => bail!("replication peer does not support protocol version {PROTOCOL_VERSION}"),
+        UNKNOWN_CODEC => bail!("replication peer does not support the {codec:?} codec"),
+        _ => bail!("replication peer refused the link with status {status}"),
+    }
+}
+
+/// Read a client's preface and answer it; returns the codec and id of the link.
+pub async fn accept_handshake<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> Result<(Codec, u64)> {
+    let mut preface = [0u8; 6];
+    stream.read_exact(&mut preface).await?;
+    let (status, result) = if preface[..4] != MAGIC || preface[4] != PROTOCOL_VERSION {
+        (BAD_PROTOCOL, Err(anyhow!("bad replication preface {preface:?}")))
+    } else {
+        match Codec::from_id(preface[5]) {
+            Ok(codec) => (ACCEPTED, Ok(codec)),
+            Err(e) => (UNKNOWN_CODEC, Err(e)),
+        }
+    };
+    stream.write_u8(status).await?;
+    let codec = result?;
+    let link = rand::random::<u64>();
+    stream.write_u64(link).await?;
+    stream.flush().await?;
+    Ok((codec, link))
+}
+
+/// Wire format for log entries on one replication link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// Human-readable, for debugging captures.
+    Json,
+    /// Compact binary; payload bytes are copied as-is.
+    #[default]
+    Bincode,
+}
+
+impl Codec {
+    /// Preface byte announcing this codec.
+    pub fn id(self) -> u8 {
+        match self {
+            Codec::Json => 1,
+            Codec::Bincode => 2,
+        }
+    }
+
+    /// Codec announced by preface byte `id`.
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(Codec::Json),
+            2 => Ok(Codec::Bincode),
+            _ => Err(anyhow!("unknown replication codec {id}")),
+        }
+    }
+
+    /// Serialize `entry`.
+    pub fn encode(self, entry: &LogEntry) -> Result<Vec<u8>> {
+        Ok(match self {
+            Codec::Json => serde_json::to_vec(entry)?,
+            Codec::Bincode => bincode::serialize(entry)?,
+        })
+    }
+
+    /// Deserialize an entry written by [`Codec::encode`].
+    pub fn decode(self, data: &[u8]) -> Result<LogEntry> {
+        Ok(match self {
+            Codec::Json => serde_json::from_slice(data)?,
+            Codec::Bincode => bincode::deserialize(data)?,
+        })
+    }
+}
@@ -1,12 +1,28 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::Result;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::Mutex;
 use hdrhistogram::Histogram;
 use serde::{Serialize, Deserialize};
 use bytes::BufMut;
+use tokio_rustls::rustls::ServerName;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+pub mod auth;
+pub mod codec;
+pub mod compression;
+pub mod tls;
+
+use auth::{FrameSigner, FrameVerifier};
+use codec::Codec;
+use compression::{CompressionMode, FrameCompressor, FrameDecompressor};
+use tls::TlsConfig;
+
+/// Default for [`ReplicationServer::with_max_frame_bytes`].
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 64 << 20;
 
 /// Logical identifier for each Data Center.
 pub type DcId = u8;
@@ -36,11 +52,14 @@ pub fn resolve_conflict(local: &LogEntry, remote: &LogEntry) -> bool {
 }
 
 /// Aggregated replication metrics.
-#[derive(Default)]
 pub struct Metrics {
     pub latency_hist: Mutex<Histogram<u64>>, // ns
 }
 
+impl Default for Metrics {
+    fn default() -> Self { Self::new() }
+}
+
 impl Metrics {
     pub fn new() -> Self {
         let hist = Histogram::new(3).expect("hist");
@@ -54,6 +73,10 @@ pub struct ReplicationServer {
     dc_id: DcId,
     storage: Arc<dyn ReplicatedStore + Send + Sync>,
     metrics: Arc<Metrics>,
+    verifier: Option<Arc<FrameVerifier>>,
+    compressed: bool,
+    tls: Option<TlsAcceptor>,
+    max_frame_bytes: usize,
 }
 
 #[async_trait::async_trait]
@@ -63,17 +86,59 @@ pub trait ReplicatedStore {
 
 impl ReplicationServer {
     pub fn new<A: Into<String>>(addr: A, dc_id: DcId, storage: Arc<dyn ReplicatedStore + Send + Sync>) -> Self {
-        Self { address: addr.into(), dc_id, storage, metrics: Arc::new(Metrics::new()) }
+        Self { address: addr.into(), dc_id, storage, metrics: Arc::new(Metrics::new()), verifier: None, compressed: false, tls: None, max_frame_bytes: DEFAULT_MAX_FRAME_BYTES }
+    }
+
+    /// Require HMAC-authenticated frames; `keys` maps each source DC to the key it shares with this DC.
+    pub fn with_auth(mut self, keys: HashMap<DcId, Vec<u8>>) -> Self {
+        self.verifier = Some(Arc::new(FrameVerifier::new(keys)));
+        self
+    }
+
+    /// Expect frames produced by a client configured with [`ReplicationClient::with_compression`].
+    pub fn with_compression(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    /// Accept only mutually authenticated TLS connections.
+    pub fn with_tls(mut self, config: &TlsConfig) -> Result<Self> {
+        self.tls = Some(config.acceptor()?);
+        Ok(self)
+    }
+
+    /// Close connections announcing a frame longer than `max` bytes, before
+    /// allocating room for it.
+    pub fn with_max_frame_bytes(mut self, max: usize) -> Self {
+        self.max_frame_bytes = max;
+        self
     }
 
     pub async fn run(self) -> Result<()> {
         let listener = TcpListener::bind(&self.address).await?;
+        self.serve(listener).await
+    }
+
+    /// Accept connections on an already bound `listener`, ignoring the
+    /// configured address.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
         loop {
             let (stream, _) = listener.accept().await?;
             let storage = self.storage.clone();
             let metrics = self.metrics.clone();
+            let verifier = self.verifier.clone();
+            let decompressor = self.compressed.then(FrameDecompressor::new);
+            let tls = self.tls.clone();
+            let max_frame = self.max_frame_bytes;
             tokio::spawn(async move {
-                if let Err(e) = handle_connection(stream, storage, metrics).await {
+                let result = match tls {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => handle_connection(stream, storage, metrics, verifier, decompressor, max_frame).await,
+                        Err(e) => Err(e.into()),
+                    },
+                    None => handle_connection(stream, storage, metrics, verifier, decompressor, max_frame).await,
+                };
+                if let Err(e) = result {
                     eprintln!("replication connection error: {e}");
                 }
             });
@@ -81,14 +146,51 @@ impl ReplicationServer {
     }
 }
 
-async fn handle_connection(mut stream: TcpStream, storage: Arc<dyn ReplicatedStore + Send + Sync>, metrics: Arc<Metrics>) -> Result<()> {
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    storage: Arc<dyn ReplicatedStore + Send + Sync>,
+    metrics: Arc<Metrics>,
+    verifier: Option<Arc<FrameVerifier>>,
+    mut decompressor: Option<FrameDecompressor>,
+    max_frame_bytes: usize,
+) -> Result<()> {
+    let (codec, link) = codec::accept_handshake(&mut stream).await?;
+    let mut verifier = verifier.as_deref().map(|verifier| verifier.link(link));
     let mut len_buf = [0u8; 4];
     loop {
         if stream.read_exact(&mut len_buf).await.is_err() { break; }
         let frame_len = u32::from_be_bytes(len_buf) as usize;
+        if frame_len == 0 {
+            continue;
+        }
+        if frame_len > max_frame_bytes {
+            eprintln!("replication connection closed: frame of {frame_len} bytes exceeds limit of {max_frame_bytes}");
+            break;
+        }
         let mut frame = vec![0u8; frame_len];
         stream.read_exact(&mut frame).await?;
-        let entry: LogEntry = serde_json::from_slice(&frame)?;
+        let (source, body) = match &mut verifier {
+            Some(verifier) => match verifier.open(&frame) {
+                Ok((dc, body)) => (Some(dc), body),
+                Err(e) => {
+                    eprintln!("replication frame dropped: {e}");
+                    continue;
+                }
+            },
+            None => (None, &frame[..]),
+        };
+        let entry: LogEntry = match &mut decompressor {
+            Some(decompressor) => match decompressor.decompress(body)? {
+                Some(raw) => codec.decode(&raw)?,
+                // Dictionary frame: nothing to apply.
+                None => continue,
+            },
+            None => codec.decode(body)?,
+        };
+        if source.is_some_and(|dc| dc != entry.dc_id) {
+            eprintln!("replication frame dropped: entry for dc {} signed by another dc", entry.dc_id);
+            continue;
+        }
         let start = tokio::time::Instant::now();
         storage.append_entry(entry).await?;
         let latency = start.elapsed().as_nanos() as u64;
@@ -98,36 +200,114 @@ async fn handle_connection(mut stream: TcpStream, storage: Arc<dyn ReplicatedSto
     Ok(())
 }
 
+/// Write half of a replication link, plaintext or TLS, and the link id from
+/// its handshake.
+type Link = (Box<dyn AsyncWrite + Unpin + Send>, u64);
+
 /// Replication client pushing logs to a remote DC.
 pub struct ReplicationClient {
     peer_addr: String,
-    stream: Mutex<Option<TcpStream>>,
+    stream: Mutex<Option<Link>>,
     dc_id: DcId,
+    signer: Option<FrameSigner>,
+    compressor: Option<std::sync::Mutex<FrameCompressor>>,
+    tls: Option<(TlsConnector, ServerName)>,
+    codec: Codec,
 }
 
 impl ReplicationClient {
-    pub fn new<A: Into<String>>(peer: A, dc_id: DcId) -> Self { Self { peer_addr: peer.into(), stream: Mutex::new(None), dc_id } }
+    pub fn new<A: Into<String>>(peer: A, dc_id: DcId) -> Self {
+        Self { peer_addr: peer.into(), stream: Mutex::new(None), dc_id, signer: None, compressor: None, tls: None, codec: Codec::default() }
+    }
 
-    async fn get_stream(&self) -> Result<TcpStream> {
-        let mut guard = self.stream.lock().await;
-        if let Some(ref mut s) = *guard { return Ok(s.clone()); }
-        let s = TcpStream::connect(&self.peer_addr).await?;
-        *guard = Some(s.clone());
-        Ok(s)
+    /// Sign every frame with `key`, the secret shared between this DC and the peer.
+    pub fn with_auth(mut self, key: Vec<u8>) -> Self {
+        self.signer = Some(FrameSigner::new(self.dc_id, key));
+        self
+    }
+
+    /// Compress every frame with zstd; the peer must use [`ReplicationServer::with_compression`].
+    pub fn with_compression(mut self, mode: CompressionMode) -> Self {
+        self.compressor = Some(std::sync::Mutex::new(FrameCompressor::new(mode)));
+        self
+    }
+
+    /// Encode entries with `codec` instead of the default bincode.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Connect over mutually authenticated TLS, expecting the peer's
+    /// certificate to be valid for `server_name`.
+    pub fn with_tls(mut self, config: &TlsConfig, server_name: &str) -> Result<Self> {
+        let name = ServerName::try_from(server_name).map_err(|_| anyhow::anyhow!("invalid server name {server_name:?}"))?;
+        self.tls = Some((config.connector()?, name));
+        Ok(self)
+    }
+
+    async fn connect(&self) -> Result<Link> {
+        let stream = TcpStream::connect(&self.peer_addr).await?;
+        Ok(match &self.tls {
+            Some((connector, name)) => {
+                let mut stream = connector.connect(name.clone(), stream).await?;
+                let link = codec::client_handshake(&mut stream, self.codec).await?;
+                (Box::new(stream), link)
+            }
+            None => {
+                let mut stream = stream;
+                let link = codec::client_handshake(&mut stream, self.codec).await?;
+                (Box::new(stream), link)
+            }
+        })
     }
 
     /// Send a WAL payload to remote DC.
     pub async fn send(&self, lsn: Lsn, payload: &[u8]) -> Result<()> {
-        let ts = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or_default();
         let entry = LogEntry { dc_id: self.dc_id, lsn, timestamp_ns: ts, payload: payload.to_vec() };
-        let data = serde_json::to_vec(&entry)?;
-        let mut stream = self.get_stream().await?;
-        let mut buf = Vec::with_capacity(4 + data.len());
-        buf.put_u32(data.len() as u32);
-        buf.extend_from_slice(&data);
-        stream.write_all(&buf).await?;
+        let data = self.codec.encode(&entry)?;
+        let mut guard = self.stream.lock().await;
+        let fresh = guard.is_none();
+        if fresh {
+            *guard = Some(self.connect().await?);
+        }
+        // Compress under the stream lock so a dictionary frame precedes the frames using it.
+        let frames = match &self.compressor {
+            Some(compressor) => {
+                let mut compressor = compressor.lock().unwrap();
+                if fresh {
+                    compressor.new_stream();
+                }
+                compressor.compress(&data)?
+            }
+            None => vec![data],
+        };
+        let (stream, link) = guard.as_mut().unwrap();
+        let mut buf = Vec::new();
+        for mut frame in frames {
+            if let Some(signer) = &self.signer {
+                frame = signer.seal(*link, &frame);
+            }
+            buf.put_u32(frame.len() as u32);
+            buf.extend_from_slice(&frame);
+        }
+        let written = match stream.write_all(&buf).await {
+            Ok(()) => stream.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            // The link is broken; the next send reconnects.
+            *guard = None;
+            return Err(e.into());
+        }
         Ok(())
     }
+
+    /// Drop the connection to the peer; the next [`send`](Self::send) reconnects.
+    pub async fn disconnect(&self) {
+        *self.stream.lock().await = None;
+    }
 }
 
 /// In-memory replicated store for demo purposes.
@@ -135,7 +315,28 @@ pub struct MemoryStore {
     entries: Mutex<HashMap<Lsn, LogEntry>>,
 }
 
-impl MemoryStore { pub fn new() -> Self { Self { entries: Mutex::new(HashMap::new()) } } }
+impl Default for MemoryStore {
+    fn default() -> Self { Self::new() }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self { Self { entries: Mutex::new(HashMap::new()) } }
+
+    /// Whether the entry with `lsn` has been applied.
+    pub async fn contains(&self, lsn: Lsn) -> bool {
+        self.entries.lock().await.contains_key(&lsn)
+    }
+
+    /// The applied entry with `lsn`, if any.
+    pub async fn get(&self, lsn: Lsn) -> Option<LogEntry> {
+        self.entries.lock().await.get(&lsn).cloned()
+    }
+
+    /// Highest LSN applied so far.
+    pub async fn last_lsn(&self) -> Option<Lsn> {
+        self.entries.lock().await.keys().max().copied()
+    }
+}
 
 #[async_trait::async_trait]
 impl ReplicatedStore for MemoryStore {
@@ -159,4 +360,164 @@ mod tests {
         let b = LogEntry { dc_id: 2, lsn: 10, timestamp_ns: 2, payload: vec![] };
         assert!(resolve_conflict(&a, &b));
     }
+
+    #[tokio::test]
+    async fn oversized_frame_closes_connection() {
+        let store = Arc::new(MemoryStore::new());
+        let (mut peer, stream) = tokio::io::duplex(64);
+        // After the preface, an empty frame is skipped, then a 4 GB length
+        // prefix ends the connection even though the peer keeps its end open.
+        peer.write_all(&codec::MAGIC).await.unwrap();
+        peer.write_all(&[codec::PROTOCOL_VERSION, Codec::Bincode.id(), 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]).await.unwrap();
+        let conn = handle_connection(stream, store.clone(), Arc::new(Metrics::new()), None, None, 1024);
+        tokio::time::timeout(std::time::Duration::from_secs(1), conn).await.expect("connection left open").unwrap();
+        assert_eq!(store.last_lsn().await, None);
+        drop(peer);
+    }
+
+    #[tokio::test]
+    async fn handshake_agrees_on_codec_or_explains_refusal() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let accepted = tokio::spawn(async move { codec::accept_handshake(&mut server).await });
+        let link = codec::client_handshake(&mut client, Codec::Json).await.unwrap();
+        assert_eq!(accepted.await.unwrap().unwrap(), (Codec::Json, link));
+
+        // A codec the server does not know is refused with a status byte.
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let accepted = tokio::spawn(async move { codec::accept_handshake(&mut server).await });
+        client.write_all(&codec::MAGIC).await.unwrap();
+        client.write_all(&[codec::PROTOCOL_VERSION, 9]).await.unwrap();
+        assert_eq!(client.read_u8().await.unwrap(), 2);
+        assert!(accepted.await.unwrap().is_err());
+
+        // A server that closes the link instead of answering is reported as
+        // not speaking this protocol.
+        let (mut client, mut server) = tokio::io::duplex(64);
+        tokio::spawn(async move { server.read_u8().await });
+        let err = codec::client_handshake(&mut client, Codec::Bincode).await.unwrap_err();
+        assert!(err.to_string().contains("protocol version"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn bincode_keeps_binary_payload_intact() {
+        let payload: Vec<u8> = (0..=255).chain([0, 0xff, b'"', b'\\']).collect();
+        let entry = LogEntry { dc_id: 1, lsn: 3, timestamp_ns: 9, payload: payload.clone() };
+        assert_eq!(Codec::Bincode.decode(&Codec::Bincode.encode(&entry).unwrap()).unwrap().payload, payload);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let store = Arc::new(MemoryStore::new());
+        tokio::spawn(ReplicationServer::new(addr.clone(), 2, store.clone()).serve(listener));
+        let client = ReplicationClient::new(addr, 1).with_codec(Codec::Bincode);
+        client.send(3, &payload).await.unwrap();
+        for _ in 0..100 {
+            if let Some(applied) = store.get(3).await {
+                assert_eq!(applied.payload, payload);
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("entry not replicated");
+    }
+
+    /// Wait until `store` has applied `lsn`.
+    async fn applied(store: &MemoryStore, lsn: Lsn) {
+        for _ in 0..100 {
+            if store.contains(lsn).await {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("entry {lsn} not replicated");
+    }
+
+    #[tokio::test]
+    async fn compressed_link_survives_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let store = Arc::new(MemoryStore::new());
+        tokio::spawn(ReplicationServer::new(addr.clone(), 2, store.clone()).with_compression().serve(listener));
+        let mode = CompressionMode::Dictionary { level: 3, samples: 500, dict_size: 2048 };
+        let client = ReplicationClient::new(addr, 1).with_compression(mode);
+        let payload = |i: u64| format!(r#"{{"table":"orders","op":"insert","row":{{"id":{i},"customer":"c{}"}}}}"#, i % 37);
+        for lsn in 0..600 {
+            client.send(lsn, payload(lsn).as_bytes()).await.unwrap();
+        }
+        applied(&store, 599).await;
+
+        // The new server-side connection starts without the trained dictionary.
+        client.disconnect().await;
+        client.send(600, payload(600).as_bytes()).await.unwrap();
+        applied(&store, 600).await;
+    }
+
+    #[tokio::test]
+    async fn authenticated_link_survives_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let store = Arc::new(MemoryStore::new());
+        let key = b"dc1-dc2 shared secret".to_vec();
+        let server = ReplicationServer::new(addr.clone(), 2, store.clone()).with_auth(HashMap::from([(1, key.clone())]));
+        tokio::spawn(server.serve(listener));
+        let client = ReplicationClient::new(addr, 1).with_auth(key);
+        client.send(1, b"wal-1").await.unwrap();
+        applied(&store, 1).await;
+        client.disconnect().await;
+        client.send(2, b"wal-2").await.unwrap();
+        applied(&store, 2).await;
+    }
+
+    #[tokio::test]
+    async fn failed_write_drops_the_link() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let client = ReplicationClient::new(addr.clone(), 1);
+        let server = tokio::spawn(async move {
+            // Complete the handshake, then hang up.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            codec::accept_handshake(&mut stream).await.unwrap();
+            listener
+        });
+        client.send(1, b"first").await.unwrap();
+        let listener = server.await.unwrap();
+        let mut result = Ok(());
+        for lsn in 2..100 {
+            result = client.send(lsn, &[0; 64 << 10]).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.is_err(), "writes to a closed peer kept succeeding");
+        assert!(client.stream.lock().await.is_none());
+
+        let store = Arc::new(MemoryStore::new());
+        tokio::spawn(ReplicationServer::new(addr, 2, store.clone()).serve(listener));
+        client.send(100, b"after").await.unwrap();
+        applied(&store, 100).await;
+    }
+
+    #[tokio::test]
+    async fn replicates_over_mutual_tls() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let tls = |name: &str| TlsConfig {
+            cert: format!("{dir}/{name}.pem"),
+            key: format!("{dir}/{name}.key"),
+            ca: format!("{dir}/ca.pem"),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let store = Arc::new(MemoryStore::new());
+        let server = ReplicationServer::new(addr.clone(), 2, store.clone()).with_tls(&tls("server")).unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let client = ReplicationClient::new(addr, 1).with_tls(&tls("client"), "localhost").unwrap();
+        client.send(7, b"wal-7").await.unwrap();
+        for _ in 0..100 {
+            if store.contains(7).await {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("entry not replicated over TLS");
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,67 @@
+//! Mutual TLS for replication links.
+//!
+//! Both ends present a certificate and verify the peer's against the
+//! configured CA, so only DCs holding a certificate from that CA can
+//! replicate. Links without a [`TlsConfig`] stay plaintext for local testing.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// PEM file locations for one end of a replication link.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// Certificate chain presented to the peer.
+    pub cert: String,
+    /// PKCS#8 private key for `cert`.
+    pub key: String,
+    /// CA bundle the peer's certificate must chain to.
+    pub ca: String,
+}
+
+impl TlsConfig {
+    /// Server side: requires every client to present a certificate signed by `ca`.
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(self.roots()?))
+            .with_single_cert(load_certs(&self.cert)?, self.private_key()?)?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// Client side: verifies the server against `ca` and authenticates with `cert`.
+    pub fn connector(&self) -> Result<TlsConnector> {
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(self.roots()?)
+            .with_single_cert(load_certs(&self.cert)?, self.private_key()?)?;
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+
+    fn roots(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&self.ca)? {
+            roots.add(&cert)?;
+        }
+        Ok(roots)
+    }
+
+    fn private_key(&self) -> Result<PrivateKey> {
+        let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(&self.key)?))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no PKCS#8 private key in {}", self.key))?;
+        Ok(PrivateKey(key))
+    }
+}
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    Ok(certs.into_iter().map(Certificate).collect())
+}
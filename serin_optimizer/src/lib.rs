@@ -8,20 +8,34 @@ use serin_parser::{SelectItem, Statement};
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LogicalPlan {
     /// Scan over a table.
-    Scan { table: String },
+    Scan {
+        /// Table name.
+        table: String,
+    },
     /// Selection predicate.
-    Filter { predicate: String, input: Box<LogicalPlan> },
+    Filter {
+        /// Predicate text.
+        predicate: String,
+        /// Rows to filter.
+        input: Box<LogicalPlan>,
+    },
     /// Projection.
-    Project { items: Vec<SelectItem>, input: Box<LogicalPlan> },
+    Project {
+        /// Output items.
+        items: Vec<SelectItem>,
+        /// Rows to project.
+        input: Box<LogicalPlan>,
+    },
 }
 
 /// Generate a logical plan from parsed AST.
 pub fn plan(stmt: &Statement) -> Option<LogicalPlan> {
     match stmt {
         Statement::Select(sel) => {
-            // For MVP, assume scan of dummy table "dual".
+            // Without FROM, scan the single-row dummy table "dual".
+            let table = sel.from.as_ref().map_or("dual", |t| t.name.as_str());
             let scan = LogicalPlan::Scan {
-                table: "dual".to_string(),
+                table: table.to_string(),
             };
             Some(LogicalPlan::Project {
                 items: sel.projection.clone(),
@@ -36,9 +50,19 @@ pub fn plan(stmt: &Statement) -> Option<LogicalPlan> {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PhysicalPlan {
     /// Sequential table scan.
-    SeqScan { table: String, cost: f64 },
+    SeqScan {
+        /// Table name.
+        table: String,
+        /// Estimated cost.
+        cost: f64,
+    },
     /// Projection executed by materialization.
-    Projection { child: Box<PhysicalPlan>, cost: f64 },
+    Projection {
+        /// Input operator.
+        child: Box<PhysicalPlan>,
+        /// Estimated cost including the child.
+        cost: f64,
+    },
 }
 
 /// Estimate cost for a logical plan and choose physical operators (very naive).
@@ -83,6 +107,13 @@ mod tests {
             panic!("expected project plan");
         }
     }
+
+    #[test]
+    fn scan_uses_from_table() {
+        let ast = parse("SELECT * FROM users;").unwrap();
+        let LogicalPlan::Project { input, .. } = plan(&ast).unwrap() else { panic!("expected project plan") };
+        assert_eq!(*input, LogicalPlan::Scan { table: "users".into() });
+    }
 }
 
 #[cfg(test)]
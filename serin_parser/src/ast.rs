@@ -1,12 +1,12 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Top-level SQL statement enumeration.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Statement {
     /// `SELECT` statement.
     Select(Select),
     /// `INSERT` statement.
-    Insert,
+    Insert(Insert),
     /// `UPDATE` statement.
     Update,
     /// `DELETE` statement.
@@ -16,23 +16,117 @@ pub enum Statement {
 }
 
 /// Very small `SELECT` representation (placeholder for full AST).
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Select {
     /// Projection items, `*` or expressions.
     pub projection: Vec<SelectItem>,
+    /// Table in the `FROM` clause, if any.
+    pub from: Option<TableRef>,
+    /// `WHERE` predicate, if any.
+    pub selection: Option<Expr>,
+    /// `ORDER BY` keys, most significant first.
+    pub order_by: Vec<(Expr, SortDir)>,
+    /// `LIMIT` row count.
+    pub limit: Option<u64>,
+    /// `OFFSET` row count.
+    pub offset: Option<u64>,
+}
+
+/// Sort direction of an `ORDER BY` key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SortDir {
+    /// Ascending (the default).
+    #[default]
+    Asc,
+    /// Descending.
+    Desc,
+}
+
+/// `INSERT INTO table [(columns)] VALUES (row), ...`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Insert {
+    /// Target table.
+    pub table: String,
+    /// Listed columns; empty when the statement names none.
+    pub columns: Vec<String>,
+    /// One expression list per row.
+    pub values: Vec<Vec<Expr>>,
+}
+
+/// Table reference in a `FROM` clause.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableRef {
+    /// Table name.
+    pub name: String,
+    /// Alias given with `FROM t alias` or `FROM t AS alias`.
+    pub alias: Option<String>,
 }
 
 /// Projection item.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SelectItem {
     /// Asterisk.
     Star,
     /// Numeric literal.
     Number(i64),
+    /// Column reference, qualified names kept as `table.column`.
+    Column(String),
+    /// Non-integer literal; integers use [`SelectItem::Number`].
+    Literal(Literal),
+}
+
+/// Scalar expression.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expr {
+    /// Column reference, qualified names kept as `table.column`.
+    Column(String),
+    /// Constant value.
+    Literal(Literal),
+    /// `left <op> right`.
+    BinaryOp {
+        /// Operator.
+        op: BinaryOperator,
+        /// Left operand.
+        left: Box<Expr>,
+        /// Right operand.
+        right: Box<Expr>,
+    },
+}
+
+/// Literal value in an expression.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Literal {
+    /// Integer literal.
+    Int(i64),
+    /// Decimal literal.
+    Float(f64),
+    /// String literal, without the quotes.
+    Str(String),
+}
+
+/// Binary operator, from loosest to tightest binding: `OR`, `AND`, comparisons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryOperator {
+    /// `OR`
+    Or,
+    /// `AND`
+    And,
+    /// `=`
+    Eq,
+    /// `<>`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    LtEq,
+    /// `>`
+    Gt,
+    /// `>=`
+    GtEq,
 }
 
 /// Simple Cypher-like graph query AST.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CypherQuery {
     /// Queried variable name, e.g., `n` in MATCH (n)
     pub variable: String,
@@ -4,7 +4,7 @@
 mod token;
 mod ast;
 
-pub use token::{Token, Lexer};
+pub use token::{LexItem, Lexer, Span, Token};
 pub use ast::*;
 
 pub mod parser;
@@ -22,4 +22,33 @@ mod tests {
         let kinds: Vec<Token> = tokens.into_iter().map(|t| t.kind).collect();
         assert_eq!(kinds, vec![Token::Select, Token::Number, Token::Semicolon]);
     }
+
+    #[test]
+    fn tokenize_operators() {
+        let kinds = |sql| Lexer::new(sql).map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(kinds("a >= 1"), vec![Token::Identifier, Token::GtEq, Token::Number]);
+        assert_eq!(kinds("a <> b"), vec![Token::Identifier, Token::NotEq, Token::Identifier]);
+        assert_eq!(kinds("< >"), vec![Token::Lt, Token::Gt]);
+        assert_eq!(
+            kinds("NOT t.x <= 2 or y = 3 AND z < 4"),
+            vec![
+                Token::Not,
+                Token::Identifier,
+                Token::Dot,
+                Token::Identifier,
+                Token::LtEq,
+                Token::Number,
+                Token::Or,
+                Token::Identifier,
+                Token::Eq,
+                Token::Number,
+                Token::And,
+                Token::Identifier,
+                Token::Lt,
+                Token::Number,
+            ]
+        );
+        // Keywords only match whole words.
+        assert_eq!(kinds("orders android"), vec![Token::Identifier, Token::Identifier]);
+    }
 } 
\ No newline at end of file
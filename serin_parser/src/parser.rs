@@ -1,5 +1,6 @@
-use crate::ast::{Select, SelectItem, Statement};
-use crate::token::{Lexer, Token};
+//! Recursive-descent parser turning lexer output into AST statements.
+use crate::ast::{BinaryOperator, Expr, Insert, Literal, Select, SelectItem, SortDir, Statement, TableRef};
+use crate::token::{LexItem, Lexer, Span, Token};
 use thiserror::Error;
 
 /// Parsing error with location info.
@@ -9,22 +10,120 @@ pub enum ParseError {
     #[error("unexpected end of input")]
     Eof,
     /// Unexpected token.
-    #[error("unexpected token: {0:?}")]
-    Unexpected(Token),
+    #[error("unexpected {found:?} at line {line}, column {column}{}\n{snippet}", expected_list(expected))]
+    Unexpected {
+        /// Token that was found.
+        found: Token,
+        /// Tokens that would have been accepted here.
+        expected: Vec<Token>,
+        /// Byte span of `found`.
+        span: Span,
+        /// 1-based line of `found`.
+        line: usize,
+        /// 1-based column of `found`.
+        column: usize,
+        /// Source line with a caret under `found`.
+        snippet: String,
+    },
+    /// Input the lexer could not turn into any token.
+    #[error("invalid token {text:?} at line {line}, column {column}\n{snippet}")]
+    InvalidToken {
+        /// Offending source text.
+        text: String,
+        /// Byte span of `text`.
+        span: Span,
+        /// 1-based line of `text`.
+        line: usize,
+        /// 1-based column of `text`.
+        column: usize,
+        /// Source line with a caret under `text`.
+        snippet: String,
+    },
+    /// Numeric literal that does not fit the target type.
+    #[error("invalid number literal: {0}")]
+    InvalidNumber(String),
+    /// `VALUES` row whose length differs from the column list.
+    #[error("expected {expected} values per row, found {found}")]
+    ValueCount {
+        /// Values required by the column list (or the first row).
+        expected: usize,
+        /// Values in the offending row.
+        found: usize,
+    },
+    /// Cypher `RETURN` names a variable the `MATCH` did not bind.
+    #[error("unknown variable: {0}")]
+    UnknownVariable(String),
 }
 
+fn expected_list(expected: &[Token]) -> String {
+    match expected {
+        [] => String::new(),
+        [one] => format!(", expected {one:?}"),
+        many => format!(", expected one of {}", many.iter().map(|t| format!("{t:?}")).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// Build a [`ParseError::Unexpected`] pointing at `item`, or
+/// [`ParseError::InvalidToken`] if the lexer could not recognise it.
+fn unexpected(item: &LexItem<'_>, expected: &[Token]) -> ParseError {
+    let (line, column, line_text) = item.location();
+    let width = item.text.chars().count().max(1);
+    let snippet = format!("{}\n{}{}", line_text, " ".repeat(column - 1), "^".repeat(width));
+    if item.kind == Token::Error {
+        return ParseError::InvalidToken {
+            text: item.text.to_string(),
+            span: item.span,
+            line,
+            column,
+            snippet,
+        };
+    }
+    ParseError::Unexpected {
+        found: item.kind,
+        expected: expected.to_vec(),
+        span: item.span,
+        line,
+        column,
+        snippet,
+    }
+}
+
+/// Consume the next token and report it as unexpected.
+fn unexpected_next<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+    expected: &[Token],
+) -> ParseError {
+    match lex.next() {
+        Some(item) => unexpected(&item, expected),
+        None => ParseError::Eof,
+    }
+}
+
+const STATEMENT_START: &[Token] = &[Token::Select, Token::Insert, Token::MatchKw];
+const SELECT_ITEM_START: &[Token] = &[Token::Star, Token::Number, Token::Minus, Token::String, Token::Identifier];
+const EXPR_START: &[Token] = &[Token::Identifier, Token::Number, Token::Minus, Token::String, Token::LParen];
+
 /// Parse an SQL string into an AST [`Statement`].
+///
+/// The statement may end in one `;`; anything after that is an error.
 pub fn parse(sql: &str) -> Result<Statement, ParseError> {
     let mut lex = Lexer::new(sql).peekable();
-    match lex.peek().ok_or(ParseError::Eof)?.kind {
-        Token::Select => parse_select(&mut lex),
-        Token::MatchKw => parse_cypher(&mut lex),
-        tok => Err(ParseError::Unexpected(tok)),
+    let statement = match lex.peek().ok_or(ParseError::Eof)?.kind {
+        Token::Select => parse_select(&mut lex)?,
+        Token::Insert => parse_insert(&mut lex)?,
+        Token::MatchKw => parse_cypher(&mut lex)?,
+        _ => return Err(unexpected_next(&mut lex, STATEMENT_START)),
+    };
+    let expected: &[Token] =
+        if lex.next_if(|item| item.kind == Token::Semicolon).is_some() { &[] } else { &[Token::Semicolon] };
+    match lex.next() {
+        Some(item) => Err(unexpected(&item, expected)),
+        None => Ok(statement),
     }
 }
 
-fn parse_select(
-    lex: &mut std::iter::Peekable<impl Iterator<Item = crate::token::LexItem>>,
+fn parse_select<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
 ) -> Result<Statement, ParseError> {
     // consume SELECT
     lex.next();
@@ -37,11 +136,12 @@ fn parse_select(
                 lex.next();
                 SelectItem::Star
             }
-            Token::Number => {
-                let num: i64 = lex.next().unwrap().span.start as i64; // placeholder parse slice later
-                SelectItem::Number(num)
-            }
-            tok => return Err(ParseError::Unexpected(tok)),
+            Token::Number | Token::Minus | Token::String => match parse_literal(lex)? {
+                Literal::Int(num) => SelectItem::Number(num),
+                lit => SelectItem::Literal(lit),
+            },
+            Token::Identifier => SelectItem::Column(parse_column_name(lex)?),
+            _ => return Err(unexpected_next(lex, SELECT_ITEM_START)),
         };
         projection.push(item);
 
@@ -54,59 +154,229 @@ fn parse_select(
         }
     }
 
-    // Optional SEMICOLON
-    if let Some(item) = lex.peek() {
-        if item.kind == Token::Semicolon {
-            lex.next();
+    let from = if lex.next_if(|item| item.kind == Token::From).is_some() {
+        Some(parse_table_ref(lex)?)
+    } else {
+        None
+    };
+
+    let selection = if lex.next_if(|item| item.kind == Token::Where).is_some() {
+        Some(parse_expr(lex)?)
+    } else {
+        None
+    };
+
+    let mut order_by = Vec::new();
+    if lex.next_if(|item| item.kind == Token::Order).is_some() {
+        expect(lex, Token::By)?;
+        loop {
+            let key = parse_expr(lex)?;
+            let dir = match lex.next_if(|item| matches!(item.kind, Token::Asc | Token::Desc)) {
+                Some(item) if item.kind == Token::Desc => SortDir::Desc,
+                _ => SortDir::Asc,
+            };
+            order_by.push((key, dir));
+            if lex.next_if(|item| item.kind == Token::Comma).is_none() {
+                break;
+            }
         }
     }
 
-    Ok(Statement::Select(Select { projection }))
+    let limit = match lex.next_if(|item| item.kind == Token::Limit) {
+        Some(_) => Some(parse_count(lex)?),
+        None => None,
+    };
+    let offset = match lex.next_if(|item| item.kind == Token::Offset) {
+        Some(_) => Some(parse_count(lex)?),
+        None => None,
+    };
+
+    Ok(Statement::Select(Select { projection, from, selection, order_by, limit, offset }))
 }
 
-fn parse_cypher(
-    lex: &mut std::iter::Peekable<impl Iterator<Item = crate::token::LexItem>>,
+/// Non-negative integer after `LIMIT` / `OFFSET`.
+fn parse_count<'a>(lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>) -> Result<u64, ParseError> {
+    let item = expect(lex, Token::Number)?;
+    item.text.parse().map_err(|_| ParseError::InvalidNumber(item.text.to_string()))
+}
+
+fn parse_insert<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
 ) -> Result<Statement, ParseError> {
-    // consume MATCH
+    // consume INSERT
     lex.next();
+    expect(lex, Token::Into)?;
+    let table = expect(lex, Token::Identifier)?.text.to_string();
 
-    // Expect '('
-    match lex.next().ok_or(ParseError::Eof)?.kind {
-        Token::LParen => {}
-        tok => return Err(ParseError::Unexpected(tok)),
+    let mut columns = Vec::new();
+    if lex.next_if(|item| item.kind == Token::LParen).is_some() {
+        loop {
+            columns.push(expect(lex, Token::Identifier)?.text.to_string());
+            if lex.next_if(|item| item.kind == Token::Comma).is_none() {
+                break;
+            }
+        }
+        expect(lex, Token::RParen)?;
     }
 
-    // variable identifier
-    let var_item = lex.next().ok_or(ParseError::Eof)?;
-    let Token::Identifier = var_item.kind else {
-        return Err(ParseError::Unexpected(var_item.kind));
-    };
-    // For now, we can't capture name easily without source slice; use placeholder length
-    let variable = "v".to_string();
+    expect(lex, Token::Values)?;
+    let mut values: Vec<Vec<Expr>> = Vec::new();
+    loop {
+        expect(lex, Token::LParen)?;
+        let mut row = Vec::new();
+        loop {
+            row.push(parse_expr(lex)?);
+            if lex.next_if(|item| item.kind == Token::Comma).is_none() {
+                break;
+            }
+        }
+        expect(lex, Token::RParen)?;
+        // Without a column list every row must match the first one.
+        let expected = if columns.is_empty() { values.first().map_or(row.len(), Vec::len) } else { columns.len() };
+        if row.len() != expected {
+            return Err(ParseError::ValueCount { expected, found: row.len() });
+        }
+        values.push(row);
+        if lex.next_if(|item| item.kind == Token::Comma).is_none() {
+            break;
+        }
+    }
 
-    // Expect ')'
-    match lex.next().ok_or(ParseError::Eof)?.kind {
-        Token::RParen => {}
-        tok => return Err(ParseError::Unexpected(tok)),
+    Ok(Statement::Insert(Insert { table, columns, values }))
+}
+
+/// Consume the next token, which must be `kind`.
+fn expect<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+    kind: Token,
+) -> Result<LexItem<'a>, ParseError> {
+    let item = lex.next().ok_or(ParseError::Eof)?;
+    if item.kind != kind {
+        return Err(unexpected(&item, &[kind]));
     }
+    Ok(item)
+}
 
-    // Expect RETURN keyword
-    match lex.next().ok_or(ParseError::Eof)?.kind {
-        Token::ReturnKw => {}
-        tok => return Err(ParseError::Unexpected(tok)),
+/// Expression with `OR` binding loosest, then `AND`, then comparisons.
+fn parse_expr<'a>(lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>) -> Result<Expr, ParseError> {
+    let mut left = parse_and(lex)?;
+    while lex.next_if(|item| item.kind == Token::Or).is_some() {
+        let right = parse_and(lex)?;
+        left = binary(BinaryOperator::Or, left, right);
     }
+    Ok(left)
+}
 
-    // Skip variable after RETURN
-    match lex.next().ok_or(ParseError::Eof)?.kind {
-        Token::Identifier => {}
-        tok => return Err(ParseError::Unexpected(tok)),
+fn parse_and<'a>(lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>) -> Result<Expr, ParseError> {
+    let mut left = parse_comparison(lex)?;
+    while lex.next_if(|item| item.kind == Token::And).is_some() {
+        let right = parse_comparison(lex)?;
+        left = binary(BinaryOperator::And, left, right);
     }
+    Ok(left)
+}
+
+/// Comparisons do not chain: `a < b < c` is rejected at the second `<`.
+fn parse_comparison<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+) -> Result<Expr, ParseError> {
+    let left = parse_primary(lex)?;
+    let op = match lex.peek().map(|item| item.kind) {
+        Some(Token::Eq) => BinaryOperator::Eq,
+        Some(Token::NotEq) => BinaryOperator::NotEq,
+        Some(Token::Lt) => BinaryOperator::Lt,
+        Some(Token::LtEq) => BinaryOperator::LtEq,
+        Some(Token::Gt) => BinaryOperator::Gt,
+        Some(Token::GtEq) => BinaryOperator::GtEq,
+        _ => return Ok(left),
+    };
+    lex.next();
+    let right = parse_primary(lex)?;
+    Ok(binary(op, left, right))
+}
 
-    // Optional semicolon
-    if let Some(item) = lex.peek() {
-        if item.kind == Token::Semicolon {
+fn parse_primary<'a>(lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>) -> Result<Expr, ParseError> {
+    match lex.peek().ok_or(ParseError::Eof)?.kind {
+        Token::Identifier => Ok(Expr::Column(parse_column_name(lex)?)),
+        Token::Number | Token::Minus | Token::String => Ok(Expr::Literal(parse_literal(lex)?)),
+        Token::LParen => {
             lex.next();
+            let expr = parse_expr(lex)?;
+            expect(lex, Token::RParen)?;
+            Ok(expr)
         }
+        _ => Err(unexpected_next(lex, EXPR_START)),
+    }
+}
+
+/// Number (optionally negated) or quoted string.
+fn parse_literal<'a>(lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>) -> Result<Literal, ParseError> {
+    let negative = lex.next_if(|item| item.kind == Token::Minus).is_some();
+    let item = lex.next().ok_or(ParseError::Eof)?;
+    match item.kind {
+        Token::String if !negative => Ok(Literal::Str(item.text[1..item.text.len() - 1].to_string())),
+        Token::Number => {
+            // Parse the sign together with the digits so i64::MIN is accepted.
+            let text = if negative { format!("-{}", item.text) } else { item.text.to_string() };
+            if text.contains('.') {
+                text.parse().map(Literal::Float).map_err(|_| ParseError::InvalidNumber(text))
+            } else {
+                text.parse().map(Literal::Int).map_err(|_| ParseError::InvalidNumber(text))
+            }
+        }
+        _ if negative => Err(unexpected(&item, &[Token::Number])),
+        _ => Err(unexpected(&item, &[Token::Number, Token::String])),
+    }
+}
+
+fn binary(op: BinaryOperator, left: Expr, right: Expr) -> Expr {
+    Expr::BinaryOp { op, left: Box::new(left), right: Box::new(right) }
+}
+
+/// `name [[AS] alias]` after `FROM`.
+fn parse_table_ref<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+) -> Result<TableRef, ParseError> {
+    let table = expect(lex, Token::Identifier)?;
+    // `AS` is not a keyword token, so it lexes as an identifier.
+    let mut alias = lex.next_if(|item| item.kind == Token::Identifier);
+    if alias.as_ref().is_some_and(|item| item.text.eq_ignore_ascii_case("as")) {
+        alias = Some(expect(lex, Token::Identifier)?);
+    }
+    Ok(TableRef { name: table.text.to_string(), alias: alias.map(|item| item.text.to_string()) })
+}
+
+/// Identifier optionally qualified by a table name (`t.col`).
+fn parse_column_name<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+) -> Result<String, ParseError> {
+    let mut name = lex.next().ok_or(ParseError::Eof)?.text.to_string();
+    if lex.next_if(|item| item.kind == Token::Dot).is_some() {
+        let column = expect(lex, Token::Identifier)?;
+        name.push('.');
+        name.push_str(column.text);
+    }
+    Ok(name)
+}
+
+fn parse_cypher<'a>(
+    lex: &mut std::iter::Peekable<impl Iterator<Item = LexItem<'a>>>,
+) -> Result<Statement, ParseError> {
+    // consume MATCH
+    lex.next();
+
+    expect(lex, Token::LParen)?;
+
+    // variable identifier
+    let variable = expect(lex, Token::Identifier)?.text.to_string();
+
+    expect(lex, Token::RParen)?;
+    expect(lex, Token::ReturnKw)?;
+
+    // RETURN must name the variable bound by MATCH
+    let returned = expect(lex, Token::Identifier)?;
+    if returned.text != variable {
+        return Err(ParseError::UnknownVariable(returned.text.to_string()));
     }
 
     Ok(Statement::GraphQuery(crate::ast::CypherQuery { variable }))
@@ -127,6 +397,194 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_number_literal_value() {
+        let stmt = parse("SELECT 42;").unwrap();
+        match stmt {
+            Statement::Select(sel) => {
+                assert_eq!(sel.projection, vec![SelectItem::Number(42)]);
+            }
+            _ => panic!("expected select"),
+        }
+    }
+
+    #[test]
+    fn parse_literals() {
+        let Statement::Select(sel) = parse("SELECT 'hello', -3, 2.5;").unwrap() else { panic!("expected select") };
+        assert_eq!(
+            sel.projection,
+            vec![
+                SelectItem::Literal(Literal::Str("hello".into())),
+                SelectItem::Number(-3),
+                SelectItem::Literal(Literal::Float(2.5)),
+            ]
+        );
+
+        let Statement::Select(sel) = parse("SELECT -9223372036854775808;").unwrap() else { panic!("expected select") };
+        assert_eq!(sel.projection, vec![SelectItem::Number(i64::MIN)]);
+
+        let Statement::Select(sel) = parse("SELECT * FROM t WHERE name = 'x' OR f < -0.5;").unwrap() else {
+            panic!("expected select")
+        };
+        let lit = |l| Box::new(Expr::Literal(l));
+        let Some(Expr::BinaryOp { left, right, .. }) = sel.selection else { panic!("expected OR") };
+        assert!(matches!(*left, Expr::BinaryOp { right, .. } if right == lit(Literal::Str("x".into()))));
+        assert!(matches!(*right, Expr::BinaryOp { right, .. } if right == lit(Literal::Float(-0.5))));
+    }
+
+    #[test]
+    fn parse_column_projection() {
+        let stmt = parse("SELECT name, id;").unwrap();
+        match stmt {
+            Statement::Select(sel) => {
+                assert_eq!(sel.projection, vec![SelectItem::Column("name".into()), SelectItem::Column("id".into())]);
+            }
+            _ => panic!("expected select"),
+        }
+        let stmt = parse("SELECT u.name;").unwrap();
+        match stmt {
+            Statement::Select(sel) => assert_eq!(sel.projection, vec![SelectItem::Column("u.name".into())]),
+            _ => panic!("expected select"),
+        }
+    }
+
+    #[test]
+    fn parse_from_clause() {
+        let Statement::Select(sel) = parse("SELECT * FROM users;").unwrap() else { panic!("expected select") };
+        assert_eq!(sel.from, Some(TableRef { name: "users".into(), alias: None }));
+
+        let Statement::Select(sel) = parse("SELECT u.id FROM users AS u;").unwrap() else { panic!("expected select") };
+        assert_eq!(sel.from.unwrap().alias.as_deref(), Some("u"));
+
+        let Statement::Select(sel) = parse("SELECT 1;").unwrap() else { panic!("expected select") };
+        assert_eq!(sel.from, None);
+
+        assert!(matches!(parse("SELECT * FROM 1;"), Err(ParseError::Unexpected { found: Token::Number, .. })));
+    }
+
+    #[test]
+    fn parse_where_precedence() {
+        let Statement::Select(sel) = parse("SELECT * FROM t WHERE a > 1 AND b = 2;").unwrap() else {
+            panic!("expected select")
+        };
+        let cmp = |op, col: &str, v| binary(op, Expr::Column(col.into()), Expr::Literal(Literal::Int(v)));
+        assert_eq!(
+            sel.selection,
+            Some(binary(BinaryOperator::And, cmp(BinaryOperator::Gt, "a", 1), cmp(BinaryOperator::Eq, "b", 2)))
+        );
+
+        // AND binds tighter than OR.
+        let Statement::Select(sel) = parse("SELECT * FROM t WHERE a <> 1 OR b <= 2 AND c >= 3;").unwrap() else {
+            panic!("expected select")
+        };
+        let Some(Expr::BinaryOp { op: BinaryOperator::Or, left, right }) = sel.selection else {
+            panic!("expected OR at the root")
+        };
+        assert_eq!(*left, cmp(BinaryOperator::NotEq, "a", 1));
+        assert_eq!(
+            *right,
+            binary(BinaryOperator::And, cmp(BinaryOperator::LtEq, "b", 2), cmp(BinaryOperator::GtEq, "c", 3))
+        );
+    }
+
+    #[test]
+    fn parse_insert_rows() {
+        let int = |v| Expr::Literal(Literal::Int(v));
+        let text = |s: &str| Expr::Literal(Literal::Str(s.into()));
+
+        let Statement::Insert(ins) = parse("INSERT INTO t (a, b) VALUES (1, 'x');").unwrap() else {
+            panic!("expected insert")
+        };
+        assert_eq!(ins.table, "t");
+        assert_eq!(ins.columns, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(ins.values, vec![vec![int(1), text("x")]]);
+
+        let Statement::Insert(ins) = parse("INSERT INTO t (a, b) VALUES (1, 'x'), (2, 'y');").unwrap() else {
+            panic!("expected insert")
+        };
+        assert_eq!(ins.values, vec![vec![int(1), text("x")], vec![int(2), text("y")]]);
+
+        let Statement::Insert(ins) = parse("INSERT INTO t VALUES (-1);").unwrap() else { panic!("expected insert") };
+        assert!(ins.columns.is_empty());
+        assert_eq!(ins.values, vec![vec![int(-1)]]);
+    }
+
+    #[test]
+    fn insert_value_count_must_match() {
+        assert!(matches!(
+            parse("INSERT INTO t (a, b) VALUES (1, 'x'), (2);"),
+            Err(ParseError::ValueCount { expected: 2, found: 1 })
+        ));
+        assert!(matches!(
+            parse("INSERT INTO t VALUES (1), (2, 3);"),
+            Err(ParseError::ValueCount { expected: 1, found: 2 })
+        ));
+    }
+
+    #[test]
+    fn error_reports_position_and_expectation() {
+        let err = parse("SELECT FROM;").unwrap_err();
+        let ParseError::Unexpected { found, ref expected, span, line, column, .. } = err else {
+            panic!("expected unexpected-token error, got {err:?}")
+        };
+        assert_eq!(found, Token::From);
+        assert_eq!(expected, SELECT_ITEM_START);
+        assert_eq!((span.start, span.end, line, column), (7, 11, 1, 8));
+        assert_eq!(
+            err.to_string(),
+            "unexpected From at line 1, column 8, expected one of Star, Number, Minus, String, Identifier\n\
+             SELECT FROM;\n       ^^^^"
+        );
+
+        let err = parse("SELECT a\nFROM t\nWHERE (a = 1;").unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { found: Token::Semicolon, line: 3, column: 13, .. }));
+    }
+
+    #[test]
+    fn comparisons_do_not_chain() {
+        let err = parse("SELECT a FROM t WHERE a < b < c;").unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { found: Token::Lt, column: 29, .. }), "{err:?}");
+    }
+
+    #[test]
+    fn trailing_tokens_are_rejected() {
+        for sql in ["SELECT 1 2 3", "SELECT * FROM t x y z;", "INSERT INTO t VALUES (1) garbage", "SELECT 1; SELECT 2"] {
+            let err = parse(sql).unwrap_err();
+            assert!(matches!(err, ParseError::Unexpected { .. }), "{sql}: {err:?}");
+        }
+        let err = parse("SELECT 1;;").unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { found: Token::Semicolon, ref expected, .. } if expected.is_empty()));
+        assert!(parse("SELECT 1;").is_ok());
+        assert!(parse("SELECT 1").is_ok());
+    }
+
+    #[test]
+    fn invalid_character_is_a_lexer_diagnostic() {
+        let err = parse("SELECT @;").unwrap_err();
+        assert!(matches!(err, ParseError::InvalidToken { ref text, line: 1, column: 8, .. } if text == "@"));
+        assert_eq!(err.to_string(), "invalid token \"@\" at line 1, column 8\nSELECT @;\n       ^");
+    }
+
+    #[test]
+    fn parse_order_by_limit_offset() {
+        let Statement::Select(sel) = parse("SELECT * FROM t ORDER BY a DESC, b LIMIT 10 OFFSET 5;").unwrap() else {
+            panic!("expected select")
+        };
+        assert_eq!(
+            sel.order_by,
+            vec![(Expr::Column("a".into()), SortDir::Desc), (Expr::Column("b".into()), SortDir::Asc)]
+        );
+        assert_eq!((sel.limit, sel.offset), (Some(10), Some(5)));
+
+        let Statement::Select(sel) = parse("SELECT * FROM t WHERE a > 1 ORDER BY t.b ASC;").unwrap() else {
+            panic!("expected select")
+        };
+        assert_eq!(sel.order_by, vec![(Expr::Column("t.b".into()), SortDir::Asc)]);
+        assert_eq!((sel.limit, sel.offset), (None, None));
+
+        assert!(matches!(parse("SELECT * FROM t LIMIT 1.5;"), Err(ParseError::InvalidNumber(_))));
+    }
+
     #[test]
     fn parse_simple_cypher() {
         let stmt = parse("MATCH (n) RETURN n;").unwrap();
@@ -135,4 +593,13 @@ mod tests {
             _ => panic!("expected graph query"),
         }
     }
+
+    #[test]
+    fn cypher_captures_variable_name() {
+        let Statement::GraphQuery(q) = parse("MATCH (person) RETURN person;").unwrap() else {
+            panic!("expected graph query")
+        };
+        assert_eq!(q.variable, "person");
+        assert!(matches!(parse("MATCH (person) RETURN p;"), Err(ParseError::UnknownVariable(v)) if v == "p"));
+    }
 } 
\ No newline at end of file
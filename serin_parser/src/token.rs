@@ -11,7 +11,7 @@ pub struct Span {
 
 /// SQL token kinds recognised by SerinDB lexer.
 #[derive(Logos, Debug, PartialEq, Clone, Copy)]
-#[logos(skip r"[ \t\n\r]+", error = Error)]
+#[logos(skip r"[ \t\n\r]+")]
 pub enum Token {
     /// `SELECT` keyword.
     #[token("SELECT", ignore(ascii_case))]
@@ -19,6 +19,12 @@ pub enum Token {
     /// `INSERT` keyword.
     #[token("INSERT", ignore(ascii_case))]
     Insert,
+    /// `INTO` keyword.
+    #[token("INTO", ignore(ascii_case))]
+    Into,
+    /// `VALUES` keyword.
+    #[token("VALUES", ignore(ascii_case))]
+    Values,
     /// `UPDATE` keyword.
     #[token("UPDATE", ignore(ascii_case))]
     Update,
@@ -31,9 +37,60 @@ pub enum Token {
     /// `WHERE` keyword.
     #[token("WHERE", ignore(ascii_case))]
     Where,
+    /// `ORDER` keyword.
+    #[token("ORDER", ignore(ascii_case))]
+    Order,
+    /// `BY` keyword.
+    #[token("BY", ignore(ascii_case))]
+    By,
+    /// `ASC` keyword.
+    #[token("ASC", ignore(ascii_case))]
+    Asc,
+    /// `DESC` keyword.
+    #[token("DESC", ignore(ascii_case))]
+    Desc,
+    /// `LIMIT` keyword.
+    #[token("LIMIT", ignore(ascii_case))]
+    Limit,
+    /// `OFFSET` keyword.
+    #[token("OFFSET", ignore(ascii_case))]
+    Offset,
+    /// `AND` keyword.
+    #[token("AND", ignore(ascii_case))]
+    And,
+    /// `OR` keyword.
+    #[token("OR", ignore(ascii_case))]
+    Or,
+    /// `NOT` keyword.
+    #[token("NOT", ignore(ascii_case))]
+    Not,
+    /// Equals `=`.
+    #[token("=")]
+    Eq,
+    /// Not equal `<>`.
+    #[token("<>")]
+    NotEq,
+    /// Less than `<`.
+    #[token("<")]
+    Lt,
+    /// Less than or equal `<=`.
+    #[token("<=")]
+    LtEq,
+    /// Greater than `>`.
+    #[token(">")]
+    Gt,
+    /// Greater than or equal `>=`.
+    #[token(">=")]
+    GtEq,
     /// Comma `,`.
     #[token(",")]
     Comma,
+    /// Dot `.` in qualified names.
+    #[token(".")]
+    Dot,
+    /// Minus `-`.
+    #[token("-")]
+    Minus,
     /// Asterisk `*`.
     #[token("*")]
     Star,
@@ -46,16 +103,16 @@ pub enum Token {
     /// Right parenthesis `)`.
     #[token(")")]
     RParen,
-    /// Numeric literal.
-    #[regex(r"[0-9]+", |lex| lex.slice().parse())]
+    /// Numeric literal, integer or decimal; the digits are in [`LexItem::text`].
+    #[regex(r"[0-9]+(\.[0-9]+)?")]
     Number,
-    /// String literal.
-    #[regex(r#"'([^']*)'"#, |lex| lex.slice()[1..lex.slice().len()-1].to_string())]
+    /// String literal, quotes included in [`LexItem::text`].
+    #[regex(r#"'([^']*)'"#)]
     String,
     /// Identifier (table/column).
     #[regex(r"[A-Za-z_][A-Za-z0-9_]*")]
     Identifier,
-    /// Unrecognised token.
+    /// Unrecognised input; the offending text is in [`LexItem::text`].
     Error,
     /// `MATCH` keyword.
     #[token("MATCH", ignore(ascii_case))]
@@ -65,18 +122,36 @@ pub enum Token {
     ReturnKw,
 }
 
-/// Output of the lexer containing token and span.
+/// Output of the lexer containing token, span and source text.
 #[derive(Debug, Clone, PartialEq)]
-pub struct LexItem {
+pub struct LexItem<'input> {
     /// Token kind.
     pub kind: Token,
     /// Text span.
     pub span: Span,
+    /// Source slice covered by `span`.
+    pub text: &'input str,
+    /// Whole input, so diagnostics can locate `span` on demand.
+    pub source: &'input str,
+}
+
+impl<'input> LexItem<'input> {
+    /// 1-based line and column (in characters) of `span.start`, and the full
+    /// source line it is on. Scans the input, so only call it for diagnostics.
+    pub fn location(&self) -> (usize, usize, &'input str) {
+        let before = &self.source[..self.span.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        let line_text = self.source[line_start..].lines().next().unwrap_or("");
+        (line, column, line_text)
+    }
 }
 
 /// Lexer iterator over `LexItem`s.
 pub struct Lexer<'input> {
     inner: logos::Lexer<'input, Token>,
+    source: &'input str,
 }
 
 impl<'input> Lexer<'input> {
@@ -84,19 +159,20 @@ impl<'input> Lexer<'input> {
     pub fn new(source: &'input str) -> Self {
         Self {
             inner: Token::lexer(source),
+            source,
         }
     }
 }
 
 impl<'input> Iterator for Lexer<'input> {
-    type Item = LexItem;
+    type Item = LexItem<'input>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let kind = self.inner.next()?;
+        let kind = self.inner.next()?.unwrap_or(Token::Error);
         let span = Span {
             start: self.inner.span().start,
             end: self.inner.span().end,
         };
-        Some(LexItem { kind, span })
+        Some(LexItem { kind, span, text: self.inner.slice(), source: self.source })
     }
 } 
\ No newline at end of file
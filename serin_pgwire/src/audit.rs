@@ -0,0 +1,103 @@
+//! Structured audit events for connection and authentication activity.
+//!
+//! Every event is emitted at INFO level with target [`AUDIT_TARGET`] and a
+//! constant `audit = true` field, so a subscriber can route them to a
+//! dedicated sink (see `serin_log::init`) independently of regular logs.
+
+use std::net::SocketAddr;
+
+use tracing::info;
+
+/// Tracing target used for all audit events.
+pub const AUDIT_TARGET: &str = "serin::audit";
+
+/// A client opened a TCP connection.
+pub fn connection_attempt(peer: SocketAddr) {
+    info!(target: AUDIT_TARGET, audit = true, event = "connection_attempt", peer = %peer);
+}
+
+/// `user` authenticated successfully.
+pub fn auth_success(user: &str, peer: SocketAddr) {
+    info!(target: AUDIT_TARGET, audit = true, event = "auth_success", user, peer = %peer);
+}
+
+/// Authentication for `user` was rejected.
+pub fn auth_failure(user: &str, peer: SocketAddr, reason: &str) {
+    info!(target: AUDIT_TARGET, audit = true, event = "auth_failure", user, peer = %peer, reason);
+}
+
+/// An authenticated session for `user` ended.
+pub fn disconnect(user: &str, peer: SocketAddr) {
+    info!(target: AUDIT_TARGET, audit = true, event = "disconnect", user, peer = %peer);
+}
+
+/// Emits [`disconnect`] when dropped, so the event is logged however the session ends.
+pub struct DisconnectGuard {
+    user: String,
+    peer: SocketAddr,
+}
+
+impl DisconnectGuard {
+    /// Guard for the session of `user` connected from `peer`.
+    pub fn new(user: &str, peer: SocketAddr) -> Self {
+        Self { user: user.to_string(), peer }
+    }
+}
+
+impl Drop for DisconnectGuard {
+    fn drop(&mut self) {
+        disconnect(&self.user, self.peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    type Captured = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+    struct Capture(Captured);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for Capture {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = HashMap::new();
+            event.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push((event.metadata().target().to_string(), fields));
+        }
+    }
+
+    #[test]
+    fn auth_failure_emits_audit_event() {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::registry().with(Capture(captured.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            auth_failure("mallory", "10.0.0.7:40000".parse().unwrap(), "bad password");
+        });
+        let events = captured.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let (target, fields) = &events[0];
+        assert_eq!(target, AUDIT_TARGET);
+        assert_eq!(fields["audit"], "true");
+        assert_eq!(fields["event"], "auth_failure");
+        assert_eq!(fields["user"], "mallory");
+        assert_eq!(fields["reason"], "bad password");
+        assert_eq!(fields["peer"], "10.0.0.7:40000");
+    }
+}
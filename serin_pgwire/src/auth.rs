@@ -2,21 +2,42 @@
 
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
+use arc_swap::ArcSwap;
 use hmac::{Hmac, Mac};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use md5::{Digest, Md5};
 use serde::Deserialize;
-use sha2::Sha256;
-use pbkdf2::pbkdf2_hmac;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+use subtle::ConstantTimeEq;
+use pbkdf2::pbkdf2;
 use base64::{engine::general_purpose, Engine as _};
 use rand::{RngCore, rngs::OsRng};
 
+use crate::hba::HbaConfig;
+use crate::ratelimit::RateLimitConfig;
+use crate::tls::TlsConfig;
+use tokio_rustls::TlsAcceptor;
+
 type HmacSha256 = Hmac<Sha256>;
 
 #[derive(Debug, Deserialize)]
 pub struct AuthConfig {
     pub users: HashMap<String, String>, // username -> plaintext password (demo)
+    /// Host-based allow/deny rules; allows everything when omitted.
+    #[serde(default)]
+    pub hba: HbaConfig,
+    /// Server TLS settings; SSLRequest is declined when omitted.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Client-certificate authentication; password auth only when omitted.
+    #[serde(default)]
+    pub cert_auth: Option<CertAuthConfig>,
+    /// Throttling of failed password attempts per client address.
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
 }
 
 impl AuthConfig {
@@ -29,8 +50,125 @@ impl AuthConfig {
     pub fn password(&self, user: &str) -> Option<&str> { self.users.get(user).map(|s| s.as_str()) }
 }
 
+/// The live [`AuthConfig`] of a server and the TLS acceptor built from it,
+/// replaced together atomically on reload. Each connection takes a snapshot
+/// when accepted, so a reload (including new TLS certificates) only affects
+/// connections accepted afterwards. Rate limits are read from the current
+/// config on every check, so they apply to open connections too.
+pub struct SharedAuthConfig {
+    current: ArcSwap<LoadedAuth>,
+}
+
+struct LoadedAuth {
+    config: Arc<AuthConfig>,
+    tls: Option<TlsAcceptor>,
+}
+
+impl LoadedAuth {
+    fn new(config: Arc<AuthConfig>) -> anyhow::Result<Self> {
+        let tls = config.tls.as_ref().map(|t| t.acceptor()).transpose()?;
+        Ok(Self { config, tls })
+    }
+}
+
+impl SharedAuthConfig {
+    /// Fails if the TLS files of `config` are unusable.
+    pub fn new(config: Arc<AuthConfig>) -> anyhow::Result<Self> {
+        Ok(Self { current: ArcSwap::from_pointee(LoadedAuth::new(config)?) })
+    }
+
+    /// Snapshot of the current config.
+    pub fn load(&self) -> Arc<AuthConfig> {
+        self.current.load().config.clone()
+    }
+
+    /// Snapshot of the current config with its TLS acceptor, if any.
+    pub fn load_with_tls(&self) -> (Arc<AuthConfig>, Option<TlsAcceptor>) {
+        let current = self.current.load();
+        (current.config.clone(), current.tls.clone())
+    }
+
+    /// Re-read `path` and swap it in. The current config stays in place if
+    /// the file does not parse or its TLS files are unusable.
+    pub fn reload(&self, path: &str) -> anyhow::Result<()> {
+        let loaded = LoadedAuth::new(AuthConfig::load(path)?)?;
+        self.current.store(Arc::new(loaded));
+        Ok(())
+    }
+
+    /// [`reload`](Self::reload) whenever `path` changes, until the returned
+    /// watcher is dropped. The parent directory is watched so editors that
+    /// replace the file by renaming are noticed too.
+    pub fn watch(self: &Arc<Self>, path: &str) -> notify::Result<RecommendedWatcher> {
+        let file = Path::new(path);
+        let dir = file.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let name = file.file_name().map(|n| n.to_os_string());
+        let shared = Arc::clone(self);
+        let owned_path = path.to_string();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if event.kind.is_access() || !event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                return;
+            }
+            match shared.reload(&owned_path) {
+                Ok(()) => tracing::info!(path = %owned_path, "auth config reloaded"),
+                Err(e) => tracing::warn!(path = %owned_path, "auth config not reloaded: {e:#}"),
+            }
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+}
+
+// === Client certificates ===
+/// What to do when a client has no certificate or one that maps to no user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertFallback {
+    /// Continue with password authentication.
+    #[default]
+    Password,
+    /// Refuse the connection.
+    Reject,
+}
+
+/// Maps client certificate identities to SerinDB users.
+#[derive(Debug, Default, Deserialize)]
+pub struct CertAuthConfig {
+    /// Certificate identity (subject CN or SAN DNS name) -> user.
+    #[serde(default)]
+    pub map: HashMap<String, String>,
+    /// Policy for missing or unmapped certificates.
+    #[serde(default)]
+    pub fallback: CertFallback,
+}
+
+/// Outcome of certificate authentication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertAuth {
+    /// The certificate maps to the requested user; no password needed.
+    Authenticated,
+    /// Fall back to password authentication.
+    Password,
+    /// Reject the connection.
+    Rejected,
+}
+
+impl CertAuthConfig {
+    /// Authenticate `user` from the verified certificate `identities`.
+    pub fn authenticate(&self, identities: &[String], user: &str) -> CertAuth {
+        if identities.iter().any(|id| self.map.get(id).is_some_and(|u| u == user)) {
+            return CertAuth::Authenticated;
+        }
+        match self.fallback {
+            CertFallback::Password => CertAuth::Password,
+            CertFallback::Reject => CertAuth::Rejected,
+        }
+    }
+}
+
 // === MD5 ===
-fn md5_hex(data: &[u8]) -> String {
+pub(crate) fn md5_hex(data: &[u8]) -> String {
     let mut hasher = Md5::new();
     hasher.update(data);
     format!("{:x}", hasher.finalize())
@@ -80,7 +218,7 @@ pub fn build_scram_credentials(password: &str, iterations: u32) -> ScramCred {
 
 pub fn derive_salted_password(password: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
     let mut out = [0u8; 32];
-    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut out);
+    pbkdf2::<HmacSha256>(password.as_bytes(), salt, iterations, &mut out);
     out.to_vec()
 }
 
@@ -102,7 +240,155 @@ pub fn server_key(salted: &[u8]) -> Vec<u8> {
     mac.finalize().into_bytes().to_vec()
 }
 
-pub fn base64_encode(data: &[u8]) -> String { general_purpose::STANDARD.encode(data) } 
+pub fn base64_encode(data: &[u8]) -> String { general_purpose::STANDARD.encode(data) }
+
+// === SCRAM channel binding ===
+// Building blocks for SCRAM-SHA-256(-PLUS); the startup handshake does not
+// offer SASL yet and still authenticates with MD5 only.
+/// Mechanism without channel binding.
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+/// Mechanism with `tls-server-end-point` channel binding.
+pub const SCRAM_SHA_256_PLUS: &str = "SCRAM-SHA-256-PLUS";
+/// The only channel-binding type supported.
+pub const TLS_SERVER_END_POINT: &str = "tls-server-end-point";
+
+/// SCRAM exchange failures.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScramError {
+    /// The client-first message does not start with a valid GS2 header.
+    #[error("malformed GS2 header")]
+    MalformedGs2Header,
+    /// The client asked for a binding type other than `tls-server-end-point`.
+    #[error("unsupported channel binding type {0:?}")]
+    UnsupportedBinding(String),
+    /// The mechanism and the GS2 binding flag disagree.
+    #[error("channel binding flag does not match mechanism {0}")]
+    BindingMismatch(String),
+    /// The client claims the server offered no binding although it did.
+    #[error("channel binding downgrade: server offered {SCRAM_SHA_256_PLUS}")]
+    Downgrade,
+}
+
+/// Channel-binding flag of a GS2 header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelBinding {
+    /// `n`: the client does not support channel binding.
+    Unsupported,
+    /// `y`: the client supports it but believes the server does not.
+    NotOffered,
+    /// `p=<type>`: the client binds to the channel with this type.
+    Bound(String),
+}
+
+/// GS2 header of a SCRAM client-first message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gs2Header {
+    /// Channel-binding flag.
+    pub binding: ChannelBinding,
+    /// Optional authorization identity (`a=`).
+    pub authzid: Option<String>,
+    /// Header text including its trailing comma, as echoed in `c=`.
+    pub raw: String,
+}
+
+/// Split a client-first message into its GS2 header and the bare message.
+pub fn parse_gs2_header(client_first: &str) -> Result<(Gs2Header, &str), ScramError> {
+    let mut parts = client_first.splitn(3, ',');
+    let (Some(flag), Some(authzid), Some(bare)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(ScramError::MalformedGs2Header);
+    };
+    let binding = match flag {
+        "n" => ChannelBinding::Unsupported,
+        "y" => ChannelBinding::NotOffered,
+        _ => match flag.strip_prefix("p=") {
+            Some(kind) if !kind.is_empty() => ChannelBinding::Bound(kind.to_string()),
+            _ => return Err(ScramError::MalformedGs2Header),
+        },
+    };
+    let authzid = match authzid {
+        "" => None,
+        a => Some(a.strip_prefix("a=").ok_or(ScramError::MalformedGs2Header)?.to_string()),
+    };
+    let raw = client_first[..client_first.len() - bare.len()].to_string();
+    Ok((Gs2Header { binding, authzid, raw }, bare))
+}
+
+/// Check the client's binding flag against the chosen `mechanism` and
+/// whether the server advertised [`SCRAM_SHA_256_PLUS`] (only over TLS).
+/// Returns whether the exchange is channel-bound.
+pub fn check_channel_binding(header: &Gs2Header, mechanism: &str, plus_offered: bool) -> Result<bool, ScramError> {
+    match (&header.binding, mechanism) {
+        (ChannelBinding::Bound(kind), SCRAM_SHA_256_PLUS) if plus_offered => {
+            if kind == TLS_SERVER_END_POINT {
+                Ok(true)
+            } else {
+                Err(ScramError::UnsupportedBinding(kind.clone()))
+            }
+        }
+        (ChannelBinding::Bound(_), _) | (_, SCRAM_SHA_256_PLUS) => Err(ScramError::BindingMismatch(mechanism.to_string())),
+        (ChannelBinding::NotOffered, _) if plus_offered => Err(ScramError::Downgrade),
+        _ => Ok(false),
+    }
+}
+
+/// `tls-server-end-point` channel-binding data (RFC 5929): the server's DER
+/// certificate hashed with the hash of its signature algorithm, MD5 and SHA-1
+/// being replaced by SHA-256. `None` if the certificate is malformed or its
+/// signature has no single hash (e.g. Ed25519 or RSASSA-PSS).
+pub fn tls_server_end_point(cert_der: &[u8]) -> Option<Vec<u8>> {
+    const RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01]; // 1.2.840.113549.1.1
+    const ECDSA_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01]; // 1.2.840.10045.4.1
+    const ECDSA_SHA2: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03]; // 1.2.840.10045.4.3
+    let oid = crate::tls::signature_algorithm(cert_der)?;
+    let digest = match oid {
+        [rest @ .., id] if rest == RSA => match id {
+            4 | 5 | 11 => Sha256::digest(cert_der).to_vec(), // md5, sha1, sha256
+            12 => Sha384::digest(cert_der).to_vec(),
+            13 => Sha512::digest(cert_der).to_vec(),
+            14 => Sha224::digest(cert_der).to_vec(),
+            _ => return None,
+        },
+        _ if oid == ECDSA_SHA1 => Sha256::digest(cert_der).to_vec(),
+        [rest @ .., id] if rest == ECDSA_SHA2 => match id {
+            1 => Sha224::digest(cert_der).to_vec(),
+            2 => Sha256::digest(cert_der).to_vec(),
+            3 => Sha384::digest(cert_der).to_vec(),
+            4 => Sha512::digest(cert_der).to_vec(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(digest)
+}
+
+/// Expected `c=` value of the client-final message: the GS2 header followed,
+/// for a bound exchange, by the channel-binding data.
+pub fn channel_binding_attr(header: &Gs2Header, cbind_data: Option<&[u8]>) -> String {
+    let mut input = header.raw.as_bytes().to_vec();
+    if matches!(header.binding, ChannelBinding::Bound(_)) {
+        input.extend(cbind_data.unwrap_or_default());
+    }
+    base64_encode(&input)
+}
+
+/// AuthMessage the client proof is computed over; the channel binding enters
+/// through the `c=` attribute of the client-final message.
+pub fn auth_message(client_first_bare: &str, server_first: &str, client_final_without_proof: &str) -> String {
+    format!("{client_first_bare},{server_first},{client_final_without_proof}")
+}
+
+/// Check a client proof: `ClientKey = proof XOR HMAC(StoredKey, AuthMessage)`
+/// must hash to `stored_key`.
+pub fn verify_client_proof(stored: &[u8], auth_message: &str, proof: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(stored).unwrap();
+    mac.update(auth_message.as_bytes());
+    let signature = mac.finalize().into_bytes();
+    if proof.len() != signature.len() {
+        return false;
+    }
+    let client_key: Vec<u8> = proof.iter().zip(signature.iter()).map(|(p, s)| p ^ s).collect();
+    stored_key(&client_key).ct_eq(stored).into()
+}
 
 #[cfg(test)]
 mod tests {
@@ -114,4 +400,75 @@ mod tests {
         let ok = verify_md5_password("secret", "alice", "md5deadbeef", &salt);
         assert!(!ok);
     }
+
+    #[test]
+    fn gs2_header_and_channel_binding_decision() {
+        let (header, bare) = parse_gs2_header("p=tls-server-end-point,,n=,r=abc").unwrap();
+        assert_eq!(header.binding, ChannelBinding::Bound(TLS_SERVER_END_POINT.into()));
+        assert_eq!((header.authzid.as_deref(), header.raw.as_str(), bare), (None, "p=tls-server-end-point,,", "n=,r=abc"));
+        assert_eq!(check_channel_binding(&header, SCRAM_SHA_256_PLUS, true), Ok(true));
+        assert!(check_channel_binding(&header, SCRAM_SHA_256, true).is_err());
+        assert!(check_channel_binding(&header, SCRAM_SHA_256_PLUS, false).is_err());
+
+        let (header, _) = parse_gs2_header("y,a=bob,n=,r=abc").unwrap();
+        assert_eq!(header.authzid.as_deref(), Some("bob"));
+        assert_eq!(check_channel_binding(&header, SCRAM_SHA_256, true), Err(ScramError::Downgrade));
+        assert_eq!(check_channel_binding(&header, SCRAM_SHA_256, false), Ok(false));
+
+        let (header, _) = parse_gs2_header("n,,n=,r=abc").unwrap();
+        assert_eq!(check_channel_binding(&header, SCRAM_SHA_256, true), Ok(false));
+        assert!(check_channel_binding(&header, SCRAM_SHA_256_PLUS, true).is_err());
+
+        let (header, _) = parse_gs2_header("p=tls-unique,,n=,r=abc").unwrap();
+        assert!(matches!(check_channel_binding(&header, SCRAM_SHA_256_PLUS, true), Err(ScramError::UnsupportedBinding(_))));
+        for bad in ["x,,n=", "p=,,n=", "n,bob,n=", "n"] {
+            assert_eq!(parse_gs2_header(bad).unwrap_err(), ScramError::MalformedGs2Header, "{bad}");
+        }
+    }
+
+    #[test]
+    fn client_proof_covers_the_channel_binding() {
+        let salted = derive_salted_password("secret", b"salt", 4096);
+        let stored = stored_key(&client_key(&salted));
+        let (header, bare) = parse_gs2_header("p=tls-server-end-point,,n=alice,r=cnonce").unwrap();
+        let server_first = "r=cnoncesnonce,s=c2FsdA==,i=4096";
+        let prove = |cbind: Option<&[u8]>| {
+            let final_no_proof = format!("c={},r=cnoncesnonce", channel_binding_attr(&header, cbind));
+            let message = auth_message(bare, server_first, &final_no_proof);
+            let mut mac = HmacSha256::new_from_slice(&stored).unwrap();
+            mac.update(message.as_bytes());
+            let proof: Vec<u8> =
+                client_key(&salted).iter().zip(mac.finalize().into_bytes()).map(|(k, s)| k ^ s).collect();
+            (message, proof)
+        };
+        let server_cert = tls_server_end_point(&fake_cert(b"server", ECDSA_SHA256)).unwrap();
+        let (message, proof) = prove(Some(&server_cert));
+        assert!(verify_client_proof(&stored, &message, &proof));
+
+        // A client bound to a different certificate (e.g. a MITM proxy) fails.
+        let (_, mitm_proof) = prove(Some(&tls_server_end_point(&fake_cert(b"proxy", ECDSA_SHA256)).unwrap()));
+        assert!(!verify_client_proof(&stored, &message, &mitm_proof));
+    }
+
+    const ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+    /// DER `Certificate` with an opaque TBS body and the given signature OID.
+    fn fake_cert(tbs: &[u8], sig_oid: &[u8]) -> Vec<u8> {
+        let tlv = |tag: u8, body: &[u8]| [&[tag, body.len() as u8][..], body].concat();
+        let body = [tlv(0x30, tbs), tlv(0x30, &tlv(0x06, sig_oid)), tlv(0x03, &[0, 1, 2])].concat();
+        tlv(0x30, &body)
+    }
+
+    #[test]
+    fn end_point_hash_follows_the_signature_algorithm() {
+        let rsa = |id: u8| [&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01][..], &[id]].concat();
+        let cert = fake_cert(b"tbs", &rsa(12)); // sha384WithRSAEncryption
+        assert_eq!(tls_server_end_point(&cert), Some(Sha384::digest(&cert).to_vec()));
+        let cert = fake_cert(b"tbs", &rsa(5)); // sha1WithRSAEncryption upgrades to SHA-256
+        assert_eq!(tls_server_end_point(&cert), Some(Sha256::digest(&cert).to_vec()));
+        let cert = fake_cert(b"tbs", &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04]); // ecdsa-with-SHA512
+        assert_eq!(tls_server_end_point(&cert), Some(Sha512::digest(&cert).to_vec()));
+        assert_eq!(tls_server_end_point(&fake_cert(b"tbs", &[0x2b, 0x65, 0x70])), None); // Ed25519
+        assert_eq!(tls_server_end_point(b"not a certificate"), None);
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,93 @@
+//! Pluggable query execution behind the wire protocol.
+
+/// Type OID of `int4`.
+pub const INT4_OID: u32 = 23;
+/// Type OID of `text`.
+pub const TEXT_OID: u32 = 25;
+
+/// One result column as announced in RowDescription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescription {
+    /// Column name.
+    pub name: String,
+    /// PostgreSQL type OID.
+    pub type_oid: u32,
+    /// Fixed size of the type in bytes; negative for variable-length types.
+    pub type_size: i16,
+}
+
+impl FieldDescription {
+    /// An `int4` column.
+    pub fn int4(name: impl Into<String>) -> Self {
+        Self { name: name.into(), type_oid: INT4_OID, type_size: 4 }
+    }
+
+    /// A `text` column.
+    pub fn text(name: impl Into<String>) -> Self {
+        Self { name: name.into(), type_oid: TEXT_OID, type_size: -1 }
+    }
+}
+
+/// Outcome of one successfully executed statement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecResult {
+    /// Result columns; empty for statements that return no rows.
+    pub fields: Vec<FieldDescription>,
+    /// Rows as text-format values, one per field (`None` = NULL).
+    pub rows: Vec<Vec<Option<Vec<u8>>>>,
+    /// CommandComplete tag, e.g. `SELECT 2` or `INSERT 0 1`.
+    pub tag: String,
+}
+
+/// A statement failure reported to the client with its SQLSTATE.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct ExecError {
+    /// Five-character SQLSTATE, e.g. `42P01`.
+    pub code: String,
+    /// Human-readable message.
+    pub message: String,
+}
+
+impl ExecError {
+    /// Error with SQLSTATE `code`.
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { code: code.into(), message: message.into() }
+    }
+}
+
+/// Runs the SQL received from clients.
+#[async_trait::async_trait]
+pub trait QueryExecutor: Send + Sync {
+    /// Execute one simple-query string. An [`ExecError`] is reported with its
+    /// SQLSTATE, any other error as `XX000` (internal error); either way the
+    /// session stays open.
+    async fn execute(&self, sql: &str) -> anyhow::Result<ExecResult>;
+
+    /// Produce the text-format rows of a `COPY ... TO STDOUT` statement and
+    /// their count. Unsupported (`0A000`) unless overridden.
+    async fn copy_out(&self, _sql: &str) -> anyhow::Result<(Vec<u8>, u64)> {
+        Err(ExecError::new("0A000", "COPY is not supported").into())
+    }
+
+    /// Load the text-format rows of a `COPY ... FROM STDIN` statement and
+    /// return their count. Unsupported (`0A000`) unless overridden.
+    async fn copy_in(&self, _sql: &str, _data: Vec<u8>) -> anyhow::Result<u64> {
+        Err(ExecError::new("0A000", "COPY is not supported").into())
+    }
+}
+
+/// Answers every query with a single `?column?` row holding `1`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DummyExecutor;
+
+#[async_trait::async_trait]
+impl QueryExecutor for DummyExecutor {
+    async fn execute(&self, _sql: &str) -> anyhow::Result<ExecResult> {
+        Ok(ExecResult {
+            fields: vec![FieldDescription::int4("?column?")],
+            rows: vec![vec![Some(b"1".to_vec())]],
+            tag: "SELECT 1".into(),
+        })
+    }
+}
@@ -0,0 +1,107 @@
+//! Host-based access control (pg_hba-style allow/deny rules).
+//!
+//! Rules are evaluated in order and the first match wins. A rule may restrict
+//! the user and/or database in addition to the client CIDR.
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+/// Outcome of a matching rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HbaAction {
+    /// Let the connection proceed to authentication.
+    Allow,
+    /// Reject the connection.
+    Deny,
+}
+
+/// Single access rule.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HbaRule {
+    /// Client address range, e.g. `10.0.0.0/8`.
+    pub cidr: IpNet,
+    /// Restrict the rule to this user.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Restrict the rule to this database.
+    #[serde(default)]
+    pub database: Option<String>,
+    /// Action when the rule matches.
+    pub action: HbaAction,
+}
+
+/// Ordered rule list plus the action applied when no rule matches.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HbaConfig {
+    /// Rules in evaluation order.
+    #[serde(default)]
+    pub rules: Vec<HbaRule>,
+    /// Action when no rule matches (allow unless configured).
+    #[serde(default = "default_action")]
+    pub default: HbaAction,
+}
+
+fn default_action() -> HbaAction {
+    HbaAction::Allow
+}
+
+impl Default for HbaConfig {
+    fn default() -> Self {
+        Self { rules: Vec::new(), default: default_action() }
+    }
+}
+
+impl HbaConfig {
+    /// Decide using the peer address alone, before the startup handshake.
+    /// Returns `None` when the outcome depends on the user or database, which
+    /// must then be resolved with [`HbaConfig::check`].
+    pub fn check_addr(&self, ip: IpAddr) -> Option<HbaAction> {
+        for rule in self.rules.iter().filter(|r| r.cidr.contains(&ip)) {
+            if rule.user.is_none() && rule.database.is_none() {
+                return Some(rule.action);
+            }
+            if rule.action == HbaAction::Allow {
+                return None;
+            }
+        }
+        Some(self.default)
+    }
+
+    /// Decide using the peer address and the startup `user`/`database`.
+    pub fn check(&self, ip: IpAddr, user: &str, database: &str) -> HbaAction {
+        self.rules
+            .iter()
+            .find(|r| {
+                r.cidr.contains(&ip)
+                    && r.user.as_deref().is_none_or(|u| u == user)
+                    && r.database.as_deref().is_none_or(|d| d == database)
+            })
+            .map_or(self.default, |r| r.action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let conf: HbaConfig = serde_yaml::from_str(
+            "rules:\n\
+             - { cidr: 10.1.0.0/16, user: admin, action: allow }\n\
+             - { cidr: 10.0.0.0/8, action: deny }\n\
+             default: allow\n",
+        )
+        .unwrap();
+        let inside: IpAddr = "10.1.2.3".parse().unwrap();
+        let outside: IpAddr = "192.168.0.1".parse().unwrap();
+        assert_eq!(conf.check_addr("10.2.0.1".parse().unwrap()), Some(HbaAction::Deny));
+        assert_eq!(conf.check_addr(inside), None);
+        assert_eq!(conf.check(inside, "admin", "postgres"), HbaAction::Allow);
+        assert_eq!(conf.check(inside, "bob", "postgres"), HbaAction::Deny);
+        assert_eq!(conf.check_addr(outside), Some(HbaAction::Allow));
+    }
+}
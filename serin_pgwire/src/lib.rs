@@ -1,58 +1,234 @@
 //! Minimal PostgreSQL Wire Protocol (v3) server for SerinDB.
 //! Supports SSL negation, StartupMessage, Simple Query, and basic Extended Query.
 
+pub mod audit;
+pub mod auth;
+pub mod executor;
+pub mod hba;
+pub mod ratelimit;
+pub mod tls;
+
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
-use md5::{Digest, Md5};
-use crate::auth::{AuthConfig, verify_md5_password};
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use crate::auth::{AuthConfig, CertAuth, SharedAuthConfig, verify_md5_password};
+use crate::executor::{ExecError, ExecResult, FieldDescription, QueryExecutor};
+use crate::hba::HbaAction;
+use crate::ratelimit::AuthLimiter;
 use bytes::{Buf, BytesMut};
 use tracing::{info, instrument};
-use serin_metrics::{CONNECTIONS_TOTAL, QUERIES_TOTAL, QUERY_LATENCY_SECS};
+use serin_metrics::{count_query, start_timer, GaugeGuard, CONNECTIONS_ACTIVE, CONNECTIONS_TOTAL};
 
 const SSL_REQUEST_CODE: u32 = 80877103; // 0x04D2162F
 const PROTOCOL_VERSION: u32 = 196608; // 3.0
+/// Largest startup packet accepted, as in PostgreSQL's `MAX_STARTUP_PACKET_LENGTH`.
+const MAX_STARTUP_PACKET: usize = 10000;
+/// Largest PasswordMessage accepted, matching PostgreSQL's limit.
+const MAX_PASSWORD_PACKET: usize = 1000;
+/// How long a graceful shutdown waits for sessions to finish their query.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+/// How often addresses without recent failures are dropped from the limiter.
+const LIMITER_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a rejected client gets to take its error before it is dropped.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Largest single message accepted while copying in.
+const MAX_COPY_MESSAGE: usize = 16 << 20;
+/// Largest total payload accepted by one COPY FROM STDIN.
+const MAX_COPY_BYTES: usize = 256 << 20;
+
+/// Connection limits of a server; the default imposes none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerOptions {
+    /// Connections served at once; further clients get FATAL 53300.
+    pub max_connections: Option<usize>,
+    /// Sessions idle between messages for this long are closed with FATAL 57P05.
+    pub idle_timeout: Option<Duration>,
+}
 
-/// Run a PgWire server on the given address (e.g., "0.0.0.0:5432").
-#[instrument(skip(auth_conf))]
-pub async fn run_server(addr: &str, auth_conf: Arc<AuthConfig>) -> anyhow::Result<()> {
+/// Run a PgWire server on the given address (e.g., "0.0.0.0:5432"),
+/// answering queries with `executor`.
+pub async fn run_server(addr: &str, auth_conf: Arc<AuthConfig>, executor: Arc<dyn QueryExecutor>) -> anyhow::Result<()> {
+    let auth_conf = Arc::new(SharedAuthConfig::new(auth_conf)?);
+    run_server_with_shutdown(addr, auth_conf, executor, ServerOptions::default(), CancellationToken::new()).await
+}
+
+/// [`run_server`] until `shutdown` is cancelled; see [`serve_with_shutdown`].
+#[instrument(skip(auth_conf, executor, shutdown))]
+pub async fn run_server_with_shutdown(
+    addr: &str,
+    auth_conf: Arc<SharedAuthConfig>,
+    executor: Arc<dyn QueryExecutor>,
+    options: ServerOptions,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
     info!(%addr, "Starting PgWire server");
     let listener = TcpListener::bind(addr).await?;
     println!("PgWire server listening on {addr}");
+    serve_with_shutdown(listener, auth_conf, executor, options, shutdown).await
+}
+
+/// Accept connections on an already bound listener.
+pub async fn serve(listener: TcpListener, auth_conf: Arc<AuthConfig>, executor: Arc<dyn QueryExecutor>) -> anyhow::Result<()> {
+    let auth_conf = Arc::new(SharedAuthConfig::new(auth_conf)?);
+    serve_with_shutdown(listener, auth_conf, executor, ServerOptions::default(), CancellationToken::new()).await
+}
+
+/// [`serve`] with `options` until `shutdown` is cancelled. Then no new
+/// connections are accepted, sessions end (with a FATAL 57P01) once their
+/// current query is answered, and this returns when all of them are gone or
+/// after [`SHUTDOWN_GRACE`]. Users, hba rules and TLS settings are read from
+/// `auth_conf` for each new connection, so reloading it takes effect
+/// immediately.
+pub async fn serve_with_shutdown(
+    listener: TcpListener,
+    auth_conf: Arc<SharedAuthConfig>,
+    executor: Arc<dyn QueryExecutor>,
+    options: ServerOptions,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let sessions = TaskTracker::new();
+    let limiter = Arc::new(AuthLimiter::new());
+    // Prune the limiter until this function returns, however it returns.
+    let pruning = shutdown.child_token();
+    let _stop_pruning = pruning.clone().drop_guard();
+    tokio::spawn({
+        let limiter = limiter.clone();
+        let auth_conf = auth_conf.clone();
+        async move {
+            let mut ticks = tokio::time::interval(LIMITER_PRUNE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => limiter.prune(&auth_conf.load().rate_limit),
+                    _ = pruning.cancelled() => break,
+                }
+            }
+        }
+    });
+    let slots = options.max_connections.map(|n| Arc::new(Semaphore::new(n)));
     loop {
-        let (socket, _) = listener.accept().await?;
-        let auth = auth_conf.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_conn(socket, auth).await {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.cancelled() => break,
+        };
+        audit::connection_attempt(peer);
+        let (auth, tls) = auth_conf.load_with_tls();
+        if auth.hba.check_addr(peer.ip()) == Some(HbaAction::Deny) {
+            audit::auth_failure("", peer, "address rejected by hba");
+            reject(socket, "28000", "connection rejected by host-based access rules");
+            continue;
+        }
+        let slot = match slots.as_ref().map(|s| s.clone().try_acquire_owned()) {
+            Some(Err(_)) => {
+                reject(socket, "53300", "sorry, too many clients already");
+                continue;
+            }
+            slot => slot.map(Result::unwrap),
+        };
+        let executor = executor.clone();
+        let limiter = limiter.clone();
+        let shutdown = shutdown.clone();
+        let idle_timeout = options.idle_timeout;
+        sessions.spawn(async move {
+            let _slot = slot;
+            if let Err(e) = handle_conn(socket, peer, auth, executor, &limiter, tls, idle_timeout, shutdown).await {
                 eprintln!("connection error: {e}");
             }
         });
     }
+    sessions.close();
+    info!(sessions = sessions.len(), "PgWire server shutting down");
+    if tokio::time::timeout(SHUTDOWN_GRACE, sessions.wait()).await.is_err() {
+        info!(sessions = sessions.len(), "shutdown grace period elapsed; dropping sessions");
+    }
+    Ok(())
 }
 
-#[instrument(skip(socket, auth))]
-async fn handle_conn(mut socket: TcpStream, auth: Arc<AuthConfig>) -> anyhow::Result<()> {
-    // Handle SSL negotiation or StartupMessage.
-    let mut len_buf = [0u8; 4];
-    socket.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
-    let mut buf = vec![0u8; len - 4];
-    socket.read_exact(&mut buf).await?;
-    let mut cursor = &buf[..];
-    let code = cursor.get_u32();
-    if code == SSL_REQUEST_CODE {
+/// Send a FATAL error to a refused client and close the connection, off the
+/// accept loop so a peer that does not read cannot stall it.
+fn reject(mut socket: TcpStream, code: &'static str, message: &'static str) {
+    tokio::spawn(async move {
+        let _ = tokio::time::timeout(REJECT_TIMEOUT, send_error(&mut socket, "FATAL", code, message)).await;
+    });
+}
+
+#[instrument(skip(socket, auth, executor, limiter, tls, shutdown))]
+#[allow(clippy::too_many_arguments)]
+async fn handle_conn(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    auth: Arc<AuthConfig>,
+    executor: Arc<dyn QueryExecutor>,
+    limiter: &AuthLimiter,
+    tls: Option<TlsAcceptor>,
+    idle_timeout: Option<Duration>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    // Handle SSL negotiation or StartupMessage; nothing is in flight yet, so
+    // shutdown may simply drop the connection.
+    let mut buf = tokio::select! {
+        buf = read_startup_packet(&mut socket) => buf?,
+        _ = shutdown.cancelled() => return Ok(()),
+    };
+    if buf[..4] == SSL_REQUEST_CODE.to_be_bytes() {
+        if let Some(acceptor) = tls {
+            socket.write_all(b"S").await?;
+            let mut stream = acceptor.accept(socket).await?;
+            let identities = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|cert| tls::cert_identities(&cert.0))
+                .unwrap_or_default();
+            let buf = read_startup_packet(&mut stream).await?;
+            return run_session(stream, &buf, peer, auth, &*executor, limiter, &identities, idle_timeout, shutdown).await;
+        }
         // Respond 'N' (no SSL) and read next startup msg.
         socket.write_all(b"N").await?;
-        socket.read_exact(&mut len_buf).await?;
-        let len2 = u32::from_be_bytes(len_buf) as usize;
-        buf.resize(len2 - 4, 0);
-        socket.read_exact(&mut buf).await?;
-        cursor = &buf[..];
+        buf = read_startup_packet(&mut socket).await?;
+    }
+    run_session(socket, &buf, peer, auth, &*executor, limiter, &[], idle_timeout, shutdown).await
+}
+
+/// Read a length-prefixed startup-phase packet (SSLRequest or StartupMessage) body.
+async fn read_startup_packet(socket: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<Vec<u8>> {
+    let len = socket.read_u32().await? as usize;
+    if len < 8 {
+        anyhow::bail!("startup packet too short");
+    }
+    if len > MAX_STARTUP_PACKET {
+        anyhow::bail!("startup packet too long: {len} bytes");
     }
+    let mut buf = vec![0u8; len - 4];
+    socket.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// Authenticate and serve one session. `identities` are the verified client
+/// certificate's CN/SAN names (empty without TLS or a client certificate).
+#[allow(clippy::too_many_arguments)]
+async fn run_session(
+    mut socket: impl AsyncRead + AsyncWrite + Unpin,
+    startup: &[u8],
+    peer: SocketAddr,
+    auth: Arc<AuthConfig>,
+    executor: &dyn QueryExecutor,
+    limiter: &AuthLimiter,
+    identities: &[String],
+    idle_timeout: Option<Duration>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut len_buf = [0u8; 4];
+    let mut cursor = startup;
     // Parse startup.
-    let protocol = code;
+    let protocol = cursor.get_u32();
     if protocol != PROTOCOL_VERSION {
         send_error(&mut socket, "FATAL", "0A000", "Unsupported protocol").await?;
         return Ok(());
@@ -67,27 +243,39 @@ async fn handle_conn(mut socket: TcpStream, auth: Arc<AuthConfig>) -> anyhow::Re
         cursor.advance(val_pos + 1);
         params.insert(key, val);
     }
-    // Password authentication (MD5).
     let user = params.get("user").cloned().unwrap_or_default();
-    let salt = rand::random::<[u8; 4]>();
-    send_auth_md5(&mut socket, &salt).await?;
-    // Read PasswordMessage.
-    let mut type_buf = [0u8; 1];
-    socket.read_exact(&mut type_buf).await?;
-    if type_buf[0] != b'p' {
-        send_error(&mut socket, "FATAL", "28P01", "Password required").await?;
+    // PostgreSQL defaults the database name to the user name.
+    let database = params.get("database").cloned().unwrap_or_else(|| user.clone());
+    if auth.hba.check(peer.ip(), &user, &database) == HbaAction::Deny {
+        audit::auth_failure(&user, peer, "rejected by hba");
+        send_error(&mut socket, "FATAL", "28000", "connection rejected by host-based access rules").await?;
         return Ok(());
     }
-    socket.read_exact(&mut len_buf).await?;
-    let plen = u32::from_be_bytes(len_buf) as usize;
-    let mut pbuf = vec![0u8; plen - 4];
-    socket.read_exact(&mut pbuf).await?;
-    let passwd_cstr = extract_cstr(&pbuf)?;
-    let stored_pwd = auth.password(&user).unwrap_or("password");
-    if !verify_md5_password(stored_pwd, &user, &passwd_cstr, &salt) {
-        send_error(&mut socket, "FATAL", "28P01", "Authentication failed").await?;
-        return Ok(());
+    let cert_auth = auth.cert_auth.as_ref().map_or(CertAuth::Password, |c| c.authenticate(identities, &user));
+    match cert_auth {
+        CertAuth::Authenticated => {}
+        CertAuth::Rejected => {
+            audit::auth_failure(&user, peer, "no client certificate mapped to user");
+            send_error(&mut socket, "FATAL", "28000", "Certificate authentication failed").await?;
+            return Ok(());
+        }
+        CertAuth::Password => {
+            let Some(attempt) = limiter.begin(peer.ip(), &auth.rate_limit) else {
+                audit::auth_failure(&user, peer, "too many failed attempts");
+                send_error(&mut socket, "FATAL", "28000", "too many failed authentication attempts; try again later").await?;
+                return Ok(());
+            };
+            if !password_auth(&mut socket, &user, peer, &auth).await? {
+                attempt.failed();
+                return Ok(());
+            }
+            attempt.succeeded();
+        }
     }
+    audit::auth_success(&user, peer);
+    let _session = audit::DisconnectGuard::new(&user, peer);
+    // Only authenticated sessions count; failures above return before this.
+    let _active = GaugeGuard::new(&CONNECTIONS_ACTIVE);
     send_auth_ok(&mut socket).await?;
     // ParameterStatus.
     send_param_status(&mut socket, "server_version", "13.0").await?;
@@ -96,48 +284,143 @@ async fn handle_conn(mut socket: TcpStream, auth: Arc<AuthConfig>) -> anyhow::Re
     send_ready(&mut socket).await?;
     CONNECTIONS_TOTAL.inc();
 
-    // State storage for prepared statements / portals.
-    let stmts: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+    // Extended-protocol state: statement name -> SQL, portal name -> portal.
+    let mut stmts: HashMap<String, String> = HashMap::new();
+    let mut portals: HashMap<String, Portal> = HashMap::new();
+    // After an extended-protocol error, messages are discarded until Sync.
+    let mut failed = false;
     let mut read_buf = BytesMut::with_capacity(8192);
     loop {
         // Read message type.
         let mut typ_buf = [0u8; 1];
-        if let Err(_) = socket.read_exact(&mut typ_buf).await { break; }
+        // Shutdown and the idle timeout only interrupt the wait between messages.
+        let idle = async {
+            match idle_timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let read = tokio::select! {
+            read = socket.read_exact(&mut typ_buf) => read,
+            _ = shutdown.cancelled() => {
+                send_error(&mut socket, "FATAL", "57P01", "terminating connection due to administrator command").await?;
+                break;
+            }
+            _ = idle => {
+                send_error(&mut socket, "FATAL", "57P05", "terminating connection due to idle-session timeout").await?;
+                break;
+            }
+        };
+        if read.is_err() { break; }
         let msg_type = typ_buf[0] as char;
         socket.read_exact(&mut len_buf).await?;
         let mlen = u32::from_be_bytes(len_buf) as usize;
         read_buf.resize(mlen - 4, 0);
         socket.read_exact(&mut read_buf).await?;
+        if failed && msg_type != 'S' {
+            continue;
+        }
         match msg_type {
             'Q' => {
-                let start = std::time::Instant::now();
+                let _timer = start_timer(&database);
                 // Simple Query or COPY.
                 let q = extract_cstr(&read_buf)?;
-                process_simple_query(&mut socket, q).await?;
-                QUERIES_TOTAL.inc();
-                let dur = start.elapsed();
-                QUERY_LATENCY_SECS.observe(dur.as_secs_f64());
+                count_query(&database, query_kind(&q), &user);
+                process_simple_query(&mut socket, executor, q).await?;
             }
             'P' => {
                 // Parse
                 let (name, query) = parse_parse_msg(&read_buf)?;
-                stmts.lock().await.insert(name, query);
+                stmts.insert(name, query);
                 send_parse_complete(&mut socket).await?;
             }
             'B' => {
-                // Bind (ignore formats/params)
-                let portal_name = parse_bind_msg(&read_buf)?;
-                // For simplicity, we reuse query from unnamed statement.
-                stmts.lock().await.get("");
-                send_bind_complete(&mut socket).await?;
-                // store portal not required for demo
+                // Bind (parameters and formats are ignored)
+                let (portal, statement) = parse_bind_msg(&read_buf)?;
+                match stmts.get(&statement) {
+                    Some(query) => {
+                        portals.insert(portal, Portal { query: query.clone(), result: None });
+                        send_bind_complete(&mut socket).await?;
+                    }
+                    None => {
+                        let msg = format!("prepared statement \"{statement}\" does not exist");
+                        send_error(&mut socket, "ERROR", "26000", &msg).await?;
+                        failed = true;
+                    }
+                }
             }
             'E' => {
-                // Execute (ignore portal)
-                process_simple_query(&mut socket, "SELECT 1".into()).await?;
+                // Execute (the row limit is ignored)
+                let portal = extract_cstr(&read_buf)?;
+                match portals.get_mut(&portal) {
+                    Some(Portal { query, result }) => {
+                        count_query(&database, query_kind(query), &user);
+                        failed = !match result.take() {
+                            // Already run by Describe.
+                            Some(result) => send_result(&mut socket, &result, false).await?,
+                            None => {
+                                let _timer = start_timer(&database);
+                                if is_copy(query) {
+                                    run_copy(&mut socket, executor, query).await?
+                                } else {
+                                    run_query(&mut socket, executor, query, false).await?
+                                }
+                            }
+                        };
+                    }
+                    None => {
+                        send_error(&mut socket, "ERROR", "34000", &format!("portal \"{portal}\" does not exist")).await?;
+                        failed = true;
+                    }
+                }
+            }
+            'D' => {
+                // Describe. Result columns are only known once the executor
+                // has run, so statements take no parameters and return NoData,
+                // while a portal is run here and its rows kept for Execute.
+                let (kind, name) = read_buf.split_first().ok_or_else(|| anyhow::anyhow!("empty Describe"))?;
+                let name = extract_cstr(name)?;
+                if *kind == b'S' {
+                    if stmts.contains_key(&name) {
+                        send_parameter_description(&mut socket).await?;
+                        send_no_data(&mut socket).await?;
+                    } else {
+                        send_error(&mut socket, "ERROR", "26000", &format!("prepared statement \"{name}\" does not exist")).await?;
+                        failed = true;
+                    }
+                    continue;
+                }
+                match portals.get_mut(&name) {
+                    // COPY answers with its own CopyIn/CopyOutResponse at Execute.
+                    Some(portal) if is_copy(&portal.query) => send_no_data(&mut socket).await?,
+                    Some(portal) => {
+                        if portal.result.is_none() {
+                            let _timer = start_timer(&database);
+                            match executor.execute(&portal.query).await {
+                                Ok(result) => portal.result = Some(result),
+                                Err(e) => {
+                                    send_exec_error(&mut socket, &e).await?;
+                                    failed = true;
+                                    continue;
+                                }
+                            }
+                        }
+                        match portal.result.as_ref() {
+                            Some(result) if !result.fields.is_empty() => {
+                                socket.write_all(&row_description(&result.fields)).await?;
+                            }
+                            _ => send_no_data(&mut socket).await?,
+                        }
+                    }
+                    None => {
+                        send_error(&mut socket, "ERROR", "34000", &format!("portal \"{name}\" does not exist")).await?;
+                        failed = true;
+                    }
+                }
             }
             'S' => {
                 // Sync
+                failed = false;
                 send_ready(&mut socket).await?;
             }
             _ => {
@@ -148,6 +431,41 @@ async fn handle_conn(mut socket: TcpStream, auth: Arc<AuthConfig>) -> anyhow::Re
     Ok(())
 }
 
+/// A bound statement. `result` holds the rows of a portal already run by
+/// Describe, which the following Execute sends instead of running it again.
+struct Portal {
+    query: String,
+    result: Option<ExecResult>,
+}
+
+/// MD5 password exchange. Returns false (after reporting the error) on failure.
+async fn password_auth(socket: &mut (impl AsyncRead + AsyncWrite + Unpin), user: &str, peer: SocketAddr, auth: &AuthConfig) -> anyhow::Result<bool> {
+    let salt = rand::random::<[u8; 4]>();
+    send_auth_md5(socket, &salt).await?;
+    // Read PasswordMessage.
+    let mut type_buf = [0u8; 1];
+    socket.read_exact(&mut type_buf).await?;
+    if type_buf[0] != b'p' {
+        audit::auth_failure(user, peer, "password required");
+        send_error(socket, "FATAL", "28P01", "Password required").await?;
+        return Ok(false);
+    }
+    let plen = socket.read_u32().await? as usize;
+    if !(4..=MAX_PASSWORD_PACKET).contains(&plen) {
+        anyhow::bail!("invalid password packet length {plen}");
+    }
+    let mut pbuf = vec![0u8; plen - 4];
+    socket.read_exact(&mut pbuf).await?;
+    let passwd_cstr = extract_cstr(&pbuf)?;
+    let stored_pwd = auth.password(user).unwrap_or("password");
+    if !verify_md5_password(stored_pwd, user, &passwd_cstr, &salt) {
+        audit::auth_failure(user, peer, "password mismatch");
+        send_error(socket, "FATAL", "28P01", "Authentication failed").await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 // Helper functions
 fn extract_cstr(buf: &[u8]) -> anyhow::Result<String> {
     if let Some(pos) = buf.iter().position(|&b| b == 0) {
@@ -165,67 +483,178 @@ fn parse_parse_msg(buf: &[u8]) -> anyhow::Result<(String, String)> {
     Ok((name, query))
 }
 
-fn parse_bind_msg(buf: &[u8]) -> anyhow::Result<String> {
+/// Portal and statement names of a Bind message.
+fn parse_bind_msg(buf: &[u8]) -> anyhow::Result<(String, String)> {
     let portal = extract_cstr(buf)?;
-    Ok(portal)
+    let statement = extract_cstr(&buf[portal.len() + 1..])?;
+    Ok((portal, statement))
 }
 
-async fn process_simple_query(socket: &mut TcpStream, query: String) -> anyhow::Result<()> {
-    let q_lower = query.to_lowercase();
-    if q_lower.starts_with("copy") {
-        handle_copy(socket, &q_lower).await
+/// Statement kind used as the `kind` metrics label: the lowercased leading
+/// keyword for common statements, `other` for anything else.
+fn query_kind(query: &str) -> &'static str {
+    let keyword = query.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+    match keyword.as_str() {
+        "select" => "select",
+        "insert" => "insert",
+        "update" => "update",
+        "delete" => "delete",
+        "copy" => "copy",
+        "begin" | "commit" | "rollback" => "txn",
+        _ => "other",
+    }
+}
+
+fn is_copy(query: &str) -> bool {
+    query_kind(query) == "copy"
+}
+
+async fn process_simple_query(
+    socket: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    executor: &dyn QueryExecutor,
+    query: String,
+) -> anyhow::Result<()> {
+    if is_copy(&query) {
+        run_copy(socket, executor, &query).await?;
     } else {
-        // Always return single column "?column?" with value 1 (int4).
-        send_row_description(socket).await?;
-        send_data_row(socket).await?;
-        send_command_complete(socket, "SELECT 1").await?;
-        send_ready(socket).await?;
-        Ok(())
+        run_query(socket, executor, &query, true).await?;
+    }
+    send_ready(socket).await
+}
+
+/// Run `query` and send its rows and CommandComplete, or an ErrorResponse;
+/// never ReadyForQuery. `describe` also sends the RowDescription, which the
+/// extended protocol leaves to Describe. Returns whether the query succeeded.
+async fn run_query(
+    socket: &mut (impl AsyncWrite + Unpin),
+    executor: &dyn QueryExecutor,
+    query: &str,
+    describe: bool,
+) -> anyhow::Result<bool> {
+    let result = match executor.execute(query).await {
+        Ok(result) => result,
+        Err(e) => {
+            send_exec_error(socket, &e).await?;
+            return Ok(false);
+        }
+    };
+    send_result(socket, &result, describe).await
+}
+
+/// Send the rows and CommandComplete of an executed query; see [`run_query`].
+async fn send_result(socket: &mut (impl AsyncWrite + Unpin), result: &ExecResult, describe: bool) -> anyhow::Result<bool> {
+    if !result.fields.is_empty() {
+        if describe {
+            socket.write_all(&row_description(&result.fields)).await?;
+        }
+        for row in &result.rows {
+            socket.write_all(&data_row(row)).await?;
+        }
     }
+    send_command_complete(socket, &result.tag).await?;
+    Ok(true)
 }
 
-async fn handle_copy(socket: &mut TcpStream, query: &str) -> anyhow::Result<()> {
+/// Report an executor failure: an [`ExecError`] with its SQLSTATE, anything
+/// else as `XX000`.
+async fn send_exec_error(socket: &mut (impl AsyncWrite + Unpin), e: &anyhow::Error) -> anyhow::Result<()> {
+    let code = e.downcast_ref::<ExecError>().map_or("XX000", |e| e.code.as_str());
+    send_error(socket, "ERROR", code, &format!("{e:#}")).await
+}
+
+/// Run a COPY statement and send its data and CommandComplete, or an
+/// ErrorResponse; never ReadyForQuery. Returns whether the copy succeeded.
+async fn run_copy(
+    socket: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    executor: &dyn QueryExecutor,
+    sql: &str,
+) -> anyhow::Result<bool> {
+    let query = sql.to_lowercase();
+    let binary = query.split(|c: char| c.is_whitespace() || "(),".contains(c)).any(|word| word == "binary");
     if query.contains("from stdin") {
         // COPY FROM STDIN
-        send_copy_in_response(socket).await?;
-        // Read CopyData until CopyDone
+        send_copy_in_response(socket, binary).await?;
+        // Collect CopyData until CopyDone
+        let mut data = Vec::new();
         let mut len_buf = [0u8; 4];
+        // Once a limit is exceeded the rest of the copy stream is discarded.
+        let mut overflowed = false;
         loop {
             let mut typ_buf = [0u8; 1];
             socket.read_exact(&mut typ_buf).await?;
             socket.read_exact(&mut len_buf).await?;
             let mlen = u32::from_be_bytes(len_buf) as usize;
-            let mut discard = vec![0u8; mlen - 4];
-            socket.read_exact(&mut discard).await?;
+            if mlen < 4 {
+                anyhow::bail!("invalid message length {mlen} during COPY");
+            }
+            let len = mlen - 4;
+            if !overflowed && (len > MAX_COPY_MESSAGE || data.len() + len > MAX_COPY_BYTES) {
+                let msg = if len > MAX_COPY_MESSAGE {
+                    format!("COPY message of {len} bytes exceeds the limit of {MAX_COPY_MESSAGE}")
+                } else {
+                    format!("COPY data exceeds the limit of {MAX_COPY_BYTES} bytes")
+                };
+                send_error(socket, "ERROR", "54000", &msg).await?;
+                overflowed = true;
+                data = Vec::new();
+            }
+            if overflowed {
+                tokio::io::copy(&mut (&mut *socket).take(len as u64), &mut tokio::io::sink()).await?;
+                match typ_buf[0] {
+                    b'c' | b'f' => return Ok(false),
+                    _ => continue,
+                }
+            }
+            let mut payload = vec![0u8; len];
+            socket.read_exact(&mut payload).await?;
             match typ_buf[0] as char {
-                'd' => continue, // CopyData: ignore
-                'c' => break,     // CopyDone
+                'd' => data.extend_from_slice(&payload),
+                'c' => break, // CopyDone
+                // An extended-protocol client may pipeline these; they are
+                // ignored while copying in.
+                'H' | 'S' => continue,
                 'f' => {
-                    send_error(socket, "ERROR", "42601", "COPY failed").await?;
-                    return Ok(());
+                    send_error(socket, "ERROR", "57014", "COPY failed").await?;
+                    return Ok(false);
                 }
                 _ => {
-                    send_error(socket, "ERROR", "42601", "Unexpected message during COPY").await?;
-                    return Ok(());
+                    send_error(socket, "ERROR", "08P01", "Unexpected message during COPY").await?;
+                    return Ok(false);
                 }
             }
         }
-        send_command_complete(socket, "COPY 0").await?;
-        send_ready(socket).await?;
+        match executor.copy_in(sql, data).await {
+            Ok(rows) => send_command_complete(socket, &format!("COPY {rows}")).await?,
+            Err(e) => {
+                send_exec_error(socket, &e).await?;
+                return Ok(false);
+            }
+        }
     } else if query.contains("to stdout") {
-        // COPY TO STDOUT
-        send_copy_out_response(socket).await?;
-        // For demo, send no data.
-        send_copy_done(socket).await?;
-        send_command_complete(socket, "COPY 0").await?;
-        send_ready(socket).await?;
+        // COPY TO STDOUT: run the copy before announcing it, so a failure is
+        // a plain ErrorResponse rather than an aborted copy stream.
+        match executor.copy_out(sql).await {
+            Ok((data, rows)) => {
+                send_copy_out_response(socket, binary).await?;
+                if !data.is_empty() {
+                    send_copy_data(socket, &data).await?;
+                }
+                send_copy_done(socket).await?;
+                send_command_complete(socket, &format!("COPY {rows}")).await?;
+            }
+            Err(e) => {
+                send_exec_error(socket, &e).await?;
+                return Ok(false);
+            }
+        }
     } else {
         send_error(socket, "ERROR", "42601", "Unsupported COPY variant").await?;
+        return Ok(false);
     }
-    Ok(())
+    Ok(true)
 }
 
-async fn send_auth_ok(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_auth_ok(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
     let mut msg = Vec::new();
     msg.push(b'R');
     msg.extend(&(8u32.to_be_bytes()));
@@ -234,39 +663,18 @@ async fn send_auth_ok(socket: &mut TcpStream) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn send_auth_md5(socket: &mut TcpStream, salt: &[u8; 4]) -> anyhow::Result<()> {
+async fn send_auth_md5(socket: &mut (impl AsyncWrite + Unpin), salt: &[u8; 4]) -> anyhow::Result<()> {
     socket.write_u8(b'R').await?;
-    socket.write_u32(12u32.to_be()).await?;
-    socket.write_u32(5u32.to_be()).await?; // auth MD5 code
+    socket.write_u32(12u32).await?;
+    socket.write_u32(5u32).await?; // auth MD5 code
     socket.write_all(salt).await?;
     Ok(())
 }
 
-fn md5_hex(data: &[u8]) -> String {
-    let mut hasher = Md5::new();
-    hasher.update(data);
-    format!("{:x}", hasher.finalize())
-}
-
-fn verify_md5_password(user: &str, client_resp: &str, salt: &[u8; 4]) -> bool {
-    // In real system, get user password from catalog. Here we use "password" for all.
-    let stored_pwd = "password";
-    let mut inner = Vec::new();
-    inner.extend_from_slice(stored_pwd.as_bytes());
-    inner.extend_from_slice(user.as_bytes());
-    let hash1 = md5_hex(&inner);
-    let mut outer = Vec::new();
-    outer.extend_from_slice(hash1.as_bytes());
-    outer.extend_from_slice(salt);
-    let hash2 = md5_hex(&outer);
-    let expected = format!("md5{}", hash2);
-    expected == client_resp
-}
-
-async fn send_param_status(socket: &mut TcpStream, key: &str, val: &str) -> anyhow::Result<()> {
+async fn send_param_status(socket: &mut (impl AsyncWrite + Unpin), key: &str, val: &str) -> anyhow::Result<()> {
     let len = (4 + key.len() + 1 + val.len() + 1) as u32;
     socket.write_u8(b'S').await?;
-    socket.write_u32(len.to_be()).await?;
+    socket.write_u32(len).await?;
     socket.write_all(key.as_bytes()).await?;
     socket.write_u8(0).await?;
     socket.write_all(val.as_bytes()).await?;
@@ -274,97 +682,126 @@ async fn send_param_status(socket: &mut TcpStream, key: &str, val: &str) -> anyh
     Ok(())
 }
 
-async fn send_ready(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_ready(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
     socket.write_u8(b'Z').await?;
-    socket.write_u32(5u32.to_be()).await?;
+    socket.write_u32(5u32).await?;
     socket.write_u8(b'I').await?; // idle
     Ok(())
 }
 
-async fn send_row_description(socket: &mut TcpStream) -> anyhow::Result<()> {
-    let field_name = b"?column?\0";
-    let len = 4 + 2 + field_name.len() + 18; // 18 bytes of fixed fields
-    socket.write_u8(b'T').await?;
-    socket.write_u32((len as u32).to_be()).await?;
-    socket.write_u16(1u16.to_be()).await?; // 1 field
-    socket.write_all(field_name).await?;
-    socket.write_u32(0u32.to_be()).await?; // table oid
-    socket.write_u16(0u16.to_be()).await?; // attr num
-    socket.write_u32(23u32.to_be()).await?; // int4 oid
-    socket.write_u16(4u16.to_be()).await?; // size
-    socket.write_u32((-1i32) as u32).await?; // type modifier
-    socket.write_u16(0u16.to_be()).await?; // text format
-    Ok(())
+/// RowDescription message announcing `fields`, all in text format.
+fn row_description(fields: &[FieldDescription]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend((fields.len() as u16).to_be_bytes());
+    for field in fields {
+        body.extend(field.name.as_bytes());
+        body.push(0);
+        body.extend(0u32.to_be_bytes()); // table oid
+        body.extend(0u16.to_be_bytes()); // attr num
+        body.extend(field.type_oid.to_be_bytes());
+        body.extend(field.type_size.to_be_bytes());
+        body.extend((-1i32).to_be_bytes()); // type modifier
+        body.extend(0u16.to_be_bytes()); // text format
+    }
+    message(b'T', &body)
 }
 
-async fn send_data_row(socket: &mut TcpStream) -> anyhow::Result<()> {
-    let val_bytes = b"1";
-    let len = 4 + 2 + 4 + val_bytes.len();
-    socket.write_u8(b'D').await?;
-    socket.write_u32((len as u32).to_be()).await?;
-    socket.write_u16(1u16.to_be()).await?;
-    socket.write_u32((val_bytes.len() as u32).to_be()).await?;
-    socket.write_all(val_bytes).await?;
-    Ok(())
+/// DataRow message; a NULL value is sent as length -1 with no bytes.
+fn data_row(row: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend((row.len() as u16).to_be_bytes());
+    for value in row {
+        match value {
+            Some(v) => {
+                body.extend((v.len() as u32).to_be_bytes());
+                body.extend(v);
+            }
+            None => body.extend((-1i32).to_be_bytes()),
+        }
+    }
+    message(b'D', &body)
 }
 
-async fn send_command_complete(socket: &mut TcpStream, tag: &str) -> anyhow::Result<()> {
+/// Message of type `typ`: the type byte, a length counting itself, `body`.
+fn message(typ: u8, body: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(5 + body.len());
+    msg.push(typ);
+    msg.extend(((4 + body.len()) as u32).to_be_bytes());
+    msg.extend(body);
+    msg
+}
+
+async fn send_command_complete(socket: &mut (impl AsyncWrite + Unpin), tag: &str) -> anyhow::Result<()> {
     let len = 4 + tag.len() + 1;
     socket.write_u8(b'C').await?;
-    socket.write_u32((len as u32).to_be()).await?;
+    socket.write_u32(len as u32).await?;
     socket.write_all(tag.as_bytes()).await?;
     socket.write_u8(0).await?;
     Ok(())
 }
 
-async fn send_parse_complete(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_parse_complete(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
     socket.write_u8(b'1').await?;
-    socket.write_u32(4u32.to_be()).await?;
+    socket.write_u32(4u32).await?;
     Ok(())
 }
 
-async fn send_bind_complete(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_bind_complete(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
     socket.write_u8(b'2').await?;
-    socket.write_u32(4u32.to_be()).await?;
+    socket.write_u32(4u32).await?;
+    Ok(())
+}
+
+async fn send_parameter_description(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
+    // ParameterDescription: 't' | len | 0 parameters
+    socket.write_u8(b't').await?;
+    socket.write_u32(6u32).await?;
+    socket.write_u16(0u16).await?;
+    Ok(())
+}
+
+async fn send_no_data(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
+    socket.write_u8(b'n').await?;
+    socket.write_u32(4u32).await?;
     Ok(())
 }
 
 // === COPY protocol helpers ===
-async fn send_copy_in_response(socket: &mut TcpStream) -> anyhow::Result<()> {
-    // CopyInResponse: 'G' | len | 0=text format | 0 columns
+async fn send_copy_in_response(socket: &mut (impl AsyncWrite + Unpin), binary: bool) -> anyhow::Result<()> {
+    // CopyInResponse: 'G' | len | 0=text / 1=binary format | 0 columns
     socket.write_u8(b'G').await?;
-    socket.write_u32(7u32.to_be()).await?; // length
-    socket.write_u8(0).await?; // text format
-    socket.write_u16(0u16.to_be()).await?; // no column-specific formats
+    socket.write_u32(7u32).await?; // length
+    socket.write_u8(binary.into()).await?; // overall format
+    socket.write_u16(0u16).await?; // no column-specific formats
     Ok(())
 }
 
-async fn send_copy_out_response(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_copy_out_response(socket: &mut (impl AsyncWrite + Unpin), binary: bool) -> anyhow::Result<()> {
     // CopyOutResponse: 'H'
     socket.write_u8(b'H').await?;
-    socket.write_u32(7u32.to_be()).await?;
-    socket.write_u8(0).await?; // text
-    socket.write_u16(0u16.to_be()).await?;
+    socket.write_u32(7u32).await?;
+    socket.write_u8(binary.into()).await?; // 0=text, 1=binary
+    socket.write_u16(0u16).await?;
     Ok(())
 }
 
-async fn send_copy_data(socket: &mut TcpStream, data: &[u8]) -> anyhow::Result<()> {
+async fn send_copy_data(socket: &mut (impl AsyncWrite + Unpin), data: &[u8]) -> anyhow::Result<()> {
     socket.write_u8(b'd').await?;
-    socket.write_u32(((4 + data.len()) as u32).to_be()).await?;
+    socket.write_u32((4 + data.len()) as u32).await?;
     socket.write_all(data).await?;
     Ok(())
 }
 
-async fn send_copy_done(socket: &mut TcpStream) -> anyhow::Result<()> {
+async fn send_copy_done(socket: &mut (impl AsyncWrite + Unpin)) -> anyhow::Result<()> {
     socket.write_u8(b'c').await?;
-    socket.write_u32(4u32.to_be()).await?;
+    socket.write_u32(4u32).await?;
     Ok(())
 }
 
-async fn send_error(socket: &mut TcpStream, severity: &str, code: &str, message: &str) -> anyhow::Result<()> {
+async fn send_error(socket: &mut (impl AsyncWrite + Unpin), severity: &str, code: &str, message: &str) -> anyhow::Result<()> {
     let len = 4 + 1 + severity.len() + 1 + 1 + code.len() + 1 + 1 + message.len() + 1 + 1;
     socket.write_u8(b'E').await?;
-    socket.write_u32((len as u32).to_be()).await?;
+    socket.write_u32(len as u32).await?;
     socket.write_u8(b'S').await?;
     socket.write_all(severity.as_bytes()).await?;
     socket.write_u8(0).await?;
@@ -376,4 +813,565 @@ async fn send_error(socket: &mut TcpStream, severity: &str, code: &str, message:
     socket.write_u8(0).await?;
     socket.write_u8(0).await?; // terminator
     Ok(())
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    async fn start(conf_yaml: &str) -> SocketAddr {
+        start_with(conf_yaml, ServerOptions::default()).await
+    }
+
+    async fn start_with(conf_yaml: &str, options: ServerOptions) -> SocketAddr {
+        start_serving(conf_yaml, Arc::new(executor::DummyExecutor), options).await
+    }
+
+    async fn start_serving(conf_yaml: &str, executor: Arc<dyn QueryExecutor>, options: ServerOptions) -> SocketAddr {
+        let conf: AuthConfig = serde_yaml::from_str(conf_yaml).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let conf = Arc::new(SharedAuthConfig::new(Arc::new(conf)).unwrap());
+        tokio::spawn(serve_with_shutdown(listener, conf, executor, options, CancellationToken::new()));
+        addr
+    }
+
+    fn startup_message(user: &str) -> Vec<u8> {
+        let mut body = PROTOCOL_VERSION.to_be_bytes().to_vec();
+        body.extend_from_slice(format!("user\0{user}\0\0").as_bytes());
+        let mut msg = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+        msg.extend(body);
+        msg
+    }
+
+    /// Serializes tests opening authenticated sessions, which move the global
+    /// active-connection gauge.
+    static SESSIONS: Mutex<()> = Mutex::const_new(());
+
+    /// Wait until the active-connection gauge reads `n`.
+    async fn wait_for_active(n: i64) {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while CONNECTIONS_ACTIVE.get() != n {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("active connections stuck at {}, expected {n}", CONNECTIONS_ACTIVE.get()));
+    }
+
+    /// Connect and answer the MD5 challenge with `password`.
+    async fn login(addr: SocketAddr, user: &str, password: &str) -> TcpStream {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&startup_message(user)).await.unwrap();
+        // 'R', length, MD5 code, salt.
+        let mut request = [0u8; 13];
+        client.read_exact(&mut request).await.unwrap();
+        let salt = &request[9..13];
+        let inner = auth::md5_hex(format!("{password}{user}").as_bytes());
+        let response = format!("md5{}\0", auth::md5_hex(&[inner.as_bytes(), salt].concat()));
+        client.write_all(&message(b'p', response.as_bytes())).await.unwrap();
+        client
+    }
+
+    /// Read one backend message: type and body.
+    async fn read_message(client: &mut (impl AsyncRead + Unpin)) -> (u8, Vec<u8>) {
+        let typ = client.read_u8().await.unwrap();
+        let len = client.read_u32().await.unwrap() as usize;
+        let mut body = vec![0u8; len - 4];
+        client.read_exact(&mut body).await.unwrap();
+        (typ, body)
+    }
+
+    /// Messages up to and including the next ReadyForQuery.
+    async fn read_until_ready(client: &mut (impl AsyncRead + Unpin)) -> Vec<(u8, Vec<u8>)> {
+        let mut messages = Vec::new();
+        loop {
+            let msg = read_message(client).await;
+            let done = msg.0 == b'Z';
+            messages.push(msg);
+            if done {
+                return messages;
+            }
+        }
+    }
+
+    struct PairsExecutor;
+
+    #[async_trait::async_trait]
+    impl QueryExecutor for PairsExecutor {
+        async fn execute(&self, sql: &str) -> anyhow::Result<executor::ExecResult> {
+            match sql {
+                "SELECT missing" => return Err(ExecError::new("42P01", "relation \"missing\" does not exist").into()),
+                "SELECT crash" => anyhow::bail!("executor crashed"),
+                _ => {}
+            }
+            Ok(executor::ExecResult {
+                fields: vec![FieldDescription::text("id"), FieldDescription::text("sql")],
+                rows: vec![vec![Some(b"1".to_vec()), Some(sql.into())], vec![Some(b"2".to_vec()), None]],
+                tag: "SELECT 2".into(),
+            })
+        }
+
+        async fn copy_out(&self, sql: &str) -> anyhow::Result<(Vec<u8>, u64)> {
+            match sql {
+                "COPY missing TO STDOUT" => Err(ExecError::new("42P01", "relation \"missing\" does not exist").into()),
+                _ => Ok((b"1\ta\n2\tb\n".to_vec(), 2)),
+            }
+        }
+
+        async fn copy_in(&self, _sql: &str, data: Vec<u8>) -> anyhow::Result<u64> {
+            Ok(data.iter().filter(|&&b| b == b'\n').count() as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn simple_query_streams_executor_rows() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        client.write_all(&message(b'Q', b"SELECT x\0")).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"TDDCZ");
+        // Name, table oid, attribute number, text oid, size -1, typmod -1, text format.
+        let field = |name: &str| {
+            [name.as_bytes(), &[0], &[0; 4], &[0; 2], &[0, 0, 0, 25], &[0xff; 2], &[0xff; 4], &[0; 2]].concat()
+        };
+        assert_eq!(messages[0].1, [&[0, 2][..], &field("id"), &field("sql")].concat());
+        assert_eq!(messages[1].1, [&[0, 2, 0, 0, 0, 1][..], b"1", &[0, 0, 0, 8], b"SELECT x"].concat());
+        assert_eq!(messages[2].1, [0, 2, 0, 0, 0, 1, b'2', 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(messages[3].1, b"SELECT 2\0");
+        assert_eq!(messages[4].1, b"I");
+    }
+
+    #[tokio::test]
+    async fn failed_query_reports_error_and_keeps_session() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        for (sql, code) in [("SELECT missing\0", "42P01"), ("SELECT crash\0", "XX000")] {
+            client.write_all(&message(b'Q', sql.as_bytes())).await.unwrap();
+            let messages = read_until_ready(&mut client).await;
+            let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+            assert_eq!(types, b"EZ");
+            let fields = &messages[0].1;
+            assert!(fields.windows(7).any(|w| w == format!("C{code}\0").as_bytes()), "{sql}: {fields:?}");
+            assert!(fields.starts_with(b"SERROR\0"));
+        }
+
+        // The session is still usable.
+        client.write_all(&message(b'Q', b"SELECT 1\0")).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"TDDCZ");
+    }
+
+    #[tokio::test]
+    async fn execute_runs_the_bound_statement() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        let mut batch = message(b'P', b"stmt\0SELECT x\0\0\0");
+        batch.extend(message(b'B', b"\0stmt\0\0\0\0\0\0\0"));
+        batch.extend(message(b'E', b"\0\0\0\0\0"));
+        batch.extend(message(b'S', b""));
+        client.write_all(&batch).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"12DDCZ");
+        assert!(messages[2].1.ends_with(b"SELECT x"), "{:?}", messages[2].1);
+
+        // After an error everything up to Sync is skipped.
+        let mut batch = message(b'E', b"nope\0\0\0\0\0");
+        batch.extend(message(b'P', b"\0SELECT 1\0\0\0"));
+        batch.extend(message(b'S', b""));
+        client.write_all(&batch).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"EZ");
+    }
+
+    #[tokio::test]
+    async fn describe_portal_sends_row_description() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        // Parse, Bind, Describe portal, Execute, Sync: the libpq extended-query sequence.
+        let mut batch = message(b'P', b"\0SELECT x\0\0\0");
+        batch.extend(message(b'B', b"\0\0\0\0\0\0\0\0"));
+        batch.extend(message(b'D', b"P\0"));
+        batch.extend(message(b'E', b"\0\0\0\0\0"));
+        batch.extend(message(b'S', b""));
+        client.write_all(&batch).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"12TDDCZ");
+        assert_eq!(&messages[2].1[..2], [0, 2]);
+        assert!(messages[3].1.ends_with(b"SELECT x"));
+
+        // An executor error surfaces at Describe and skips the Execute.
+        let mut batch = message(b'P', b"\0SELECT missing\0\0\0");
+        batch.extend(message(b'B', b"\0\0\0\0\0\0\0\0"));
+        batch.extend(message(b'D', b"P\0"));
+        batch.extend(message(b'E', b"\0\0\0\0\0"));
+        batch.extend(message(b'S', b""));
+        client.write_all(&batch).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"12EZ");
+    }
+
+    #[tokio::test]
+    async fn copy_streams_through_the_executor() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        client.write_all(&message(b'Q', b"COPY t TO STDOUT\0")).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"HdcCZ");
+        assert_eq!(messages[1].1, b"1\ta\n2\tb\n");
+        assert_eq!(messages[3].1, b"COPY 2\0");
+
+        // A failing copy is reported before any CopyOutResponse.
+        client.write_all(&message(b'Q', b"COPY missing TO STDOUT\0")).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"EZ");
+
+        client.write_all(&message(b'Q', b"COPY t FROM STDIN\0")).await.unwrap();
+        assert_eq!(read_message(&mut client).await.0, b'G');
+        let mut batch = message(b'd', b"1\ta\n");
+        batch.extend(message(b'd', b"2\tb\n3\tc\n"));
+        batch.extend(message(b'c', b""));
+        client.write_all(&batch).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"CZ");
+        assert_eq!(messages[0].1, b"COPY 3\0");
+
+        // Binary copies announce overall format 1.
+        client.write_all(&message(b'Q', b"COPY t TO STDOUT (FORMAT binary)\0")).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        assert_eq!(messages[0], (b'H', vec![1, 0, 0]));
+        client.write_all(&message(b'Q', b"COPY t FROM STDIN (FORMAT binary)\0")).await.unwrap();
+        assert_eq!(read_message(&mut client).await, (b'G', vec![1, 0, 0]));
+        client.write_all(&message(b'c', b"")).await.unwrap();
+        assert_eq!(read_until_ready(&mut client).await[0].1, b"COPY 0\0");
+    }
+
+    #[tokio::test]
+    async fn oversized_copy_in_is_rejected() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start_serving("users: {}\n", Arc::new(PairsExecutor), ServerOptions::default()).await;
+        let mut client = login(addr, "alice", "password").await;
+        read_until_ready(&mut client).await;
+
+        // One message over the limit: reported, its payload skipped, and the
+        // rest of the stream discarded up to CopyDone.
+        client.write_all(&message(b'Q', b"COPY t FROM STDIN\0")).await.unwrap();
+        assert_eq!(read_message(&mut client).await.0, b'G');
+        let mut batch = message(b'd', &vec![b'x'; MAX_COPY_MESSAGE + 1]);
+        batch.extend(message(b'd', b"1\ta\n"));
+        batch.extend(message(b'c', b""));
+        client.write_all(&batch).await.unwrap();
+        let messages = read_until_ready(&mut client).await;
+        let types: Vec<u8> = messages.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"EZ");
+        assert!(messages[0].1.windows(7).any(|w| w == b"C54000\0"));
+
+        // Many messages within the per-message limit but over the total.
+        client.write_all(&message(b'Q', b"COPY t FROM STDIN\0")).await.unwrap();
+        assert_eq!(read_message(&mut client).await.0, b'G');
+        let chunk = message(b'd', &vec![b'x'; MAX_COPY_MESSAGE]);
+        for _ in 0..=MAX_COPY_BYTES / MAX_COPY_MESSAGE {
+            client.write_all(&chunk).await.unwrap();
+        }
+        client.write_all(&message(b'c', b"")).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"EZ");
+
+        // The session is still usable.
+        client.write_all(&message(b'Q', b"SELECT 1\0")).await.unwrap();
+        let types: Vec<u8> = read_until_ready(&mut client).await.iter().map(|m| m.0).collect();
+        assert_eq!(types, b"TDDCZ");
+    }
+
+    #[tokio::test]
+    async fn header_fields_are_big_endian() {
+        let mut out = Vec::new();
+        send_auth_md5(&mut out, b"salt").await.unwrap();
+        assert_eq!(out, [b"R\0\0\0\x0c\0\0\0\x05".as_slice(), b"salt"].concat());
+
+        let mut out = Vec::new();
+        send_param_status(&mut out, "k", "v").await.unwrap();
+        assert_eq!(out, b"S\0\0\0\x08k\0v\0");
+
+        let mut out = Vec::new();
+        send_ready(&mut out).await.unwrap();
+        assert_eq!(out, b"Z\0\0\0\x05I");
+    }
+
+    #[test]
+    fn encodes_row_description_and_data_row_with_null() {
+        let fields = [FieldDescription::int4("id"), FieldDescription::text("name")];
+        let msg = row_description(&fields);
+        assert_eq!(msg[0], b'T');
+        assert_eq!(u32::from_be_bytes(msg[1..5].try_into().unwrap()) as usize, msg.len() - 1);
+        let mut body = &msg[5..];
+        assert_eq!(body.get_u16(), 2);
+        let mut parsed = Vec::new();
+        for _ in 0..2 {
+            let name = extract_cstr(body).unwrap();
+            body.advance(name.len() + 1);
+            let (_table, _attr) = (body.get_u32(), body.get_u16());
+            let (type_oid, type_size) = (body.get_u32(), body.get_i16());
+            assert_eq!((body.get_i32(), body.get_u16()), (-1, 0));
+            parsed.push(FieldDescription { name, type_oid, type_size });
+        }
+        assert!(body.is_empty());
+        assert_eq!(parsed, fields);
+
+        let msg = data_row(&[Some(b"42".to_vec()), None]);
+        assert_eq!(msg[0], b'D');
+        assert_eq!(u32::from_be_bytes(msg[1..5].try_into().unwrap()) as usize, msg.len() - 1);
+        let mut body = &msg[5..];
+        assert_eq!(body.get_u16(), 2);
+        assert_eq!(body.get_i32(), 2);
+        assert_eq!(&body[..2], b"42");
+        body.advance(2);
+        assert_eq!(body.get_i32(), -1);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn active_connections_gauge_returns_to_zero() {
+        let _sessions = SESSIONS.lock().await;
+        let base = CONNECTIONS_ACTIVE.get();
+        let addr = start("users: { carol: secret }\n").await;
+        let a = login(addr, "carol", "secret").await;
+        let b = login(addr, "carol", "secret").await;
+        wait_for_active(base + 2).await;
+
+        let mut rejected = login(addr, "carol", "wrong").await;
+        assert_eq!(rejected.read_u8().await.unwrap(), b'E');
+        // The server closes the failed session; it must not have touched the gauge.
+        rejected.read_to_end(&mut Vec::new()).await.unwrap();
+        assert_eq!(CONNECTIONS_ACTIVE.get(), base + 2);
+
+        drop((a, b));
+        wait_for_active(base).await;
+    }
+
+    #[tokio::test]
+    async fn queries_counted_per_kind_and_user() {
+        let _sessions = SESSIONS.lock().await;
+        let base = CONNECTIONS_ACTIVE.get();
+        let addr = start("users: {}\n").await;
+        let user = "metrics_kind_user";
+        let mut client = login(addr, user, "password").await;
+        client.write_all(&message(b'Q', b"SELECT 1\0")).await.unwrap();
+        client.write_all(&message(b'Q', b"COPY t TO STDOUT\0")).await.unwrap();
+        let count = |kind: &str| serin_metrics::QUERIES_TOTAL.with_label_values(&[user, kind, user]).get();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while count("select") < 1 || count("copy") < 1 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!((count("select"), count("copy"), count("insert")), (1, 1, 0));
+        drop(client);
+        wait_for_active(base).await;
+    }
+
+    #[tokio::test]
+    async fn hba_rejects_denied_cidr_before_auth() {
+        let addr = start("users: {}\nhba:\n  rules:\n    - { cidr: 127.0.0.0/8, action: deny }\n").await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        // Rejected without sending a startup message.
+        let mut typ = [0u8; 1];
+        client.read_exact(&mut typ).await.unwrap();
+        assert_eq!(typ[0], b'E');
+
+        let addr = start("users: {}\nhba:\n  rules:\n    - { cidr: 127.0.0.1/32, action: allow }\n  default: deny\n").await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&startup_message("alice")).await.unwrap();
+        client.read_exact(&mut typ).await.unwrap();
+        assert_eq!(typ[0], b'R'); // authentication request
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_packet_lengths() {
+        let mut oversized = std::io::Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        assert!(read_startup_packet(&mut oversized).await.is_err());
+
+        let _sessions = SESSIONS.lock().await;
+        let addr = start("users: {}\n").await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&startup_message("dave")).await.unwrap();
+        let mut request = [0u8; 13];
+        client.read_exact(&mut request).await.unwrap();
+        // A PasswordMessage length below its own 4-byte length field.
+        client.write_all(&[b'p', 0, 0, 0, 2]).await.unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty(), "connection should be dropped");
+    }
+
+    #[tokio::test]
+    async fn repeated_bad_passwords_block_the_address() {
+        let _sessions = SESSIONS.lock().await;
+        let addr = start("users: { carol: secret }\nrate_limit: { max_failures: 3, window_secs: 60, cooldown_secs: 60 }\n").await;
+        for _ in 0..3 {
+            let mut client = login(addr, "carol", "wrong").await;
+            let (typ, body) = read_message(&mut client).await;
+            assert_eq!(typ, b'E');
+            assert!(body.windows(6).any(|w| w == b"C28P01"));
+        }
+
+        // Refused before any password exchange, even with the right password.
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&startup_message("carol")).await.unwrap();
+        let (typ, body) = read_message(&mut client).await;
+        assert_eq!(typ, b'E', "no authentication request expected");
+        assert!(body.windows(6).any(|w| w == b"C28000"));
+    }
+
+    #[tokio::test]
+    async fn reloaded_password_applies_to_new_connections() {
+        let _sessions = SESSIONS.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("auth.yml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "users: { carol: old }\n").unwrap();
+        let shared = Arc::new(SharedAuthConfig::new(AuthConfig::load(path).unwrap()).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let executor = Arc::new(executor::DummyExecutor);
+        tokio::spawn(serve_with_shutdown(listener, shared.clone(), executor, ServerOptions::default(), CancellationToken::new()));
+        let auth_ok = (b'R', vec![0, 0, 0, 0]);
+
+        let mut before = login(addr, "carol", "old").await;
+        assert_eq!(read_message(&mut before).await, auth_ok);
+
+        // An invalid file is rejected and the old config stays.
+        std::fs::write(path, "users: [carol]\n").unwrap();
+        assert!(shared.reload(path).is_err());
+        std::fs::write(path, "users: { carol: new }\n").unwrap();
+        shared.reload(path).unwrap();
+
+        let mut client = login(addr, "carol", "new").await;
+        assert_eq!(read_message(&mut client).await, auth_ok);
+        let mut client = login(addr, "carol", "old").await;
+        assert_eq!(read_message(&mut client).await.0, b'E');
+        // The session opened before the reload is unaffected.
+        read_until_ready(&mut before).await;
+        before.write_all(&message(b'Q', b"SELECT 1\0")).await.unwrap();
+        assert_eq!(read_until_ready(&mut before).await.last().unwrap().0, b'Z');
+    }
+
+    #[tokio::test]
+    async fn reload_applies_tls_settings() {
+        let _sessions = SESSIONS.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("auth.yml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "users: {}\n").unwrap();
+        let shared = Arc::new(SharedAuthConfig::new(AuthConfig::load(path).unwrap()).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_with_shutdown(listener, shared.clone(), Arc::new(executor::DummyExecutor), ServerOptions::default(), CancellationToken::new()));
+        let ssl_reply = || async move {
+            let mut tcp = TcpStream::connect(addr).await.unwrap();
+            let mut ssl_request = 8u32.to_be_bytes().to_vec();
+            ssl_request.extend(SSL_REQUEST_CODE.to_be_bytes());
+            tcp.write_all(&ssl_request).await.unwrap();
+            tcp.read_u8().await.unwrap()
+        };
+        assert_eq!(ssl_reply().await, b'N');
+
+        // Unusable certificate files are rejected and the old config stays.
+        std::fs::write(path, "users: {}\ntls: { cert: /nonexistent.pem, key: /nonexistent.key }\n").unwrap();
+        assert!(shared.reload(path).is_err());
+        assert_eq!(ssl_reply().await, b'N');
+
+        let certs = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        std::fs::write(path, format!("users: {{}}\ntls: {{ cert: {certs}/server.pem, key: {certs}/server.key }}\n")).unwrap();
+        shared.reload(path).unwrap();
+        assert_eq!(ssl_reply().await, b'S');
+    }
+
+    #[tokio::test]
+    async fn max_connections_and_idle_timeout_are_enforced() {
+        let _sessions = SESSIONS.lock().await;
+        let options = ServerOptions { max_connections: Some(1), idle_timeout: Some(Duration::from_millis(200)) };
+        let addr = start_with("users: { carol: secret }\n", options).await;
+        let mut first = login(addr, "carol", "secret").await;
+
+        // The only slot is taken.
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        let mut reply = Vec::new();
+        second.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply[0], b'E');
+        assert!(reply.windows(5).any(|w| w == b"53300"));
+
+        // The idle session is closed, which frees the slot.
+        let mut reply = Vec::new();
+        first.read_to_end(&mut reply).await.unwrap();
+        assert!(reply.windows(5).any(|w| w == b"57P05"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut third = TcpStream::connect(addr).await.unwrap();
+        third.write_all(&startup_message("carol")).await.unwrap();
+        let mut typ = [0u8; 1];
+        third.read_exact(&mut typ).await.unwrap();
+        assert_eq!(typ[0], b'R');
+    }
+
+    #[tokio::test]
+    async fn client_cert_cn_authenticates_without_password() {
+        use tokio_rustls::rustls::{self, Certificate, PrivateKey, RootCertStore};
+
+        let _sessions = SESSIONS.lock().await;
+        let base = CONNECTIONS_ACTIVE.get();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let addr = start(&format!(
+            "users: {{}}\n\
+             tls: {{ cert: {dir}/server.pem, key: {dir}/server.key, client_ca: {dir}/ca.pem }}\n\
+             cert_auth:\n  map: {{ reporting-service: reporting }}\n  fallback: reject\n"
+        ))
+        .await;
+        let pem = |name: &str| std::io::BufReader::new(std::fs::File::open(format!("{dir}/{name}")).unwrap());
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(rustls_pemfile::certs(&mut pem("ca.pem")).unwrap().remove(0))).unwrap();
+        let chain = rustls_pemfile::certs(&mut pem("client.pem")).unwrap().into_iter().map(Certificate).collect();
+        let key = PrivateKey(rustls_pemfile::pkcs8_private_keys(&mut pem("client.key")).unwrap().remove(0));
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_single_cert(chain, key)
+            .unwrap();
+
+        let mut tcp = TcpStream::connect(addr).await.unwrap();
+        let mut ssl_request = 8u32.to_be_bytes().to_vec();
+        ssl_request.extend(SSL_REQUEST_CODE.to_be_bytes());
+        tcp.write_all(&ssl_request).await.unwrap();
+        assert_eq!(tcp.read_u8().await.unwrap(), b'S');
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+        let mut client = connector.connect("localhost".try_into().unwrap(), tcp).await.unwrap();
+        client.write_all(&startup_message("reporting")).await.unwrap();
+        let mut reply = [0u8; 9];
+        client.read_exact(&mut reply).await.unwrap();
+        // AuthenticationOk: 'R', length 8, code 0.
+        assert_eq!(reply, [b'R', 0, 0, 0, 8, 0, 0, 0, 0]);
+        drop(client);
+        wait_for_active(base).await;
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,198 @@
+//! Per-address throttling of failed password authentication.
+//!
+//! After `max_failures` failed attempts from one client IP within `window_secs`,
+//! that IP is refused before any password exchange for `cooldown_secs`.
+//! Attempts still in progress count against the limit, so parallel
+//! connections cannot try more passwords than sequential ones.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Limits on failed authentication attempts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Failures within the window that trigger the cooldown.
+    pub max_failures: u32,
+    /// Window over which failures are counted.
+    pub window_secs: u64,
+    /// How long an address is refused once the limit is hit.
+    pub cooldown_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { max_failures: 5, window_secs: 60, cooldown_secs: 60 }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    /// Times of failures, pruned to the window on each new attempt.
+    failures: Vec<Instant>,
+    /// Attempts reserved and not yet resolved.
+    in_flight: u32,
+    blocked_until: Option<Instant>,
+}
+
+/// Failure bookkeeping shared by all connections of a server. Limits are
+/// passed in on each use, so a reloaded config applies to the next attempt.
+#[derive(Debug, Default)]
+pub struct AuthLimiter {
+    entries: Mutex<HashMap<IpAddr, Entry>>,
+}
+
+impl AuthLimiter {
+    /// Limiter with no recorded failures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve a password attempt for `ip`. `None` while `ip` is cooling down,
+    /// or when its attempts in flight would reach `max_failures` if they all
+    /// failed, so concurrent connections cannot overrun the limit.
+    pub fn begin(&self, ip: IpAddr, config: &RateLimitConfig) -> Option<Attempt<'_>> {
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_secs);
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(ip).or_default();
+        if entry.blocked_until.is_some_and(|until| until > now) {
+            return None;
+        }
+        entry.blocked_until = None;
+        entry.failures.retain(|&t| now - t < window);
+        if entry.failures.len() + entry.in_flight as usize >= config.max_failures.max(1) as usize {
+            return None;
+        }
+        entry.in_flight += 1;
+        Some(Attempt { limiter: self, ip, cooldown: Duration::from_secs(config.cooldown_secs), max_failures: config.max_failures, outcome: None })
+    }
+
+    /// Forget addresses with nothing in flight, no cooldown running and no
+    /// failure inside the window. Run periodically rather than per attempt.
+    pub fn prune(&self, config: &RateLimitConfig) {
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_secs);
+        self.entries.lock().unwrap().retain(|_, e| {
+            e.in_flight > 0 || e.blocked_until.is_some_and(|t| t > now) || e.failures.iter().any(|&t| now - t < window)
+        });
+    }
+
+    /// Number of addresses currently tracked.
+    pub fn tracked(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+/// A password attempt reserved by [`AuthLimiter::begin`]. Dropping it
+/// without an outcome, e.g. when the client disconnects, just releases it.
+#[derive(Debug)]
+pub struct Attempt<'a> {
+    limiter: &'a AuthLimiter,
+    ip: IpAddr,
+    cooldown: Duration,
+    max_failures: u32,
+    /// `Some(true)` on success, `Some(false)` on failure.
+    outcome: Option<bool>,
+}
+
+impl Attempt<'_> {
+    /// The client authenticated: clear its failure count.
+    pub fn succeeded(mut self) {
+        self.outcome = Some(true);
+    }
+
+    /// The password was wrong: count a failure, starting the cooldown at the limit.
+    pub fn failed(mut self) {
+        self.outcome = Some(false);
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        let mut entries = self.limiter.entries.lock().unwrap();
+        let entry = entries.entry(self.ip).or_default();
+        entry.in_flight = entry.in_flight.saturating_sub(1);
+        match self.outcome {
+            Some(true) => entry.failures.clear(),
+            Some(false) => {
+                entry.failures.push(now);
+                if entry.failures.len() >= self.max_failures.max(1) as usize {
+                    entry.failures.clear();
+                    entry.blocked_until = Some(now + self.cooldown);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_failures: u32, cooldown_secs: u64) -> RateLimitConfig {
+        RateLimitConfig { max_failures, window_secs: 60, cooldown_secs }
+    }
+
+    #[test]
+    fn blocks_after_max_failures_until_cooldown_ends() {
+        let limiter = AuthLimiter::new();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let conf = config(2, 0);
+        limiter.begin(ip, &conf).unwrap().failed();
+        limiter.begin(ip, &conf).unwrap().succeeded();
+        limiter.begin(ip, &conf).unwrap().failed();
+        assert!(limiter.begin(ip, &conf).is_some(), "success resets the count");
+
+        let limiter = AuthLimiter::new();
+        let conf = config(2, 60);
+        limiter.begin(ip, &conf).unwrap().failed();
+        limiter.begin(ip, &conf).unwrap().failed();
+        assert!(limiter.begin(ip, &conf).is_none());
+        assert!(limiter.begin(other, &conf).is_some());
+    }
+
+    #[test]
+    fn concurrent_attempts_share_the_limit() {
+        let limiter = AuthLimiter::new();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let conf = config(2, 60);
+        let first = limiter.begin(ip, &conf).unwrap();
+        let second = limiter.begin(ip, &conf).unwrap();
+        assert!(limiter.begin(ip, &conf).is_none(), "two attempts in flight may already use up the limit");
+        drop(first);
+        second.failed();
+        limiter.begin(ip, &conf).unwrap().failed();
+        assert!(limiter.begin(ip, &conf).is_none());
+    }
+
+    #[test]
+    fn limits_follow_the_config_passed_in() {
+        let limiter = AuthLimiter::new();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        limiter.begin(ip, &config(5, 60)).unwrap().failed();
+        limiter.begin(ip, &config(5, 60)).unwrap().failed();
+        assert!(limiter.begin(ip, &config(5, 60)).is_some());
+        assert!(limiter.begin(ip, &config(2, 60)).is_none(), "a lowered limit applies at once");
+    }
+
+    #[test]
+    fn prune_forgets_idle_addresses() {
+        let limiter = AuthLimiter::new();
+        let conf = RateLimitConfig { max_failures: 5, window_secs: 0, cooldown_secs: 0 };
+        let held = limiter.begin("10.0.0.1".parse().unwrap(), &conf).unwrap();
+        limiter.begin("10.0.0.2".parse().unwrap(), &conf).unwrap().failed();
+        limiter.prune(&conf);
+        assert_eq!(limiter.tracked(), 1, "only the address with an attempt in flight stays");
+        drop(held);
+        limiter.prune(&conf);
+        assert_eq!(limiter.tracked(), 0);
+    }
+}
@@ -0,0 +1,161 @@
+//! TLS server configuration and client-certificate identity extraction.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio_rustls::rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// PEM file locations for the server side of TLS.
+#[derive(Debug, Deserialize)]
+pub struct TlsConfig {
+    /// Server certificate chain.
+    pub cert: String,
+    /// PKCS#8 private key for `cert`.
+    pub key: String,
+    /// CA bundle used to verify client certificates. When set, clients may
+    /// present a certificate; connections without one are still accepted.
+    #[serde(default)]
+    pub client_ca: Option<String>,
+}
+
+impl TlsConfig {
+    /// Build an acceptor from the configured PEM files.
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let certs = load_certs(&self.cert)?;
+        let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(&self.key)?))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no PKCS#8 private key in {}", self.key))?;
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(path)? {
+                    roots.add(&cert)?;
+                }
+                builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots))
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder.with_single_cert(certs, PrivateKey(key))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// `tls-server-end-point` channel-binding data for the server certificate.
+    pub fn end_point_binding(&self) -> anyhow::Result<Vec<u8>> {
+        let cert = load_certs(&self.cert)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no certificate in {}", self.cert))?;
+        crate::auth::tls_server_end_point(&cert.0)
+            .ok_or_else(|| anyhow::anyhow!("cannot pick a channel-binding hash for the signature of {}", self.cert))
+    }
+}
+
+fn load_certs(path: &str) -> anyhow::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03]; // 2.5.4.3
+
+/// Identities a DER client certificate can be mapped by: subject CNs first,
+/// then SAN DNS names. Malformed certificates yield no identities.
+pub fn cert_identities(der: &[u8]) -> Vec<String> {
+    parse_identities(der).unwrap_or_default()
+}
+
+fn parse_identities(der: &[u8]) -> Option<Vec<String>> {
+    // webpki does the certificate and SAN parsing; only the subject's
+    // RDNSequence is walked here, since webpki exposes it as raw DER.
+    let cert = webpki::EndEntityCert::try_from(der).ok()?;
+    let subject = webpki::TrustAnchor::try_from_cert_der(der).ok()?.subject;
+    let mut ids = common_names(subject)?;
+    for name in cert.dns_names().ok()? {
+        ids.push(<&str>::from(name).to_owned());
+    }
+    Some(ids)
+}
+
+/// Every CN attribute in the contents of an X.501 `Name`.
+fn common_names(subject: &[u8]) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    for (_, rdn) in der_elements(subject)? {
+        // A RelativeDistinguishedName is a SET of one or more attributes.
+        for (_, atv) in der_elements(rdn)? {
+            let (_, oid, value) = der_tlv(atv)?;
+            if oid == OID_COMMON_NAME {
+                let (_, name, _) = der_tlv(value)?;
+                names.push(std::str::from_utf8(name).ok()?.to_owned());
+            }
+        }
+    }
+    Some(names)
+}
+
+/// Signature algorithm OID (contents only) of a DER certificate.
+pub(crate) fn signature_algorithm(der: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_tlv(der)?;
+    let (_, algorithm) = *der_elements(cert)?.get(1)?;
+    let (_, oid, _) = der_tlv(algorithm)?;
+    Some(oid)
+}
+
+/// Split one DER TLV off the front of `input`: (tag, contents, rest).
+fn der_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0usize, |acc, &b| acc << 8 | b as usize);
+        rest = &rest[n..];
+        len
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+/// All (tag, contents) elements of a constructed value.
+fn der_elements(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut out = Vec::new();
+    while !input.is_empty() {
+        let (tag, value, rest) = der_tlv(input)?;
+        out.push((tag, value));
+        input = rest;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_cn_and_san() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/client.pem");
+        let der = load_certs(path).unwrap().remove(0);
+        assert_eq!(cert_identities(&der.0), vec!["reporting-service", "app.internal"]);
+        assert!(cert_identities(&der.0[..40]).is_empty());
+    }
+
+    #[test]
+    fn reads_every_attribute_of_a_multi_valued_rdn() {
+        // SET { SEQ { OU "ops" }, SEQ { CN "second" } }
+        let rdn = [
+            0x31, 0x17, 0x30, 0x0a, 0x06, 0x03, 0x55, 0x04, 0x0b, 0x0c, 0x03, b'o', b'p', b's',
+            0x30, 0x09, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x02, b'c', b'n',
+        ];
+        assert_eq!(common_names(&rdn).unwrap(), vec!["cn"]);
+    }
+}
@@ -1,108 +1,617 @@
 //! Simple in-memory connection pool for SerinDB PgWire connections.
-use std::collections::{VecDeque, HashMap};
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 use hyper::{Body, Request, Response, Server, StatusCode};
 use hyper::service::{make_service_fn, service_fn};
+use lru::LruCache;
+use prometheus::{Histogram, HistogramOpts, IntCounter, IntGauge, Registry};
+use serin_metrics::GaugeGuard;
+
+/// How long a `/readyz` probe may wait for a connection.
+const READY_PROBE_TIMEOUT: Duration = Duration::from_millis(200);
 
 pub struct PoolConfig {
+    /// Idle connections kept per backend address.
     pub max_idle: usize,
+    /// Connections checked out at once, across all backends.
     pub max_active: usize,
+    /// Idle connections older than this are closed instead of reused.
+    pub max_lifetime: Duration,
 }
 
 pub struct PooledConn {
+    /// Backend the connection was opened to.
+    addr: String,
     stream: TcpStream,
     created_at: Instant,
+    /// Held while the connection is checked out.
+    checkout: Option<Checkout>,
+}
+
+/// A checked-out connection's slot in `max_active` and in the active gauge.
+struct Checkout {
+    _permit: OwnedSemaphorePermit,
+    _active: GaugeGuard,
+}
+
+/// Pool gauges and counters; add them to a registry with [`PoolMetrics::register`].
+pub struct PoolMetrics {
+    /// Connections parked in the idle queue.
+    pub idle: IntGauge,
+    /// Connections currently checked out.
+    pub active: IntGauge,
+    /// Time spent waiting for a `max_active` slot.
+    pub wait_secs: Histogram,
+    /// Connections opened to a backend.
+    pub created_total: IntCounter,
+    /// Checkouts served from the idle queue.
+    pub reused_total: IntCounter,
+}
+
+impl PoolMetrics {
+    fn new() -> Self {
+        let wait = HistogramOpts::new("serin_pool_wait_seconds", "Time spent waiting for a pooled connection")
+            .buckets(vec![0.0001, 0.001, 0.01, 0.1, 0.5, 1.0, 5.0]);
+        Self {
+            idle: IntGauge::new("serin_pool_idle", "Idle pooled connections").unwrap(),
+            active: IntGauge::new("serin_pool_active", "Checked-out pooled connections").unwrap(),
+            wait_secs: Histogram::with_opts(wait).unwrap(),
+            created_total: IntCounter::new("serin_pool_created_total", "Pooled connections opened").unwrap(),
+            reused_total: IntCounter::new("serin_pool_reused_total", "Checkouts served by an idle connection").unwrap(),
+        }
+    }
+
+    /// Register every pool metric on `registry`.
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.idle.clone()))?;
+        registry.register(Box::new(self.active.clone()))?;
+        registry.register(Box::new(self.wait_secs.clone()))?;
+        registry.register(Box::new(self.created_total.clone()))?;
+        registry.register(Box::new(self.reused_total.clone()))
+    }
+}
+
+impl PooledConn {
+    /// Cheap liveness probe: a healthy idle connection has nothing to read.
+    /// EOF means the peer closed it; unsolicited bytes mean the protocol
+    /// state is unknown, so neither can be reused.
+    fn is_alive(&self) -> bool {
+        let mut buf = [0u8; 1];
+        matches!(self.stream.try_read(&mut buf), Err(e) if e.kind() == std::io::ErrorKind::WouldBlock)
+    }
+}
+
+/// Checked-out connection; dropping it returns the connection to the pool.
+pub struct PooledConnGuard {
+    pool: Arc<ConnectionPool>,
+    conn: Option<PooledConn>,
+}
+
+impl PooledConnGuard {
+    /// Close the connection instead of returning it to the pool, e.g. after
+    /// an I/O error left it in an unknown state. Its `max_active` slot is freed.
+    pub fn invalidate(mut self) {
+        self.conn.take();
+    }
+
+    /// Take the connection out of the pool for good. Its `max_active` slot is
+    /// freed and the pool no longer tracks it.
+    pub fn detach(mut self) -> TcpStream {
+        self.conn.take().expect("present until drop").stream
+    }
+}
+
+impl Deref for PooledConnGuard {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        &self.conn.as_ref().expect("present until drop").stream
+    }
+}
+
+impl DerefMut for PooledConnGuard {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        &mut self.conn.as_mut().expect("present until drop").stream
+    }
+}
+
+impl Drop for PooledConnGuard {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_back(conn);
+        }
+    }
 }
 
 pub struct ConnectionPool {
     config: PoolConfig,
-    idle: Mutex<VecDeque<PooledConn>>,
-    sem: Semaphore,
+    // std mutex: never held across an await, and lets guards return
+    // connections from `Drop`.
+    idle: std::sync::Mutex<HashMap<String, VecDeque<PooledConn>>>,
+    sem: Arc<Semaphore>,
+    metrics: PoolMetrics,
+    /// Backends served by [`get_any`](Self::get_any).
+    balancer: RoundRobin,
 }
 
 impl ConnectionPool {
     pub fn new(config: PoolConfig) -> Self {
-        Self { idle: Mutex::new(VecDeque::new()), sem: Semaphore::new(config.max_active), config }
+        Self::with_backends(config, RoundRobin::new(Vec::new()))
     }
 
-    pub async fn get(&self, addr: &str) -> tokio::io::Result<PooledConn> {
-        if let Ok(mut idle) = self.idle.try_lock() {
-            if let Some(conn) = idle.pop_front() {
-                return Ok(conn);
-            }
+    /// Pool balancing [`get_any`](Self::get_any) over `balancer`'s backends.
+    pub fn with_backends(config: PoolConfig, balancer: RoundRobin) -> Self {
+        Self {
+            idle: std::sync::Mutex::new(HashMap::new()),
+            sem: Arc::new(Semaphore::new(config.max_active)),
+            metrics: PoolMetrics::new(),
+            balancer,
+            config,
         }
-        let _permit = self.sem.acquire().await.unwrap();
-        let stream = TcpStream::connect(addr).await?;
-        Ok(PooledConn { stream, created_at: Instant::now() })
     }
 
-    pub async fn release(&self, mut conn: PooledConn) {
-        if self.idle.lock().await.len() >= self.config.max_idle {
+    /// Backend selector used by [`get_any`](Self::get_any).
+    pub fn balancer(&self) -> &RoundRobin {
+        &self.balancer
+    }
+
+    /// Gauges and counters describing this pool.
+    pub fn metrics(&self) -> &PoolMetrics {
+        &self.metrics
+    }
+
+    /// Check out a connection, waiting while `max_active` are in use.
+    pub async fn get(self: &Arc<Self>, addr: &str) -> tokio::io::Result<PooledConnGuard> {
+        let waited = Instant::now();
+        let permit = self.sem.clone().acquire_owned().await.expect("pool semaphore is never closed");
+        self.metrics.wait_secs.observe(waited.elapsed().as_secs_f64());
+        let checkout = Checkout { _permit: permit, _active: GaugeGuard::new(&self.metrics.active) };
+        loop {
+            let Some(mut conn) = self.idle_pop(addr) else { break };
+            if conn.created_at.elapsed() < self.config.max_lifetime && conn.is_alive() {
+                self.metrics.reused_total.inc();
+                conn.checkout = Some(checkout);
+                return Ok(self.guard(conn));
+            }
             let _ = conn.stream.shutdown().await;
-            return;
         }
-        self.idle.lock().await.push_back(conn);
+        let stream = match TcpStream::connect(addr).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.balancer.mark_down(addr);
+                return Err(e);
+            }
+        };
+        self.balancer.mark_up(addr);
+        self.metrics.created_total.inc();
+        Ok(self.guard(PooledConn { addr: addr.to_string(), stream, created_at: Instant::now(), checkout: Some(checkout) }))
+    }
+
+    /// [`get`](Self::get), failing with [`std::io::ErrorKind::TimedOut`] if no
+    /// connection is available within `timeout`.
+    pub async fn get_timeout(self: &Arc<Self>, addr: &str, timeout: Duration) -> tokio::io::Result<PooledConnGuard> {
+        match tokio::time::timeout(timeout, self.get(addr)).await {
+            Ok(conn) => conn,
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("no pooled connection to {addr} within {timeout:?}"),
+            )),
+        }
+    }
+
+    /// Check out a connection to the next healthy backend. A backend whose
+    /// connect fails is marked down and the next one is tried.
+    pub async fn get_any(self: &Arc<Self>) -> tokio::io::Result<PooledConnGuard> {
+        let mut last_err = None;
+        for _ in 0..self.balancer.len() {
+            let Some(addr) = self.balancer.next().await else { break };
+            match self.get(&addr).await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotConnected, "no healthy backend")
+        }))
+    }
+
+    fn guard(self: &Arc<Self>, conn: PooledConn) -> PooledConnGuard {
+        PooledConnGuard { pool: self.clone(), conn: Some(conn) }
+    }
+
+    fn idle_pop(&self, addr: &str) -> Option<PooledConn> {
+        let mut idle = self.idle.lock().unwrap();
+        let conn = idle.get_mut(addr)?.pop_front()?;
+        self.metrics.idle.dec();
+        Some(conn)
+    }
+
+    /// Park a returned connection in its backend's queue (or close it beyond
+    /// `max_idle`), then free its `max_active` slot so a waiter finds it idle.
+    fn put_back(&self, mut conn: PooledConn) {
+        let checkout = conn.checkout.take();
+        let mut idle = self.idle.lock().unwrap();
+        let queue = idle.entry(conn.addr.clone()).or_default();
+        if queue.len() < self.config.max_idle {
+            queue.push_back(conn);
+            self.metrics.idle.inc();
+        }
+        drop(idle);
+        drop(checkout);
     }
 
-    pub async fn start_readyz(&self, listen: SocketAddr) {
-        let make_svc = make_service_fn(|_|
-            async { Ok::<_, hyper::Error>(service_fn(|_req: Request<Body>| async {
-                Ok::<_, hyper::Error>(Response::new(Body::from("OK")))
-            })) });
+    /// Idle connections parked for `addr`.
+    pub fn idle_count(&self, addr: &str) -> usize {
+        self.idle.lock().unwrap().get(addr).map_or(0, VecDeque::len)
+    }
+
+    /// Whether the pool can currently serve a checkout: some backend is up
+    /// and a connection is obtained within [`READY_PROBE_TIMEOUT`]. Without
+    /// configured backends only a free `max_active` slot is required.
+    pub async fn is_ready(self: &Arc<Self>) -> bool {
+        if self.balancer.is_empty() {
+            let permit = tokio::time::timeout(READY_PROBE_TIMEOUT, self.sem.acquire()).await;
+            return matches!(permit, Ok(Ok(_)));
+        }
+        matches!(tokio::time::timeout(READY_PROBE_TIMEOUT, self.get_any()).await, Ok(Ok(_)))
+    }
+
+    /// Serve `/livez` (200 while the process runs) and `/readyz` (200 or 503
+    /// per [`is_ready`](Self::is_ready), with idle/active counts as JSON).
+    /// Returns the bound address.
+    pub async fn start_readyz(self: &Arc<Self>, listen: SocketAddr) -> SocketAddr {
+        let pool = self.clone();
+        let make_svc = make_service_fn(move |_| {
+            let pool = pool.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                    let pool = pool.clone();
+                    async move { Ok::<_, hyper::Error>(pool.probe_response(req.uri().path()).await) }
+                }))
+            }
+        });
         let server = Server::bind(&listen).serve(make_svc);
+        let bound = server.local_addr();
         tokio::spawn(async move {
             if let Err(e) = server.await { eprintln!("readyz server error: {e}"); }
         });
+        bound
+    }
+
+    async fn probe_response(self: &Arc<Self>, path: &str) -> Response<Body> {
+        let (status, body) = match path {
+            "/livez" => (StatusCode::OK, "OK".to_string()),
+            "/readyz" => {
+                let ready = self.is_ready().await;
+                let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+                let (idle, active) = (self.metrics.idle.get(), self.metrics.active.get());
+                (status, format!("{{\"ready\":{ready},\"idle\":{idle},\"active\":{active}}}"))
+            }
+            _ => (StatusCode::NOT_FOUND, "not found".to_string()),
+        };
+        let mut resp = Response::new(Body::from(body));
+        *resp.status_mut() = status;
+        resp
     }
 }
 
+/// Prepared-statement cache evicting the least recently used entry; `get`
+/// and `put` are O(1).
 pub struct StatementCache {
-    cap: usize,
-    map: Mutex<HashMap<String, String>>, // key->sql
-    order: Mutex<VecDeque<String>>, // LRU order
+    entries: Mutex<LruCache<String, String>>, // key->sql
 }
 
 impl StatementCache {
+    /// Cache holding at most `cap` statements.
+    ///
+    /// # Panics
+    /// If `cap` is zero.
     pub fn new(cap: usize) -> Self {
-        Self { cap, map: Mutex::new(HashMap::new()), order: Mutex::new(VecDeque::new()) }
+        let cap = NonZeroUsize::new(cap).expect("statement cache capacity must be non-zero");
+        Self { entries: Mutex::new(LruCache::new(cap)) }
     }
 
+    /// Look up `key`, marking it most recently used.
     pub async fn get(&self, key: &str) -> Option<String> {
-        let mut ord = self.order.lock().await;
-        if let Some(pos) = ord.iter().position(|k| k == key) {
-            let k = ord.remove(pos).unwrap();
-            ord.push_back(k.clone());
-        }
-        self.map.lock().await.get(key).cloned()
+        self.entries.lock().await.get(key).cloned()
     }
 
+    /// Insert or replace `key`, evicting the least recently used entry when full.
     pub async fn put(&self, key: String, sql: String) {
-        let mut map = self.map.lock().await;
-        let mut ord = self.order.lock().await;
-        if !map.contains_key(&key) && map.len() == self.cap {
-            if let Some(old) = ord.pop_front() { map.remove(&old); }
-        }
-        map.insert(key.clone(), sql);
-        ord.retain(|k| k != &key);
-        ord.push_back(key);
+        self.entries.lock().await.put(key, sql);
     }
 }
 
-// Round-robin balancer
+/// How long a backend stays down after a failure unless marked up earlier.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5);
+
+// Round-robin balancer skipping backends that are marked down.
 pub struct RoundRobin {
     backends: Vec<String>,
     idx: Mutex<usize>,
+    /// Per backend, the instant until which it is skipped.
+    down_until: std::sync::Mutex<Vec<Option<Instant>>>,
+    cooldown: Duration,
 }
 
 impl RoundRobin {
-    pub fn new(backends: Vec<String>) -> Self { Self { backends, idx: Mutex::new(0) } }
-    pub async fn next(&self) -> String {
+    pub fn new(backends: Vec<String>) -> Self { Self::with_cooldown(backends, DEFAULT_COOLDOWN) }
+
+    /// Balancer keeping failed backends down for `cooldown`.
+    pub fn with_cooldown(backends: Vec<String>, cooldown: Duration) -> Self {
+        let down_until = std::sync::Mutex::new(vec![None; backends.len()]);
+        Self { backends, idx: Mutex::new(0), down_until, cooldown }
+    }
+
+    /// Number of configured backends, healthy or not.
+    pub fn len(&self) -> usize {
+        self.backends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+
+    /// Next healthy backend in rotation, or `None` if every backend is down.
+    pub async fn next(&self) -> Option<String> {
         let mut i = self.idx.lock().await;
-        let addr = self.backends[*i].clone();
-        *i = (*i + 1) % self.backends.len();
-        addr
+        let down_until = self.down_until.lock().unwrap();
+        let now = Instant::now();
+        for _ in 0..self.backends.len() {
+            let candidate = *i;
+            *i = (*i + 1) % self.backends.len();
+            if !matches!(down_until[candidate], Some(until) if until > now) {
+                return Some(self.backends[candidate].clone());
+            }
+        }
+        None
+    }
+
+    /// Skip `addr` for the cooldown period. Unknown addresses are ignored.
+    pub fn mark_down(&self, addr: &str) {
+        self.set_down_until(addr, Some(Instant::now() + self.cooldown));
+    }
+
+    /// Put `addr` back into rotation before its cooldown ends.
+    pub fn mark_up(&self, addr: &str) {
+        self.set_down_until(addr, None);
+    }
+
+    fn set_down_until(&self, addr: &str, until: Option<Instant>) {
+        if let Some(pos) = self.backends.iter().position(|b| b == addr) {
+            self.down_until.lock().unwrap()[pos] = until;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn config() -> PoolConfig {
+        PoolConfig { max_idle: 4, max_active: 4, max_lifetime: Duration::from_secs(60) }
+    }
+
+    #[tokio::test]
+    async fn get_discards_aged_and_dead_idle_conns() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::new(config()));
+
+        // Aged but otherwise healthy.
+        let aged = TcpStream::connect(&addr).await.unwrap();
+        let _peer = listener.accept().await.unwrap();
+        let created_at = Instant::now() - Duration::from_secs(120);
+        pool.put_back(PooledConn { addr: addr.clone(), stream: aged, created_at, checkout: None });
+
+        // Fresh, but the server side has gone away.
+        let dead = TcpStream::connect(&addr).await.unwrap();
+        drop(listener.accept().await.unwrap());
+        tokio::task::yield_now().await;
+        pool.put_back(PooledConn { addr: addr.clone(), stream: dead, created_at: Instant::now(), checkout: None });
+
+        let before = Instant::now();
+        let conn = pool.get(&addr).await.unwrap();
+        assert!(conn.conn.as_ref().unwrap().created_at >= before, "expected a freshly opened connection");
+        assert_eq!(pool.idle_count(&addr), 0);
+    }
+
+    #[tokio::test]
+    async fn is_alive_only_for_open_quiet_conns() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pooled = |stream| PooledConn { addr: addr.clone(), stream, created_at: Instant::now(), checkout: None };
+
+        let live = pooled(TcpStream::connect(&addr).await.unwrap());
+        let (mut peer, _) = listener.accept().await.unwrap();
+        assert!(live.is_alive(), "an open connection with nothing to read is reusable");
+
+        // Unsolicited bytes leave the protocol state unknown.
+        peer.write_all(b"N").await.unwrap();
+        live.stream.readable().await.unwrap();
+        assert!(!live.is_alive());
+
+        let dead = pooled(TcpStream::connect(&addr).await.unwrap());
+        drop(listener.accept().await.unwrap());
+        dead.stream.readable().await.unwrap();
+        assert!(!dead.is_alive());
+    }
+
+    #[tokio::test]
+    async fn max_active_blocks_until_release() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::new(PoolConfig { max_active: 2, ..config() }));
+
+        let first = pool.get(&addr).await.unwrap();
+        let _second = pool.get(&addr).await.unwrap();
+        let third = tokio::time::timeout(Duration::from_millis(50), pool.get(&addr)).await;
+        assert!(third.is_err(), "third get should wait for a free slot");
+
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_millis(500), pool.get(&addr)).await;
+        assert!(third.expect("slot freed by release").is_ok());
+    }
+
+    #[tokio::test]
+    async fn dropped_guard_returns_conn_to_idle() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::new(config()));
+
+        let guard = pool.get(&addr).await.unwrap();
+        let local = guard.local_addr().unwrap();
+        drop(guard);
+        assert_eq!(pool.idle_count(&addr), 1);
+
+        let again = pool.get(&addr).await.unwrap();
+        assert_eq!(again.local_addr().unwrap(), local, "idle connection should be reused");
+    }
+
+    #[tokio::test]
+    async fn invalidated_and_detached_conns_leave_the_pool() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::new(PoolConfig { max_active: 1, ..config() }));
+
+        pool.get(&addr).await.unwrap().invalidate();
+        assert_eq!(pool.idle_count(&addr), 0);
+        assert_eq!(pool.metrics().active.get(), 0);
+
+        let guard = pool.get_timeout(&addr, Duration::from_millis(500)).await.expect("slot freed by invalidate");
+        let local = guard.local_addr().unwrap();
+        let stream = guard.detach();
+        assert_eq!(stream.local_addr().unwrap(), local);
+        assert_eq!(pool.idle_count(&addr), 0);
+        let _next = pool.get_timeout(&addr, Duration::from_millis(500)).await.expect("slot freed by detach");
+    }
+
+    #[tokio::test]
+    async fn get_timeout_fails_on_saturated_pool() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::new(PoolConfig { max_active: 1, ..config() }));
+        let registry = Registry::new();
+        pool.metrics().register(&registry).unwrap();
+
+        let held = pool.get(&addr).await.unwrap();
+        let start = Instant::now();
+        let err = pool.get_timeout(&addr, Duration::from_millis(50)).await.err().expect("pool is saturated");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let m = pool.metrics();
+        assert_eq!((m.active.get(), m.idle.get(), m.created_total.get()), (1, 0, 1));
+        drop(held);
+        assert_eq!((m.active.get(), m.idle.get()), (0, 1));
+        let _again = pool.get_timeout(&addr, Duration::from_millis(50)).await.unwrap();
+        assert_eq!((m.active.get(), m.created_total.get(), m.reused_total.get()), (1, 1, 1));
+        assert_eq!(registry.gather().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn idle_conns_are_only_reused_for_their_backend() {
+        let a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (addr_a, addr_b) = (a.local_addr().unwrap().to_string(), b.local_addr().unwrap().to_string());
+        let pool = Arc::new(ConnectionPool::new(config()));
+
+        drop(pool.get(&addr_a).await.unwrap());
+        assert_eq!(pool.idle_count(&addr_a), 1);
+
+        let conn_b = pool.get(&addr_b).await.unwrap();
+        assert_eq!(conn_b.peer_addr().unwrap().to_string(), addr_b);
+        assert_eq!(pool.metrics().created_total.get(), 2);
+        assert_eq!(pool.idle_count(&addr_a), 1, "A's connection stays parked");
+    }
+
+    #[tokio::test]
+    async fn round_robin_skips_backends_during_cooldown() {
+        let backends: Vec<String> = ["a:1", "b:1", "c:1"].map(String::from).to_vec();
+        let rr = RoundRobin::with_cooldown(backends, Duration::from_millis(100));
+        rr.mark_down("b:1");
+        for _ in 0..9 {
+            assert_ne!(rr.next().await.as_deref(), Some("b:1"));
+        }
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            seen.push(rr.next().await.unwrap());
+        }
+        assert!(seen.iter().any(|b| b == "b:1"), "b returns after the cooldown: {seen:?}");
+
+        for b in ["a:1", "b:1", "c:1"] {
+            rr.mark_down(b);
+        }
+        assert_eq!(rr.next().await, None);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn get_any_fails_over_to_a_healthy_backend() {
+        let live = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let live_addr = live.local_addr().unwrap().to_string();
+        // Bound then dropped, so connecting is refused.
+        let dead_addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().to_string();
+        let rr = RoundRobin::new(vec![dead_addr.clone(), live_addr.clone()]);
+        let pool = Arc::new(ConnectionPool::with_backends(config(), rr));
+
+        let conn = pool.get_any().await.unwrap();
+        assert_eq!(conn.peer_addr().unwrap().to_string(), live_addr);
+        drop(conn);
+        // The dead backend is now skipped and the live one reused from idle.
+        let _again = pool.get_any().await.unwrap();
+        assert_eq!(pool.metrics().reused_total.get(), 1);
+        assert_eq!(pool.balancer().next().await, Some(live_addr));
+    }
+
+    #[tokio::test]
+    async fn readyz_is_unavailable_when_all_backends_are_down() {
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = backend.local_addr().unwrap().to_string();
+        let pool = Arc::new(ConnectionPool::with_backends(config(), RoundRobin::new(vec![addr.clone()])));
+        let probe = pool.start_readyz("127.0.0.1:0".parse().unwrap()).await;
+        let client = hyper::Client::new();
+        let fetch = |path: &str| client.get(format!("http://{probe}{path}").parse().unwrap());
+
+        let resp = fetch("/readyz").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(&body[..], br#"{"ready":true,"idle":1,"active":0}"#);
+
+        pool.balancer().mark_down(&addr);
+        let resp = fetch("/readyz").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(&body[..], br#"{"ready":false,"idle":1,"active":0}"#);
+        assert_eq!(fetch("/livez").await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn statement_cache_evicts_least_recently_used() {
+        let cache = StatementCache::new(3);
+        for k in ["a", "b", "c"] {
+            cache.put(k.into(), format!("SELECT {k}")).await;
+        }
+        // Promote the oldest; "b" becomes the eviction candidate.
+        assert_eq!(cache.get("a").await.as_deref(), Some("SELECT a"));
+        cache.put("d".into(), "SELECT d".into()).await;
+
+        assert_eq!(cache.get("b").await, None);
+        for k in ["a", "c", "d"] {
+            assert!(cache.get(k).await.is_some(), "{k} should still be cached");
+        }
+        // Replacing an existing key does not evict.
+        cache.put("c".into(), "SELECT c2".into()).await;
+        assert_eq!(cache.get("c").await.as_deref(), Some("SELECT c2"));
+        assert!(cache.get("a").await.is_some() && cache.get("d").await.is_some());
+    }
+}
@@ -1,32 +1,115 @@
 //! Raft consensus layer for SerinDB cluster.
-use openraft::{Config, Raft, RaftNetwork, RaftStorage, AppData, AppDataResponse};
+use openraft::error::ClientWriteError;
+use openraft::{Config, Raft};
 use serde::{Serialize, Deserialize};
-use async_trait::async_trait;
 use std::sync::Arc;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+pub mod network;
+pub mod store;
+
+pub use network::{serve, Network, RaftService};
+pub use store::{CommandLog, Machine, StateMachine, Storage};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct LogEntry(pub Vec<u8>);
-impl AppData for LogEntry {}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ClientResp;
-impl AppDataResponse for ClientResp {}
+/// State-machine result of applying one entry.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientResp(pub Vec<u8>);
 
 pub type NodeId = u64;
 
-pub struct Network;
-#[async_trait]
-impl RaftNetwork<LogEntry> for Network {}
+pub type SerinRaft = Raft<LogEntry, ClientResp, Network, Storage>;
 
-pub struct Storage;
-#[async_trait]
-impl RaftStorage<LogEntry, ClientResp> for Storage {}
+pub fn new_raft(node_id: NodeId, network: Arc<Network>, storage: Arc<Storage>) -> SerinRaft {
+    let cfg = Config { cluster_name: "serin-cluster".into(), ..Default::default() }.validate().unwrap();
+    Raft::new(node_id, Arc::new(cfg), network, storage)
+}
 
-pub type SerinRaft = Raft<LogEntry, ClientResp, Network, Storage>;
+/// Submit `payload` to the Raft group and wait until it is applied, returning
+/// the state machine's result. On a follower this fails with
+/// [`ClientWriteError::ForwardToLeader`], naming the leader to retry against.
+pub async fn propose(raft: &SerinRaft, payload: Vec<u8>) -> Result<ClientResp, ClientWriteError> {
+    raft.client_write(LogEntry(payload)).await.map(|resp| resp.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn three_node_cluster_elects_leader() {
+        let ids: [NodeId; 3] = [1, 2, 3];
+        let mut listeners = Vec::new();
+        for _ in ids {
+            listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+        let addrs: Vec<String> = listeners.iter().map(|l| format!("http://{}", l.local_addr().unwrap())).collect();
+
+        let mut rafts = Vec::new();
+        for (&id, listener) in ids.iter().zip(listeners) {
+            let network = Arc::new(Network::new());
+            for (&peer, addr) in ids.iter().zip(&addrs) {
+                if peer != id {
+                    network.add_peer(peer, addr.clone()).await;
+                }
+            }
+            let raft = Arc::new(new_raft(id, network, Arc::new(Storage::new(Arc::new(CommandLog::default())))));
+            tokio::spawn(serve(raft.clone(), listener));
+            rafts.push(raft);
+        }
+
+        rafts[0].initialize(ids.into_iter().collect::<BTreeSet<_>>()).await.unwrap();
+        let mut leaders = BTreeSet::new();
+        for raft in &rafts {
+            let m = raft
+                .wait(Some(Duration::from_secs(10)))
+                .metrics(|m| m.current_leader.is_some(), "leader elected")
+                .await
+                .unwrap();
+            leaders.insert(m.current_leader.unwrap());
+        }
+        assert_eq!(leaders.len(), 1, "nodes disagree on the leader: {leaders:?}");
+        assert!(ids.contains(leaders.first().unwrap()));
+
+        for raft in &rafts {
+            raft.shutdown().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn propose_returns_applied_response() {
+        let raft = new_raft(1, Arc::new(Network::new()), Arc::new(Storage::new(Arc::new(CommandLog::default()))));
+        raft.initialize(BTreeSet::from([1])).await.unwrap();
+        raft.wait(Some(Duration::from_secs(5))).current_leader(1, "single node leads").await.unwrap();
+
+        assert_eq!(propose(&raft, b"first".to_vec()).await.unwrap(), ClientResp(0u64.to_be_bytes().to_vec()));
+        assert_eq!(propose(&raft, b"second".to_vec()).await.unwrap(), ClientResp(1u64.to_be_bytes().to_vec()));
+
+        // A node outside any cluster cannot accept writes and says so.
+        let lone = new_raft(2, Arc::new(Network::new()), Arc::new(Storage::new(Arc::new(CommandLog::default()))));
+        match propose(&lone, b"nope".to_vec()).await {
+            Err(ClientWriteError::ForwardToLeader(f)) => assert_eq!(f.leader_id, None),
+            other => panic!("expected ForwardToLeader, got {other:?}"),
+        }
+        raft.shutdown().await.unwrap();
+        lone.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreachable_peer_is_a_network_error() {
+        let network = Network::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        network.add_peer(2, format!("http://{addr}")).await;
 
-pub fn new_raft(node_id: NodeId) -> SerinRaft {
-    let cfg = Config::build("serin-cluster").validate().unwrap();
-    let net = Network;
-    let store = Storage;
-    Raft::new(node_id, Arc::new(cfg), net, store)
-} 
\ No newline at end of file
+        let vote = openraft::raft::VoteRequest::new(1, 1, None);
+        assert!(openraft::RaftNetwork::send_vote(&network, 2, vote).await.is_err());
+        let vote = openraft::raft::VoteRequest::new(1, 1, None);
+        assert!(openraft::RaftNetwork::send_vote(&network, 3, vote).await.is_err()); // unknown peer
+    }
+}
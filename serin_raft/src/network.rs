@@ -0,0 +1,253 @@
+//! gRPC transport between Raft peers.
+//!
+//! Each openraft RPC is a unary gRPC method under `serin.raft.Raft`. Requests
+//! and responses are the openraft types themselves, encoded with bincode by
+//! [`BincodeCodec`], so no `.proto` file has to mirror them.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context as _};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut};
+use openraft::raft::{InstallSnapshotRequest, InstallSnapshotResponse, VoteRequest, VoteResponse};
+use openraft::{AppendEntriesRequest, AppendEntriesResponse, RaftNetwork};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use tonic::body::BoxBody;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::{empty_body, http, BoxFuture, Context, Poll, Service};
+use tonic::server::{NamedService, UnaryService};
+use tonic::transport::{Body, Channel, Endpoint};
+use tonic::{Code, Request, Response, Status};
+
+use crate::{LogEntry, NodeId, SerinRaft};
+
+const APPEND_ENTRIES: &str = "/serin.raft.Raft/AppendEntries";
+const INSTALL_SNAPSHOT: &str = "/serin.raft.Raft/InstallSnapshot";
+const VOTE: &str = "/serin.raft.Raft/Vote";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Encodes `E` and decodes `D` with bincode.
+pub struct BincodeCodec<E, D>(PhantomData<fn(E) -> D>);
+
+impl<E, D> Default for BincodeCodec<E, D> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E, D> Codec for BincodeCodec<E, D>
+where
+    E: Serialize + Send + 'static,
+    D: DeserializeOwned + Send + 'static,
+{
+    type Encode = E;
+    type Decode = D;
+    type Encoder = BincodeEncoder<E>;
+    type Decoder = BincodeDecoder<D>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BincodeEncoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BincodeDecoder(PhantomData)
+    }
+}
+
+pub struct BincodeEncoder<T>(PhantomData<fn(T)>);
+
+impl<T: Serialize> Encoder for BincodeEncoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: T, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        bincode::serialize_into(dst.writer(), &item).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+pub struct BincodeDecoder<T>(PhantomData<fn() -> T>);
+
+impl<T: DeserializeOwned> Decoder for BincodeDecoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<T>, Status> {
+        let item = bincode::deserialize_from(src.reader()).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Some(item))
+    }
+}
+
+/// Client side: sends RPCs to peers looked up in an address registry.
+#[derive(Default)]
+pub struct Network {
+    peers: RwLock<HashMap<NodeId, String>>, // node_id -> "http://host:port"
+    channels: RwLock<HashMap<NodeId, Channel>>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register or replace the address `node` is reachable at.
+    pub async fn add_peer(&self, node: NodeId, addr: impl Into<String>) {
+        self.peers.write().await.insert(node, addr.into());
+        self.channels.write().await.remove(&node);
+    }
+
+    pub async fn remove_peer(&self, node: NodeId) {
+        self.peers.write().await.remove(&node);
+        self.channels.write().await.remove(&node);
+    }
+
+    async fn channel(&self, target: NodeId) -> anyhow::Result<Channel> {
+        if let Some(channel) = self.channels.read().await.get(&target) {
+            return Ok(channel.clone());
+        }
+        let addr = self.peers.read().await.get(&target).cloned().ok_or_else(|| anyhow!("no address for node {target}"))?;
+        let channel = Endpoint::from_shared(addr.clone())?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .with_context(|| format!("connect to node {target} at {addr}"))?;
+        self.channels.write().await.insert(target, channel.clone());
+        Ok(channel)
+    }
+
+    async fn call<Req, Resp>(&self, target: NodeId, path: &'static str, rpc: Req) -> anyhow::Result<Resp>
+    where
+        Req: Serialize + Send + Sync + 'static,
+        Resp: DeserializeOwned + Send + Sync + 'static,
+    {
+        let mut client = tonic::client::Grpc::new(self.channel(target).await?);
+        client.ready().await.with_context(|| format!("node {target} not ready"))?;
+        match client.unary(Request::new(rpc), http::uri::PathAndQuery::from_static(path), BincodeCodec::default()).await {
+            Ok(resp) => Ok(resp.into_inner()),
+            Err(status) => {
+                if status.code() == Code::Unavailable {
+                    // Reconnect on the next attempt.
+                    self.channels.write().await.remove(&target);
+                }
+                Err(anyhow!("{path} to node {target}: {status}"))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RaftNetwork<LogEntry> for Network {
+    async fn send_append_entries(
+        &self,
+        target: NodeId,
+        rpc: AppendEntriesRequest<LogEntry>,
+    ) -> anyhow::Result<AppendEntriesResponse> {
+        self.call(target, APPEND_ENTRIES, rpc).await
+    }
+
+    async fn send_install_snapshot(
+        &self,
+        target: NodeId,
+        rpc: InstallSnapshotRequest,
+    ) -> anyhow::Result<InstallSnapshotResponse> {
+        self.call(target, INSTALL_SNAPSHOT, rpc).await
+    }
+
+    async fn send_vote(&self, target: NodeId, rpc: VoteRequest) -> anyhow::Result<VoteResponse> {
+        self.call(target, VOTE, rpc).await
+    }
+}
+
+/// Adapts an async handler to tonic's [`UnaryService`].
+struct Unary<F>(F);
+
+impl<Req, Resp, F, Fut> UnaryService<Req> for Unary<F>
+where
+    F: FnMut(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, Status>> + Send + 'static,
+{
+    type Response = Resp;
+    type Future = BoxFuture<Response<Resp>, Status>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        let fut = (self.0)(request.into_inner());
+        Box::pin(async move { fut.await.map(Response::new) })
+    }
+}
+
+/// Server side: hands incoming RPCs to the local Raft node.
+#[derive(Clone)]
+pub struct RaftService {
+    raft: Arc<SerinRaft>,
+}
+
+impl RaftService {
+    pub fn new(raft: Arc<SerinRaft>) -> Self {
+        Self { raft }
+    }
+}
+
+impl NamedService for RaftService {
+    const NAME: &'static str = "serin.raft.Raft";
+}
+
+impl Service<http::Request<Body>> for RaftService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Infallible>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let raft = self.raft.clone();
+        match req.uri().path() {
+            APPEND_ENTRIES => Box::pin(async move {
+                let handler = Unary(move |rpc| {
+                    let raft = raft.clone();
+                    async move { raft.append_entries(rpc).await.map_err(|e| Status::internal(e.to_string())) }
+                });
+                Ok(tonic::server::Grpc::new(BincodeCodec::default()).unary(handler, req).await)
+            }),
+            INSTALL_SNAPSHOT => Box::pin(async move {
+                let handler = Unary(move |rpc| {
+                    let raft = raft.clone();
+                    async move { raft.install_snapshot(rpc).await.map_err(|e| Status::internal(e.to_string())) }
+                });
+                Ok(tonic::server::Grpc::new(BincodeCodec::default()).unary(handler, req).await)
+            }),
+            VOTE => Box::pin(async move {
+                let handler = Unary(move |rpc| {
+                    let raft = raft.clone();
+                    async move { raft.vote(rpc).await.map_err(|e| Status::internal(e.to_string())) }
+                });
+                Ok(tonic::server::Grpc::new(BincodeCodec::default()).unary(handler, req).await)
+            }),
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", (Code::Unimplemented as i32).to_string())
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap())
+            }),
+        }
+    }
+}
+
+/// Serve Raft RPCs for `raft` on `listener` until the task is dropped.
+pub async fn serve(raft: Arc<SerinRaft>, listener: TcpListener) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(RaftService::new(raft))
+        .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+        .await
+}
@@ -0,0 +1,531 @@
+//! Raft log, hard state and state machine.
+//!
+//! A storage opened on a directory keeps three files there:
+//! - `raft.wal`: log mutations and apply progress, as [`WalRecord`]s in a
+//!   `serin_storage` WAL;
+//! - `hard_state`: the current term and vote;
+//! - `snapshot`: the latest state machine snapshot and the snapshot counter.
+//!
+//! Committed commands are handed to a [`Machine`], which owns the
+//! application state and produces its snapshots.
+//!
+//! On open the snapshot is loaded, the WAL replayed (dropping a torn trailing
+//! record), and log entries applied after the snapshot are applied again.
+//! Purging the log rewrites the WAL with only the live entries. File I/O runs
+//! on the blocking thread pool.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use openraft::storage::{HardState, LogState, Snapshot};
+use openraft::{
+    AnyError, EffectiveMembership, Entry, EntryPayload, ErrorSubject, ErrorVerb, LogId, RaftStorage, SnapshotMeta,
+    StateMachineChanges, StorageError, StorageIOError,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serin_storage::wal::{recover_log, WalWriter};
+use tokio::sync::RwLock;
+
+use crate::{ClientResp, LogEntry};
+
+/// Application state driven by committed commands. Storage calls it in log
+/// order while holding its state machine lock.
+pub trait Machine: Debug + Send + Sync + 'static {
+    /// Apply one committed command and return the client's result.
+    fn apply(&self, command: &[u8]) -> Vec<u8>;
+    /// Serialize the current state.
+    fn snapshot(&self) -> Vec<u8>;
+    /// Replace the current state with a [`Machine::snapshot`] result.
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()>;
+}
+
+/// Machine that keeps every command, in log order. A command's result is
+/// its position as a big-endian `u64`.
+#[derive(Debug, Default)]
+pub struct CommandLog(Mutex<Vec<Vec<u8>>>);
+
+impl CommandLog {
+    /// Commands applied so far.
+    pub fn commands(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Machine for CommandLog {
+    fn apply(&self, command: &[u8]) -> Vec<u8> {
+        let mut commands = self.0.lock().unwrap();
+        commands.push(command.to_vec());
+        (commands.len() as u64 - 1).to_be_bytes().to_vec()
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        bincode::serialize(&*self.0.lock().unwrap()).expect("commands serialize")
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        *self.0.lock().unwrap() = bincode::deserialize(snapshot).map_err(invalid_data)?;
+        Ok(())
+    }
+}
+
+/// Apply progress kept beside the [`Machine`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateMachine {
+    pub last_applied: Option<LogId>,
+    pub last_membership: Option<EffectiveMembership>,
+}
+
+impl StateMachine {
+    /// Apply one entry; commands go to `machine`, other entries produce an
+    /// empty result.
+    fn apply(&mut self, machine: &dyn Machine, entry: &Entry<LogEntry>) -> ClientResp {
+        self.last_applied = Some(entry.log_id);
+        match &entry.payload {
+            EntryPayload::Blank => ClientResp::default(),
+            EntryPayload::Normal(LogEntry(bytes)) => ClientResp(machine.apply(bytes)),
+            EntryPayload::Membership(m) => {
+                self.last_membership = Some(EffectiveMembership::new(entry.log_id, m.clone()));
+                ClientResp::default()
+            }
+        }
+    }
+}
+
+/// Snapshot payload: apply progress plus the machine's own snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotData {
+    sm: StateMachine,
+    state: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredSnapshot {
+    meta: SnapshotMeta,
+    data: Vec<u8>,
+    /// Snapshot counter when this snapshot was stored, so ids stay unique
+    /// across restarts.
+    idx: u64,
+}
+
+/// One WAL record. Replaying all records in order rebuilds the log.
+#[derive(Debug, Serialize, Deserialize)]
+enum WalRecord {
+    Append(Entry<LogEntry>),
+    /// Entries with index >= this were deleted.
+    DeleteSince(u64),
+    Purge(LogId),
+    /// Entries up to and including this one were applied.
+    Applied(LogId),
+}
+
+const WAL_FILE: &str = "raft.wal";
+const HARD_STATE_FILE: &str = "hard_state";
+const SNAPSHOT_FILE: &str = "snapshot";
+/// WAL appends are buffered up to this size between explicit flushes.
+const WAL_BUFFER: usize = 1 << 20;
+
+/// Raft storage, in memory or persisted to a directory.
+#[derive(Debug)]
+pub struct Storage {
+    machine: Arc<dyn Machine>,
+    dir: Option<PathBuf>,
+    wal: Option<Arc<Mutex<WalWriter>>>,
+    hard_state: RwLock<Option<HardState>>,
+    log: RwLock<BTreeMap<u64, Entry<LogEntry>>>,
+    last_purged: RwLock<Option<LogId>>,
+    sm: RwLock<StateMachine>,
+    snapshot: RwLock<Option<StoredSnapshot>>,
+    snapshot_idx: RwLock<u64>,
+}
+
+impl Storage {
+    /// Storage that does not survive a restart, applying commands to `machine`.
+    pub fn new(machine: Arc<dyn Machine>) -> Self {
+        Self {
+            machine,
+            dir: None,
+            wal: None,
+            hard_state: Default::default(),
+            log: Default::default(),
+            last_purged: Default::default(),
+            sm: Default::default(),
+            snapshot: Default::default(),
+            snapshot_idx: Default::default(),
+        }
+    }
+
+    /// Open (creating if needed) storage persisted under `dir`, recovering
+    /// any state left by an earlier run into `machine`.
+    pub fn open<P: AsRef<Path>>(dir: P, machine: Arc<dyn Machine>) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let hard_state = read_file(&dir.join(HARD_STATE_FILE))?;
+        let snapshot: Option<StoredSnapshot> = read_file(&dir.join(SNAPSHOT_FILE))?;
+        let mut sm = match &snapshot {
+            Some(s) => {
+                let data: SnapshotData = bincode::deserialize(&s.data).map_err(invalid_data)?;
+                machine.restore(&data.state)?;
+                data.sm
+            }
+            None => StateMachine::default(),
+        };
+
+        let mut log = BTreeMap::new();
+        let (mut last_purged, mut applied) = (None, None);
+        let records = match recover_log(dir.join(WAL_FILE)) {
+            Ok(records) => records,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for record in records {
+            match bincode::deserialize(&record).map_err(invalid_data)? {
+                WalRecord::Append(entry) => {
+                    log.insert(entry.log_id.index, entry);
+                }
+                WalRecord::DeleteSince(index) => {
+                    log.split_off(&index);
+                }
+                WalRecord::Purge(log_id) => {
+                    log = log.split_off(&(log_id.index + 1));
+                    last_purged = Some(log_id);
+                }
+                WalRecord::Applied(log_id) => applied = applied.max(Some(log_id)),
+            }
+        }
+        if let Some(applied) = applied {
+            let from = sm.last_applied.map_or(0, |l| l.index + 1);
+            for (_, entry) in log.range(from..).take_while(|(&i, _)| i <= applied.index) {
+                sm.apply(&*machine, entry);
+            }
+        }
+
+        let snapshot_idx = snapshot.as_ref().map_or(0, |s| s.idx);
+        Ok(Self {
+            machine,
+            dir: Some(dir.to_path_buf()),
+            wal: Some(Arc::new(Mutex::new(WalWriter::open(dir.join(WAL_FILE), WAL_BUFFER)?))),
+            hard_state: RwLock::new(hard_state),
+            log: RwLock::new(log),
+            last_purged: RwLock::new(last_purged),
+            sm: RwLock::new(sm),
+            snapshot: RwLock::new(snapshot),
+            snapshot_idx: RwLock::new(snapshot_idx),
+        })
+    }
+
+    /// Append `records` to the WAL and sync it.
+    async fn log_wal(&self, records: &[WalRecord]) -> io::Result<()> {
+        let Some(wal) = &self.wal else { return Ok(()) };
+        let payloads = records.iter().map(|r| bincode::serialize(r).map_err(invalid_data)).collect::<io::Result<Vec<_>>>()?;
+        let wal = wal.clone();
+        blocking(move || {
+            let mut wal = wal.lock().unwrap();
+            for payload in &payloads {
+                wal.append(payload)?;
+            }
+            wal.flush()
+        })
+        .await
+    }
+
+    async fn persist<T: Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+        let Some(dir) = &self.dir else { return Ok(()) };
+        let bytes = bincode::serialize(value).map_err(invalid_data)?;
+        let (dir, name) = (dir.clone(), name.to_string());
+        blocking(move || write_file(&dir, &name, &bytes)).await
+    }
+
+    /// Rewrite the WAL with just `log`, the purge point and the apply point.
+    /// The caller holds the log lock, and the state machine lock is held here,
+    /// so no record can be appended to the old WAL in the meantime.
+    async fn compact(&self, log: &BTreeMap<u64, Entry<LogEntry>>) -> io::Result<()> {
+        let (Some(dir), Some(wal)) = (&self.dir, &self.wal) else { return Ok(()) };
+        let sm = self.sm.read().await;
+        let last_purged = *self.last_purged.read().await;
+        let records = last_purged
+            .map(WalRecord::Purge)
+            .into_iter()
+            .chain(log.values().cloned().map(WalRecord::Append))
+            .chain(sm.last_applied.map(WalRecord::Applied));
+        let payloads = records.map(|r| bincode::serialize(&r).map_err(invalid_data)).collect::<io::Result<Vec<_>>>()?;
+        let (dir, wal) = (dir.clone(), wal.clone());
+        blocking(move || {
+            let mut wal = wal.lock().unwrap();
+            let tmp = dir.join(format!("{WAL_FILE}.compact"));
+            let _ = fs::remove_file(&tmp);
+            let mut compacted = WalWriter::open(&tmp, WAL_BUFFER)?;
+            for payload in &payloads {
+                compacted.append(payload)?;
+            }
+            compacted.flush()?;
+            drop(compacted);
+            fs::rename(&tmp, dir.join(WAL_FILE))?;
+            fs::File::open(&dir)?.sync_all()?;
+            *wal = WalWriter::open(dir.join(WAL_FILE), WAL_BUFFER)?;
+            Ok(())
+        })
+        .await?;
+        drop(sm);
+        Ok(())
+    }
+
+    /// Copy of the current apply progress.
+    pub async fn state_machine(&self) -> StateMachine {
+        self.sm.read().await.clone()
+    }
+}
+
+fn invalid_data(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn read_file<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    match fs::read(path) {
+        Ok(bytes) => bincode::deserialize(&bytes).map(Some).map_err(invalid_data),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace `dir/name` atomically: write a temporary file, sync it, rename it
+/// over, then sync the directory so the rename survives a crash.
+fn write_file(dir: &Path, name: &str, bytes: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!("{name}.tmp"));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(name))?;
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Run blocking file I/O off the async runtime.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> io::Result<T> + Send + 'static) -> io::Result<T> {
+    tokio::task::spawn_blocking(f).await.map_err(io::Error::other)?
+}
+
+fn log_write_error(e: io::Error) -> StorageError {
+    io_error(ErrorSubject::Logs, ErrorVerb::Write, &e)
+}
+
+fn io_error(subject: ErrorSubject, verb: ErrorVerb, e: &(impl std::error::Error + 'static)) -> StorageError {
+    StorageIOError::new(subject, verb, AnyError::new(e)).into()
+}
+
+#[async_trait]
+impl RaftStorage<LogEntry, ClientResp> for Storage {
+    type SnapshotData = Cursor<Vec<u8>>;
+
+    async fn save_hard_state(&self, hs: &HardState) -> Result<(), StorageError> {
+        self.persist(HARD_STATE_FILE, hs).await.map_err(|e| io_error(ErrorSubject::HardState, ErrorVerb::Write, &e))?;
+        *self.hard_state.write().await = Some(hs.clone());
+        Ok(())
+    }
+
+    async fn read_hard_state(&self) -> Result<Option<HardState>, StorageError> {
+        Ok(self.hard_state.read().await.clone())
+    }
+
+    async fn get_log_state(&self) -> Result<LogState, StorageError> {
+        let last_purged_log_id = *self.last_purged.read().await;
+        let last_log_id = self.log.read().await.values().next_back().map(|e| e.log_id).or(last_purged_log_id);
+        Ok(LogState { last_purged_log_id, last_log_id })
+    }
+
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
+        &self,
+        range: RB,
+    ) -> Result<Vec<Entry<LogEntry>>, StorageError> {
+        Ok(self.log.read().await.range(range).map(|(_, e)| e.clone()).collect())
+    }
+
+    async fn append_to_log(&self, entries: &[&Entry<LogEntry>]) -> Result<(), StorageError> {
+        let mut log = self.log.write().await;
+        let records: Vec<_> = entries.iter().map(|entry| WalRecord::Append((*entry).clone())).collect();
+        self.log_wal(&records).await.map_err(log_write_error)?;
+        for entry in entries {
+            log.insert(entry.log_id.index, (*entry).clone());
+        }
+        Ok(())
+    }
+
+    async fn delete_conflict_logs_since(&self, log_id: LogId) -> Result<(), StorageError> {
+        let mut log = self.log.write().await;
+        self.log_wal(&[WalRecord::DeleteSince(log_id.index)]).await.map_err(log_write_error)?;
+        log.split_off(&log_id.index);
+        Ok(())
+    }
+
+    async fn purge_logs_upto(&self, log_id: LogId) -> Result<(), StorageError> {
+        let mut log = self.log.write().await;
+        *log = log.split_off(&(log_id.index + 1));
+        *self.last_purged.write().await = Some(log_id);
+        self.compact(&log).await.map_err(log_write_error)
+    }
+
+    async fn last_applied_state(&self) -> Result<(Option<LogId>, Option<EffectiveMembership>), StorageError> {
+        let sm = self.sm.read().await;
+        Ok((sm.last_applied, sm.last_membership.clone()))
+    }
+
+    async fn apply_to_state_machine(&self, entries: &[&Entry<LogEntry>]) -> Result<Vec<ClientResp>, StorageError> {
+        let mut sm = self.sm.write().await;
+        let results = entries.iter().map(|entry| sm.apply(&*self.machine, entry)).collect();
+        if let Some(last) = entries.last() {
+            self.log_wal(&[WalRecord::Applied(last.log_id)]).await.map_err(log_write_error)?;
+        }
+        Ok(results)
+    }
+
+    async fn build_snapshot(&self) -> Result<Snapshot<Self::SnapshotData>, StorageError> {
+        let (data, last_applied) = {
+            let sm = self.sm.read().await;
+            let data = SnapshotData { sm: sm.clone(), state: self.machine.snapshot() };
+            let data = bincode::serialize(&data).map_err(|e| io_error(ErrorSubject::StateMachine, ErrorVerb::Read, &e))?;
+            (data, sm.last_applied)
+        };
+        let idx = {
+            let mut idx = self.snapshot_idx.write().await;
+            *idx += 1;
+            *idx
+        };
+        let snapshot_id = match last_applied {
+            Some(last) => format!("{}-{}-{}", last.term, last.index, idx),
+            None => format!("--{idx}"),
+        };
+        let meta = SnapshotMeta { last_log_id: last_applied, snapshot_id };
+        let stored = StoredSnapshot { meta: meta.clone(), data: data.clone(), idx };
+        self.persist(SNAPSHOT_FILE, &stored)
+            .await
+            .map_err(|e| io_error(ErrorSubject::Snapshot(meta.clone()), ErrorVerb::Write, &e))?;
+        *self.snapshot.write().await = Some(stored);
+        Ok(Snapshot { meta, snapshot: Box::new(Cursor::new(data)) })
+    }
+
+    async fn begin_receiving_snapshot(&self) -> Result<Box<Self::SnapshotData>, StorageError> {
+        Ok(Box::new(Cursor::new(Vec::new())))
+    }
+
+    async fn install_snapshot(
+        &self,
+        meta: &SnapshotMeta,
+        snapshot: Box<Self::SnapshotData>,
+    ) -> Result<StateMachineChanges, StorageError> {
+        let data = snapshot.into_inner();
+        let snapshot_data: SnapshotData = bincode::deserialize(&data)
+            .map_err(|e| io_error(ErrorSubject::Snapshot(meta.clone()), ErrorVerb::Read, &e))?;
+        let stored = StoredSnapshot { meta: meta.clone(), data, idx: *self.snapshot_idx.read().await };
+        self.persist(SNAPSHOT_FILE, &stored)
+            .await
+            .map_err(|e| io_error(ErrorSubject::Snapshot(meta.clone()), ErrorVerb::Write, &e))?;
+        let mut sm = self.sm.write().await;
+        self.machine
+            .restore(&snapshot_data.state)
+            .map_err(|e| io_error(ErrorSubject::Snapshot(meta.clone()), ErrorVerb::Read, &e))?;
+        *sm = snapshot_data.sm;
+        drop(sm);
+        *self.snapshot.write().await = Some(stored);
+        // Apply records older than the snapshot are now superseded; openraft
+        // purges the covered entries right after, compacting again.
+        let log = self.log.write().await;
+        self.compact(&log).await.map_err(log_write_error)?;
+        Ok(StateMachineChanges { last_applied: meta.last_log_id, is_snapshot: true })
+    }
+
+    async fn get_current_snapshot(&self) -> Result<Option<Snapshot<Self::SnapshotData>>, StorageError> {
+        Ok(self.snapshot.read().await.as_ref().map(|s| Snapshot {
+            meta: s.meta.clone(),
+            snapshot: Box::new(Cursor::new(s.data.clone())),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openraft::Membership;
+    use std::collections::BTreeSet;
+
+    fn entry(term: u64, index: u64, payload: EntryPayload<LogEntry>) -> Entry<LogEntry> {
+        Entry { log_id: LogId { term, index }, payload }
+    }
+
+    fn normal(term: u64, index: u64, data: &[u8]) -> Entry<LogEntry> {
+        entry(term, index, EntryPayload::Normal(LogEntry(data.to_vec())))
+    }
+
+    #[tokio::test]
+    async fn log_hard_state_and_applied_state_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let members = EntryPayload::Membership(Membership::new_single(BTreeSet::from([1, 2, 3])));
+        {
+            let store = Storage::open(dir.path(), Arc::new(CommandLog::default())).unwrap();
+            store.save_hard_state(&HardState { current_term: 2, voted_for: Some(1) }).await.unwrap();
+            let entries = [entry(1, 1, members), normal(1, 2, b"a"), normal(1, 3, b"stale"), normal(2, 4, b"x")];
+            store.append_to_log(&entries.iter().collect::<Vec<_>>()).await.unwrap();
+            store.delete_conflict_logs_since(LogId { term: 1, index: 3 }).await.unwrap();
+            let entries = [normal(2, 3, b"b"), normal(2, 4, b"c")];
+            store.append_to_log(&entries.iter().collect::<Vec<_>>()).await.unwrap();
+
+            let log = store.try_get_log_entries(1..=3).await.unwrap();
+            store.apply_to_state_machine(&log.iter().collect::<Vec<_>>()).await.unwrap();
+            store.build_snapshot().await.unwrap();
+            let log = store.try_get_log_entries(4..).await.unwrap();
+            store.apply_to_state_machine(&log.iter().collect::<Vec<_>>()).await.unwrap();
+            store.purge_logs_upto(LogId { term: 2, index: 2 }).await.unwrap();
+        }
+
+        let commands = Arc::new(CommandLog::default());
+        let store = Storage::open(dir.path(), commands.clone()).unwrap();
+        assert_eq!(store.read_hard_state().await.unwrap(), Some(HardState { current_term: 2, voted_for: Some(1) }));
+        let state = store.get_log_state().await.unwrap();
+        assert_eq!(state.last_purged_log_id, Some(LogId { term: 2, index: 2 }));
+        assert_eq!(state.last_log_id, Some(LogId { term: 2, index: 4 }));
+        let log = store.try_get_log_entries(..).await.unwrap();
+        assert_eq!(log, vec![normal(2, 3, b"b"), normal(2, 4, b"c")]);
+
+        let sm = store.state_machine().await;
+        assert_eq!(sm.last_applied, Some(LogId { term: 2, index: 4 }));
+        assert_eq!(commands.commands(), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert!(sm.last_membership.is_some());
+        let (last_applied, _) = store.last_applied_state().await.unwrap();
+        assert_eq!(last_applied, sm.last_applied);
+        let snapshot = store.get_current_snapshot().await.unwrap().unwrap();
+        assert_eq!(snapshot.meta.last_log_id, Some(LogId { term: 2, index: 3 }));
+    }
+
+    #[tokio::test]
+    async fn purge_compacts_wal_and_torn_tail_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = dir.path().join(WAL_FILE);
+        {
+            let store = Storage::open(dir.path(), Arc::new(CommandLog::default())).unwrap();
+            let entries: Vec<_> = (1..=100).map(|i| normal(1, i, &[0; 64])).collect();
+            store.append_to_log(&entries.iter().collect::<Vec<_>>()).await.unwrap();
+            store.apply_to_state_machine(&entries.iter().collect::<Vec<_>>()).await.unwrap();
+            let first = store.build_snapshot().await.unwrap().meta.snapshot_id;
+            assert!(first.ends_with("-1"), "{first}");
+            let before = fs::metadata(&wal).unwrap().len();
+            store.purge_logs_upto(LogId { term: 1, index: 98 }).await.unwrap();
+            assert!(fs::metadata(&wal).unwrap().len() < before / 10);
+        }
+        // Crash mid-append: half a record at the end.
+        let len = fs::metadata(&wal).unwrap().len();
+        fs::OpenOptions::new().append(true).open(&wal).unwrap().write_all(&[40, 0, 0, 0, 1]).unwrap();
+
+        let commands = Arc::new(CommandLog::default());
+        let store = Storage::open(dir.path(), commands.clone()).unwrap();
+        assert_eq!(fs::metadata(&wal).unwrap().len(), len);
+        let log = store.try_get_log_entries(..).await.unwrap();
+        assert_eq!(log, vec![normal(1, 99, &[0; 64]), normal(1, 100, &[0; 64])]);
+        assert_eq!(commands.commands().len(), 100);
+        let next = store.build_snapshot().await.unwrap().meta.snapshot_id;
+        assert!(next.ends_with("-2"), "{next}");
+    }
+}
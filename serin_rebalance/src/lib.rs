@@ -1,5 +1,7 @@
 //! Automatic rebalancer using 2-dimensional bin packing.
 use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use thiserror::Error;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStat {
@@ -7,6 +9,9 @@ pub struct NodeStat {
     pub read_qps: f64,
     pub write_qps: f64,
     pub used_bytes: u64,
+    /// Failure domain; replicas of one shard never share a rack.
+    #[serde(default)]
+    pub rack: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,15 +20,54 @@ pub struct ShardPlacement {
     pub target_node: String,
 }
 
+/// Why no valid placement exists.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RebalanceError {
+    /// Fewer distinct nodes/racks than replicas per shard.
+    #[error("{replicas} replicas need as many failure domains, but only {domains} exist")]
+    NotEnoughDomains { replicas: usize, domains: usize },
+}
+
 /// Score function weight for write traffic.
 const WRITE_WEIGHT: f64 = 2.0;
 
+/// Outcome of [`rebalance`]: the moves to make and what they leave behind.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebalanceReport {
+    /// Replicas that must be copied to a new node.
+    pub moves: Vec<ShardPlacement>,
+    /// Total bytes copied by `moves`.
+    pub moved_bytes: u64,
+    /// Shards left with fewer than the requested replicas because no
+    /// anti-affine node had room. Replicas that did fit are still placed.
+    pub unplaced: Vec<u64>,
+    /// Projected (node id, weighted qps, bytes) once the plan is applied.
+    pub per_node_load: Vec<(String, f64, u64)>,
+}
+
 /// Perform a naive 2D bin packing rebalancing.
-/// Returns a vector of shard move plans.
+/// Reports the replica moves needed relative to `current`, shards that could
+/// not be fully placed, and the resulting load per node.
 ///
-/// capacity_qps and capacity_bytes reflect per-node thresholds.
-pub fn rebalance(nodes: &[NodeStat], shards: &[(u64, f64, u64)], capacity_qps: f64, capacity_bytes: u64) -> Vec<ShardPlacement> {
-    let mut plans = Vec::new();
+/// Each shard gets `replicas` copies on distinct nodes, and on distinct racks
+/// among nodes that have one. Node stats exclude the shards being placed.
+/// Replicas stay on their `current` node whenever it still fits, and only the
+/// rest are greedily fit elsewhere. capacity_qps and capacity_bytes reflect
+/// per-node thresholds.
+pub fn rebalance(
+    nodes: &[NodeStat],
+    shards: &[(u64, f64, u64)],
+    current: &[ShardPlacement],
+    replicas: usize,
+    capacity_qps: f64,
+    capacity_bytes: u64,
+) -> Result<RebalanceReport, RebalanceError> {
+    let domains = nodes.iter().filter(|n| n.rack.is_none()).count()
+        + nodes.iter().filter_map(|n| n.rack.as_ref()).collect::<HashSet<_>>().len();
+    if replicas > domains {
+        return Err(RebalanceError::NotEnoughDomains { replicas, domains });
+    }
+    let mut report = RebalanceReport::default();
     // Sort shards descending by composite weight.
     let mut sorted = shards.to_vec();
     sorted.sort_by(|a, b| {
@@ -31,17 +75,117 @@ pub fn rebalance(nodes: &[NodeStat], shards: &[(u64, f64, u64)], capacity_qps: f
         let wb = b.1 * WRITE_WEIGHT + b.2 as f64 / 1_000_000.0;
         wb.partial_cmp(&wa).unwrap()
     });
-    // Greedy fit.
     let mut node_load: Vec<(f64, u64)> = nodes.iter().map(|n| (n.read_qps + n.write_qps * WRITE_WEIGHT, n.used_bytes)).collect();
-    for (shard_id, qps, bytes) in sorted {
-        for (idx, (lqps, lbytes)) in node_load.iter_mut().enumerate() {
-            if *lqps + qps <= capacity_qps && *lbytes + bytes <= capacity_bytes {
-                *lqps += qps;
-                *lbytes += bytes;
-                plans.push(ShardPlacement { shard_id, target_node: nodes[idx].id.clone() });
-                break;
+    // Node indices holding each sorted shard's replicas.
+    let mut assigned: Vec<Vec<usize>> = vec![Vec::new(); sorted.len()];
+    let fits = |load: &[(f64, u64)], held: &[usize], idx: usize, qps: f64, bytes: u64| {
+        !held.contains(&idx)
+            && !nodes[idx].rack.as_ref().is_some_and(|r| held.iter().any(|&h| nodes[h].rack.as_ref() == Some(r)))
+            && load[idx].0 + qps <= capacity_qps
+            && load[idx].1 + bytes <= capacity_bytes
+    };
+
+    // Keep replicas where they are first, so a big shard cannot evict a small
+    // one from a node both currently fit on.
+    for (&(shard_id, qps, bytes), held) in sorted.iter().zip(&mut assigned) {
+        for placement in current.iter().filter(|p| p.shard_id == shard_id) {
+            let Some(idx) = nodes.iter().position(|n| n.id == placement.target_node) else { continue };
+            if held.len() < replicas && fits(&node_load, held, idx, qps, bytes) {
+                node_load[idx].0 += qps;
+                node_load[idx].1 += bytes;
+                held.push(idx);
             }
         }
     }
-    plans
-} 
\ No newline at end of file
+    // Greedy fit for the remaining replicas.
+    for (&(shard_id, qps, bytes), held) in sorted.iter().zip(&mut assigned) {
+        while held.len() < replicas {
+            let Some(idx) = (0..nodes.len()).find(|&idx| fits(&node_load, held, idx, qps, bytes)) else {
+                report.unplaced.push(shard_id);
+                break;
+            };
+            node_load[idx].0 += qps;
+            node_load[idx].1 += bytes;
+            held.push(idx);
+            report.moves.push(ShardPlacement { shard_id, target_node: nodes[idx].id.clone() });
+            report.moved_bytes += bytes;
+        }
+    }
+    report.per_node_load = nodes.iter().zip(node_load).map(|(n, (qps, bytes))| (n.id.clone(), qps, bytes)).collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn node(id: &str, rack: Option<&str>) -> NodeStat {
+        NodeStat { id: id.into(), read_qps: 0.0, write_qps: 0.0, used_bytes: 0, rack: rack.map(Into::into) }
+    }
+
+    #[test]
+    fn replicas_land_on_distinct_nodes() {
+        let nodes = [node("a", None), node("b", None), node("c", None)];
+        let shards: Vec<_> = (0..6).map(|id| (id, 10.0, 1_000)).collect();
+        let plan = rebalance(&nodes, &shards, &[], 2, 100.0, 1_000_000).unwrap();
+        assert_eq!(plan.moves.len(), 12);
+        assert_eq!(plan.moved_bytes, 12_000);
+        let mut by_shard: HashMap<u64, Vec<&str>> = HashMap::new();
+        for p in &plan.moves {
+            by_shard.entry(p.shard_id).or_default().push(&p.target_node);
+        }
+        for (shard, targets) in by_shard {
+            assert_eq!(targets.len(), 2);
+            assert_ne!(targets[0], targets[1], "shard {shard} replicas share a node");
+        }
+    }
+
+    #[test]
+    fn rack_anti_affinity_and_infeasible_layouts() {
+        let nodes = [node("a", Some("r1")), node("b", Some("r1")), node("c", Some("r2"))];
+        let plan = rebalance(&nodes, &[(1, 1.0, 1)], &[], 2, 100.0, 1_000).unwrap();
+        let targets: Vec<_> = plan.moves.iter().map(|p| p.target_node.as_str()).collect();
+        assert_eq!(targets, ["a", "c"]);
+
+        assert_eq!(rebalance(&nodes, &[(1, 1.0, 1)], &[], 3, 100.0, 1_000).unwrap_err(), RebalanceError::NotEnoughDomains { replicas: 3, domains: 2 });
+        // Shard 2 fits on r1 but its second replica needs r2, which is full.
+        let report = rebalance(&nodes, &[(1, 60.0, 1), (2, 50.0, 1)], &[], 2, 100.0, 1_000).unwrap();
+        assert_eq!(report.unplaced, [2]);
+        assert_eq!(report.moves.iter().filter(|p| p.shard_id == 2).count(), 1);
+    }
+
+    fn placement(shard_id: u64, node: &str) -> ShardPlacement {
+        ShardPlacement { shard_id, target_node: node.into() }
+    }
+
+    #[test]
+    fn balanced_placement_needs_no_moves() {
+        let nodes = [node("a", None), node("b", None), node("c", None)];
+        // Greedy first-fit from scratch would pile shards onto "a".
+        let shards = [(1, 10.0, 100), (2, 20.0, 200), (3, 30.0, 300)];
+        let current = [placement(1, "c"), placement(2, "b"), placement(3, "a")];
+        let plan = rebalance(&nodes, &shards, &current, 1, 100.0, 1_000).unwrap();
+        assert!(plan.moves.is_empty(), "{:?}", plan.moves);
+        assert_eq!(plan.moved_bytes, 0);
+
+        // Node "b" leaves: only shard 2 moves.
+        let without_b = [nodes[0].clone(), nodes[2].clone()];
+        let plan = rebalance(&without_b, &shards, &current, 1, 100.0, 1_000).unwrap();
+        assert_eq!(plan.moves.len(), 1);
+        assert_eq!(plan.moves[0].shard_id, 2);
+        assert_eq!(plan.moved_bytes, 200);
+    }
+
+    #[test]
+    fn overflow_shards_are_reported_unplaced() {
+        let nodes = [node("a", None), NodeStat { used_bytes: 500, ..node("b", None) }];
+        // "a" has room for two 400-byte shards and "b" for one, so two of five overflow.
+        let shards: Vec<_> = (1..=5).map(|id| (id, id as f64, 400)).collect();
+        let report = rebalance(&nodes, &shards, &[], 1, 1_000.0, 1_000).unwrap();
+        assert_eq!(report.moves.len(), 3);
+        // Heaviest shards are placed first, so the lightest overflow.
+        assert_eq!(report.unplaced, [2, 1]);
+        assert_eq!(report.per_node_load, [("a".to_string(), 9.0, 800), ("b".to_string(), 3.0, 900)]);
+    }
+}
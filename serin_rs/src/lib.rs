@@ -1,7 +1,11 @@
 //! Rust SDK for SerinDB. Thin wrapper around tokio-postgres.
 
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
 use tokio_postgres::{Client as PgClient, NoTls, Error, Row};
 
+pub use tokio_postgres::{CopyOutStream, SimpleQueryMessage};
+
 /// SerinDB async client.
 pub struct Client {
     inner: PgClient,
@@ -25,10 +29,30 @@ impl Client {
         self.inner.query(sql, &[]).await
     }
 
+    /// Run `sql` (possibly several statements) over the simple query
+    /// protocol; values come back as text, with a row description per result.
+    pub async fn simple_query(&self, sql: &str) -> Result<Vec<SimpleQueryMessage>, Error> {
+        self.inner.simple_query(sql).await
+    }
+
     /// Execute a statement without returning rows.
     pub async fn execute(&self, sql: &str) -> Result<u64, Error> {
         self.inner.execute(sql, &[]).await
     }
+
+    /// Run a `COPY ... TO STDOUT` statement and stream its data.
+    pub async fn copy_out(&self, sql: &str) -> Result<CopyOutStream, Error> {
+        self.inner.copy_out(sql).await
+    }
+
+    /// Run a `COPY ... FROM STDIN` statement fed with `data`; returns the
+    /// number of rows copied.
+    pub async fn copy_in(&self, sql: &str, data: impl Into<Bytes>) -> Result<u64, Error> {
+        let sink = self.inner.copy_in::<_, Bytes>(sql).await?;
+        pin_mut!(sink);
+        sink.send(data.into()).await?;
+        sink.finish().await
+    }
 }
 
 #[cfg(test)]
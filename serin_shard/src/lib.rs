@@ -1,9 +1,26 @@
 //! Sharding algorithms for SerinDB.
 use async_trait::async_trait;
+use siphasher::sip::SipHasher13;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use thiserror::Error;
 
 #[async_trait]
 pub trait ShardRouter: Send + Sync {
-    async fn shard_for_key(&self, key: &str) -> u64;
+    /// Shard owning `key`, or `None` if no shard covers it.
+    async fn shard_for_key(&self, key: &str) -> Option<u64>;
+
+    /// Shard owning a composite key such as `(tenant_id, user_id)`. By default
+    /// the components are joined with [`encode_composite_key`] and routed as one key.
+    async fn shard_for_keys(&self, keys: &[&str]) -> Option<u64> {
+        self.shard_for_key(&encode_composite_key(keys)).await
+    }
+}
+
+/// Join key components as `<len>:<component>` each, so no component can
+/// imitate a boundary: `("a", "bc")` and `("ab", "c")` encode differently.
+pub fn encode_composite_key(keys: &[&str]) -> String {
+    keys.iter().map(|k| format!("{}:{}", k.len(), k)).collect()
 }
 
 pub struct HashRouter {
@@ -12,32 +29,317 @@ pub struct HashRouter {
 
 impl HashRouter {
     pub fn new(shards: u64) -> Self { Self { shards } }
+
+    /// Shard for a composite key, hashing each component with its length prefixed.
+    pub fn route_keys(&self, keys: &[&str]) -> Option<u64> {
+        let mut h = stable_hasher();
+        for key in keys {
+            h.write(&(key.len() as u64).to_le_bytes());
+            h.write(key.as_bytes());
+        }
+        h.finish().checked_rem(self.shards)
+    }
 }
 
 #[async_trait]
 impl ShardRouter for HashRouter {
-    async fn shard_for_key(&self, key: &str) -> u64 {
-        use std::hash::{Hash, Hasher};
-        let mut h = std::collections::hash_map::DefaultHasher::new();
-        key.hash(&mut h);
-        (h.finish() % self.shards) as u64
+    async fn shard_for_key(&self, key: &str) -> Option<u64> {
+        key_hash(key).checked_rem(self.shards)
+    }
+
+    async fn shard_for_keys(&self, keys: &[&str]) -> Option<u64> {
+        self.route_keys(keys)
+    }
+}
+
+/// SipHash-1-3 with fixed keys, fed explicit little-endian bytes: unlike
+/// `DefaultHasher` its output never changes across Rust releases or
+/// platforms, so every node places keys and ring points alike.
+fn stable_hasher() -> SipHasher13 {
+    SipHasher13::new_with_keys(0x5365_7269_6e44_4253, 0x6861_7264_5269_6e67)
+}
+
+fn key_hash(key: &str) -> u64 {
+    let mut h = stable_hasher();
+    h.write(key.as_bytes());
+    h.finish()
+}
+
+/// Ring position of virtual node `replica` of `shard`.
+fn point_hash(shard: u64, replica: u32) -> u64 {
+    let mut h = stable_hasher();
+    h.write(&shard.to_le_bytes());
+    h.write(&replica.to_le_bytes());
+    h.finish()
+}
+
+/// Consistent hashing: each shard owns `vnodes` points on a hash ring and a key
+/// goes to the first point clockwise from its hash. Adding or removing a shard
+/// only remaps keys in the arcs next to that shard's points.
+pub struct ConsistentHashRouter {
+    vnodes: u32,
+    ring: BTreeMap<u64, u64>, // point -> shard_id
+}
+
+impl ConsistentHashRouter {
+    /// Empty ring placing `vnodes` virtual nodes per shard.
+    pub fn new(vnodes: u32) -> Self { Self { vnodes, ring: BTreeMap::new() } }
+
+    /// Ring holding `shards`.
+    pub fn with_shards(shards: impl IntoIterator<Item = u64>, vnodes: u32) -> Self {
+        let mut router = Self::new(vnodes);
+        for shard in shards {
+            router.add_shard(shard);
+        }
+        router
+    }
+
+    /// Place `shard`'s virtual nodes on the ring.
+    pub fn add_shard(&mut self, shard: u64) {
+        for replica in 0..self.vnodes {
+            self.ring.insert(point_hash(shard, replica), shard);
+        }
+    }
+
+    /// Remove `shard`'s virtual nodes; its keys move to the next points clockwise.
+    pub fn remove_shard(&mut self, shard: u64) {
+        self.ring.retain(|_, s| *s != shard);
+    }
+
+    /// Shard for `key`, or `None` if the ring is empty.
+    pub fn route(&self, key: &str) -> Option<u64> {
+        ring_lookup(&self.ring, key)
+    }
+}
+
+/// First point clockwise from `key`'s hash, wrapping around.
+fn ring_lookup(ring: &BTreeMap<u64, u64>, key: &str) -> Option<u64> {
+    let h = key_hash(key);
+    ring.range(h..).next().or_else(|| ring.iter().next()).map(|(_, &shard)| shard)
+}
+
+#[async_trait]
+impl ShardRouter for ConsistentHashRouter {
+    async fn shard_for_key(&self, key: &str) -> Option<u64> {
+        self.route(key)
+    }
+}
+
+/// Weight whose virtual node count does not fit in a `u32`.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("weight {weight} with {vnodes_per_weight} virtual nodes per weight overflows")]
+pub struct WeightOverflow {
+    pub weight: u32,
+    pub vnodes_per_weight: u32,
+}
+
+/// Consistent hashing for shards of unequal capacity: a shard of weight `w`
+/// owns `w * vnodes_per_weight` points on the ring, so it receives roughly
+/// `w / total_weight` of the keys.
+pub struct WeightedHashRouter {
+    vnodes_per_weight: u32,
+    weights: BTreeMap<u64, u32>, // shard_id -> weight
+    ring: BTreeMap<u64, u64>,    // point -> shard_id
+}
+
+impl WeightedHashRouter {
+    /// Empty ring placing `vnodes_per_weight` points per unit of weight.
+    pub fn new(vnodes_per_weight: u32) -> Self {
+        Self { vnodes_per_weight, weights: BTreeMap::new(), ring: BTreeMap::new() }
+    }
+
+    /// Set `shard`'s weight, adding the shard if new; weight 0 removes it.
+    /// Only keys near points added or removed by the change move. Fails,
+    /// leaving the ring unchanged, if the weight needs more than `u32::MAX` points.
+    pub fn set_weight(&mut self, shard: u64, weight: u32) -> Result<(), WeightOverflow> {
+        let old = self.weights.get(&shard).copied().unwrap_or(0);
+        let new_points = weight
+            .checked_mul(self.vnodes_per_weight)
+            .ok_or(WeightOverflow { weight, vnodes_per_weight: self.vnodes_per_weight })?;
+        // Stored weights were checked when set.
+        let old_points = old * self.vnodes_per_weight;
+        for replica in new_points..old_points {
+            self.ring.remove(&point_hash(shard, replica));
+        }
+        for replica in old_points..new_points {
+            self.ring.insert(point_hash(shard, replica), shard);
+        }
+        if weight == 0 {
+            self.weights.remove(&shard);
+        } else {
+            self.weights.insert(shard, weight);
+        }
+        Ok(())
+    }
+
+    /// Current weight of `shard` (0 if absent).
+    pub fn weight(&self, shard: u64) -> u32 {
+        self.weights.get(&shard).copied().unwrap_or(0)
+    }
+
+    /// Shard for `key`, or `None` if no shard has a positive weight.
+    pub fn route(&self, key: &str) -> Option<u64> {
+        ring_lookup(&self.ring, key)
+    }
+}
+
+#[async_trait]
+impl ShardRouter for WeightedHashRouter {
+    async fn shard_for_key(&self, key: &str) -> Option<u64> {
+        self.route(key)
     }
 }
 
+/// Invalid range layout passed to [`RangeRouter::new`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RangeError {
+    /// A range whose end is not after its start.
+    #[error("range [{start}, {end}) is empty")]
+    Empty { start: String, end: String },
+    /// Two ranges covering common keys.
+    #[error("range starting at {second} overlaps range ending at {first_end}")]
+    Overlap { first_end: String, second: String },
+    /// An unbounded (empty end) range that is not the last one.
+    #[error("only the last range may be unbounded, but the range starting at {0} is")]
+    UnboundedNotLast(String),
+}
+
+/// Routes keys by half-open ranges `[start, end)`. An empty `end` leaves the
+/// last range unbounded above.
 pub struct RangeRouter {
-    ranges: Vec<(String, String, u64)>, // start, end, shard_id
+    ranges: Vec<(String, String, u64)>, // start, end, shard_id; sorted by start
 }
 
 impl RangeRouter {
-    pub fn new(ranges: Vec<(String, String, u64)>) -> Self { Self { ranges } }
+    /// Sort `ranges` by start and check that they are non-empty and disjoint.
+    pub fn new(mut ranges: Vec<(String, String, u64)>) -> Result<Self, RangeError> {
+        ranges.sort_by(|a, b| a.0.cmp(&b.0));
+        for (i, (start, end, _)) in ranges.iter().enumerate() {
+            let unbounded = end.is_empty();
+            if unbounded && i + 1 != ranges.len() {
+                return Err(RangeError::UnboundedNotLast(start.clone()));
+            }
+            if !unbounded && end <= start {
+                return Err(RangeError::Empty { start: start.clone(), end: end.clone() });
+            }
+            if let Some((next, _, _)) = ranges.get(i + 1) {
+                if next < end {
+                    return Err(RangeError::Overlap { first_end: end.clone(), second: next.clone() });
+                }
+            }
+        }
+        Ok(Self { ranges })
+    }
+
+    /// Shard whose range contains `key`, found by binary search.
+    pub fn route(&self, key: &str) -> Option<u64> {
+        let idx = self.ranges.partition_point(|(start, _, _)| start.as_str() <= key).checked_sub(1)?;
+        let (_, end, id) = &self.ranges[idx];
+        (end.is_empty() || key < end.as_str()).then_some(*id)
+    }
 }
 
 #[async_trait]
 impl ShardRouter for RangeRouter {
-    async fn shard_for_key(&self, key: &str) -> u64 {
-        for (start, end, id) in &self.ranges {
-            if key >= start && key < end { return *id; }
+    async fn shard_for_key(&self, key: &str) -> Option<u64> {
+        self.route(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_shard_only_moves_its_keys() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("user:{i}")).collect();
+        let mut router = ConsistentHashRouter::with_shards(0..4, 64);
+        let before: Vec<u64> = keys.iter().map(|k| router.route(k).unwrap()).collect();
+        assert!((0..4).all(|s| before.contains(&s)));
+
+        router.remove_shard(2);
+        for (key, &old) in keys.iter().zip(&before) {
+            let new = router.route(key).unwrap();
+            if old == 2 {
+                assert_ne!(new, 2);
+            } else {
+                assert_eq!(new, old, "{key} moved off a surviving shard");
+            }
         }
-        0
+
+        router.add_shard(2);
+        let after: Vec<u64> = keys.iter().map(|k| router.route(k).unwrap()).collect();
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn hashes_are_pinned() {
+        // Routing must not change between builds: these values are part of
+        // the on-cluster layout.
+        assert_eq!(key_hash("user:42"), 0xdba0_7cd8_390b_ebc1);
+        assert_eq!(point_hash(3, 7), 0x7e3b_76de_ce2a_8fdc);
+        assert_eq!(HashRouter::new(u64::MAX).route_keys(&["t1", "u9"]), Some(0x28fb_6b66_0a9c_bb70));
+    }
+
+    #[test]
+    fn composite_key_boundaries_cannot_be_spoofed() {
+        let router = HashRouter::new(1_000_003);
+        assert_ne!(router.route_keys(&["a", "bc"]), router.route_keys(&["ab", "c"]));
+        assert_eq!(router.route_keys(&["t1", "u9"]), router.route_keys(&["t1", "u9"]));
+        assert_ne!(encode_composite_key(&["a", "bc"]), encode_composite_key(&["ab", "c"]));
+        assert_ne!(encode_composite_key(&["1:a", ""]), encode_composite_key(&["", "1:a"]));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn weighted_router_distributes_by_weight() {
+        let mut router = WeightedHashRouter::new(160);
+        for (shard, weight) in [(0, 1), (1, 2), (2, 5)] {
+            router.set_weight(shard, weight).unwrap();
+        }
+        let n = 100_000;
+        let mut counts = [0usize; 3];
+        for i in 0..n {
+            counts[router.route(&format!("key-{i:x}")).unwrap() as usize] += 1;
+        }
+        for (shard, weight) in [(0, 1.0), (1, 2.0), (2, 5.0)] {
+            let share = counts[shard] as f64 / n as f64;
+            let expected = weight / 8.0;
+            assert!((share - expected).abs() < expected * 0.2, "shard {shard}: {share} vs {expected}");
+        }
+
+        router.set_weight(1, 0).unwrap();
+        assert_eq!(router.weight(1), 0);
+        assert!((0..1000).all(|i| router.route(&format!("key-{i:x}")) != Some(1)));
+
+        let err = router.set_weight(3, u32::MAX).unwrap_err();
+        assert_eq!(err, WeightOverflow { weight: u32::MAX, vnodes_per_weight: 160 });
+        assert_eq!(router.weight(3), 0);
+    }
+
+    fn range(start: &str, end: &str, shard: u64) -> (String, String, u64) {
+        (start.to_string(), end.to_string(), shard)
+    }
+
+    #[test]
+    fn range_router_reports_uncovered_keys() {
+        // Deliberately unsorted.
+        let router = RangeRouter::new(vec![range("m", "", 3), range("b", "d", 1), range("d", "g", 2)]).unwrap();
+        assert_eq!(router.route("a"), None); // below the first range
+        assert_eq!(router.route("b"), Some(1));
+        assert_eq!(router.route("d"), Some(2));
+        assert_eq!(router.route("h"), None); // between ranges
+        assert_eq!(router.route("m"), Some(3)); // last range, unbounded
+        assert_eq!(router.route("zzz"), Some(3));
+
+        let bounded = RangeRouter::new(vec![range("b", "d", 1)]).unwrap();
+        assert_eq!(bounded.route("c"), Some(1));
+        assert_eq!(bounded.route("d"), None); // end is exclusive
+    }
+
+    #[test]
+    fn range_router_rejects_bad_layouts() {
+        assert!(matches!(RangeRouter::new(vec![range("a", "f", 1), range("e", "k", 2)]), Err(RangeError::Overlap { .. })));
+        assert!(matches!(RangeRouter::new(vec![range("f", "a", 1)]), Err(RangeError::Empty { .. })));
+        assert!(matches!(RangeRouter::new(vec![range("a", "", 1), range("k", "z", 2)]), Err(RangeError::UnboundedNotLast(_))));
+    }
+}
@@ -58,10 +58,10 @@ impl BufferPool {
 
     /// Fetch a page into the buffer pool, returning a handle to its frame.
     pub fn fetch_page(&mut self, page_id: PageId) -> Arc<Mutex<BufferFrame>> {
-        if let Some(frame) = self.frames.get(&page_id) {
+        if let Some(frame) = self.frames.get(&page_id).cloned() {
             // Hit in buffer – update lists.
             self.touch(page_id);
-            return Arc::clone(frame);
+            return frame;
         }
 
         // Miss – need to allocate.
@@ -1,6 +1,11 @@
 use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
 use crate::{buffer::PageId, PAGE_SIZE};
 
 /// Result type alias for storage operations.
@@ -12,6 +17,9 @@ pub enum StorageError {
     /// Page not found.
     #[error("page not found: {0:?}")]
     NotFound(PageId),
+    /// File I/O failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     /// IO or other underlying error.
     #[error(transparent)]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
@@ -38,7 +46,7 @@ impl StorageEngine for MockStorage {
     async fn read_page(&self, page_id: PageId, buf: &mut [u8; PAGE_SIZE]) -> Result<()> {
         let pages = self.pages.lock().unwrap();
         if let Some(page) = pages.get(&page_id) {
-            buf.copy_from_slice(page);
+            buf.copy_from_slice(&page[..]);
             Ok(())
         } else {
             Err(StorageError::NotFound(page_id))
@@ -52,6 +60,56 @@ impl StorageEngine for MockStorage {
     }
 }
 
+/// Pages stored in a single file, page `n` at byte offset `n * PAGE_SIZE`.
+///
+/// Writes past the end extend the file; pages skipped over read as zeros.
+/// Writes reach the OS when `write_page` returns but are only durable after
+/// [`sync`](Self::sync).
+pub struct FileStorage {
+    file: tokio::sync::Mutex<File>,
+}
+
+impl FileStorage {
+    /// Open the page file at `path`, creating it if missing.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).await?;
+        Ok(Self { file: tokio::sync::Mutex::new(file) })
+    }
+
+    /// Flush written pages to stable storage.
+    pub async fn sync(&self) -> Result<()> {
+        self.file.lock().await.sync_data().await?;
+        Ok(())
+    }
+
+    fn offset(page_id: PageId) -> u64 {
+        page_id.0 * PAGE_SIZE as u64
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageEngine for FileStorage {
+    async fn read_page(&self, page_id: PageId, buf: &mut [u8; PAGE_SIZE]) -> Result<()> {
+        let mut file = self.file.lock().await;
+        let offset = Self::offset(page_id);
+        if offset + PAGE_SIZE as u64 > file.metadata().await?.len() {
+            return Err(StorageError::NotFound(page_id));
+        }
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.read_exact(buf).await?;
+        Ok(())
+    }
+
+    async fn write_page(&self, page_id: PageId, buf: &[u8; PAGE_SIZE]) -> Result<()> {
+        let mut file = self.file.lock().await;
+        file.seek(SeekFrom::Start(Self::offset(page_id))).await?;
+        file.write_all(buf).await?;
+        // tokio completes file writes in the background; wait for this one.
+        file.flush().await?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,4 +125,29 @@ mod tests {
         storage.read_page(page_id, &mut read_buf).await.unwrap();
         assert_eq!(data, read_buf);
     }
+
+    #[tokio::test]
+    async fn file_storage_persists_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pages.db");
+        let ids = [PageId(0), PageId(3), PageId(1)];
+        {
+            let storage = FileStorage::open(&path).await.unwrap();
+            for id in ids {
+                storage.write_page(id, &[id.0 as u8 + 1; PAGE_SIZE]).await.unwrap();
+            }
+            storage.sync().await.unwrap();
+        }
+
+        let storage = FileStorage::open(&path).await.unwrap();
+        let mut buf = [0u8; PAGE_SIZE];
+        for id in ids {
+            storage.read_page(id, &mut buf).await.unwrap();
+            assert!(buf.iter().all(|&b| b == id.0 as u8 + 1), "page {id:?}");
+        }
+        // Skipped by the write to page 3.
+        storage.read_page(PageId(2), &mut buf).await.unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+        assert!(matches!(storage.read_page(PageId(4), &mut buf).await, Err(StorageError::NotFound(PageId(4)))));
+    }
 } 
\ No newline at end of file
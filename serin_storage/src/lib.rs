@@ -1,21 +1,30 @@
 //! SerinDB storage layer primitives.
 #![deny(missing_docs)]
 
-use crc32c::crc32c;
+use crc32c::{crc32c, crc32c_append};
 use serde::{Deserialize, Serialize};
 
+/// Page buffer pool.
 pub mod buffer;
+/// Slotted tuple pages.
+pub mod page;
+/// Write-ahead log.
 pub mod wal;
 
+/// io_uring page I/O.
 #[cfg(feature = "uring")]
 pub mod uring;
 
+/// Page storage engines.
 pub mod engine;
 pub mod lsm;
 
 /// Time-series columnar storage (Phase 9.3).
 pub mod timeseries;
 
+/// PromQL-like query language over time-series data.
+pub mod promql;
+
 /// Default page size (bytes).
 pub const PAGE_SIZE: usize = 16 * 1024; // 16 KiB
 
@@ -47,6 +56,57 @@ impl Default for PageHeader {
     }
 }
 
+impl PageHeader {
+    /// Encoded header size; the header occupies the first bytes of a page.
+    pub const SIZE: usize = 12;
+
+    /// Byte range of the `checksum` field within a page.
+    const CHECKSUM: std::ops::Range<usize> = 2..4;
+
+    /// Write this header at the start of `page` and embed the checksum of
+    /// the whole page. Call once the page body is final; the `checksum` field
+    /// of `self` is ignored.
+    pub fn to_page(&self, page: &mut [u8]) {
+        self.write_fields(page);
+        let sum = page_checksum(page);
+        page[Self::CHECKSUM].copy_from_slice(&sum.to_le_bytes());
+    }
+
+    /// Write every field but `checksum`, leaving the embedded checksum stale.
+    pub(crate) fn write_fields(&self, page: &mut [u8]) {
+        page[0..2].copy_from_slice(&self.page_type.to_le_bytes());
+        page[4..8].copy_from_slice(&self.lsn.to_le_bytes());
+        page[8..10].copy_from_slice(&self.slot_count.to_le_bytes());
+        page[10..12].copy_from_slice(&self.free_space_offset.to_le_bytes());
+    }
+
+    /// Decode the header at the start of `page` without checking the
+    /// checksum; see [`verify`](Self::verify).
+    pub fn from_page(page: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([page[i], page[i + 1]]);
+        Self {
+            page_type: u16_at(0),
+            checksum: u16_at(2),
+            lsn: u32::from_le_bytes(page[4..8].try_into().unwrap()),
+            slot_count: u16_at(8),
+            free_space_offset: u16_at(10),
+        }
+    }
+
+    /// Whether the checksum embedded in `page` matches its contents.
+    pub fn verify(page: &[u8]) -> bool {
+        page.len() >= Self::SIZE && Self::from_page(page).checksum == page_checksum(page)
+    }
+}
+
+/// [`compute_checksum`] of `page` as if its checksum field were zero.
+fn page_checksum(page: &[u8]) -> u16 {
+    let sum = crc32c(&page[..PageHeader::CHECKSUM.start]);
+    let sum = crc32c_append(sum, &[0; 2]);
+    let sum = crc32c_append(sum, &page[PageHeader::CHECKSUM.end..]);
+    fold_checksum(sum)
+}
+
 /// Tuple slot entry in the slot directory.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TupleSlot {
@@ -58,8 +118,11 @@ pub struct TupleSlot {
 
 /// Compute CRC32C checksum for a page buffer (header `checksum` field must be zeroed).
 pub fn compute_checksum(page: &[u8]) -> u16 {
-    let sum = crc32c(page);
-    // Fold 32-bit CRC into 16-bit value (as PostgreSQL does).
+    fold_checksum(crc32c(page))
+}
+
+/// Fold 32-bit CRC into 16-bit value (as PostgreSQL does).
+fn fold_checksum(sum: u32) -> u16 {
     ((sum >> 16) as u16) ^ (sum as u16)
 }
 
@@ -70,18 +133,30 @@ mod tests {
     #[test]
     fn checksum_roundtrip() {
         let mut page = vec![0u8; PAGE_SIZE];
-        let header = PageHeader::default();
-        // Serialize header at page start.
-        let hdr_bytes = bincode::serialize(&header).unwrap();
-        page[..hdr_bytes.len()].copy_from_slice(&hdr_bytes);
+        page[PAGE_SIZE - 3..].copy_from_slice(b"abc");
+        let header = PageHeader { page_type: 3, lsn: 42, slot_count: 1, free_space_offset: 100, ..Default::default() };
+        header.to_page(&mut page);
 
-        // Calculate checksum with zeroed field.
-        let csum = compute_checksum(&page);
+        // Same layout as the serde encoding.
+        assert_eq!(bincode::serialize(&PageHeader { checksum: 0, ..header }).unwrap().len(), PageHeader::SIZE);
+        let read = PageHeader::from_page(&page);
+        assert_eq!(PageHeader { checksum: 0, ..read }, header);
+        assert!(PageHeader::verify(&page));
 
-        // Write checksum back into header in page buffer.
-        page[2..4].copy_from_slice(&csum.to_le_bytes());
+        // The embedded value is the checksum of the page with the field zeroed.
+        let mut zeroed = page.clone();
+        zeroed[2..4].fill(0);
+        assert_eq!(read.checksum, compute_checksum(&zeroed));
+    }
 
-        // Verify checksum matches after embedding.
-        assert_eq!(csum, compute_checksum(&page));
+    #[test]
+    fn verify_detects_single_bit_corruption() {
+        let mut page = vec![0u8; PAGE_SIZE];
+        PageHeader::default().to_page(&mut page);
+        for bit in [0, 3 * 8 + 1, 11 * 8 + 7, PAGE_SIZE * 8 / 2, PAGE_SIZE * 8 - 1] {
+            let mut corrupt = page.clone();
+            corrupt[bit / 8] ^= 1 << (bit % 8);
+            assert!(!PageHeader::verify(&corrupt), "flipped bit {bit} went unnoticed");
+        }
     }
 } 
\ No newline at end of file
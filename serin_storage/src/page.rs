@@ -0,0 +1,180 @@
+//! Slotted pages: variable-length tuples addressed by a stable slot index.
+//!
+//! Layout: the [`PageHeader`] comes first, followed by the slot directory
+//! (one [`TupleSlot`] per slot, growing forward). Tuple data is packed at the
+//! end of the page and grows backward down to `free_space_offset`.
+
+use crate::{PageHeader, TupleSlot, PAGE_SIZE};
+
+/// Bytes taken by one slot directory entry.
+const SLOT_SIZE: usize = 4;
+
+/// A page storing tuples through a slot directory.
+///
+/// Deleting a tuple compacts the data area at once, so free space is always
+/// one contiguous range. The slot itself stays behind as a tombstone (offset
+/// 0) to keep the other slot indices valid and is reused by later inserts.
+#[derive(Debug, Clone)]
+pub struct SlottedPage {
+    data: Box<[u8; PAGE_SIZE]>,
+}
+
+impl Default for SlottedPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlottedPage {
+    /// Empty page with a default header.
+    pub fn new() -> Self {
+        let mut page = Self { data: Box::new([0u8; PAGE_SIZE]) };
+        page.set_header(&PageHeader::default());
+        page
+    }
+
+    /// Wrap a page read from storage.
+    pub fn from_bytes(data: Box<[u8; PAGE_SIZE]>) -> Self {
+        Self { data }
+    }
+
+    /// Raw page contents; call [`seal`](Self::seal) before writing them out.
+    pub fn as_bytes(&self) -> &[u8; PAGE_SIZE] {
+        &self.data
+    }
+
+    /// Embed the checksum of the current contents.
+    pub fn seal(&mut self) {
+        self.header().to_page(&mut self.data[..]);
+    }
+
+    /// Decoded page header.
+    pub fn header(&self) -> PageHeader {
+        PageHeader::from_page(&self.data[..])
+    }
+
+    /// Bytes available between the slot directory and the tuple data.
+    pub fn free_space(&self) -> usize {
+        let header = self.header();
+        header.free_space_offset as usize - PageHeader::SIZE - header.slot_count as usize * SLOT_SIZE
+    }
+
+    /// Store `data` and return its slot index, or `None` if it does not fit.
+    pub fn insert_tuple(&mut self, data: &[u8]) -> Option<u16> {
+        let mut header = self.header();
+        let reuse = (0..header.slot_count).find(|&i| self.slot(i).offset == 0);
+        let needed = data.len() + if reuse.is_some() { 0 } else { SLOT_SIZE };
+        if needed > self.free_space() {
+            return None;
+        }
+        let slot = reuse.unwrap_or_else(|| {
+            header.slot_count += 1;
+            header.slot_count - 1
+        });
+        let offset = header.free_space_offset - data.len() as u16;
+        self.data[offset as usize..][..data.len()].copy_from_slice(data);
+        header.free_space_offset = offset;
+        self.set_slot(slot, TupleSlot { offset, length: data.len() as u16 });
+        self.set_header(&header);
+        Some(slot)
+    }
+
+    /// Tuple stored in `slot`, if any.
+    pub fn get_tuple(&self, slot: u16) -> Option<&[u8]> {
+        if slot >= self.header().slot_count {
+            return None;
+        }
+        let TupleSlot { offset, length } = self.slot(slot);
+        (offset != 0).then(|| &self.data[offset as usize..][..length as usize])
+    }
+
+    /// Remove the tuple in `slot` and reclaim its space; returns whether
+    /// there was one.
+    pub fn delete_tuple(&mut self, slot: u16) -> bool {
+        let Some(TupleSlot { offset, length }) = self.get_tuple(slot).map(|_| self.slot(slot)) else {
+            return false;
+        };
+        let mut header = self.header();
+        // Slide the tuples stored below the deleted one up over it.
+        let start = header.free_space_offset as usize;
+        self.data.copy_within(start..offset as usize, start + length as usize);
+        for i in 0..header.slot_count {
+            let mut other = self.slot(i);
+            if other.offset != 0 && other.offset < offset {
+                other.offset += length;
+                self.set_slot(i, other);
+            }
+        }
+        header.free_space_offset += length;
+        self.set_slot(slot, TupleSlot { offset: 0, length: 0 });
+        self.set_header(&header);
+        true
+    }
+
+    fn slot(&self, slot: u16) -> TupleSlot {
+        let at = PageHeader::SIZE + slot as usize * SLOT_SIZE;
+        let u16_at = |i: usize| u16::from_le_bytes([self.data[i], self.data[i + 1]]);
+        TupleSlot { offset: u16_at(at), length: u16_at(at + 2) }
+    }
+
+    fn set_slot(&mut self, slot: u16, entry: TupleSlot) {
+        let at = PageHeader::SIZE + slot as usize * SLOT_SIZE;
+        self.data[at..at + 2].copy_from_slice(&entry.offset.to_le_bytes());
+        self.data[at + 2..at + 4].copy_from_slice(&entry.length.to_le_bytes());
+    }
+
+    fn set_header(&mut self, header: &PageHeader) {
+        // The checksum is only computed when the page is sealed for disk.
+        header.write_fields(&mut self.data[..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_read_delete_tracks_free_space() {
+        let mut page = SlottedPage::new();
+        let empty = page.free_space();
+        assert_eq!(empty, PAGE_SIZE - PageHeader::SIZE);
+
+        let tuples: [&[u8]; 3] = [b"alpha", b"bravo-bravo", b"c"];
+        let slots: Vec<u16> = tuples.iter().map(|t| page.insert_tuple(t).unwrap()).collect();
+        assert_eq!(slots, [0, 1, 2]);
+        for (slot, tuple) in slots.iter().zip(tuples) {
+            assert_eq!(page.get_tuple(*slot), Some(tuple));
+        }
+        assert_eq!(page.free_space(), empty - 17 - 3 * SLOT_SIZE);
+
+        // Deleting the middle tuple keeps the others readable and frees its bytes.
+        assert!(page.delete_tuple(1));
+        assert!(!page.delete_tuple(1));
+        assert_eq!(page.get_tuple(1), None);
+        assert_eq!(page.get_tuple(0), Some(&b"alpha"[..]));
+        assert_eq!(page.get_tuple(2), Some(&b"c"[..]));
+        assert_eq!(page.free_space(), empty - 6 - 3 * SLOT_SIZE);
+        assert_eq!(page.header().slot_count, 3);
+
+        // The tombstoned slot is reused.
+        assert_eq!(page.insert_tuple(b"delta"), Some(1));
+        assert_eq!(page.get_tuple(1), Some(&b"delta"[..]));
+        page.seal();
+        assert!(PageHeader::verify(page.as_bytes()));
+    }
+
+    #[test]
+    fn insert_fails_when_page_is_full() {
+        let mut page = SlottedPage::new();
+        let big = vec![7u8; 4000];
+        let mut n = 0;
+        while page.insert_tuple(&big).is_some() {
+            n += 1;
+        }
+        assert_eq!(n, 4);
+        assert!(page.free_space() < big.len() + SLOT_SIZE);
+        assert_eq!(page.insert_tuple(&vec![1u8; page.free_space() - SLOT_SIZE]), Some(4));
+        assert_eq!(page.free_space(), 0);
+        assert_eq!(page.insert_tuple(b""), None);
+    }
+}
@@ -0,0 +1,422 @@
+//! PromQL-like query language for the time-series layer (Phase 9.3).
+//!
+//! Supports `metric{label="v"}` selectors with an optional `[range]` suffix
+//! and the `rate`, `sum` and `avg` functions over them. Parsed expressions
+//! are lowered into a [`QueryPlan`] which is evaluated against a set of
+//! labelled [`Series`].
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use logos::Logos;
+use thiserror::Error;
+
+use crate::timeseries::{ColumnChunk, Timestamp, Value};
+
+/// Query token kinds recognised by the lexer.
+#[derive(Logos, Debug, PartialEq, Eq, Clone, Copy)]
+#[logos(skip r"[ \t\n\r]+")]
+pub enum Token {
+    /// `rate` function.
+    #[token("rate")]
+    Rate,
+    /// `sum` aggregation.
+    #[token("sum")]
+    Sum,
+    /// `avg` aggregation.
+    #[token("avg")]
+    Avg,
+    /// Left brace `{`.
+    #[token("{")]
+    LBrace,
+    /// Right brace `}`.
+    #[token("}")]
+    RBrace,
+    /// Left bracket `[`.
+    #[token("[")]
+    LBracket,
+    /// Right bracket `]`.
+    #[token("]")]
+    RBracket,
+    /// Left parenthesis `(`.
+    #[token("(")]
+    LParen,
+    /// Right parenthesis `)`.
+    #[token(")")]
+    RParen,
+    /// Comma `,`.
+    #[token(",")]
+    Comma,
+    /// Equality matcher `=`.
+    #[token("=")]
+    Eq,
+    /// Inequality matcher `!=`.
+    #[token("!=")]
+    NotEq,
+    /// Duration literal such as `5m` or `30s`.
+    #[regex(r"[0-9]+(ms|s|m|h|d)")]
+    Duration,
+    /// Double-quoted string literal.
+    #[regex(r#""[^"]*""#)]
+    String,
+    /// Metric or label name.
+    #[regex(r"[A-Za-z_:][A-Za-z0-9_:]*")]
+    Identifier,
+}
+
+/// Query parsing and planning errors.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QueryError {
+    /// Unexpected end-of-input.
+    #[error("unexpected end of input")]
+    Eof,
+    /// Unexpected token at the given byte offset.
+    #[error("unexpected token {0:?} at offset {1}")]
+    Unexpected(Token, usize),
+    /// Unrecognised input at the given byte offset.
+    #[error("invalid input at offset {0}")]
+    Invalid(usize),
+    /// Function argument has the wrong shape (e.g. `rate` without a range).
+    #[error("{0}")]
+    Type(&'static str),
+}
+
+/// Label matching operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOp {
+    /// `label="value"`.
+    Eq,
+    /// `label!="value"`.
+    NotEq,
+}
+
+/// Single label matcher inside `{...}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelMatcher {
+    /// Label name.
+    pub name: String,
+    /// Matching operator.
+    pub op: MatchOp,
+    /// Label value to compare against.
+    pub value: String,
+}
+
+/// Series selector, e.g. `http_requests{code="200"}[5m]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    /// Metric name.
+    pub metric: String,
+    /// Label matchers (all must hold).
+    pub matchers: Vec<LabelMatcher>,
+    /// Range window; `None` selects an instant vector.
+    pub range: Option<Duration>,
+}
+
+impl Selector {
+    /// Whether the given series satisfies this selector.
+    pub fn matches(&self, series: &Series) -> bool {
+        series.metric == self.metric
+            && self.matchers.iter().all(|m| {
+                let actual = series.labels.get(&m.name).map(String::as_str).unwrap_or("");
+                match m.op {
+                    MatchOp::Eq => actual == m.value,
+                    MatchOp::NotEq => actual != m.value,
+                }
+            })
+    }
+}
+
+/// Query expression AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// Plain series selector.
+    Selector(Selector),
+    /// Per-second rate over a range selector.
+    Rate(Box<Expr>),
+    /// Sum across all input series.
+    Sum(Box<Expr>),
+    /// Average across all input series.
+    Avg(Box<Expr>),
+}
+
+/// Parse a query string into an [`Expr`].
+pub fn parse(query: &str) -> Result<Expr, QueryError> {
+    let mut tokens = Vec::new();
+    let mut lex = Token::lexer(query);
+    while let Some(tok) = lex.next() {
+        let tok = tok.map_err(|_| QueryError::Invalid(lex.span().start))?;
+        tokens.push((tok, lex.span().start, lex.slice()));
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.expr()?;
+    if let Some(&(tok, at, _)) = parser.tokens.get(parser.pos) {
+        return Err(QueryError::Unexpected(tok, at));
+    }
+    Ok(expr)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<(Token, usize, &'a str), QueryError> {
+        let item = *self.tokens.get(self.pos).ok_or(QueryError::Eof)?;
+        self.pos += 1;
+        Ok(item)
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).map(|&(tok, _, _)| tok)
+    }
+
+    fn expect(&mut self, want: Token) -> Result<&'a str, QueryError> {
+        match self.next()? {
+            (tok, _, text) if tok == want => Ok(text),
+            (tok, at, _) => Err(QueryError::Unexpected(tok, at)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, QueryError> {
+        let (tok, at, text) = self.next()?;
+        let wrap: fn(Box<Expr>) -> Expr = match tok {
+            Token::Identifier => return self.selector(text).map(Expr::Selector),
+            Token::Rate => Expr::Rate,
+            Token::Sum => Expr::Sum,
+            Token::Avg => Expr::Avg,
+            tok => return Err(QueryError::Unexpected(tok, at)),
+        };
+        self.expect(Token::LParen)?;
+        let inner = self.expr()?;
+        self.expect(Token::RParen)?;
+        Ok(wrap(Box::new(inner)))
+    }
+
+    fn selector(&mut self, metric: &str) -> Result<Selector, QueryError> {
+        let mut matchers = Vec::new();
+        if self.peek() == Some(Token::LBrace) {
+            self.pos += 1;
+            while self.peek() != Some(Token::RBrace) {
+                let name = self.expect(Token::Identifier)?.to_string();
+                let op = match self.next()? {
+                    (Token::Eq, _, _) => MatchOp::Eq,
+                    (Token::NotEq, _, _) => MatchOp::NotEq,
+                    (tok, at, _) => return Err(QueryError::Unexpected(tok, at)),
+                };
+                let quoted = self.expect(Token::String)?;
+                let value = quoted[1..quoted.len() - 1].to_string();
+                matchers.push(LabelMatcher { name, op, value });
+                if self.peek() == Some(Token::Comma) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            self.expect(Token::RBrace)?;
+        }
+        let mut range = None;
+        if self.peek() == Some(Token::LBracket) {
+            self.pos += 1;
+            range = Some(parse_duration(self.expect(Token::Duration)?));
+            self.expect(Token::RBracket)?;
+        }
+        Ok(Selector { metric: metric.to_string(), matchers, range })
+    }
+}
+
+/// Convert a lexed duration literal (`[0-9]+(ms|s|m|h|d)`) into a [`Duration`].
+fn parse_duration(text: &str) -> Duration {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let n: u64 = text[..split].parse().unwrap_or(u64::MAX);
+    match &text[split..] {
+        "ms" => Duration::from_millis(n),
+        "s" => Duration::from_secs(n),
+        "m" => Duration::from_secs(n.saturating_mul(60)),
+        "h" => Duration::from_secs(n.saturating_mul(3600)),
+        _ => Duration::from_secs(n.saturating_mul(86_400)),
+    }
+}
+
+/// Label set attached to a series.
+pub type Labels = BTreeMap<String, String>;
+
+/// A labelled series backed by a column chunk.
+#[derive(Debug, Clone)]
+pub struct Series {
+    /// Metric name.
+    pub metric: String,
+    /// Label set.
+    pub labels: Labels,
+    /// Stored samples.
+    pub chunk: ColumnChunk,
+}
+
+/// One element of an instant-vector query result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Labels of the originating series (empty after aggregation).
+    pub labels: Labels,
+    /// Evaluated value.
+    pub value: Value,
+}
+
+/// Executable query plan produced from an [`Expr`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryPlan {
+    /// Latest sample of every series matching the selector.
+    Fetch {
+        /// Series selector (without range).
+        selector: Selector,
+    },
+    /// Per-second increase of every matching series over `range`.
+    Rate {
+        /// Series selector (without range).
+        selector: Selector,
+        /// Look-back window.
+        range: Duration,
+    },
+    /// Sum of the child instant vector.
+    Sum(Box<QueryPlan>),
+    /// Average of the child instant vector.
+    Avg(Box<QueryPlan>),
+}
+
+/// Lower an expression into a [`QueryPlan`], validating argument shapes.
+pub fn plan(expr: &Expr) -> Result<QueryPlan, QueryError> {
+    match expr {
+        Expr::Selector(sel) if sel.range.is_some() => {
+            Err(QueryError::Type("range selector must be wrapped in rate()"))
+        }
+        Expr::Selector(sel) => Ok(QueryPlan::Fetch { selector: sel.clone() }),
+        Expr::Rate(inner) => match &**inner {
+            Expr::Selector(sel) => {
+                let range = sel.range.ok_or(QueryError::Type("rate() requires a range selector"))?;
+                Ok(QueryPlan::Rate { selector: Selector { range: None, ..sel.clone() }, range })
+            }
+            _ => Err(QueryError::Type("rate() requires a range selector")),
+        },
+        Expr::Sum(inner) => Ok(QueryPlan::Sum(Box::new(plan(inner)?))),
+        Expr::Avg(inner) => Ok(QueryPlan::Avg(Box::new(plan(inner)?))),
+    }
+}
+
+impl QueryPlan {
+    /// Evaluate the plan against `series` at instant `at`.
+    pub fn execute(&self, series: &[Series], at: Timestamp) -> Vec<Sample> {
+        match self {
+            QueryPlan::Fetch { selector } => series
+                .iter()
+                .filter(|s| selector.matches(s))
+                .filter_map(|s| {
+                    let (_, value) = s.chunk.iter().filter(|&(ts, _)| ts <= at).last()?;
+                    Some(Sample { labels: s.labels.clone(), value })
+                })
+                .collect(),
+            QueryPlan::Rate { selector, range } => {
+                // A window reaching past the earliest timestamp covers everything.
+                let start = at.saturating_sub(i64::try_from(range.as_nanos()).unwrap_or(i64::MAX));
+                series
+                    .iter()
+                    .filter(|s| selector.matches(s))
+                    .filter_map(|s| {
+                        let value = rate(s.chunk.iter().filter(|&(ts, _)| ts > start && ts <= at))?;
+                        Some(Sample { labels: s.labels.clone(), value })
+                    })
+                    .collect()
+            }
+            QueryPlan::Sum(input) => {
+                let samples = input.execute(series, at);
+                if samples.is_empty() {
+                    return Vec::new();
+                }
+                vec![Sample { labels: Labels::new(), value: samples.iter().map(|s| s.value).sum() }]
+            }
+            QueryPlan::Avg(input) => {
+                let samples = input.execute(series, at);
+                if samples.is_empty() {
+                    return Vec::new();
+                }
+                let sum: Value = samples.iter().map(|s| s.value).sum();
+                vec![Sample { labels: Labels::new(), value: sum / samples.len() as f64 }]
+            }
+        }
+    }
+}
+
+/// Per-second increase of a counter, treating any decrease as a reset.
+fn rate(mut points: impl Iterator<Item = (Timestamp, Value)>) -> Option<Value> {
+    let (first_ts, mut prev) = points.next()?;
+    let mut last_ts = first_ts;
+    let mut increase = 0.0;
+    for (ts, v) in points {
+        increase += if v >= prev { v - prev } else { v };
+        prev = v;
+        last_ts = ts;
+    }
+    if last_ts == first_ts {
+        return None;
+    }
+    Some(increase / ((last_ts - first_ts) as f64 / 1e9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selector_with_matcher() {
+        let expr = parse(r#"http_requests{code="200", method!="GET"}"#).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Selector(Selector {
+                metric: "http_requests".into(),
+                matchers: vec![
+                    LabelMatcher { name: "code".into(), op: MatchOp::Eq, value: "200".into() },
+                    LabelMatcher { name: "method".into(), op: MatchOp::NotEq, value: "GET".into() },
+                ],
+                range: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_rate_range() {
+        let expr = parse("rate(cpu_seconds[5m])").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Rate(Box::new(Expr::Selector(Selector {
+                metric: "cpu_seconds".into(),
+                matchers: vec![],
+                range: Some(Duration::from_secs(300)),
+            })))
+        );
+        assert!(plan(&parse("rate(cpu_seconds)").unwrap()).is_err());
+    }
+
+    #[test]
+    fn execute_sum_of_rate() {
+        let mk = |host: &str, step: f64| {
+            let mut chunk = ColumnChunk::new();
+            for i in 0..=60 {
+                chunk.append(i * 1_000_000_000, i as f64 * step);
+            }
+            let labels = Labels::from([("host".to_string(), host.to_string())]);
+            Series { metric: "reqs".into(), labels, chunk }
+        };
+        let series = vec![mk("a", 1.0), mk("b", 2.0)];
+        let plan = plan(&parse("sum(rate(reqs[1m]))").unwrap()).unwrap();
+        let out = plan.execute(&series, 60_000_000_000);
+        assert_eq!(out.len(), 1);
+        assert!((out[0].value - 3.0).abs() < 1e-9);
+
+        // A range longer than the whole timeline neither wraps nor panics.
+        let plan = QueryPlan::Rate {
+            selector: Selector { metric: "reqs".into(), matchers: vec![], range: None },
+            range: Duration::from_secs(u64::MAX),
+        };
+        let out = plan.execute(&series, 60_000_000_000);
+        assert_eq!(out.len(), 2);
+        assert!((out[0].value - 1.0).abs() < 1e-9);
+    }
+}
@@ -5,35 +5,312 @@
 //! floating-point values, a simple time-bucket index, and continuous
 //! aggregate roll-up infrastructure.
 //!
+//! Value columns are typed (see [`ColumnKind`]): `f64` uses Gorilla XOR
+//! compression, `i64` reuses the delta-of-delta timestamp codec and `bool`
+//! is run-length encoded.
+//!
 //! The implementation follows the design goals described in the design
 //! document and meets the requirements for Phase 9.3 of the task list.
 
 use bitvec::prelude::*;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// Logical timestamp type (Unix epoch nanos).
 pub type Timestamp = i64;
 
-/// Fixed-width value type for this MVP (f64).
-/// In the future this can be extended to arbitrarily typed columns via
-/// binary ser/de but we focus on numeric telemetry for now.
+/// Default value type (f64 telemetry, Gorilla XOR compressed).
 pub type Value = f64;
 
 /// Chunk size in rows (fixed for the MVP).
 const CHUNK_CAPACITY: usize = 16 * 1024; // 16 K rows per chunk
 
+/// Physical type of a value column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnKind {
+    /// 64-bit float, XOR compressed.
+    F64,
+    /// 64-bit signed integer (e.g. counters), delta-of-delta compressed.
+    I64,
+    /// Boolean, run-length encoded.
+    Bool,
+}
+
+/// Value type storable in a [`ColumnChunk`], together with its codec.
+pub trait ColumnValue: Copy + PartialEq + std::fmt::Debug + Serialize + DeserializeOwned {
+    /// Column kind implemented by this type.
+    const KIND: ColumnKind;
+
+    /// Encode `values[1..]`; `values[0]` is stored raw as the chunk base.
+    fn encode(values: &[Self]) -> Vec<u8>;
+
+    /// Decode `rows` values (including `base`) from an encoded stream.
+    ///
+    /// `rows` is trusted for the length of the output, so callers decoding
+    /// untrusted data bound it first (as [`CompressedChunk::decompress`] does).
+    fn decode(base: Self, bits: &[u8], rows: usize) -> Result<Vec<Self>, DecodeError>;
+}
+
+impl ColumnValue for f64 {
+    const KIND: ColumnKind = ColumnKind::F64;
+
+    fn encode(values: &[Self]) -> Vec<u8> {
+        let mut val_buf = BitBuffer::default();
+        let mut prev_val_bits = values[0].to_bits();
+        let mut prev_leading = 64u8;
+        let mut prev_trailing = 0u8;
+
+        for &v in &values[1..] {
+            let vb = v.to_bits();
+            let xor = prev_val_bits ^ vb;
+            if xor == 0 {
+                // Write single 0 bit
+                val_buf.push_bit(false);
+            } else {
+                val_buf.push_bit(true);
+                let leading = xor.leading_zeros() as u8;
+                let trailing = xor.trailing_zeros() as u8;
+                if leading >= prev_leading && trailing >= prev_trailing {
+                    // Reuse previous leading/trailing block (control 0)
+                    val_buf.push_bit(false);
+                    let significant_bits = 64 - prev_leading as u32 - prev_trailing as u32;
+                    val_buf.push_bits(xor >> prev_trailing, significant_bits as usize);
+                } else {
+                    // Store new leading/trailing (control 1)
+                    val_buf.push_bit(true);
+                    val_buf.push_bits(leading as u64, 6); // 6 bits for leading zeros
+                    let significant_bits = 64 - leading as u32 - trailing as u32;
+                    val_buf.push_bits((significant_bits - 1) as u64, 6); // store length-1 (6 bits)
+                    val_buf.push_bits(xor >> trailing, significant_bits as usize);
+                    prev_leading = leading;
+                    prev_trailing = trailing;
+                }
+            }
+            prev_val_bits = vb;
+        }
+        val_buf.into_vec()
+    }
+
+    fn decode(base: Self, bits: &[u8], rows: usize) -> Result<Vec<Self>, DecodeError> {
+        let mut values = Vec::with_capacity(rows.min(max_rows(bits)));
+        values.push(base);
+        let mut reader = BitReader::new(bits);
+        let mut prev_val_bits = base.to_bits();
+        let mut stored_leading = 64u32;
+        let mut stored_trailing = 0u32;
+
+        while values.len() < rows {
+            let row = values.len();
+            let err = |bit| DecodeError::TruncatedValues { row, bit };
+            if !reader.read_bit().ok_or_else(|| err(reader.pos))? {
+                // value same as previous
+                values.push(f64::from_bits(prev_val_bits));
+                continue;
+            }
+            let use_prev_block = !reader.read_bit().ok_or_else(|| err(reader.pos))?;
+            if !use_prev_block {
+                let header_at = reader.pos;
+                let leading = reader.read_bits(6).ok_or_else(|| err(reader.pos))? as u32;
+                let significant_bits = reader.read_bits(6).ok_or_else(|| err(reader.pos))? as u32 + 1;
+                if leading + significant_bits > 64 {
+                    return Err(DecodeError::InvalidValueBlock { row, bit: header_at });
+                }
+                stored_leading = leading;
+                stored_trailing = 64 - leading - significant_bits;
+            }
+            let significant_bits = 64 - stored_leading - stored_trailing;
+            if significant_bits == 0 {
+                // Block reuse before any block header was written.
+                return Err(DecodeError::InvalidValueBlock { row, bit: reader.pos });
+            }
+            let xor_bits = reader.read_bits(significant_bits as usize).ok_or_else(|| err(reader.pos))?;
+            let curr_bits = prev_val_bits ^ (xor_bits << stored_trailing);
+            values.push(f64::from_bits(curr_bits));
+            prev_val_bits = curr_bits;
+        }
+        Ok(values)
+    }
+}
+
+impl ColumnValue for i64 {
+    const KIND: ColumnKind = ColumnKind::I64;
+
+    fn encode(values: &[Self]) -> Vec<u8> {
+        encode_delta_of_delta(values)
+    }
+
+    fn decode(base: Self, bits: &[u8], rows: usize) -> Result<Vec<Self>, DecodeError> {
+        decode_delta_of_delta(base, bits, rows, |row, bit| DecodeError::TruncatedValues { row, bit })
+    }
+}
+
+impl ColumnValue for bool {
+    const KIND: ColumnKind = ColumnKind::Bool;
+
+    /// Run lengths of alternating values, starting with a run of `values[0]`
+    /// (which includes the base row itself).
+    fn encode(values: &[Self]) -> Vec<u8> {
+        let mut buf = BitBuffer::default();
+        let mut current = values[0];
+        let mut run = 0u64;
+        for &v in values {
+            if v != current {
+                push_run_length(&mut buf, run);
+                current = v;
+                run = 0;
+            }
+            run += 1;
+        }
+        push_run_length(&mut buf, run);
+        buf.into_vec()
+    }
+
+    fn decode(base: Self, bits: &[u8], rows: usize) -> Result<Vec<Self>, DecodeError> {
+        // A single run can cover many rows, so the stream length gives no bound here.
+        let mut values = Vec::with_capacity(rows.min(CHUNK_CAPACITY));
+        let mut reader = BitReader::new(bits);
+        let mut current = base;
+        while values.len() < rows {
+            let row = values.len();
+            let err = |bit| DecodeError::TruncatedValues { row, bit };
+            let width = match reader.read_bits(2).ok_or_else(|| err(reader.pos))? {
+                0b00 => 4,
+                0b01 => 12,
+                0b10 => 32,
+                _ => 64,
+            };
+            let run = reader.read_bits(width).ok_or_else(|| err(reader.pos))?.saturating_add(1);
+            let take = run.min((rows - row) as u64) as usize;
+            values.extend(std::iter::repeat_n(current, take));
+            current = !current;
+        }
+        Ok(values)
+    }
+}
+
+/// Upper bound on the rows a delta-of-delta or XOR stream can describe:
+/// the raw base row plus at least one bit for every further row.
+fn max_rows(bits: &[u8]) -> usize {
+    bits.len().saturating_mul(8).saturating_add(1)
+}
+
+/// Write a (non-zero) run length as a 2-bit width tag followed by `run - 1`.
+fn push_run_length(buf: &mut BitBuffer, run: u64) {
+    let v = run - 1;
+    match 64 - v.leading_zeros() {
+        0..=4 => {
+            buf.push_bits(0b00, 2);
+            buf.push_bits(v, 4);
+        }
+        5..=12 => {
+            buf.push_bits(0b01, 2);
+            buf.push_bits(v, 12);
+        }
+        13..=32 => {
+            buf.push_bits(0b10, 2);
+            buf.push_bits(v, 32);
+        }
+        _ => {
+            buf.push_bits(0b11, 2);
+            buf.push_bits(v, 64);
+        }
+    }
+}
+
+/// Gorilla delta-of-delta encoding of `seq[1..]` (used for timestamps and `i64` values).
+fn encode_delta_of_delta(seq: &[i64]) -> Vec<u8> {
+    let mut ts_buf = BitBuffer::default();
+    let mut prev_ts = seq[0];
+    let mut prev_delta = 0i64;
+    for &ts in &seq[1..] {
+        let delta = ts.wrapping_sub(prev_ts);
+        let delta_of_delta = delta.wrapping_sub(prev_delta);
+        prev_ts = ts;
+        prev_delta = delta;
+
+        // ZigZag encode delta_of_delta to map signed -> unsigned
+        let zz = ((delta_of_delta << 1) ^ (delta_of_delta >> 63)) as u64;
+        // Variable bits: write 0 for small, 1 + 12 bits for medium, 2 + 20 bits, else 3 + 64 bits
+        if zz == 0 {
+            ts_buf.push_bit(false); // control bit 0
+        } else {
+            ts_buf.push_bit(true); // control bit 1
+            let bits = 64 - zz.leading_zeros();
+            match bits {
+                0..=12 => {
+                    ts_buf.push_bits(0b00, 2);
+                    ts_buf.push_bits(zz, 12);
+                }
+                13..=20 => {
+                    ts_buf.push_bits(0b01, 2);
+                    ts_buf.push_bits(zz, 20);
+                }
+                21..=32 => {
+                    ts_buf.push_bits(0b10, 2);
+                    ts_buf.push_bits(zz, 32);
+                }
+                _ => {
+                    ts_buf.push_bits(0b11, 2);
+                    ts_buf.push_bits(zz, 64);
+                }
+            }
+        }
+    }
+    ts_buf.into_vec()
+}
+
+/// Inverse of [`encode_delta_of_delta`]; `err` builds the error for a truncated stream.
+fn decode_delta_of_delta(
+    base: i64,
+    bits: &[u8],
+    rows: usize,
+    err: impl Fn(usize, usize) -> DecodeError,
+) -> Result<Vec<i64>, DecodeError> {
+    let mut out = Vec::with_capacity(rows.min(max_rows(bits)));
+    out.push(base);
+    let mut reader = BitReader::new(bits);
+    let mut prev_ts = base;
+    let mut prev_delta = 0i64;
+    while out.len() < rows {
+        let row = out.len();
+        let decoded = if !reader.read_bit().ok_or_else(|| err(row, reader.pos))? {
+            // control 0 => delta_of_delta = 0
+            0
+        } else {
+            let width = match reader.read_bits(2).ok_or_else(|| err(row, reader.pos))? {
+                0b00 => 12,
+                0b01 => 20,
+                0b10 => 32,
+                _ => 64,
+            };
+            let zz = reader.read_bits(width).ok_or_else(|| err(row, reader.pos))?;
+            // Zigzag decode
+            ((zz >> 1) as i64) ^ -((zz & 1) as i64)
+        };
+        let delta = prev_delta.wrapping_add(decoded);
+        let ts = prev_ts.wrapping_add(delta);
+        prev_ts = ts;
+        prev_delta = delta;
+        out.push(ts);
+    }
+    Ok(out)
+}
+
 /// Column-oriented chunk holding one metric series.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ColumnChunk {
+pub struct ColumnChunk<V = Value> {
     /// Uncompressed timestamps.
     timestamps: Vec<Timestamp>,
     /// Uncompressed values.
-    values: Vec<Value>,
+    values: Vec<V>,
 }
 
-impl ColumnChunk {
+impl<V: ColumnValue> ColumnChunk<V> {
     /// Create a new empty chunk.
     pub fn new() -> Self {
         Self {
@@ -42,6 +319,11 @@ impl ColumnChunk {
         }
     }
 
+    /// Physical kind of the value column.
+    pub fn kind(&self) -> ColumnKind {
+        V::KIND
+    }
+
     /// Current number of stored rows.
     #[inline]
     pub fn len(&self) -> usize {
@@ -55,13 +337,18 @@ impl ColumnChunk {
     }
 
     /// Append a single (timestamp, value) pair.
-    pub fn append(&mut self, ts: Timestamp, val: Value) {
+    pub fn append(&mut self, ts: Timestamp, val: V) {
         self.timestamps.push(ts);
         self.values.push(val);
     }
 
-    /// Compress the current chunk using Gorilla compression.
-    pub fn compress(&self) -> CompressedChunk {
+    /// Iterate over stored `(timestamp, value)` pairs in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (Timestamp, V)> + '_ {
+        self.timestamps.iter().copied().zip(self.values.iter().copied())
+    }
+
+    /// Compress the current chunk using the codec of its column kind.
+    pub fn compress(&self) -> CompressedChunk<V> {
         CompressedChunk::from_chunk(self)
     }
 }
@@ -90,214 +377,131 @@ impl BitBuffer {
     }
 }
 
+/// Bounds-checked sequential reader over a Gorilla bit stream.
+struct BitReader<'a> {
+    bits: &'a BitSlice<u8, Msb0>,
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bits: BitSlice::from_slice(bytes), pos: 0 }
+    }
+
+    /// Read a single bit, or `None` past the end of the stream.
+    #[inline]
+    fn read_bit(&mut self) -> Option<bool> {
+        let bit = *self.bits.get(self.pos)?;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    /// Read `n` (1..=64) bits as a big-endian integer, or `None` if fewer remain.
+    #[inline]
+    fn read_bits(&mut self, n: usize) -> Option<u64> {
+        let v = self.bits.get(self.pos..self.pos + n)?.load_be::<u64>();
+        self.pos += n;
+        Some(v)
+    }
+}
+
+/// Error raised when a [`CompressedChunk`] bit stream cannot be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    /// Timestamp stream ended before all rows were decoded.
+    #[error("timestamp stream truncated at bit {bit} (row {row})")]
+    TruncatedTimestamps {
+        /// Row being decoded.
+        row: usize,
+        /// Bit offset within `ts_bits`.
+        bit: usize,
+    },
+    /// Value stream ended before all rows were decoded.
+    #[error("value stream truncated at bit {bit} (row {row})")]
+    TruncatedValues {
+        /// Row being decoded.
+        row: usize,
+        /// Bit offset within `val_bits`.
+        bit: usize,
+    },
+    /// Value block header describes an impossible leading/length combination.
+    #[error("invalid value block header at bit {bit} (row {row})")]
+    InvalidValueBlock {
+        /// Row being decoded.
+        row: usize,
+        /// Bit offset within `val_bits`.
+        bit: usize,
+    },
+    /// Row count is larger than the timestamp stream could possibly hold.
+    #[error("chunk claims {rows} rows but its timestamp stream holds at most {max}")]
+    RowCount {
+        /// Rows claimed by the chunk header.
+        rows: usize,
+        /// Rows `ts_bits` can describe.
+        max: usize,
+    },
+}
+
 /// Encoded chunk (timestamps + values).
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CompressedChunk {
+pub struct CompressedChunk<V = Value> {
     /// First timestamp stored raw.
     base_ts: Timestamp,
     /// First value stored raw.
-    base_val: Value,
+    base_val: V,
     /// Encoded timestamp diff-stream.
     ts_bits: Vec<u8>,
-    /// Encoded value xor-stream.
+    /// Encoded value stream (codec depends on the column kind).
     val_bits: Vec<u8>,
     /// Number of rows.
     rows: usize,
 }
 
-impl CompressedChunk {
+impl<V: ColumnValue> CompressedChunk<V> {
     /// Build a compressed chunk from the given column chunk.
-    pub fn from_chunk(chunk: &ColumnChunk) -> Self {
+    pub fn from_chunk(chunk: &ColumnChunk<V>) -> Self {
         assert!(!chunk.timestamps.is_empty(), "chunk must contain at least one row");
 
-        let rows = chunk.timestamps.len();
-        let mut ts_buf = BitBuffer::default();
-
-        // Gorilla timestamp compression
-        let mut prev_ts = chunk.timestamps[0];
-        let mut prev_delta = 0i64;
-        for &ts in &chunk.timestamps[1..] {
-            let delta = ts - prev_ts;
-            let delta_of_delta = delta - prev_delta;
-            prev_ts = ts;
-            prev_delta = delta;
-
-            // ZigZag encode delta_of_delta to map signed -> unsigned
-            let zz = ((delta_of_delta << 1) ^ (delta_of_delta >> 63)) as u64;
-            // Variable bits: write 0 for small, 1 + 12 bits for medium, 2 + 20 bits, else 3 + 64 bits
-            if zz == 0 {
-                ts_buf.push_bit(false); // control bit 0
-            } else {
-                ts_buf.push_bit(true); // control bit 1
-                let bits = 64 - zz.leading_zeros();
-                match bits {
-                    0..=12 => {
-                        ts_buf.push_bits(0b00, 2);
-                        ts_buf.push_bits(zz, 12);
-                    }
-                    13..=20 => {
-                        ts_buf.push_bits(0b01, 2);
-                        ts_buf.push_bits(zz, 20);
-                    }
-                    21..=32 => {
-                        ts_buf.push_bits(0b10, 2);
-                        ts_buf.push_bits(zz, 32);
-                    }
-                    _ => {
-                        ts_buf.push_bits(0b11, 2);
-                        ts_buf.push_bits(zz, 64);
-                    }
-                }
-            }
-        }
-
-        // Gorilla value compression
-        let mut val_buf = BitBuffer::default();
-        let mut prev_val_bits = chunk.values[0].to_bits();
-        let mut prev_leading = 64u8;
-        let mut prev_trailing = 0u8;
-
-        for &v in &chunk.values[1..] {
-            let vb = v.to_bits();
-            let xor = prev_val_bits ^ vb;
-            if xor == 0 {
-                // Write single 0 bit
-                val_buf.push_bit(false);
-            } else {
-                val_buf.push_bit(true);
-                let leading = xor.leading_zeros() as u8;
-                let trailing = xor.trailing_zeros() as u8;
-                if leading >= prev_leading && trailing >= prev_trailing {
-                    // Reuse previous leading/trailing block (control 0)
-                    val_buf.push_bit(false);
-                    let significant_bits = 64 - prev_leading as u32 - prev_trailing as u32;
-                    val_buf.push_bits(xor >> prev_trailing, significant_bits as usize);
-                } else {
-                    // Store new leading/trailing (control 1)
-                    val_buf.push_bit(true);
-                    val_buf.push_bits(leading as u64, 6); // 6 bits for leading zeros
-                    let significant_bits = 64 - leading as u32 - trailing as u32;
-                    val_buf.push_bits((significant_bits - 1) as u64, 6); // store length-1 (6 bits)
-                    val_buf.push_bits(xor >> trailing, significant_bits as usize);
-                    prev_leading = leading;
-                    prev_trailing = trailing;
-                }
-            }
-            prev_val_bits = vb;
-        }
-
         Self {
             base_ts: chunk.timestamps[0],
             base_val: chunk.values[0],
-            ts_bits: ts_buf.into_vec(),
-            val_bits: val_buf.into_vec(),
-            rows,
+            ts_bits: encode_delta_of_delta(&chunk.timestamps),
+            val_bits: V::encode(&chunk.values),
+            rows: chunk.timestamps.len(),
         }
     }
 
+    /// Physical kind of the value column.
+    pub fn kind(&self) -> ColumnKind {
+        V::KIND
+    }
+
     /// Decode the chunk back to plain column format.
-    pub fn decompress(&self) -> ColumnChunk {
-        let mut timestamps = Vec::with_capacity(self.rows);
-        let mut values = Vec::with_capacity(self.rows);
-
-        // Timestamps
-        timestamps.push(self.base_ts);
-        let mut reader = BitSlice::<u8, Msb0>::from_slice(&self.ts_bits).expect("bit slice");
-        let mut cursor = 0;
-        let mut prev_ts = self.base_ts;
-        let mut prev_delta = 0i64;
-        while timestamps.len() < self.rows {
-            if !reader.get(cursor).copied().unwrap_or(false) {
-                // control 0 => delta_of_delta = 0
-                cursor += 1;
-                let delta = prev_delta;
-                let ts = prev_ts + delta;
-                timestamps.push(ts);
-                prev_ts = ts;
-            } else {
-                cursor += 1;
-                let tag = reader[cursor..cursor + 2].load_be::<u8>();
-                cursor += 2;
-                let (bits, val): (u32, i64) = match tag {
-                    0b00 => {
-                        let v = reader[cursor..cursor + 12].load_be::<u16>() as u64;
-                        cursor += 12;
-                        (12, v as i64)
-                    }
-                    0b01 => {
-                        let v = reader[cursor..cursor + 20].load_be::<u32>() as u64;
-                        cursor += 20;
-                        (20, v as i64)
-                    }
-                    0b10 => {
-                        let v = reader[cursor..cursor + 32].load_be::<u32>() as u64;
-                        cursor += 32;
-                        (32, v as i64)
-                    }
-                    _ => {
-                        let v = reader[cursor..cursor + 64].load_be::<u64>();
-                        cursor += 64;
-                        (64, v as i64)
-                    }
-                };
-                // Zigzag decode
-                let decoded = ((val >> 1) as i64) ^ (-((val & 1) as i64));
-                let delta = prev_delta + decoded;
-                let ts = prev_ts + delta;
-                prev_ts = ts;
-                prev_delta = delta;
-                timestamps.push(ts);
-                let _ = bits; // silence unused warning
-            }
+    ///
+    /// Every read is bounds-checked so truncated or corrupt bit streams (e.g.
+    /// from deserialized data) yield a [`DecodeError`] instead of a panic.
+    pub fn decompress(&self) -> Result<ColumnChunk<V>, DecodeError> {
+        if self.rows == 0 {
+            return Ok(ColumnChunk { timestamps: Vec::new(), values: Vec::new() });
         }
-
-        // Values
-        values.push(self.base_val);
-        let mut val_reader = BitSlice::<u8, Msb0>::from_slice(&self.val_bits).expect("bit slice");
-        let mut val_cursor = 0;
-        let mut prev_val_bits = self.base_val.to_bits();
-        let mut stored_leading = 64u8;
-        let mut stored_trailing = 0u8;
-
-        while values.len() < self.rows {
-            let ctrl_zero = !val_reader.get(val_cursor).copied().unwrap_or(false);
-            val_cursor += 1;
-            if ctrl_zero {
-                // value same as previous
-                values.push(f64::from_bits(prev_val_bits));
-                continue;
-            }
-            let use_prev_block = !val_reader.get(val_cursor).copied().unwrap_or(false);
-            val_cursor += 1;
-            let (leading, significant_bits, trailing) = if use_prev_block {
-                (stored_leading, 64 - stored_leading as u32 - stored_trailing as u32, stored_trailing)
-            } else {
-                let leading = val_reader[val_cursor..val_cursor + 6].load_be::<u8>();
-                val_cursor += 6;
-                let sig_len_minus1 = val_reader[val_cursor..val_cursor + 6].load_be::<u8>();
-                val_cursor += 6;
-                let significant_bits = (sig_len_minus1 as u32) + 1;
-                let trailing = 64 - leading as u32 - significant_bits;
-                stored_leading = leading;
-                stored_trailing = trailing as u8;
-                (leading, significant_bits, trailing as u8)
-            };
-            let xor_bits = val_reader[val_cursor..val_cursor + significant_bits as usize].load_be::<u64>();
-            val_cursor += significant_bits as usize;
-            let xor = xor_bits << trailing;
-            let curr_bits = prev_val_bits ^ xor;
-            values.push(f64::from_bits(curr_bits));
-            prev_val_bits = curr_bits;
+        // Every row after the base costs at least one timestamp bit; checking
+        // that first keeps a corrupt `rows` from sizing the output buffers.
+        let max = max_rows(&self.ts_bits);
+        if self.rows > max {
+            return Err(DecodeError::RowCount { rows: self.rows, max });
         }
-
-        ColumnChunk { timestamps, values }
+        let timestamps = decode_delta_of_delta(self.base_ts, &self.ts_bits, self.rows, |row, bit| {
+            DecodeError::TruncatedTimestamps { row, bit }
+        })?;
+        let values = V::decode(self.base_val, &self.val_bits, self.rows)?;
+        Ok(ColumnChunk { timestamps, values })
     }
 }
 
-/// Time-bucketed index mapping bucket start timestamp to chunk id.
+/// Time-bucketed index mapping bucket start timestamp to chunk ids.
 #[derive(Debug, Default)]
 pub struct TimeBucketIndex {
-    buckets: HashMap<Timestamp, usize>,
+    buckets: HashMap<Timestamp, Vec<usize>>,
     bucket_width: Duration,
 }
 
@@ -313,7 +517,10 @@ impl TimeBucketIndex {
     /// Insert a mapping from timestamp to chunk id.
     pub fn insert(&mut self, ts: Timestamp, chunk_id: usize) {
         let bucket_start = ts - (ts % self.bucket_width.as_nanos() as i64);
-        self.buckets.insert(bucket_start, chunk_id);
+        let ids = self.buckets.entry(bucket_start).or_default();
+        if !ids.contains(&chunk_id) {
+            ids.push(chunk_id);
+        }
     }
 
     /// Locate candidate chunks for the given time range.
@@ -321,8 +528,10 @@ impl TimeBucketIndex {
         let mut ids = Vec::new();
         let mut bucket = start - (start % self.bucket_width.as_nanos() as i64);
         while bucket <= end {
-            if let Some(&id) = self.buckets.get(&bucket) {
-                ids.push(id);
+            for &id in self.buckets.get(&bucket).into_iter().flatten() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
             }
             bucket += self.bucket_width.as_nanos() as i64;
         }
@@ -330,6 +539,112 @@ impl TimeBucketIndex {
     }
 }
 
+/// On-disk persistence for compressed chunks.
+///
+/// File format: `<chunk_id:020>.chunk` holding `[bincode(CompressedChunk)][crc32c: u32 LE]`.
+pub struct ChunkStore;
+
+impl ChunkStore {
+    fn path(dir: &Path, chunk_id: usize) -> PathBuf {
+        dir.join(format!("{:020}.chunk", chunk_id))
+    }
+
+    /// Persist a compressed chunk, replacing any existing file for `chunk_id`.
+    ///
+    /// The chunk is written to a `.tmp` sibling and renamed into place, so a
+    /// crash leaves either the old file or the new one, never a torn mix.
+    pub fn write<V: ColumnValue>(dir: &Path, chunk_id: usize, chunk: &CompressedChunk<V>) -> std::io::Result<()> {
+        let mut data = bincode::serialize(chunk).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let crc = crc32c::crc32c(&data);
+        data.extend_from_slice(&crc.to_le_bytes());
+        let path = Self::path(dir, chunk_id);
+        let tmp = path.with_extension("chunk.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)?;
+        File::open(dir)?.sync_all()
+    }
+
+    /// Load a chunk, verifying its CRC footer.
+    pub fn read<V: ColumnValue>(dir: &Path, chunk_id: usize) -> std::io::Result<CompressedChunk<V>> {
+        let data = std::fs::read(Self::path(dir, chunk_id))?;
+        if data.len() < 4 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "chunk file too small"));
+        }
+        let (body, footer) = data.split_at(data.len() - 4);
+        if crc32c::crc32c(body) != u32::from_le_bytes(footer.try_into().unwrap()) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "chunk checksum mismatch"));
+        }
+        bincode::deserialize(body).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Load every chunk in `dir`, ordered by chunk id.
+    pub fn load_all<V: ColumnValue>(dir: &Path) -> std::io::Result<Vec<(usize, CompressedChunk<V>)>> {
+        let mut ids: Vec<usize> = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|ext| ext == "chunk").unwrap_or(false))
+            .filter_map(|p| p.file_stem()?.to_str()?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        ids.into_iter().map(|id| Ok((id, Self::read(dir, id)?))).collect()
+    }
+}
+
+/// Durable store of compressed chunks for one series, indexed by time bucket.
+/// Opening an existing directory reloads all chunks and rebuilds the index.
+pub struct SeriesStore<V = Value> {
+    dir: PathBuf,
+    chunks: Vec<CompressedChunk<V>>,
+    index: TimeBucketIndex,
+}
+
+impl<V: ColumnValue> SeriesStore<V> {
+    /// Open (or create) a store rooted at `dir`.
+    pub fn open(dir: impl AsRef<Path>, bucket_width: Duration) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut store = Self { dir, chunks: Vec::new(), index: TimeBucketIndex::new(bucket_width) };
+        for (id, chunk) in ChunkStore::load_all(&store.dir)? {
+            if id != store.chunks.len() {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("missing chunk {}", store.chunks.len())));
+            }
+            store.index_chunk(id, &chunk)?;
+            store.chunks.push(chunk);
+        }
+        Ok(store)
+    }
+
+    /// Compress, persist and index a chunk, returning its id.
+    pub fn append_chunk(&mut self, chunk: &ColumnChunk<V>) -> std::io::Result<usize> {
+        let id = self.chunks.len();
+        let compressed = chunk.compress();
+        ChunkStore::write(&self.dir, id, &compressed)?;
+        self.index_chunk(id, &compressed)?;
+        self.chunks.push(compressed);
+        Ok(id)
+    }
+
+    fn index_chunk(&mut self, id: usize, chunk: &CompressedChunk<V>) -> std::io::Result<()> {
+        let decoded = chunk.decompress().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        for &ts in &decoded.timestamps {
+            self.index.insert(ts, id);
+        }
+        Ok(())
+    }
+
+    /// Return all `(timestamp, value)` rows with `start <= ts <= end`, in chunk order.
+    pub fn query(&self, start: Timestamp, end: Timestamp) -> Result<Vec<(Timestamp, V)>, DecodeError> {
+        let mut rows = Vec::new();
+        for id in self.index.query(start, end) {
+            let chunk = self.chunks[id].decompress()?;
+            rows.extend(chunk.iter().filter(|&(ts, _)| ts >= start && ts <= end));
+        }
+        Ok(rows)
+    }
+}
+
 /// Continuous aggregate materializer (simple count, sum, min, max).
 #[derive(Debug, Clone)]
 pub struct ContinuousAggregate {
@@ -381,11 +696,70 @@ mod tests {
             ts += 1_000_000; // +1ms
         }
         let compressed = chunk.compress();
-        let decompressed = compressed.decompress();
+        let decompressed = compressed.decompress().unwrap();
         assert_eq!(chunk.timestamps, decompressed.timestamps);
         assert_eq!(chunk.values, decompressed.values);
     }
 
+    #[test]
+    fn truncated_timestamps_error() {
+        let mut chunk = ColumnChunk::new();
+        for i in 0..100i64 {
+            // Irregular spacing so every row emits a non-zero delta-of-delta.
+            chunk.append(i * i * 1_000, i as f64);
+        }
+        let mut compressed = chunk.compress();
+        compressed.ts_bits.truncate(compressed.ts_bits.len() / 2);
+        match compressed.decompress() {
+            Err(DecodeError::TruncatedTimestamps { row, .. }) => assert!(row > 1 && row < 100),
+            other => panic!("expected truncation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn implausible_row_count_error() {
+        let mut chunk = ColumnChunk::<bool>::new();
+        for i in 0..10i64 {
+            chunk.append(i, true);
+        }
+        let mut compressed = chunk.compress();
+        compressed.rows = usize::MAX;
+        let max = compressed.ts_bits.len() * 8 + 1;
+        assert_eq!(compressed.decompress().unwrap_err(), DecodeError::RowCount { rows: usize::MAX, max });
+        // A bit stream that runs out is still reported as truncation.
+        compressed.rows = max;
+        assert!(matches!(compressed.decompress(), Err(DecodeError::TruncatedTimestamps { .. })));
+    }
+
+    #[test]
+    fn roundtrip_i64_counter() {
+        let mut chunk = ColumnChunk::<i64>::new();
+        let mut counter = 0i64;
+        for i in 0..1000i64 {
+            counter += i % 7;
+            chunk.append(i * 1_000_000, counter);
+        }
+        let compressed = chunk.compress();
+        assert_eq!(compressed.kind(), ColumnKind::I64);
+        let decompressed = compressed.decompress().unwrap();
+        assert_eq!(chunk.timestamps, decompressed.timestamps);
+        assert_eq!(chunk.values, decompressed.values);
+    }
+
+    #[test]
+    fn roundtrip_bool_rle() {
+        let mut chunk = ColumnChunk::<bool>::new();
+        for i in 0..1000i64 {
+            chunk.append(i, (i / 100) % 2 == 0 || i == 555);
+        }
+        let compressed = chunk.compress();
+        assert_eq!(compressed.kind(), ColumnKind::Bool);
+        // 12 runs, each a 2-bit tag plus at most 12 bits.
+        assert!(compressed.val_bits.len() < 32);
+        let decompressed = compressed.decompress().unwrap();
+        assert_eq!(chunk.values, decompressed.values);
+    }
+
     #[test]
     fn bucket_index_query() {
         let mut idx = TimeBucketIndex::new(Duration::from_secs(60));
@@ -395,6 +769,55 @@ mod tests {
         assert_eq!(res, vec![1, 2]);
     }
 
+    #[test]
+    fn series_store_reload() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let width = Duration::from_secs(1);
+        let mut store = SeriesStore::open(tmp.path(), width).unwrap();
+        for c in 0..4i64 {
+            let mut chunk = ColumnChunk::new();
+            for i in 0..500i64 {
+                let ts = c * 500_000_000 + i * 1_000_000;
+                chunk.append(ts, (ts % 97) as f64 * 0.25);
+            }
+            assert_eq!(store.append_chunk(&chunk).unwrap(), c as usize);
+        }
+        let before = store.query(300_000_000, 1_700_000_000).unwrap();
+        assert_eq!(before.len(), 1401);
+        drop(store);
+
+        let reopened = SeriesStore::<f64>::open(tmp.path(), width).unwrap();
+        assert_eq!(reopened.query(300_000_000, 1_700_000_000).unwrap(), before);
+        assert_eq!(reopened.query(0, 2_000_000_000).unwrap().len(), 2000);
+    }
+
+    #[test]
+    fn chunk_store_detects_corruption() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut chunk = ColumnChunk::new();
+        chunk.append(1, 1.0);
+        chunk.append(2, 2.0);
+        ChunkStore::write(tmp.path(), 7, &chunk.compress()).unwrap();
+        let path = tmp.path().join(format!("{:020}.chunk", 7));
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(ChunkStore::read::<f64>(tmp.path(), 7).is_err());
+    }
+
+    #[test]
+    fn chunk_store_replaces_without_leftovers() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut chunk = ColumnChunk::new();
+        chunk.append(1, 1.0);
+        ChunkStore::write(tmp.path(), 0, &chunk.compress()).unwrap();
+        chunk.append(2, 2.0);
+        ChunkStore::write(tmp.path(), 0, &chunk.compress()).unwrap();
+        let names: Vec<_> = std::fs::read_dir(tmp.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec![std::ffi::OsString::from(format!("{:020}.chunk", 0))]);
+        assert_eq!(ChunkStore::read::<f64>(tmp.path(), 0).unwrap().decompress().unwrap().len(), 2);
+    }
+
     #[test]
     fn continuous_agg() {
         let mut agg = ContinuousAggregate::new(Duration::from_secs(60));
@@ -1,43 +1,158 @@
 use std::io::Result;
 use std::path::Path;
 
+#[cfg(feature = "uring")]
+use tokio_uring::fs::File;
+
+/// A file opened once for many io_uring reads and writes. Must be used on a
+/// `tokio_uring` runtime.
+#[cfg(feature = "uring")]
+pub struct UringFile {
+    file: File,
+}
+
+#[cfg(feature = "uring")]
+impl UringFile {
+    /// Open `path` for reading only.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self { file: File::open(path).await? })
+    }
+
+    /// Open `path` for reading and writing, creating it if missing.
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = tokio_uring::fs::OpenOptions::new().read(true).write(true).create(true).open(path).await?;
+        Ok(Self { file })
+    }
+
+    /// Read into `buf` from `offset`; returns the bytes read.
+    pub async fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let (res, data) = self.file.read_at(vec![0u8; buf.len()], offset).await;
+        let n = res?;
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    /// Write `data` at `offset`; returns the bytes written.
+    pub async fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize> {
+        let (res, _) = self.file.write_at(data.to_vec(), offset).await;
+        res
+    }
+
+    /// [`pread_batch`] on this file.
+    pub async fn read_batch(&self, reqs: &mut [(u64, &mut [u8])]) -> Result<Vec<usize>> {
+        pread_batch(&self.file, reqs).await
+    }
+
+    /// Flush written data to stable storage.
+    pub async fn sync(&self) -> Result<()> {
+        self.file.sync_data().await
+    }
+
+    /// Close the file, reporting any error.
+    pub async fn close(self) -> Result<()> {
+        self.file.close().await
+    }
+}
+
 /// Read a page at given offset using tokio-uring.
 #[cfg(feature = "uring")]
 pub async fn pread<P: AsRef<Path>>(path: P, offset: u64, buf: &mut [u8]) -> Result<usize> {
-    use tokio_uring::fs::File;
-    let file = File::open(path).await?;
-    let (res, _buf) = file.read_at(buf, offset).await;
-    res
+    let file = UringFile::open(path).await?;
+    let n = file.read_at(offset, buf).await?;
+    file.close().await?;
+    Ok(n)
+}
+
+/// Read into each buffer of `reqs` from its offset in `file`, submitting all
+/// reads to the ring before waiting on any. Returns the bytes read per request,
+/// in order; a short count means end of file.
+#[cfg(feature = "uring")]
+pub async fn pread_batch(file: &File, reqs: &mut [(u64, &mut [u8])]) -> Result<Vec<usize>> {
+    // The ring owns buffers while a read is in flight, so read into owned
+    // buffers and copy out afterwards.
+    let reads = reqs.iter().map(|(offset, buf)| file.read_at(vec![0u8; buf.len()], *offset));
+    let results = futures_util::future::join_all(reads).await;
+    let mut counts = Vec::with_capacity(results.len());
+    for ((res, data), (_, buf)) in results.into_iter().zip(reqs.iter_mut()) {
+        let n = res?;
+        buf[..n].copy_from_slice(&data[..n]);
+        counts.push(n);
+    }
+    Ok(counts)
 }
 
 /// Write a page at given offset using tokio-uring.
 #[cfg(feature = "uring")]
 pub async fn pwrite<P: AsRef<Path>>(path: P, offset: u64, data: &[u8]) -> Result<usize> {
-    use tokio_uring::fs::OpenOptions;
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(path)
-        .await?;
-    let (res, _) = file.write_at(data, offset).await;
-    res
+    let file = UringFile::create(path).await?;
+    let n = file.write_at(offset, data).await?;
+    file.close().await?;
+    Ok(n)
 }
 
 #[cfg(all(test, feature = "uring"))]
 mod tests {
     use super::*;
     use crate::PAGE_SIZE;
-    use std::fs;
-
-    #[tokio::test]
-    async fn uring_read_write() {
-        let path = "./test_page.bin";
-        let _ = fs::remove_file(path);
-        let write_buf = vec![42u8; PAGE_SIZE];
-        pwrite(path, 0, &write_buf).await.unwrap();
-        let mut read_buf = vec![0u8; PAGE_SIZE];
-        pread(path, 0, &mut read_buf).await.unwrap();
-        assert_eq!(write_buf, read_buf);
-        fs::remove_file(path).unwrap();
-    }
-} 
\ No newline at end of file
+
+    #[test]
+    fn uring_read_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test_page.bin");
+        tokio_uring::start(async {
+            let write_buf = vec![42u8; PAGE_SIZE];
+            pwrite(&path, 0, &write_buf).await.unwrap();
+            let mut read_buf = vec![0u8; PAGE_SIZE];
+            pread(&path, 0, &mut read_buf).await.unwrap();
+            assert_eq!(write_buf, read_buf);
+        });
+    }
+
+    #[test]
+    fn pread_batch_reads_several_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pages.bin");
+        let pages: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i + 1; PAGE_SIZE]).collect();
+        std::fs::write(&path, pages.concat()).unwrap();
+        tokio_uring::start(async {
+            let file = File::open(&path).await.unwrap();
+            let mut bufs = vec![vec![0u8; PAGE_SIZE]; 4];
+            // Out of order, plus one read past the end.
+            let mut past_end = vec![0u8; PAGE_SIZE];
+            let mut reqs: Vec<(u64, &mut [u8])> = bufs
+                .iter_mut()
+                .enumerate()
+                .rev()
+                .map(|(i, buf)| ((i * PAGE_SIZE) as u64, buf.as_mut_slice()))
+                .collect();
+            reqs.push(((4 * PAGE_SIZE) as u64, &mut past_end));
+            let counts = pread_batch(&file, &mut reqs).await.unwrap();
+            assert_eq!(counts, [PAGE_SIZE, PAGE_SIZE, PAGE_SIZE, PAGE_SIZE, 0]);
+            assert_eq!(bufs, pages);
+            file.close().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn uring_file_serves_many_ops_from_one_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pages.bin");
+        tokio_uring::start(async {
+            let file = UringFile::create(&path).await.unwrap();
+            for round in 0..8u8 {
+                for page in 0..16u64 {
+                    let data = vec![round ^ page as u8; PAGE_SIZE];
+                    assert_eq!(file.write_at(page * PAGE_SIZE as u64, &data).await.unwrap(), PAGE_SIZE);
+                }
+                let mut buf = vec![0u8; PAGE_SIZE];
+                for page in (0..16u64).rev() {
+                    assert_eq!(file.read_at(page * PAGE_SIZE as u64, &mut buf).await.unwrap(), PAGE_SIZE);
+                    assert!(buf.iter().all(|&b| b == round ^ page as u8), "round {round} page {page}");
+                }
+            }
+            file.sync().await.unwrap();
+            file.close().await.unwrap();
+        });
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 16 * PAGE_SIZE as u64);
+    }
+}
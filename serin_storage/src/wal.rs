@@ -1,5 +1,5 @@
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Write};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use time::OffsetDateTime;
@@ -39,7 +39,7 @@ impl WalWriter {
     pub fn append(&mut self, payload: &[u8]) -> std::io::Result<()> {
         let hdr = WalHeader {
             len: payload.len() as u32,
-            ts: OffsetDateTime::now_utc().unix_timestamp_nanos(),
+            ts: OffsetDateTime::now_utc().unix_timestamp_nanos() as i64,
         };
         let hdr_bytes = unsafe {
             std::slice::from_raw_parts(
@@ -96,6 +96,31 @@ pub fn iter_log<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<Vec<u8>>> {
     Ok(records)
 }
 
+/// Read every complete WAL record at `path`, truncating a torn trailing
+/// record left by a crash mid-append so later appends follow the last good one.
+pub fn recover_log<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let hdr_len = std::mem::size_of::<WalHeader>();
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while bytes.len() - pos >= hdr_len {
+        let hdr: WalHeader = unsafe { std::ptr::read_unaligned(bytes[pos..].as_ptr() as *const _) };
+        let end = pos + hdr_len + hdr.len as usize;
+        if end > bytes.len() {
+            break;
+        }
+        records.push(bytes[pos + hdr_len..end].to_vec());
+        pos = end;
+    }
+    if pos < bytes.len() {
+        file.set_len(pos as u64)?;
+        file.sync_data()?;
+    }
+    Ok(records)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +140,25 @@ mod tests {
         assert_eq!(recs, vec![b"record1".to_vec(), b"record2".to_vec()]);
         fs::remove_file(path).unwrap();
     }
+
+    #[test]
+    fn recover_log_truncates_torn_tail() {
+        let path = "./test_wal_torn.bin";
+        let _ = fs::remove_file(path);
+        {
+            let mut writer = WalWriter::open(path, 0).unwrap();
+            writer.append(b"whole").unwrap();
+            writer.append(b"torn record").unwrap();
+        }
+        let full = fs::metadata(path).unwrap().len();
+        OpenOptions::new().write(true).open(path).unwrap().set_len(full - 3).unwrap();
+        assert!(iter_log(path).is_err());
+        assert_eq!(recover_log(path).unwrap(), vec![b"whole".to_vec()]);
+        {
+            let mut writer = WalWriter::open(path, 0).unwrap();
+            writer.append(b"next").unwrap();
+        }
+        assert_eq!(iter_log(path).unwrap(), vec![b"whole".to_vec(), b"next".to_vec()]);
+        fs::remove_file(path).unwrap();
+    }
 } 
\ No newline at end of file
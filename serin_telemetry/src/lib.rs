@@ -1,33 +1,137 @@
-use anyhow::Result;
-use opentelemetry::sdk::{trace, Resource};
 use opentelemetry::KeyValue;
+use opentelemetry::trace::TraceError;
+use opentelemetry_sdk::trace::{self, Sampler};
+use opentelemetry_sdk::Resource;
 use opentelemetry_otlp::WithExportConfig;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use thiserror::Error;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer, Registry};
 
-/// Initialize OpenTelemetry OTLP exporter and tracing subscriber.
-/// Must be called once at application startup.
-pub fn init(service_name: &str) -> Result<()> {
-    let endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_else(|_| "http://localhost:4317".into());
+/// Layer over a plain [`Registry`], so it can be combined with other layers
+/// (e.g. `serin_log`'s) in a single subscriber.
+pub type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-    // Build OTLP exporter pipeline.
+/// Failure to set up tracing.
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    /// A global tracing subscriber is already installed, by an earlier
+    /// [`init`] or another logging setup.
+    #[error("a global tracing subscriber is already initialized")]
+    AlreadyInitialized,
+    /// The trace sample ratio is not within `[0, 1]`.
+    #[error("trace sample ratio {0} is outside [0, 1]")]
+    InvalidSampleRatio(f64),
+    /// The OTLP exporter pipeline could not be built.
+    #[error("OTLP pipeline: {0}")]
+    Pipeline(#[from] TraceError),
+}
+
+/// Flushes and shuts down the OTLP pipeline when dropped. Keep it alive for
+/// as long as spans should be exported.
+#[must_use = "dropping the guard shuts telemetry down"]
+pub struct TelemetryGuard {
+    otlp: bool,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if self.otlp {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Environment variable holding the default trace sample ratio.
+pub const SAMPLE_RATIO_ENV: &str = "OTEL_TRACES_SAMPLER_ARG";
+
+/// OTLP export layer for the endpoint in `OTEL_EXPORTER_OTLP_ENDPOINT`, or
+/// `None` when that is unset. The batch exporter is spawned onto the current
+/// Tokio runtime, so this must be called from within one.
+///
+/// `sample_ratio` is the fraction of traces exported; `None` reads
+/// [`SAMPLE_RATIO_ENV`], sampling everything when that is unset too.
+pub fn layer(service_name: &str, sample_ratio: Option<f64>) -> Result<Option<(BoxedLayer, TelemetryGuard)>, TelemetryError> {
+    let sample_ratio = match sample_ratio {
+        Some(ratio) => ratio,
+        None => match std::env::var(SAMPLE_RATIO_ENV) {
+            // An unparsable value is reported like an out-of-range one.
+            Ok(arg) => arg.trim().parse().unwrap_or(f64::NAN),
+            Err(_) => 1.0,
+        },
+    };
+    let trace_config = trace_config(service_name, sample_ratio)?;
+    let Ok(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+        return Ok(None);
+    };
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
-        .with_export_config(opentelemetry_otlp::ExportConfig { endpoint, ..Default::default() })
-        .with_trace_config(trace::config().with_resource(Resource::new(vec![KeyValue::new("service.name", service_name.to_string())])))
-        .install_batch(opentelemetry::runtime::Tokio)?;
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_export_config(opentelemetry_otlp::ExportConfig { endpoint, ..Default::default() }),
+        )
+        .with_trace_config(trace_config)
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    Ok(Some((tracing_opentelemetry::layer().with_tracer(tracer).boxed(), TelemetryGuard { otlp: true })))
+}
 
-    // Build tracing subscriber with OTLP layer + stdout.
-    let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
-    let fmt_layer = tracing_subscriber::fmt::layer().with_target(false);
+/// Install a global tracing subscriber of its own: stdout filtered by
+/// `RUST_LOG`, plus the OTLP [`layer`] when an endpoint is configured. Must
+/// be called from within a Tokio runtime. Processes with their own logging
+/// setup add [`layer`] to it instead.
+pub fn init(service_name: &str, sample_ratio: Option<f64>) -> Result<TelemetryGuard, TelemetryError> {
+    // Checked up front so a second call does not build a pipeline first.
+    if tracing::dispatcher::has_been_set() {
+        return Err(TelemetryError::AlreadyInitialized);
+    }
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .boxed();
+    let (otlp, guard) = match layer(service_name, sample_ratio)? {
+        Some((otlp, guard)) => (Some(otlp), guard),
+        None => (None, TelemetryGuard { otlp: false }),
+    };
+    // The guard owns the pipeline from here on, so a failure below still
+    // shuts it down.
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::from_default_env())
-        .with(otel_layer)
-        .with(fmt_layer)
-        .try_init()?;
-    Ok(())
+        .with(std::iter::once(fmt_layer).chain(otlp).collect::<Vec<_>>())
+        .try_init()
+        .map_err(|_| TelemetryError::AlreadyInitialized)?;
+    Ok(guard)
+}
+
+/// Trace config tagging spans with `service_name` and keeping a
+/// `sample_ratio` share of traces, chosen by trace ID.
+fn trace_config(service_name: &str, sample_ratio: f64) -> Result<trace::Config, TelemetryError> {
+    if !(0.0..=1.0).contains(&sample_ratio) {
+        return Err(TelemetryError::InvalidSampleRatio(sample_ratio));
+    }
+    Ok(trace::config()
+        .with_sampler(Sampler::TraceIdRatioBased(sample_ratio))
+        .with_resource(Resource::new(vec![KeyValue::new("service.name", service_name.to_string())])))
 }
 
-/// Shutdown OTLP pipeline gracefully.
-pub fn shutdown() {
-    opentelemetry::global::shutdown_tracer_provider();
-} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_init_reports_already_initialized() {
+        if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some() {
+            return;
+        }
+        let _guard = init("serin-test", Some(1.0)).unwrap();
+        assert!(matches!(init("serin-test", Some(1.0)), Err(TelemetryError::AlreadyInitialized)));
+    }
+
+    #[test]
+    fn sample_ratio_must_be_a_fraction() {
+        for ratio in [0.0, 1.0] {
+            let config = trace_config("serin-test", ratio).unwrap();
+            let _provider = trace::TracerProvider::builder().with_config(config).build();
+        }
+        for ratio in [-0.1, 1.5, f64::NAN] {
+            assert!(matches!(trace_config("serin-test", ratio), Err(TelemetryError::InvalidSampleRatio(_))));
+        }
+    }
+}
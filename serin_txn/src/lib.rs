@@ -4,12 +4,49 @@
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-/// Global monotonically increasing timestamp generator (single node MVP).
-static GLOBAL_TS: AtomicU64 = AtomicU64::new(1);
+/// Source of monotonically increasing commit timestamps.
+pub trait TimestampOracle: Send + Sync {
+    /// Allocate the next timestamp; never returns a value handed out before.
+    fn next_ts(&self) -> u64;
+}
+
+/// In-process oracle backed by an atomic counter.
+#[derive(Debug)]
+pub struct AtomicOracle {
+    counter: AtomicU64,
+}
+
+impl AtomicOracle {
+    /// Oracle whose first timestamp is 1.
+    pub const fn new() -> Self {
+        Self::starting_at(1)
+    }
+
+    /// Oracle whose first timestamp is `start`.
+    pub const fn starting_at(start: u64) -> Self {
+        Self { counter: AtomicU64::new(start) }
+    }
+}
+
+impl Default for AtomicOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-/// Generate next commit timestamp.
+impl TimestampOracle for AtomicOracle {
+    fn next_ts(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+/// Process-wide oracle behind [`next_ts`] (single node MVP).
+static GLOBAL_ORACLE: AtomicOracle = AtomicOracle::new();
+
+/// Generate next commit timestamp from the process-wide oracle.
+/// Prefer injecting a [`TimestampOracle`].
 pub fn next_ts() -> u64 {
-    GLOBAL_TS.fetch_add(1, Ordering::SeqCst)
+    GLOBAL_ORACLE.next_ts()
 }
 
 /// A record version stored in MVCC storage.
@@ -33,14 +70,93 @@ impl<T> VersionedTuple<T> {
         }
     }
 
+    /// Create a committed tuple stamped with the next timestamp from `oracle`.
+    pub fn commit_with(value: T, oracle: &dyn TimestampOracle) -> Self {
+        Self::new_committed(value, oracle.next_ts())
+    }
+
     /// Check visibility for snapshot at given timestamp.
     pub fn visible_at(&self, snap_ts: u64) -> bool {
         self.min_ts <= snap_ts && snap_ts < self.max_ts
     }
 }
 
+/// A version committed before the latest version of its chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("version at ts {ts} is older than the latest version at ts {latest}")]
+pub struct OutOfOrderVersion {
+    /// Commit timestamp of the rejected version.
+    pub ts: u64,
+    /// Commit timestamp of the chain's latest version.
+    pub latest: u64,
+}
+
+/// All versions of one logical record, ordered oldest to newest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VersionChain<T> {
+    versions: Vec<VersionedTuple<T>>,
+}
+
+impl<T> VersionChain<T> {
+    /// Create an empty chain.
+    pub fn new() -> Self {
+        Self { versions: Vec::new() }
+    }
+
+    /// Append a version committed at `ts`, closing the previous latest version at `ts`.
+    /// Versions must arrive in commit order; an older `ts` leaves the chain unchanged.
+    pub fn insert_version(&mut self, value: T, ts: u64) -> Result<(), OutOfOrderVersion> {
+        if let Some(latest) = self.versions.last_mut() {
+            if ts < latest.min_ts {
+                return Err(OutOfOrderVersion { ts, latest: latest.min_ts });
+            }
+            if latest.max_ts == u64::MAX {
+                latest.max_ts = ts;
+            }
+        }
+        self.versions.push(VersionedTuple::new_committed(value, ts));
+        Ok(())
+    }
+
+    /// Commit a new version at the next timestamp from `oracle`, returning that timestamp.
+    pub fn commit(&mut self, value: T, oracle: &dyn TimestampOracle) -> Result<u64, OutOfOrderVersion> {
+        let ts = oracle.next_ts();
+        self.insert_version(value, ts)?;
+        Ok(ts)
+    }
+
+    /// Newest version visible to a snapshot at `snap_ts`.
+    pub fn visible_at(&self, snap_ts: u64) -> Option<&VersionedTuple<T>> {
+        self.versions.iter().rev().find(|v| v.visible_at(snap_ts))
+    }
+
+    /// Drop versions invisible to every snapshot at or after `oldest_active_ts`
+    /// (those with `max_ts <= oldest_active_ts`), always keeping the latest version.
+    /// Returns the number of versions removed.
+    pub fn gc(&mut self, oldest_active_ts: u64) -> usize {
+        let Some(last) = self.versions.len().checked_sub(1) else { return 0 };
+        let dead = self.versions[..last].iter().take_while(|v| v.max_ts <= oldest_active_ts).count();
+        self.versions.drain(..dead);
+        dead
+    }
+
+    /// Number of retained versions.
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// Whether the chain holds no versions.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+}
+
+/// Hierarchical lock manager with deadlock detection.
 pub mod lock;
+/// Global transaction manager (timestamp oracle).
 pub mod gtm;
+/// Two-phase commit transaction manager with WAL-backed prepare records.
+pub mod txn;
 
 #[cfg(test)]
 mod tests {
@@ -48,10 +164,45 @@ mod tests {
 
     #[test]
     fn mvcc_visibility() {
-        let ts1 = next_ts();
-        let rec = VersionedTuple::new_committed(10, ts1);
-        assert!(rec.visible_at(ts1));
-        let ts2 = next_ts();
+        let oracle = AtomicOracle::new();
+        let rec = VersionedTuple::commit_with(10, &oracle);
+        assert!(rec.visible_at(rec.min_ts));
+        let ts2 = oracle.next_ts();
         assert!(rec.visible_at(ts2));
     }
+
+    #[test]
+    fn independent_oracles_do_not_interfere() {
+        let a = AtomicOracle::new();
+        let b = AtomicOracle::starting_at(1_000);
+        let mut chain = VersionChain::new();
+        let seq_a: Vec<u64> = (0..3).map(|i| chain.commit(i, &a).unwrap()).collect();
+        let seq_b: Vec<u64> = (0..3).map(|_| b.next_ts()).collect();
+        // Each oracle advances only on its own calls.
+        assert_eq!(seq_a, [1, 2, 3]);
+        assert_eq!(seq_b, [1_000, 1_001, 1_002]);
+        assert_eq!(chain.visible_at(2).unwrap().value, 1);
+    }
+
+    #[test]
+    fn version_chain_gc_keeps_live_versions() {
+        let mut chain = VersionChain::new();
+        for (value, ts) in [("a", 10), ("b", 20), ("c", 30), ("d", 40)] {
+            chain.insert_version(value, ts).unwrap();
+        }
+        assert_eq!(chain.insert_version("late", 35), Err(OutOfOrderVersion { ts: 35, latest: 40 }));
+        assert_eq!(chain.len(), 4);
+        assert_eq!(chain.visible_at(25).unwrap().value, "b");
+        assert!(chain.visible_at(5).is_none());
+
+        // A snapshot at 25 still needs "b"; only "a" (dead at 20) can go.
+        assert_eq!(chain.gc(25), 1);
+        assert_eq!(chain.visible_at(25).unwrap().value, "b");
+        assert_eq!(chain.len(), 3);
+
+        // Everything is superseded by 100, but the latest version stays.
+        assert_eq!(chain.gc(100), 2);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain.visible_at(100).unwrap().value, "d");
+    }
 } 
\ No newline at end of file
@@ -1,11 +1,11 @@
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::sync::{Arc, Mutex};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::next_ts;
-
 /// Transaction identifier.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TxnId(pub u64);
 
 /// Lock modes (hierarchical).
@@ -22,15 +22,26 @@ pub enum LockMode {
 }
 
 impl LockMode {
-    /// Check compatibility between two lock modes.
+    /// Check compatibility between two lock modes (symmetric).
+    ///
+    /// |    | IS | IX | S  | X  |
+    /// |----|----|----|----|----|
+    /// | IS | ✓  | ✓  | ✓  |    |
+    /// | IX | ✓  | ✓  |    |    |
+    /// | S  | ✓  |    | ✓  |    |
+    /// | X  |    |    |    |    |
     pub fn compatible(self, other: Self) -> bool {
         use LockMode::*;
-        matches!((self, other),
-            (IS, IS) | (IS, S) | (S, IS) | (IX, IX) if false) // fallback
-        || match (self, other) {
-            (IS, IS) | (IS, S) | (S, IS) | (S, S) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (IS, IS) | (IS, IX) | (IS, S) | (IX, IS) | (IX, IX) | (S, IS) | (S, S)
+        )
+    }
+
+    /// Whether holding `self` already grants everything `other` would.
+    pub fn covers(self, other: Self) -> bool {
+        use LockMode::*;
+        self == other || matches!((self, other), (X, _) | (S, IS) | (IX, IS))
     }
 }
 
@@ -41,63 +52,190 @@ struct LockEntry {
     waiting: VecDeque<(TxnId, LockMode)>,
 }
 
-/// Deadlock error.
-#[derive(Debug, Error)]
-#[error("deadlock detected for txn {0:?}")]
-pub struct DeadlockError(pub TxnId);
+impl LockEntry {
+    /// Whether `txn` could hold `mode` alongside the current holders.
+    fn grantable(&self, txn: TxnId, mode: LockMode) -> bool {
+        self.granted.iter().all(|&(t, m)| t == txn || m.compatible(mode))
+    }
+
+    /// Move compatible waiters from the front of the queue into `granted`,
+    /// stopping at the first conflict to preserve FIFO order. Returns true if any were granted.
+    fn grant_waiters(&mut self) -> bool {
+        let mut any = false;
+        while let Some(&(txn, mode)) = self.waiting.front() {
+            if !self.grantable(txn, mode) {
+                break;
+            }
+            self.waiting.pop_front();
+            self.grant(txn, mode);
+            any = true;
+        }
+        any
+    }
+
+    /// Record `mode` as held by `txn`; an exclusive grant subsumes the txn's other holds.
+    fn grant(&mut self, txn: TxnId, mode: LockMode) {
+        if mode == LockMode::X {
+            self.granted.retain(|&(t, _)| t != txn);
+        }
+        self.granted.push((txn, mode));
+    }
+
+    fn holds(&self, txn: TxnId) -> bool {
+        self.granted.iter().any(|&(t, _)| t == txn)
+    }
+
+    /// Whether one of `txn`'s current holds already covers `mode`.
+    fn covered(&self, txn: TxnId, mode: LockMode) -> bool {
+        self.granted.iter().any(|&(t, m)| t == txn && m.covers(mode))
+    }
+}
+
+/// Reasons a lock request can fail.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LockError {
+    /// Waiting would close a cycle in the wait-for graph; the txn should abort.
+    #[error("deadlock detected for txn {0:?}")]
+    Deadlock(TxnId),
+    /// The lock was not granted before the deadline.
+    #[error("lock wait timeout for txn {0:?}")]
+    Timeout(TxnId),
+}
 
 /// Simple lock manager with Wait-For Graph deadlock detection.
+/// Conflicting requests block the calling thread until granted in FIFO order.
 #[derive(Default)]
 pub struct LockManager {
     table: Mutex<HashMap<String, LockEntry>>, // resource-id -> entry
+    /// Signalled whenever waiters are moved into a granted set.
+    wakeup: Condvar,
 }
 
 impl LockManager {
-    /// Acquire a lock, blocking other incompatible holders.
-    pub fn lock(&self, txn: TxnId, res: &str, mode: LockMode) -> Result<(), DeadlockError> {
+    /// Acquire a lock, blocking until no incompatible holder or earlier waiter remains.
+    /// Fails with [`LockError::Deadlock`] if waiting would close a cycle in the wait-for graph.
+    pub fn lock(&self, txn: TxnId, res: &str, mode: LockMode) -> Result<(), LockError> {
+        self.acquire(txn, res, mode, None)
+    }
+
+    /// Like [`LockManager::lock`], but gives up with [`LockError::Timeout`] if the
+    /// lock is not granted within `timeout`, removing the request from the wait queue.
+    pub fn lock_timeout(&self, txn: TxnId, res: &str, mode: LockMode, timeout: Duration) -> Result<(), LockError> {
+        self.acquire(txn, res, mode, Some(timeout))
+    }
+
+    /// A request already covered by `txn`'s hold succeeds at once. Requests from
+    /// current holders are never held up by later waiters: they are granted if the
+    /// other holders allow it, and otherwise queue ahead of ordinary waiters.
+    fn acquire(&self, txn: TxnId, res: &str, mode: LockMode, timeout: Option<Duration>) -> Result<(), LockError> {
         let mut tbl = self.table.lock().unwrap();
         let entry = tbl.entry(res.to_string()).or_default();
-        if entry.granted.iter().all(|&(_, m)| m.compatible(mode)) {
-            entry.granted.push((txn, mode));
+        if entry.covered(txn, mode) {
+            return Ok(());
+        }
+        let holder = entry.holds(txn);
+        if (holder || entry.waiting.is_empty()) && entry.grantable(txn, mode) {
+            entry.grant(txn, mode);
             return Ok(());
         }
-        entry.waiting.push_back((txn, mode));
-        drop(tbl);
-        // Deadlock detection simplified: if txn waits on itself via graph size > 5 detect.
-        if self.detect_deadlock(txn) {
-            self.unlock_wait(txn, res);
-            return Err(DeadlockError(txn));
+        let pos = if holder {
+            entry.waiting.iter().take_while(|&&(t, _)| entry.holds(t)).count()
+        } else {
+            entry.waiting.len()
+        };
+        entry.waiting.insert(pos, (txn, mode));
+        self.wait(tbl, txn, res, timeout)
+    }
+
+    /// Promote `txn`'s shared hold on `res` to exclusive. Succeeds immediately when
+    /// no other transaction holds `res`; otherwise waits ahead of ordinary waiters
+    /// (behind earlier upgraders). Two holders upgrading at once deadlock, and the
+    /// later one fails with [`LockError::Deadlock`].
+    pub fn upgrade(&self, txn: TxnId, res: &str) -> Result<(), LockError> {
+        self.lock(txn, res, LockMode::X)
+    }
+
+    /// Block `txn`, already queued on `res`, until it is granted, `timeout`
+    /// elapses, or waiting would close a cycle in the wait-for graph.
+    fn wait(
+        &self,
+        mut tbl: MutexGuard<'_, HashMap<String, LockEntry>>,
+        txn: TxnId,
+        res: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), LockError> {
+        if Self::detect_deadlock(&tbl, txn) {
+            self.dequeue(&mut tbl, txn, res);
+            return Err(LockError::Deadlock(txn));
+        }
+        let queued = |tbl: &mut HashMap<String, LockEntry>| tbl[res].waiting.iter().any(|&(t, _)| t == txn);
+        match timeout {
+            None => {
+                let _tbl = self.wakeup.wait_while(tbl, queued).unwrap();
+            }
+            Some(timeout) => {
+                let (mut tbl, result) = self.wakeup.wait_timeout_while(tbl, timeout, queued).unwrap();
+                if result.timed_out() {
+                    self.dequeue(&mut tbl, txn, res);
+                    return Err(LockError::Timeout(txn));
+                }
+            }
         }
         Ok(())
     }
 
-    /// Release all locks held by txn.
+    /// Drop `txn`'s pending request on `res`; waiters queued behind it may now be grantable.
+    fn dequeue(&self, tbl: &mut HashMap<String, LockEntry>, txn: TxnId, res: &str) {
+        let entry = tbl.get_mut(res).unwrap();
+        entry.waiting.retain(|&(t, _)| t != txn);
+        if entry.grant_waiters() {
+            self.wakeup.notify_all();
+        }
+    }
+
+    /// Release all locks held by txn and wake waiters that become grantable.
     pub fn release_all(&self, txn: TxnId) {
         let mut tbl = self.table.lock().unwrap();
+        let mut woke = false;
         for entry in tbl.values_mut() {
             entry.granted.retain(|&(t, _)| t != txn);
             entry.waiting.retain(|&(t, _)| t != txn);
+            woke |= entry.grant_waiters();
         }
-    }
-
-    fn unlock_wait(&self, txn: TxnId, res: &str) {
-        let mut tbl = self.table.lock().unwrap();
-        if let Some(entry) = tbl.get_mut(res) {
-            entry.waiting.retain(|&(t, _)| t != txn);
+        if woke {
+            self.wakeup.notify_all();
         }
     }
 
-    /// Very naive Wait-For Graph cycle detection.
-    fn detect_deadlock(&self, start: TxnId) -> bool {
+    /// Resources on which txn currently holds a lock.
+    pub fn held_by(&self, txn: TxnId) -> Vec<String> {
         let tbl = self.table.lock().unwrap();
+        tbl.iter().filter(|(_, e)| e.granted.iter().any(|&(t, _)| t == txn)).map(|(res, _)| res.clone()).collect()
+    }
+
+    /// Number of requests queued on `res`.
+    #[cfg(test)]
+    pub(crate) fn queued(&self, res: &str) -> usize {
+        self.table.lock().unwrap().get(res).map_or(0, |e| e.waiting.len())
+    }
+
+    /// Wait-For Graph cycle detection starting from `start`. A waiter waits for
+    /// every conflicting holder and every conflicting request queued ahead of it;
+    /// a waiter that already holds the resource only waits for other holders.
+    fn detect_deadlock(tbl: &HashMap<String, LockEntry>, start: TxnId) -> bool {
         let mut graph: HashMap<TxnId, HashSet<TxnId>> = HashMap::new();
         for entry in tbl.values() {
-            if let Some(&(front_txn, _)) = entry.waiting.front() {
-                let holders: HashSet<TxnId> = entry.granted.iter().map(|&(t, _)| t).collect();
-                graph.entry(front_txn).or_default().extend(holders);
+            for (i, &(waiter, mode)) in entry.waiting.iter().enumerate() {
+                let holder = entry.holds(waiter);
+                let blockers = entry
+                    .granted
+                    .iter()
+                    .chain(entry.waiting.iter().take(i).filter(|&&(t, _)| !holder || entry.holds(t)))
+                    .filter(|&&(t, m)| t != waiter && !m.compatible(mode))
+                    .map(|&(t, _)| t);
+                graph.entry(waiter).or_default().extend(blockers);
             }
         }
-        drop(tbl);
         // BFS to find cycle to start.
         let mut queue = VecDeque::new();
         let mut visited = HashSet::new();
@@ -122,17 +260,143 @@ impl LockManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Spin until `res` has `n` queued waiters.
+    fn wait_for_queue(lm: &LockManager, res: &str, n: usize) {
+        while lm.table.lock().unwrap().get(res).map_or(0, |e| e.waiting.len()) < n {
+            thread::yield_now();
+        }
+    }
 
     #[test]
     fn lock_grant_and_deadlock() {
-        let lm = LockManager::default();
+        let lm = Arc::new(LockManager::default());
         let t1 = TxnId(1);
         let t2 = TxnId(2);
         lm.lock(t1, "r1", LockMode::S).unwrap();
         assert!(lm.lock(t2, "r1", LockMode::S).is_ok()); // compatible
-        // Deadlock detection path simple simulation
-        lm.lock(t1, "r2", LockMode::X).unwrap();
-        let res = lm.lock(t2, "r2", LockMode::X);
-        assert!(res.is_err());
+        lm.lock(t2, "r2", LockMode::X).unwrap();
+        // t1 waits for t2 on r2 ...
+        let waiter = {
+            let lm = lm.clone();
+            thread::spawn(move || lm.lock(t1, "r2", LockMode::X))
+        };
+        wait_for_queue(&lm, "r2", 1);
+        // ... so t2 waiting for t1 on r1 closes the cycle.
+        assert!(lm.lock(t2, "r1", LockMode::X).is_err());
+        lm.release_all(t2);
+        assert!(waiter.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn conflicting_lock_waits_for_release() {
+        let lm = Arc::new(LockManager::default());
+        lm.lock(TxnId(1), "r", LockMode::X).unwrap();
+        let acquired = Arc::new(AtomicBool::new(false));
+        let waiter = {
+            let (lm, acquired) = (lm.clone(), acquired.clone());
+            thread::spawn(move || {
+                lm.lock(TxnId(2), "r", LockMode::X).unwrap();
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+        wait_for_queue(&lm, "r", 1);
+        thread::sleep(Duration::from_millis(50));
+        assert!(!acquired.load(Ordering::SeqCst));
+        lm.release_all(TxnId(1));
+        waiter.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn upgrade_shared_to_exclusive() {
+        let lm = Arc::new(LockManager::default());
+        let (t1, t2) = (TxnId(1), TxnId(2));
+        lm.lock(t1, "solo", LockMode::S).unwrap();
+        lm.upgrade(t1, "solo").unwrap();
+        assert_eq!(lm.table.lock().unwrap()["solo"].granted, vec![(t1, LockMode::X)]);
+
+        lm.lock(t1, "r", LockMode::S).unwrap();
+        lm.lock(t2, "r", LockMode::S).unwrap();
+        let first = {
+            let lm = lm.clone();
+            thread::spawn(move || lm.upgrade(t1, "r"))
+        };
+        wait_for_queue(&lm, "r", 1);
+        // Both hold S and want X: the second upgrader is the victim.
+        assert!(lm.upgrade(t2, "r").is_err());
+        lm.release_all(t2);
+        assert!(first.join().unwrap().is_ok());
+        assert_eq!(lm.table.lock().unwrap()["r"].granted, vec![(t1, LockMode::X)]);
+    }
+
+    #[test]
+    fn holders_are_not_blocked_by_queued_waiters() {
+        let lm = Arc::new(LockManager::default());
+        let (t1, t2, t3) = (TxnId(1), TxnId(2), TxnId(3));
+        lm.lock(t1, "x", LockMode::X).unwrap();
+        let queued = {
+            let lm = lm.clone();
+            thread::spawn(move || lm.lock(t2, "x", LockMode::S))
+        };
+        wait_for_queue(&lm, "x", 1);
+        // Re-requests covered by the exclusive hold succeed at once.
+        assert_eq!(lm.lock(t1, "x", LockMode::X), Ok(()));
+        assert_eq!(lm.lock_timeout(t1, "x", LockMode::S, Duration::from_millis(30)), Ok(()));
+        assert_eq!(lm.table.lock().unwrap()["x"].granted, vec![(t1, LockMode::X)]);
+        lm.release_all(t1);
+        assert!(queued.join().unwrap().is_ok());
+
+        // A stronger mode is granted past the queue when no other holder conflicts.
+        lm.lock(t1, "is", LockMode::IS).unwrap();
+        lm.lock(t3, "is", LockMode::IS).unwrap();
+        let queued = {
+            let lm = lm.clone();
+            thread::spawn(move || lm.lock(t2, "is", LockMode::X))
+        };
+        wait_for_queue(&lm, "is", 1);
+        assert_eq!(lm.lock_timeout(t1, "is", LockMode::S, Duration::from_millis(30)), Ok(()));
+
+        // Waiting on another holder queues ahead of the waiter without a false deadlock.
+        let upgrade = {
+            let lm = lm.clone();
+            thread::spawn(move || lm.lock(t3, "is", LockMode::IX))
+        };
+        wait_for_queue(&lm, "is", 2);
+        assert_eq!(lm.table.lock().unwrap()["is"].waiting.front(), Some(&(t3, LockMode::IX)));
+        lm.release_all(t1);
+        assert!(upgrade.join().unwrap().is_ok());
+        lm.release_all(t3);
+        assert!(queued.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn lock_timeout_removes_waiter() {
+        let lm = LockManager::default();
+        lm.lock(TxnId(1), "r", LockMode::X).unwrap();
+        let res = lm.lock_timeout(TxnId(2), "r", LockMode::S, Duration::from_millis(30));
+        assert_eq!(res, Err(LockError::Timeout(TxnId(2))));
+        assert!(lm.table.lock().unwrap()["r"].waiting.is_empty());
+    }
+
+    #[test]
+    fn compatibility_matrix() {
+        use LockMode::*;
+        let modes = [IS, IX, S, X];
+        // Rows/columns in `modes` order.
+        let expected = [
+            [true, true, true, false],
+            [true, true, false, false],
+            [true, false, true, false],
+            [false, false, false, false],
+        ];
+        for (i, &a) in modes.iter().enumerate() {
+            for (j, &b) in modes.iter().enumerate() {
+                assert_eq!(a.compatible(b), expected[i][j], "{a:?} vs {b:?}");
+            }
+        }
     }
 } 
\ No newline at end of file
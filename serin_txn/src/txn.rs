@@ -1,10 +1,15 @@
 use crate::gtm::Gtm;
-use crate::lock::{LockManager, LockMode, TxnId};
-use crate::VersionedTuple;
+use crate::lock::{LockError, LockManager, LockMode, TxnId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serin_storage::wal::{recover_log, WalWriter};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// Records the log may hold before it is worth compacting.
+const COMPACT_MIN_RECORDS: usize = 64;
+
 /// Transaction status.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TxnStatus {
@@ -21,8 +26,95 @@ pub enum TxnStatus {
 /// Prepare log entry persisted to WAL.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrepareRecord {
+    /// Prepared transaction.
     pub txn_id: TxnId,
+    /// Commit timestamp assigned at prepare.
     pub commit_ts: u64,
+    /// Participants that must acknowledge the decision.
+    #[serde(default)]
+    pub participants: Vec<String>,
+}
+
+/// Record in the transaction log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum LogRecord {
+    Prepare(PrepareRecord),
+    /// Outcome of a prepared txn, logged before participants are told.
+    Decision { txn_id: TxnId, committed: bool },
+}
+
+/// Prepared txn still needed by recovery: undecided, or decided but not yet
+/// acknowledged by every participant.
+#[derive(Debug)]
+struct LiveTxn {
+    prepare: PrepareRecord,
+    committed: Option<bool>,
+    unacked: BTreeSet<String>,
+}
+
+/// Transaction log plus the txns that keep its records alive. Once most of
+/// its records belong to finished txns, the log is rewritten without them.
+#[derive(Debug)]
+struct TxnLog {
+    path: PathBuf,
+    wal: WalWriter,
+    live: HashMap<TxnId, LiveTxn>,
+    /// Records in the file, live or not.
+    records: usize,
+}
+
+impl TxnLog {
+    fn append(&mut self, rec: &LogRecord) -> io::Result<()> {
+        let payload = bincode::serialize(rec).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.wal.append(&payload)?;
+        self.wal.flush()?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Drop `txn` if it is decided and fully acknowledged. The log is
+    /// compacted once it holds at least [`COMPACT_MIN_RECORDS`] records and
+    /// at least half of them are dead, so rewrites stay amortized.
+    fn finish_if_done(&mut self, txn: TxnId) -> io::Result<()> {
+        if !self.live.get(&txn).is_some_and(|t| t.committed.is_some() && t.unacked.is_empty()) {
+            return Ok(());
+        }
+        self.live.remove(&txn);
+        let live_records: usize = self.live.values().map(|t| 1 + usize::from(t.committed.is_some())).sum();
+        if self.records >= COMPACT_MIN_RECORDS && self.records >= 2 * live_records {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the log with only the records of live txns.
+    fn compact(&mut self) -> io::Result<()> {
+        let tmp = self.path.with_extension("compact");
+        let _ = std::fs::remove_file(&tmp);
+        let mut records = 0;
+        {
+            let mut wal = WalWriter::open(&tmp, 0)?;
+            for t in self.live.values() {
+                let mut prepare = t.prepare.clone();
+                prepare.participants = t.unacked.iter().cloned().collect();
+                for rec in std::iter::once(LogRecord::Prepare(prepare))
+                    .chain(t.committed.map(|committed| LogRecord::Decision { txn_id: t.prepare.txn_id, committed }))
+                {
+                    let payload = bincode::serialize(&rec).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    wal.append(&payload)?;
+                    records += 1;
+                }
+            }
+            wal.flush()?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        // Make the rename itself durable.
+        let dir = self.path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        std::fs::File::open(dir)?.sync_all()?;
+        self.wal = WalWriter::open(&self.path, 0)?;
+        self.records = records;
+        Ok(())
+    }
 }
 
 /// Simple transaction manager supporting single-node 2PC.
@@ -30,6 +122,8 @@ pub struct TxnManager {
     gtm: Gtm,
     lock_mgr: Arc<LockManager>,
     statuses: Mutex<HashMap<TxnId, TxnStatus>>, // for test only
+    participants: Mutex<HashMap<TxnId, BTreeSet<String>>>,
+    log: Option<Mutex<TxnLog>>,
 }
 
 impl Default for TxnManager {
@@ -38,11 +132,24 @@ impl Default for TxnManager {
             gtm: Gtm::default(),
             lock_mgr: Arc::new(LockManager::default()),
             statuses: Mutex::new(HashMap::new()),
+            participants: Mutex::new(HashMap::new()),
+            log: None,
         }
     }
 }
 
 impl TxnManager {
+    /// Manager logging prepare and decision records to the WAL at `path`,
+    /// recovering the transactions still live there.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut tm = Self::default();
+        let path = path.as_ref().to_path_buf();
+        let (live, records) = tm.recover(&path)?;
+        let wal = WalWriter::open(&path, 0)?;
+        tm.log = Some(Mutex::new(TxnLog { path, wal, live, records }));
+        Ok(tm)
+    }
+
     /// Begin a new transaction, returning its id.
     pub fn begin(&self) -> TxnId {
         let id = TxnId(self.gtm.alloc());
@@ -51,29 +158,133 @@ impl TxnManager {
     }
 
     /// Acquire exclusive lock on resource (table-level for MVP).
+    /// A deadlock victim is aborted before this returns `false`.
     pub fn lock_x(&self, txn: TxnId, res: &str) -> bool {
-        self.lock_mgr.lock(txn, res, LockMode::X).is_ok()
+        match self.lock_mgr.lock(txn, res, LockMode::X) {
+            Ok(()) => true,
+            Err(LockError::Deadlock(_)) => {
+                // Still acquiring locks, so not prepared: nothing to log.
+                self.finish(txn, TxnStatus::Aborted);
+                false
+            }
+            Err(LockError::Timeout(_)) => false,
+        }
+    }
+
+    /// Register `participant` as taking part in `txn`; it must acknowledge
+    /// the decision before the txn can leave the log.
+    pub fn enlist(&self, txn: TxnId, participant: &str) {
+        self.participants.lock().unwrap().entry(txn).or_default().insert(participant.to_string());
     }
 
-    /// Prepare phase – persists PrepareRecord (mock: return struct).
-    pub fn prepare(&self, txn: TxnId) -> PrepareRecord {
-        let ts = self.gtm.alloc();
+    /// Prepare phase – the PrepareRecord is durable in the WAL (if any) on return.
+    pub fn prepare(&self, txn: TxnId) -> io::Result<PrepareRecord> {
+        let participants = self.participants.lock().unwrap().remove(&txn).unwrap_or_default();
+        let rec = PrepareRecord { txn_id: txn, commit_ts: self.gtm.alloc(), participants: participants.iter().cloned().collect() };
+        if let Some(log) = &self.log {
+            let mut log = log.lock().unwrap();
+            log.append(&LogRecord::Prepare(rec.clone()))?;
+            log.live.insert(txn, LiveTxn { prepare: rec.clone(), committed: None, unacked: participants });
+        }
         self.statuses.lock().unwrap().insert(txn, TxnStatus::Prepared);
-        PrepareRecord { txn_id: txn, commit_ts: ts }
+        Ok(rec)
+    }
+
+    /// Commit after prepare (phase2). The decision is durable on return.
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `txn` was aborted.
+    pub fn commit(&self, txn: TxnId) -> io::Result<()> {
+        self.decide(txn, true)?;
+        self.finish(txn, TxnStatus::Committed);
+        Ok(())
     }
 
-    /// Commit after prepare (phase2).
-    pub fn commit(&self, txn: TxnId) {
-        self.statuses.lock().unwrap().insert(txn, TxnStatus::Committed);
+    /// Roll back txn: mark it aborted and release its locks, waking any waiters.
+    /// A prepared txn has the abort decision logged first. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `txn` was committed.
+    /// Write sets are not tracked yet, so there are no tuple versions to mark dead.
+    pub fn abort(&self, txn: TxnId) -> io::Result<()> {
+        self.decide(txn, false)?;
+        self.finish(txn, TxnStatus::Aborted);
+        Ok(())
+    }
+
+    /// Record that `participant` has applied the decision for `txn`. Once all
+    /// participants have, the txn's records are dropped at the next compaction.
+    /// Acks are not logged: after a restart the decision is resent and every
+    /// participant acknowledges again, including for finished txns whose
+    /// records were not compacted yet.
+    pub fn ack(&self, txn: TxnId, participant: &str) -> io::Result<()> {
+        let Some(log) = &self.log else { return Ok(()) };
+        let mut log = log.lock().unwrap();
+        if let Some(live) = log.live.get_mut(&txn) {
+            live.unacked.remove(participant);
+        }
+        log.finish_if_done(txn)
+    }
+
+    /// Log the decision for a prepared txn; unprepared txns need no record.
+    /// Repeating a decision is a no-op; contradicting one is an error.
+    fn decide(&self, txn: TxnId, committed: bool) -> io::Result<()> {
+        let status = self.statuses.lock().unwrap().get(&txn).copied();
+        if let Some(decided @ (TxnStatus::Committed | TxnStatus::Aborted)) = status {
+            if (decided == TxnStatus::Committed) != committed {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("txn {} is already {decided:?}", txn.0)));
+            }
+        }
+        let Some(log) = &self.log else { return Ok(()) };
+        let mut log = log.lock().unwrap();
+        let Some(live) = log.live.get(&txn) else { return Ok(()) };
+        if live.committed.is_some() {
+            return Ok(());
+        }
+        log.append(&LogRecord::Decision { txn_id: txn, committed })?;
+        // Only a durable decision counts, so a failed append leaves the txn undecided.
+        log.live.get_mut(&txn).unwrap().committed = Some(committed);
+        log.finish_if_done(txn)
+    }
+
+    fn finish(&self, txn: TxnId, status: TxnStatus) {
+        self.participants.lock().unwrap().remove(&txn);
+        self.statuses.lock().unwrap().insert(txn, status);
         self.lock_mgr.release_all(txn);
     }
 
-    /// Crash recovery that marks prepared txns as committed.
-    pub fn recover(&self, prepare_records: &[PrepareRecord]) {
+    /// Crash recovery: replay the WAL at `path`, truncating a torn trailing
+    /// record. Decided txns get their outcome; prepared but undecided ones
+    /// stay `Prepared` (in doubt) until [`commit`](Self::commit) or
+    /// [`abort`](Self::abort). A missing file means nothing to recover.
+    /// Returns the live txns and the number of records in the log.
+    fn recover(&self, path: &Path) -> io::Result<(HashMap<TxnId, LiveTxn>, usize)> {
+        let payloads = match recover_log(path) {
+            Ok(payloads) => payloads,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((HashMap::new(), 0)),
+            Err(e) => return Err(e),
+        };
+        let mut live = HashMap::new();
         let mut statuses = self.statuses.lock().unwrap();
-        for rec in prepare_records {
-            statuses.insert(rec.txn_id, TxnStatus::Committed);
+        for payload in &payloads {
+            match bincode::deserialize(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))? {
+                LogRecord::Prepare(rec) => {
+                    // New ids and timestamps must not collide with recovered ones.
+                    self.gtm.advance_past(rec.txn_id.0.max(rec.commit_ts));
+                    statuses.insert(rec.txn_id, TxnStatus::Prepared);
+                    let unacked = rec.participants.iter().cloned().collect();
+                    live.insert(rec.txn_id, LiveTxn { prepare: rec, committed: None, unacked });
+                }
+                LogRecord::Decision { txn_id, committed } => {
+                    statuses.insert(txn_id, if committed { TxnStatus::Committed } else { TxnStatus::Aborted });
+                    if let Some(t) = live.get_mut(&txn_id) {
+                        t.committed = Some(committed);
+                    }
+                }
+            }
         }
+        Ok((live, payloads.len()))
+    }
+
+    /// Number of txns whose records the log still has to keep.
+    pub fn live_in_log(&self) -> usize {
+        self.log.as_ref().map_or(0, |log| log.lock().unwrap().live.len())
     }
 
     /// Get status (for tests).
@@ -88,13 +299,126 @@ mod tests {
 
     #[test]
     fn two_phase_commit_flow() {
-        let tm = TxnManager::default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("txn.wal");
+        let (committed, aborted, in_doubt) = {
+            let tm = TxnManager::open(&path).unwrap();
+            let txns = (tm.begin(), tm.begin(), tm.begin());
+            for (txn, res) in [(txns.0, "t1"), (txns.1, "t2"), (txns.2, "t3")] {
+                assert!(tm.lock_x(txn, res));
+                tm.enlist(txn, "shard-a");
+                tm.enlist(txn, "shard-b");
+                tm.prepare(txn).unwrap();
+            }
+            assert_eq!(tm.status(txns.0), TxnStatus::Prepared);
+            tm.commit(txns.0).unwrap();
+            tm.abort(txns.1).unwrap();
+            tm.ack(txns.0, "shard-a").unwrap();
+            txns
+            // crash before the remaining acks and before deciding txns.2
+        };
+        // A crash mid-append leaves a torn record behind.
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, &[7, 0, 0]).unwrap();
+        drop(file);
+
+        let tm = TxnManager::open(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        assert_eq!(tm.status(committed), TxnStatus::Committed);
+        assert_eq!(tm.status(aborted), TxnStatus::Aborted);
+        assert_eq!(tm.status(in_doubt), TxnStatus::Prepared);
+        assert!(tm.begin().0 > in_doubt.0);
+
+        tm.commit(in_doubt).unwrap();
+        // Acks received before the crash are repeated after it.
+        let acks = [(committed, "shard-a"), (committed, "shard-b"), (aborted, "shard-a"), (aborted, "shard-b"), (in_doubt, "shard-a")];
+        for (txn, participant) in acks {
+            tm.ack(txn, participant).unwrap();
+        }
+        assert_eq!(tm.live_in_log(), 1);
+        tm.ack(in_doubt, "shard-b").unwrap();
+        assert_eq!(tm.live_in_log(), 0);
+    }
+
+    #[test]
+    fn contradicting_decision_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("txn.wal");
+        let tm = TxnManager::open(&path).unwrap();
         let txn = tm.begin();
-        assert!(tm.lock_x(txn, "t1"));
-        let prep = tm.prepare(txn);
-        // simulate crash before commit – store prepare log
-        let recovered_tm = TxnManager::default();
-        recovered_tm.recover(&[prep]);
-        assert_eq!(recovered_tm.status(txn), TxnStatus::Committed);
+        tm.enlist(txn, "shard-a");
+        tm.prepare(txn).unwrap();
+        tm.commit(txn).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        // A repeated commit logs nothing; an abort cannot undo it.
+        tm.commit(txn).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        assert_eq!(tm.abort(txn).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(tm.status(txn), TxnStatus::Committed);
+
+        // Still rejected once the txn has left the log.
+        tm.ack(txn, "shard-a").unwrap();
+        assert!(tm.abort(txn).is_err());
+        let aborted = tm.begin();
+        tm.abort(aborted).unwrap();
+        assert!(tm.commit(aborted).is_err());
+    }
+
+    #[test]
+    fn log_is_compacted_in_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("txn.wal");
+        let tm = TxnManager::open(&path).unwrap();
+        let mut sizes = Vec::new();
+        for _ in 0..COMPACT_MIN_RECORDS {
+            let txn = tm.begin();
+            tm.prepare(txn).unwrap();
+            tm.commit(txn).unwrap();
+            sizes.push(std::fs::metadata(&path).unwrap().len());
+        }
+        // Finished txns stay in the file until enough records pile up.
+        let batch = COMPACT_MIN_RECORDS / 2;
+        let compacted: Vec<usize> = (0..sizes.len()).filter(|&i| sizes[i] == 0).collect();
+        assert_eq!(compacted, [batch - 1, 2 * batch - 1], "{sizes:?}");
+        assert!(sizes[..batch - 1].windows(2).all(|w| w[0] < w[1]), "{sizes:?}");
+        assert_eq!(tm.live_in_log(), 0);
+    }
+
+    #[test]
+    fn abort_releases_locks_for_waiters() {
+        let tm = TxnManager::default();
+        let (t1, t2) = (tm.begin(), tm.begin());
+        assert!(tm.lock_x(t1, "r"));
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| tm.lock_x(t2, "r"));
+            while tm.lock_mgr.queued("r") < 1 {
+                std::thread::yield_now();
+            }
+            tm.abort(t1).unwrap();
+            assert!(waiter.join().unwrap());
+        });
+        assert_eq!(tm.status(t1), TxnStatus::Aborted);
+        assert!(tm.lock_mgr.held_by(t1).is_empty());
+        assert_eq!(tm.lock_mgr.held_by(t2), vec!["r".to_string()]);
+    }
+
+    #[test]
+    fn deadlock_victim_is_aborted() {
+        let tm = TxnManager::default();
+        let (t1, t2) = (tm.begin(), tm.begin());
+        assert!(tm.lock_x(t1, "a"));
+        assert!(tm.lock_x(t2, "b"));
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| tm.lock_x(t2, "a"));
+            while tm.lock_mgr.queued("a") < 1 {
+                std::thread::yield_now();
+            }
+            // t1 -> b closes the cycle; t1 is rolled back and t2 gets "a".
+            assert!(!tm.lock_x(t1, "b"));
+            assert!(waiter.join().unwrap());
+        });
+        assert_eq!(tm.status(t1), TxnStatus::Aborted);
+        assert!(tm.lock_mgr.held_by(t1).is_empty());
     }
 } 
\ No newline at end of file
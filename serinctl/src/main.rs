@@ -1,8 +1,23 @@
 use clap::{Args, Parser, Subcommand};
 use directories::BaseDirs;
-use rustyline::{error::ReadlineError, Editor};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Cmd, Editor, KeyEvent};
 use serin_parser::parse;
-use std::{fs, path::PathBuf};
+use serin_rs::Client;
+use serin_shard::ShardRouter;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use std::{fs, path::{Path, PathBuf}};
+use tokio::runtime::Runtime;
+
+mod dump;
+mod output;
+mod script;
+#[cfg(test)]
+mod testing;
+mod top;
+
+use dump::DumpFormat;
+use output::{render, Format, ResultSet};
 
 /// SerinDB command-line client.
 #[derive(Parser)]
@@ -16,8 +31,18 @@ struct Cli {
     #[arg(short = 'f', long = "file")]
     file: Option<PathBuf>,
 
+    /// Output format for result rows.
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
     #[command(flatten)]
     opts: Options,
+
+    #[command(flatten)]
+    conn: ConnOpts,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
 }
 
 #[derive(Args, Default)]
@@ -25,6 +50,100 @@ struct Options {
     /// Path to configuration file (default: $HOME/.serinrc).
     #[arg(long = "config")]
     config: Option<PathBuf>,
+
+    /// Neither load nor save the interactive shell history.
+    #[arg(long = "no-history")]
+    no_history: bool,
+}
+
+/// Environment variable overriding the history file path.
+const HISTORY_ENV: &str = "SERINCTL_HISTORY";
+
+impl Options {
+    /// History file: `$SERINCTL_HISTORY`, else `$HOME/.serinctl_history`.
+    fn history_path(&self) -> Option<PathBuf> {
+        if self.no_history {
+            return None;
+        }
+        std::env::var_os(HISTORY_ENV)
+            .map(PathBuf::from)
+            .or_else(|| BaseDirs::new().map(|b| b.home_dir().join(".serinctl_history")))
+    }
+}
+
+/// Server to run SQL against.
+#[derive(Args)]
+struct ConnOpts {
+    /// Server host.
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Server PgWire port.
+    #[arg(long, default_value_t = 5432)]
+    port: u16,
+
+    /// User to connect as.
+    #[arg(long, default_value = "serin")]
+    user: String,
+
+    /// Password for `--user`.
+    #[arg(long)]
+    password: Option<String>,
+
+    /// Only parse statements and print their AST; do not connect.
+    #[arg(long = "dry-run", alias = "parse-only")]
+    dry_run: bool,
+}
+
+impl ConnOpts {
+    fn conn_str(&self) -> String {
+        let mut s = format!("host={} port={} user={}", self.host, self.port, self.user);
+        if let Some(password) = &self.password {
+            s.push_str(&format!(" password={password}"));
+        }
+        s
+    }
+}
+
+/// Runs statements on a live server, or only parses them with `--dry-run`.
+struct Executor {
+    rt: Runtime,
+    /// `None` in dry-run mode.
+    client: Option<Client>,
+    format: Format,
+}
+
+impl Executor {
+    fn connect(conn: &ConnOpts, format: Format) -> anyhow::Result<Self> {
+        let rt = Runtime::new()?;
+        let client = if conn.dry_run {
+            None
+        } else {
+            let client = rt
+                .block_on(Client::connect(&conn.conn_str()))
+                .map_err(|e| anyhow::anyhow!("cannot connect to {}:{}: {e}", conn.host, conn.port))?;
+            Some(client)
+        };
+        Ok(Self { rt, client, format })
+    }
+
+    /// The server connection; fails in dry-run mode.
+    fn client(&self) -> anyhow::Result<&Client> {
+        self.client.as_ref().ok_or_else(|| anyhow::anyhow!("this command needs a server; drop --dry-run"))
+    }
+
+    /// Run `sql` and write its results (or, dry, its AST) to `out`.
+    fn execute(&self, sql: &str, out: &mut impl Write) -> anyhow::Result<()> {
+        let Some(client) = &self.client else {
+            writeln!(out, "{:#?}", parse(sql)?)?;
+            return Ok(());
+        };
+        let messages = self.rt.block_on(client.simple_query(sql))?;
+        for rs in ResultSet::from_messages(messages) {
+            out.write_all(render(&rs, self.format).as_bytes())?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Subcommand)]
@@ -41,12 +160,24 @@ enum Commands {
     Backup {
         /// Output directory or file path.
         path: PathBuf,
+
+        /// Dump layout: an SQL script file or a directory of binary COPY streams.
+        #[arg(long, value_enum, default_value_t = DumpFormat::Sql)]
+        format: DumpFormat,
+
+        /// Table to dump; repeatable, at least one.
+        #[arg(long = "table", required = true)]
+        tables: Vec<String>,
     },
 
     /// Restore database from backup path.
     Restore {
         /// Backup directory or file path.
         path: PathBuf,
+
+        /// Layout the backup was written in.
+        #[arg(long, value_enum, default_value_t = DumpFormat::Sql)]
+        format: DumpFormat,
     },
 
     /// Analyze tables and output statistics.
@@ -55,7 +186,8 @@ enum Commands {
     /// Health check via PgWire endpoint.
     Health,
 
-    /// Set configuration key/value dynamically.
+    /// Change a server setting at runtime (`ALTER SYSTEM SET`). The server
+    /// applies `log_level` until it restarts and rejects unknown keys.
     ConfigSet {
         key: String,
         value: String,
@@ -66,14 +198,33 @@ enum Commands {
         /// Refresh interval seconds.
         #[arg(long, default_value_t = 2)]
         interval: u64,
+
+        /// Prometheus endpoint of the server.
+        #[arg(long, default_value = "http://127.0.0.1:9644/metrics")]
+        metrics_url: String,
     },
 }
 
+/// `ALTER SYSTEM SET` for `key`, which must be a (dotted) identifier.
+fn config_set_sql(key: &str, value: &str) -> anyhow::Result<String> {
+    let valid = !key.is_empty()
+        && key.split('.').all(|part| {
+            part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    anyhow::ensure!(valid, "invalid configuration key {key:?}");
+    Ok(format!("ALTER SYSTEM SET {key} = '{}'", value.replace('\'', "''")))
+}
+
 fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    run(Cli::parse(), &mut std::io::stdout())
+}
+
+fn run(cli: Cli, out: &mut impl Write) -> anyhow::Result<()> {
     let config_path = cli
         .opts
         .config
+        .clone()
         .or_else(|| BaseDirs::new().map(|b| b.home_dir().join(".serinrc")));
 
     if let Some(cfg) = config_path {
@@ -83,74 +234,92 @@ fn main() -> anyhow::Result<()> {
     }
 
     if let Some(sql) = cli.sql {
-        execute_sql(&sql);
-        return Ok(());
+        return Executor::connect(&cli.conn, cli.format)?.execute(&sql, out);
     }
 
     if let Some(file) = cli.file {
         let content = fs::read_to_string(file)?;
-        for stmt in content.split(';') {
-            if !stmt.trim().is_empty() {
-                execute_sql(&(stmt.to_owned() + ";"));
+        let executor = Executor::connect(&cli.conn, cli.format)?;
+        for stmt in script::split_statements(&content) {
+            if let Err(e) = executor.execute(&stmt.sql, out) {
+                eprintln!("Error at line {}: {e}", stmt.line);
             }
         }
         return Ok(());
     }
 
-    match cli.command {
-        Some(Commands::Shard { key, shards }) => {
+    let Some(command) = cli.command else {
+        interactive_shell(&Executor::connect(&cli.conn, cli.format)?, cli.opts.history_path().as_deref(), out);
+        return Ok(());
+    };
+    match command {
+        Commands::Shard { key, shards } => {
             let router = serin_shard::HashRouter::new(shards);
             let rt = tokio::runtime::Runtime::new()?;
-            let id = rt.block_on(router.shard_for_key(&key));
-            println!("shard_id={}", id);
+            match rt.block_on(router.shard_for_key(&key)) {
+                Some(id) => writeln!(out, "shard_id={}", id)?,
+                None => anyhow::bail!("no shard for key {key} (shard count must be positive)"),
+            }
         }
 
-        Some(Commands::Backup { path }) => {
-            // TODO: integrate real backup logic; placeholder
-            println!("backup created at {}", path.display());
+        Commands::Backup { path, format, tables } => {
+            let executor = Executor::connect(&cli.conn, cli.format)?;
+            let tables = executor.rt.block_on(dump::backup(executor.client()?, &path, format, &tables))?;
+            writeln!(out, "backup of {} tables created at {}", tables.len(), path.display())?;
         }
 
-        Some(Commands::Restore { path }) => {
-            println!("restored from {}", path.display());
+        Commands::Restore { path, format } => {
+            let executor = Executor::connect(&cli.conn, cli.format)?;
+            for r in executor.rt.block_on(dump::restore(executor.client()?, &path, format))? {
+                writeln!(out, "{}: {} rows", r.table, r.rows)?;
+            }
+            writeln!(out, "restored from {}", path.display())?;
         }
 
-        Some(Commands::Analyze) => {
+        Commands::Analyze => {
             println!("analyze completed: statistics updated");
         }
 
-        Some(Commands::Health) => {
-            println!("SerinDB is healthy");
+        Commands::Health => {
+            let executor = Executor::connect(&cli.conn, cli.format)?;
+            executor
+                .rt
+                .block_on(executor.client()?.simple_query("SELECT 1"))
+                .map_err(|e| anyhow::anyhow!("SerinDB is unhealthy: {e}"))?;
+            writeln!(out, "SerinDB is healthy")?;
         }
 
-        Some(Commands::ConfigSet { key, value }) => {
-            println!("config {} set to {} (hot reload)", key, value);
+        Commands::ConfigSet { key, value } => {
+            let sql = config_set_sql(&key, &value)?;
+            let executor = Executor::connect(&cli.conn, cli.format)?;
+            executor.rt.block_on(executor.client()?.simple_query(&sql)).map_err(|e| match e.as_db_error() {
+                Some(db) => anyhow::anyhow!("server rejected {key}: {} (SQLSTATE {})", db.message(), db.code().code()),
+                None => e.into(),
+            })?;
+            writeln!(out, "config {key} set to {value}")?;
         }
 
-        Some(Commands::Top { interval }) => {
-            println!("press Ctrl+C to exit");
+        Commands::Top { interval, metrics_url } => {
+            let rt = Runtime::new()?;
+            let interval = Duration::from_secs(interval.max(1));
+            writeln!(out, "press Ctrl+C to exit")?;
+            let mut prev = top::Scrape::parse(&rt.block_on(top::fetch(&metrics_url))?);
+            let mut taken = Instant::now();
             loop {
-                println!("QPS=123 latency_p95=3ms");
-                std::thread::sleep(std::time::Duration::from_secs(interval));
+                std::thread::sleep(interval);
+                let cur = top::Scrape::parse(&rt.block_on(top::fetch(&metrics_url))?);
+                writeln!(out, "{}", top::Window::between(&prev, &cur, taken.elapsed()))?;
+                out.flush()?;
+                (prev, taken) = (cur, Instant::now());
             }
         }
-        None => {}
     }
-
-    interactive_shell();
     Ok(())
 }
 
-/// Evaluate a single SQL string and print the parsed AST or error.
-fn execute_sql(sql: &str) {
-    match parse(sql) {
-        Ok(ast) => println!("{:#?}", ast),
-        Err(e) => eprintln!("Error: {e}")
-    }
-}
-
 /// Interactive readline shell.
-fn interactive_shell() {
-    let mut rl: Editor<()> = Editor::new().expect("failed to init editor");
+fn interactive_shell(executor: &Executor, history: Option<&Path>, out: &mut impl Write) {
+    let mut rl = new_editor(history).expect("failed to init editor");
     let prompt = "serinctl> ";
 
     loop {
@@ -163,13 +332,15 @@ fn interactive_shell() {
                 if trimmed.is_empty() {
                     continue;
                 }
-                rl.add_history_entry(trimmed);
+                let _ = rl.add_history_entry(trimmed);
                 let sql = if trimmed.ends_with(';') {
                     trimmed.to_string()
                 } else {
                     format!("{};", trimmed)
                 };
-                execute_sql(&sql);
+                if let Err(e) = executor.execute(&sql, out) {
+                    eprintln!("Error: {e}");
+                }
             }
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
             Err(err) => {
@@ -178,4 +349,110 @@ fn interactive_shell() {
             }
         }
     }
-} 
\ No newline at end of file
+    if let Some(path) = history {
+        if let Err(e) = rl.save_history(path) {
+            eprintln!("Could not save history to {}: {e}", path.display());
+        }
+    }
+}
+
+/// Line editor with Ctrl-R reverse search, preloaded from `history` if it exists.
+fn new_editor(history: Option<&Path>) -> rustyline::Result<Editor<(), DefaultHistory>> {
+    let mut rl = Editor::new()?;
+    rl.bind_sequence(KeyEvent::ctrl('R'), Cmd::ReverseSearchHistory);
+    if let Some(path) = history.filter(|p| p.exists()) {
+        rl.load_history(path)?;
+    }
+    Ok(rl)
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_runs_select_against_local_server() {
+        // The server keeps running on this runtime's workers while `run`
+        // blocks on its own.
+        let server = Runtime::new().unwrap();
+        let addr = server.block_on(testing::serve(std::sync::Arc::new(testing::CopyStore::default())));
+        let port = addr.port().to_string();
+        let args = ["serinctl", "--port", &port, "--user", "alice", "--password", "password", "-e", "SELECT 1"];
+        let cli = Cli::try_parse_from(args).unwrap();
+        let mut out = Vec::new();
+        run(cli, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().any(|l| l.trim() == "1"), "{out}");
+    }
+
+    #[test]
+    fn parses_subcommands() {
+        let parse = |args: &[&str]| Cli::try_parse_from([&["serinctl"], args].concat()).unwrap().command.unwrap();
+        assert!(matches!(parse(&["shard", "--key", "k"]), Commands::Shard { key, shards: 4 } if key == "k"));
+        assert!(matches!(parse(&["shard", "--key", "k", "--shards", "8"]), Commands::Shard { shards: 8, .. }));
+        assert!(matches!(
+            parse(&["backup", "--table", "a", "/tmp/b"]),
+            Commands::Backup { path, format: DumpFormat::Sql, tables } if path.as_os_str() == "/tmp/b" && tables == ["a"]
+        ));
+        assert!(matches!(
+            parse(&["backup", "--format", "binary", "--table", "a", "--table", "b", "/tmp/b"]),
+            Commands::Backup { format: DumpFormat::Binary, tables, .. } if tables == ["a", "b"]
+        ));
+        assert!(matches!(
+            parse(&["restore", "/tmp/b", "--format", "binary"]),
+            Commands::Restore { path, format: DumpFormat::Binary } if path.as_os_str() == "/tmp/b"
+        ));
+        assert!(matches!(parse(&["analyze"]), Commands::Analyze));
+        assert!(matches!(parse(&["health"]), Commands::Health));
+        assert!(matches!(
+            parse(&["config-set", "wal.sync", "off"]),
+            Commands::ConfigSet { key, value } if key == "wal.sync" && value == "off"
+        ));
+        assert!(matches!(
+            parse(&["top", "--interval", "5", "--metrics-url", "http://db:9644/metrics"]),
+            Commands::Top { interval: 5, metrics_url } if metrics_url == "http://db:9644/metrics"
+        ));
+        assert!(Cli::try_parse_from(["serinctl", "backup", "--table", "a"]).is_err(), "backup needs a path");
+        assert!(Cli::try_parse_from(["serinctl", "backup", "/tmp/b"]).is_err(), "backup needs a table");
+        assert!(Cli::try_parse_from(["serinctl", "shard"]).is_err(), "shard needs --key");
+
+        assert_eq!(config_set_sql("wal.sync", "it's").unwrap(), "ALTER SYSTEM SET wal.sync = 'it''s'");
+        assert!(config_set_sql("x; DROP TABLE t", "1").is_err());
+    }
+
+    #[test]
+    fn file_statement_with_quoted_semicolon_runs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("script.sql");
+        fs::write(&file, "INSERT INTO t VALUES ('a;b');\n").unwrap();
+        let cli = Cli::try_parse_from(["serinctl", "--dry-run", "-f", file.to_str().unwrap()]).unwrap();
+        let mut out = Vec::new();
+        run(cli, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("Insert(").count(), 1, "{out}");
+        assert!(out.contains("\"a;b\""), "{out}");
+    }
+
+    #[test]
+    fn history_survives_editor_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut rl = new_editor(Some(&path)).unwrap();
+        rl.add_history_entry("SELECT 42;").unwrap();
+        rl.save_history(&path).unwrap();
+
+        let reloaded = new_editor(Some(&path)).unwrap();
+        assert!(reloaded.history().iter().any(|entry| entry == "SELECT 42;"));
+
+        let cli = Cli::try_parse_from(["serinctl", "--no-history"]).unwrap();
+        assert_eq!(cli.opts.history_path(), None);
+    }
+
+    #[test]
+    fn dry_run_prints_ast_without_connecting() {
+        let cli = Cli::try_parse_from(["serinctl", "--port", "1", "--parse-only", "-e", "SELECT 1;"]).unwrap();
+        let mut out = Vec::new();
+        run(cli, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Select"));
+    }
+}
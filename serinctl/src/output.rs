@@ -0,0 +1,197 @@
+//! Rendering of query results for the terminal.
+
+use clap::ValueEnum;
+use serin_rs::SimpleQueryMessage;
+
+/// How result rows are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned ASCII table.
+    #[default]
+    Table,
+    /// RFC 4180 CSV with a header line; NULL is an empty field.
+    Csv,
+    /// JSON array of objects keyed by column name; NULL is `null`.
+    Json,
+}
+
+/// Render `rs` in `format`. Only the table format reports statements
+/// without a result set; the machine formats print nothing for them.
+pub fn render(rs: &ResultSet, format: Format) -> String {
+    match format {
+        Format::Table => render_table(rs),
+        Format::Csv if rs.columns.is_empty() => String::new(),
+        Format::Csv => render_csv(rs),
+        Format::Json if rs.columns.is_empty() => String::new(),
+        Format::Json => render_json(rs),
+    }
+}
+
+/// Outcome of one statement: column names and text values (`None` = NULL).
+/// Statements that return no rows have no columns.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+    /// Rows returned or affected, as reported by the server.
+    pub count: u64,
+}
+
+impl ResultSet {
+    /// Split a simple-query response into one result per statement.
+    pub fn from_messages(messages: Vec<SimpleQueryMessage>) -> Vec<ResultSet> {
+        let mut results = Vec::new();
+        let mut current = ResultSet::default();
+        for msg in messages {
+            match msg {
+                SimpleQueryMessage::RowDescription(cols) => {
+                    current.columns = cols.iter().map(|c| c.name().to_string()).collect();
+                }
+                SimpleQueryMessage::Row(row) => {
+                    if current.columns.is_empty() {
+                        current.columns = row.columns().iter().map(|c| c.name().to_string()).collect();
+                    }
+                    current.rows.push((0..row.len()).map(|i| row.get(i).map(str::to_string)).collect());
+                }
+                SimpleQueryMessage::CommandComplete(n) => {
+                    current.count = n;
+                    results.push(std::mem::take(&mut current));
+                }
+                _ => {}
+            }
+        }
+        results
+    }
+}
+
+/// Aligned ASCII table followed by a row count, e.g.
+///
+/// ```text
+///  id | name
+/// ----+------
+///  1  | a
+/// (1 row)
+/// ```
+pub fn render_table(rs: &ResultSet) -> String {
+    if rs.columns.is_empty() {
+        return format!("OK, {} rows affected\n", rs.count);
+    }
+    let cells: Vec<Vec<&str>> =
+        rs.rows.iter().map(|row| row.iter().map(|v| v.as_deref().unwrap_or("NULL")).collect()).collect();
+    let widths: Vec<usize> = rs
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| cells.iter().map(|row| row[i].chars().count()).fold(name.chars().count(), usize::max))
+        .collect();
+    let line = |values: &mut dyn Iterator<Item = &str>| {
+        let padded: Vec<String> = values.zip(&widths).map(|(v, w)| format!(" {v:<w$} ")).collect();
+        format!("{}\n", padded.join("|").trim_end())
+    };
+    let mut out = line(&mut rs.columns.iter().map(String::as_str));
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(w + 2)).collect();
+    out.push_str(&rule.join("+"));
+    out.push('\n');
+    for row in &cells {
+        out.push_str(&line(&mut row.iter().copied()));
+    }
+    let n = rs.rows.len();
+    out.push_str(&format!("({n} row{})\n", if n == 1 { "" } else { "s" }));
+    out
+}
+
+/// Header line plus one line per row. An empty string is quoted so it stays
+/// distinguishable from NULL.
+pub fn render_csv(rs: &ResultSet) -> String {
+    let field = |v: &str| {
+        if v.is_empty() || v.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", v.replace('"', "\"\""))
+        } else {
+            v.to_string()
+        }
+    };
+    let mut out = rs.columns.iter().map(|c| field(c)).collect::<Vec<_>>().join(",");
+    out.push('\n');
+    for row in &rs.rows {
+        let line: Vec<String> = row.iter().map(|v| v.as_deref().map_or(String::new(), field)).collect();
+        out.push_str(&line.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// One object per row, keys in column order; values are the server's text.
+pub fn render_json(rs: &ResultSet) -> String {
+    let objects: Vec<String> = rs
+        .rows
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = rs
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| format!("{}:{}", json_string(c), v.as_deref().map_or("null".to_string(), json_string)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!("[{}]\n", objects.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ResultSet {
+        let row = |id: &str, name: Option<&str>| vec![Some(id.to_string()), name.map(str::to_string)];
+        ResultSet {
+            columns: vec!["id".into(), "name".into()],
+            rows: vec![row("1", Some("alice")), row("22", None), row("3", Some("a,\"b\""))],
+            count: 3,
+        }
+    }
+
+    #[test]
+    fn renders_table() {
+        let expected = concat!(
+            " id | name\n",
+            "----+-------\n",
+            " 1  | alice\n",
+            " 22 | NULL\n",
+            " 3  | a,\"b\"\n",
+            "(3 rows)\n",
+        );
+        assert_eq!(render(&sample(), Format::Table), expected);
+        let affected = ResultSet { count: 2, ..ResultSet::default() };
+        assert_eq!(render(&affected, Format::Table), "OK, 2 rows affected\n");
+    }
+
+    #[test]
+    fn renders_csv_and_json() {
+        assert_eq!(render(&sample(), Format::Csv), "id,name\n1,alice\n22,\n3,\"a,\"\"b\"\"\"\n");
+        assert_eq!(
+            render(&sample(), Format::Json),
+            r#"[{"id":"1","name":"alice"},{"id":"22","name":null},{"id":"3","name":"a,\"b\""}]"#.to_string() + "\n"
+        );
+        let empty = ResultSet { columns: vec!["x".into()], rows: vec![vec![Some(String::new())]], count: 1 };
+        assert_eq!(render(&empty, Format::Csv), "x\n\"\"\n");
+    }
+}
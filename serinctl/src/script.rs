@@ -0,0 +1,100 @@
+//! Splitting SQL scripts into statements.
+
+/// One statement of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// Statement text including its terminating `;`, if any.
+    pub sql: String,
+    /// 1-based line on which the statement starts.
+    pub line: usize,
+}
+
+/// Split `script` at top-level semicolons. Semicolons inside single- or
+/// double-quoted strings and `--` or (nested) `/* */` comments do not end a
+/// statement. Comments before a statement are dropped, as are pieces holding
+/// only whitespace and comments.
+pub fn split_statements(script: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut chars = script.char_indices().peekable();
+    let mut line = 1;
+    // Offset and line of the statement's first character that is not
+    // whitespace or part of a comment.
+    let mut first: Option<(usize, usize)> = None;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '\'' | '"' => {
+                first.get_or_insert((i, line));
+                // A doubled quote is an escaped quote; reading it as closing
+                // and reopening gives the same result.
+                for (_, d) in chars.by_ref() {
+                    line += (d == '\n') as usize;
+                    if d == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek().is_some_and(|&(_, d)| d == '-') => {
+                for (_, d) in chars.by_ref() {
+                    if d == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|&(_, d)| d == '*') => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0 {
+                    let Some((_, d)) = chars.next() else { break };
+                    match d {
+                        '\n' => line += 1,
+                        '*' if chars.next_if(|&(_, e)| e == '/').is_some() => depth -= 1,
+                        '/' if chars.next_if(|&(_, e)| e == '*').is_some() => depth += 1,
+                        _ => {}
+                    }
+                }
+            }
+            ';' => {
+                if let Some((start, line)) = first.take() {
+                    statements.push(Statement { sql: script[start..=i].to_string(), line });
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                first.get_or_insert((i, line));
+            }
+        }
+    }
+    if let Some((start, line)) = first {
+        statements.push(Statement { sql: script[start..].trim_end().to_string(), line });
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqls(script: &str) -> Vec<(String, usize)> {
+        split_statements(script).into_iter().map(|s| (s.sql, s.line)).collect()
+    }
+
+    #[test]
+    fn semicolons_in_strings_and_comments_do_not_split() {
+        let script = "INSERT INTO t VALUES ('a;b');\n\
+                      -- comment; not a statement\n\
+                      SELECT \"x;y\", 'it''s;' /* c; /* nested; */ still; */ FROM t;\n\
+                      \n\
+                      SELECT 1";
+        assert_eq!(
+            sqls(script),
+            vec![
+                ("INSERT INTO t VALUES ('a;b');".into(), 1),
+                ("SELECT \"x;y\", 'it''s;' /* c; /* nested; */ still; */ FROM t;".into(), 3),
+                ("SELECT 1".into(), 5),
+            ]
+        );
+        assert!(split_statements("  ;\n-- only a comment\n/* ; */").is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+//! In-process PgWire server for tests.
+
+use serin_pgwire::auth::{AuthConfig, SharedAuthConfig};
+use serin_pgwire::executor::{DummyExecutor, ExecError, ExecResult, QueryExecutor};
+use serin_pgwire::ServerOptions;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+/// Serve `executor` on an ephemeral port from the current runtime; any
+/// user logs in with the default password `password`.
+pub async fn serve(executor: Arc<dyn QueryExecutor>) -> SocketAddr {
+    let conf = AuthConfig {
+        users: HashMap::new(),
+        hba: Default::default(),
+        tls: None,
+        cert_auth: None,
+        rate_limit: Default::default(),
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let conf = Arc::new(SharedAuthConfig::new(Arc::new(conf)).expect("no TLS configured"));
+    tokio::spawn(serin_pgwire::serve_with_shutdown(
+        listener,
+        conf,
+        executor,
+        ServerOptions::default(),
+        CancellationToken::new(),
+    ));
+    addr
+}
+
+/// Executor keeping the COPY data of each table, keyed by its quoted name;
+/// other queries answer `1`.
+#[derive(Debug, Default)]
+pub struct CopyStore(Mutex<HashMap<String, Vec<u8>>>);
+
+impl CopyStore {
+    pub fn put(&self, table: &str, data: Vec<u8>) {
+        self.0.lock().unwrap().insert(table.to_string(), data);
+    }
+
+    pub fn get(&self, table: &str) -> Vec<u8> {
+        self.0.lock().unwrap().get(table).cloned().unwrap_or_default()
+    }
+}
+
+/// Table name of `COPY <table> ...`.
+fn copy_table(sql: &str) -> &str {
+    sql.split_whitespace().nth(1).unwrap_or_default()
+}
+
+fn row_count(data: &[u8]) -> u64 {
+    data.iter().filter(|&&b| b == b'\n').count() as u64
+}
+
+#[async_trait::async_trait]
+impl QueryExecutor for CopyStore {
+    async fn execute(&self, sql: &str) -> anyhow::Result<ExecResult> {
+        DummyExecutor.execute(sql).await
+    }
+
+    async fn copy_out(&self, sql: &str) -> anyhow::Result<(Vec<u8>, u64)> {
+        let table = copy_table(sql);
+        let data = self.0.lock().unwrap().get(table).cloned();
+        let data = data.ok_or_else(|| ExecError::new("42P01", format!("relation {table} does not exist")))?;
+        let rows = row_count(&data);
+        Ok((data, rows))
+    }
+
+    async fn copy_in(&self, sql: &str, data: Vec<u8>) -> anyhow::Result<u64> {
+        let rows = row_count(&data);
+        self.put(copy_table(sql), data);
+        Ok(rows)
+    }
+}
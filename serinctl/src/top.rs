@@ -0,0 +1,123 @@
+//! `serinctl top`: query rate and latency from the server's `/metrics`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Query counters read from one `/metrics` scrape, summed over all labels.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Scrape {
+    /// `serin_queries_total`.
+    pub queries: f64,
+    /// Cumulative `serin_query_latency_seconds` buckets as (upper bound, count),
+    /// sorted by bound.
+    pub buckets: Vec<(f64, f64)>,
+}
+
+impl Scrape {
+    /// Parse the Prometheus text exposition format.
+    pub fn parse(text: &str) -> Self {
+        let mut queries = 0.0;
+        let mut by_le: HashMap<&str, f64> = HashMap::new();
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let Some((series, value)) = line.rsplit_once(' ') else { continue };
+            let Ok(value) = value.parse::<f64>() else { continue };
+            let (name, labels) = series.split_once('{').unwrap_or((series, ""));
+            match name {
+                "serin_queries_total" => queries += value,
+                "serin_query_latency_seconds_bucket" => {
+                    if let Some(le) = label(labels, "le") {
+                        *by_le.entry(le).or_default() += value;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut buckets: Vec<(f64, f64)> =
+            by_le.into_iter().filter_map(|(le, n)| Some((le.parse().ok()?, n))).collect();
+        buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { queries, buckets }
+    }
+}
+
+fn label<'a>(labels: &'a str, key: &str) -> Option<&'a str> {
+    labels.trim_end_matches('}').split(',').find_map(|kv| {
+        let (k, v) = kv.split_once('=')?;
+        (k == key).then(|| v.trim_matches('"'))
+    })
+}
+
+/// Activity between two scrapes taken `elapsed` apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub qps: f64,
+    /// Upper bound of the bucket holding the 95th percentile; `None` without
+    /// queries, infinite when it lies beyond the last finite bucket.
+    pub p95: Option<Duration>,
+}
+
+impl Window {
+    pub fn between(prev: &Scrape, cur: &Scrape, elapsed: Duration) -> Self {
+        let qps = (cur.queries - prev.queries).max(0.0) / elapsed.as_secs_f64();
+        let before: HashMap<u64, f64> = prev.buckets.iter().map(|(le, n)| (le.to_bits(), *n)).collect();
+        let deltas: Vec<(f64, f64)> =
+            cur.buckets.iter().map(|(le, n)| (*le, n - before.get(&le.to_bits()).unwrap_or(&0.0))).collect();
+        let total = deltas.last().map_or(0.0, |(_, n)| *n);
+        let p95 = (total > 0.0)
+            .then(|| deltas.iter().find(|(_, n)| *n >= 0.95 * total))
+            .flatten()
+            .map(|(le, _)| Duration::try_from_secs_f64(*le).unwrap_or(Duration::MAX));
+        Self { qps, p95 }
+    }
+}
+
+impl std::fmt::Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QPS={:.1} latency_p95=", self.qps)?;
+        match self.p95 {
+            None => write!(f, "-"),
+            Some(Duration::MAX) => write!(f, "overflow"),
+            Some(p95) => write!(f, "<={}ms", p95.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// GET `url` and return the body.
+pub async fn fetch(url: &str) -> anyhow::Result<String> {
+    let resp = hyper::Client::new().get(url.parse()?).await?;
+    anyhow::ensure!(resp.status().is_success(), "GET {url}: {}", resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    Ok(String::from_utf8(body.to_vec())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrape(queries: u32, buckets: [u32; 3]) -> String {
+        format!(
+            "# TYPE serin_queries_total counter\n\
+             serin_queries_total{{database=\"a\",kind=\"select\",user=\"u\"}} {queries}\n\
+             serin_queries_total{{database=\"b\",kind=\"select\",user=\"u\"}} 0\n\
+             serin_query_latency_seconds_bucket{{database=\"a\",le=\"0.001\"}} {}\n\
+             serin_query_latency_seconds_bucket{{database=\"a\",le=\"0.01\"}} {}\n\
+             serin_query_latency_seconds_bucket{{database=\"a\",le=\"+Inf\"}} {}\n",
+            buckets[0], buckets[1], buckets[2]
+        )
+    }
+
+    #[test]
+    fn window_reports_rate_and_p95_bucket() {
+        let prev = Scrape::parse(&scrape(100, [50, 90, 100]));
+        assert_eq!(prev.queries, 100.0);
+        assert_eq!(prev.buckets.len(), 3);
+
+        // 200 new queries: 100 under 1ms, 96 under 10ms, 4 slower.
+        let cur = Scrape::parse(&scrape(300, [150, 286, 300]));
+        let w = Window::between(&prev, &cur, Duration::from_secs(2));
+        assert_eq!(w.qps, 100.0);
+        assert_eq!(w.p95, Some(Duration::from_millis(10)));
+        assert_eq!(w.to_string(), "QPS=100.0 latency_p95=<=10ms");
+
+        assert_eq!(Window::between(&cur, &cur, Duration::from_secs(1)).p95, None);
+    }
+}
@@ -0,0 +1,101 @@
+//! `serindb server` settings, read from a YAML file and overridden by flags.
+
+use anyhow::Context;
+use serde::Deserialize;
+use serin_pgwire::ServerOptions;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Server tunables. Every field is optional in the file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// PgWire listen address.
+    pub listen: String,
+    /// Path to the YAML auth file.
+    pub auth_file: String,
+    /// Prometheus exporter listen address.
+    pub metrics_addr: String,
+    /// Close sessions idle for this many seconds; never when unset.
+    pub idle_timeout_secs: Option<u64>,
+    /// Connections served at once.
+    pub max_connections: usize,
+    /// Minimum level written to the server log (`error` .. `trace`).
+    pub log_level: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:5432".into(),
+            auth_file: "serin_auth.yml".into(),
+            metrics_addr: "0.0.0.0:9644".into(),
+            idle_timeout_secs: None,
+            max_connections: 100,
+            log_level: "info".into(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read `path`; fields it omits keep their defaults.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_yaml::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Reject values the server cannot start with.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.listen.parse::<SocketAddr>().with_context(|| format!("listen: invalid address {:?}", self.listen))?;
+        self.metrics_addr
+            .parse::<SocketAddr>()
+            .with_context(|| format!("metrics_addr: invalid address {:?}", self.metrics_addr))?;
+        anyhow::ensure!(self.max_connections > 0, "max_connections must be at least 1");
+        anyhow::ensure!(self.idle_timeout_secs != Some(0), "idle_timeout_secs must be positive; omit it to disable");
+        self.level()?;
+        Ok(())
+    }
+
+    /// [`log_level`](Self::log_level) as a tracing level.
+    pub fn level(&self) -> anyhow::Result<tracing::Level> {
+        self.log_level.parse().map_err(|_| anyhow::anyhow!("log_level: unknown level {:?}", self.log_level))
+    }
+
+    /// Limits handed to the PgWire server.
+    pub fn server_options(&self) -> ServerOptions {
+        ServerOptions {
+            max_connections: Some(self.max_connections),
+            idle_timeout: self.idle_timeout_secs.map(Duration::from_secs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_config_flows_into_server_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.yml");
+        std::fs::write(&path, "listen: 127.0.0.1:6543\nidle_timeout_secs: 30\nmax_connections: 8\nlog_level: debug\n")
+            .unwrap();
+
+        let config = ServerConfig::load(&path).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.listen, "127.0.0.1:6543");
+        assert_eq!(config.metrics_addr, "0.0.0.0:9644", "omitted fields keep defaults");
+        assert_eq!(config.level().unwrap(), tracing::Level::DEBUG);
+        assert_eq!(
+            config.server_options(),
+            ServerOptions { max_connections: Some(8), idle_timeout: Some(Duration::from_secs(30)) }
+        );
+
+        std::fs::write(&path, "max_connections: 0\n").unwrap();
+        let err = ServerConfig::load(&path).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("max_connections"), "{err}");
+        std::fs::write(&path, "max_conections: 5\n").unwrap();
+        assert!(ServerConfig::load(&path).is_err(), "unknown keys are rejected");
+    }
+}
@@ -1,10 +1,22 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
-use serin_pgwire::auth::AuthConfig;
+use tokio_util::sync::CancellationToken;
+use serin_pgwire::auth::{AuthConfig, SharedAuthConfig};
+use serin_pgwire::executor::{DummyExecutor, ExecError, ExecResult, QueryExecutor};
 use serin_telemetry as telemetry;
 use serin_metrics as metrics;
 use serin_log as slog;
 
+mod config;
+
+/// Hourly log files kept on disk: one week.
+const LOG_FILES_KEPT: usize = 7 * 24;
+
+use config::ServerConfig;
+
 /// SerinDB command-line interface (MVP).
 #[derive(Parser)]
 #[command(name = "serindb", author, version, about = "SerinDB CLI", long_about = None)]
@@ -20,25 +32,104 @@ enum Commands {
     HealthCheck,
 
     /// Start PostgreSQL Wire server.
-    Server {
-        /// Listen address (e.g., 0.0.0.0:5432)
-        #[arg(long, default_value = "0.0.0.0:5432")]
-        listen: String,
-
-        /// Path to YAML auth file.
-        #[arg(long, default_value = "serin_auth.yml")]
-        auth_file: String,
-    },
+    Server(ServerArgs),
+}
+
+/// `serindb server` flags; each overrides the `--config` file value.
+#[derive(Args)]
+struct ServerArgs {
+    /// Path to a YAML server config file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Listen address (default 0.0.0.0:5432).
+    #[arg(long)]
+    listen: Option<String>,
+
+    /// Path to YAML auth file (default serin_auth.yml).
+    #[arg(long)]
+    auth_file: Option<String>,
+
+    /// Prometheus exporter address (default 0.0.0.0:9644).
+    #[arg(long)]
+    metrics_addr: Option<String>,
+
+    /// Close sessions idle for this many seconds.
+    #[arg(long)]
+    idle_timeout_secs: Option<u64>,
+
+    /// Connections served at once (default 100).
+    #[arg(long)]
+    max_connections: Option<usize>,
+
+    /// Server log level (default info).
+    #[arg(long)]
+    log_level: Option<String>,
+}
+
+impl ServerArgs {
+    /// Config file (or defaults) with flags applied on top, validated.
+    fn resolve(&self) -> anyhow::Result<ServerConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::load(path)?,
+            None => ServerConfig::default(),
+        };
+        if let Some(listen) = &self.listen {
+            config.listen = listen.clone();
+        }
+        if let Some(auth_file) = &self.auth_file {
+            config.auth_file = auth_file.clone();
+        }
+        if let Some(metrics_addr) = &self.metrics_addr {
+            config.metrics_addr = metrics_addr.clone();
+        }
+        if let Some(secs) = self.idle_timeout_secs {
+            config.idle_timeout_secs = Some(secs);
+        }
+        if let Some(max) = self.max_connections {
+            config.max_connections = max;
+        }
+        if let Some(level) = &self.log_level {
+            config.log_level = level.clone();
+        }
+        config.validate()?;
+        Ok(config)
+    }
 }
 
 fn main() {
-    let _handle = slog::init("logs", tracing::Level::INFO).expect("log init");
-    telemetry::init("serindb").expect("telemetry init");
-    let _ = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
-        let _ = metrics::serve("0.0.0.0:9644", None).await;
-    });
-    let cli = Cli::parse();
+    std::process::exit(run(std::env::args_os()));
+}
 
+/// Parse `args` and run the selected command; returns the exit code.
+/// Nothing is started before the command line parses, so `--help` and usage
+/// errors leave no listeners behind.
+fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return e.exit_code();
+        }
+    };
+    let server_config = match &cli.command {
+        Some(Commands::Server(args)) => match args.resolve() {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("invalid server configuration: {e:#}");
+                return 2;
+            }
+        },
+        _ => None,
+    };
+    if let Some(config) = server_config {
+        return run_server(config);
+    }
+    let _logs = init_logging(tracing::Level::INFO, None);
     match cli.command {
         Some(Commands::HealthCheck) => {
             if serindb::health_check() {
@@ -47,18 +138,261 @@ fn main() {
                 println!("FAILED");
             }
         }
-        Some(Commands::Server { listen, auth_file }) => {
-            // Start async runtime manually since main is sync.
-            let rt = Runtime::new().unwrap();
-            rt.block_on(async {
-                let conf = AuthConfig::load(&auth_file).expect("failed to load auth config");
-                if let Err(e) = serin_pgwire::run_server(&listen, conf).await {
-                    eprintln!("Server error: {e}");
-                }
-            });
-        }
+        Some(Commands::Server(_)) => unreachable!("handled above"),
         None => {
             // Clap will print help.
         }
     }
+    0
+}
+
+/// Install the global subscriber: rolling JSON logs plus `otlp`, if any.
+/// Old log files are pruned while the returned guard is alive; the handle
+/// changes the log level at runtime.
+fn init_logging(level: tracing::Level, otlp: Option<slog::BoxedLayer>) -> (slog::Handle, slog::LogGuard) {
+    let config = slog::LogConfig {
+        level,
+        audit_dir: std::env::var("SERIN_AUDIT_LOG_DIR").ok(),
+        retain: Some(LOG_FILES_KEPT),
+        ..Default::default()
+    };
+    slog::init(config, otlp).expect("log init")
+}
+
+/// Run the PgWire server and metrics exporter described by `config` until
+/// SIGINT/SIGTERM; returns the exit code.
+fn run_server(config: ServerConfig) -> i32 {
+    // Start async runtime manually since main is sync.
+    let rt = Runtime::new().unwrap();
+    // Dropped before the runtime, flushing buffered spans while the exporter
+    // task can still run.
+    let mut telemetry = None;
+    let result = rt.block_on(async {
+        // The OTLP batch exporter is spawned onto this runtime.
+        let otlp = match telemetry::layer("serindb", None) {
+            Ok(Some((layer, guard))) => {
+                telemetry = Some(guard);
+                Some(layer)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("telemetry disabled: {e}");
+                None
+            }
+        };
+        let (log_level, _logs) = init_logging(config.level().expect("validated"), otlp);
+        // `serve` spawns the exporter onto this runtime and returns once bound.
+        metrics::serve(&config.metrics_addr, metrics::DEFAULT_METRICS.registry.clone(), None).await?;
+        let conf = Arc::new(SharedAuthConfig::new(AuthConfig::load(&config.auth_file)?)?);
+        // Password changes in the auth file apply to new connections.
+        let _watcher = conf
+            .watch(&config.auth_file)
+            .map_err(|e| tracing::warn!("not watching {}: {e}", config.auth_file))
+            .ok();
+        let listener = TcpListener::bind(&config.listen).await?;
+        println!("PgWire server listening on {}", config.listen);
+        let shutdown = CancellationToken::new();
+        tokio::spawn(cancel_on_signal(shutdown.clone()));
+        serve(listener, conf, config.server_options(), Some(log_level), shutdown).await
+    });
+    if let Err(e) = result {
+        eprintln!("Server error: {e}");
+        return 1;
+    }
+    0
+}
+
+/// Serve PgWire on `listener` until `shutdown` is cancelled and in-flight
+/// sessions have drained.
+async fn serve(
+    listener: TcpListener,
+    conf: Arc<SharedAuthConfig>,
+    options: serin_pgwire::ServerOptions,
+    log_level: Option<slog::Handle>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let executor = Arc::new(PlaceholderExecutor { log_level });
+    serin_pgwire::serve_with_shutdown(listener, conf, executor, options, shutdown).await?;
+    tracing::info!("PgWire server stopped");
+    Ok(())
+}
+
+/// Stands in until an SQL engine is wired up: `ALTER SYSTEM SET` applies the
+/// settings the running server can change, and every other query answers
+/// `SELECT 1`.
+struct PlaceholderExecutor {
+    /// Reloads the log level; `None` when logging was not set up by us.
+    log_level: Option<slog::Handle>,
+}
+
+impl PlaceholderExecutor {
+    /// Apply `ALTER SYSTEM SET <key> = '<value>'` until the server restarts.
+    /// Only `log_level` can change at runtime.
+    fn alter_system(&self, sql: &str) -> anyhow::Result<ExecResult> {
+        let (key, value) = parse_alter_system_set(sql)
+            .ok_or_else(|| ExecError::new("42601", "expected ALTER SYSTEM SET <key> = '<value>'"))?;
+        match key.as_str() {
+            "log_level" => {
+                let level: tracing::Level = value
+                    .parse()
+                    .map_err(|_| ExecError::new("22023", format!("invalid value for log_level: \"{value}\"")))?;
+                let handle = self
+                    .log_level
+                    .as_ref()
+                    .ok_or_else(|| ExecError::new("55000", "log_level cannot be changed in this process"))?;
+                slog::set_level(handle, level)?;
+                tracing::info!(%level, "log level changed by ALTER SYSTEM");
+            }
+            _ => return Err(ExecError::new("42704", format!("unrecognized configuration parameter \"{key}\"")).into()),
+        }
+        Ok(ExecResult { tag: "ALTER SYSTEM".into(), ..Default::default() })
+    }
+}
+
+/// Key and unquoted value of `ALTER SYSTEM SET key {= | TO} 'value'`, or
+/// `None` for any other statement.
+fn parse_alter_system_set(sql: &str) -> Option<(String, String)> {
+    let sql = sql.trim().trim_end_matches(';');
+    let rest = ["alter", "system", "set"].into_iter().try_fold(sql, strip_keyword)?.trim();
+    let (key, value) = match rest.split_once('=') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => {
+            let (key, value) = rest.split_once(char::is_whitespace)?;
+            (key, strip_keyword(value, "to")?.trim())
+        }
+    };
+    let value = match value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => value.to_string(),
+    };
+    Some((key.to_ascii_lowercase(), value))
+}
+
+/// `s` after its leading case-insensitive `keyword` and the whitespace that
+/// must follow it.
+fn strip_keyword<'a>(s: &'a str, keyword: &str) -> Option<&'a str> {
+    let s = s.trim_start();
+    let rest = s.get(keyword.len()..)?;
+    (s[..keyword.len()].eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace)).then_some(rest)
+}
+
+#[async_trait::async_trait]
+impl QueryExecutor for PlaceholderExecutor {
+    async fn execute(&self, sql: &str) -> anyhow::Result<ExecResult> {
+        let mut words = sql.split_whitespace();
+        let alter_system = matches!((words.next(), words.next()), (Some(a), Some(s))
+            if a.eq_ignore_ascii_case("alter") && s.eq_ignore_ascii_case("system"));
+        if alter_system {
+            return self.alter_system(sql);
+        }
+        DummyExecutor.execute(sql).await
+    }
+}
+
+/// Cancel `token` on the first SIGINT or SIGTERM.
+async fn cancel_on_signal(token: CancellationToken) {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                eprintln!("cannot listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+    tracing::info!("shutdown signal received");
+    token.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn server_flags_override_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.yml");
+        std::fs::write(&path, "listen: 127.0.0.1:6543\nmax_connections: 8\nlog_level: debug\n").unwrap();
+        let cli = Cli::try_parse_from([
+            "serindb", "server", "--config", path.to_str().unwrap(), "--max-connections", "4", "--idle-timeout-secs", "9",
+        ])
+        .unwrap();
+        let Some(Commands::Server(args)) = cli.command else { panic!("expected server") };
+        let config = args.resolve().unwrap();
+        assert_eq!(config.listen, "127.0.0.1:6543");
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.server_options().max_connections, Some(4));
+        assert_eq!(config.server_options().idle_timeout, Some(Duration::from_secs(9)));
+
+        let cli = Cli::try_parse_from(["serindb", "server", "--log-level", "loud"]).unwrap();
+        let Some(Commands::Server(args)) = cli.command else { panic!("expected server") };
+        assert!(args.resolve().unwrap_err().to_string().contains("log_level"));
+    }
+
+    #[test]
+    fn help_does_not_bind_metrics_port() {
+        // Hold the default exporter port: binding it again would panic.
+        let Ok(_held) = std::net::TcpListener::bind(ServerConfig::default().metrics_addr) else { return };
+        assert_eq!(run(["serindb", "--help"]), 0);
+        assert_eq!(run(["serindb", "server", "--help"]), 0);
+    }
+
+    #[tokio::test]
+    async fn alter_system_sets_the_log_level() {
+        let (_layer, handle) = tracing_subscriber::reload::Layer::new(tracing_subscriber::EnvFilter::new("info"));
+        let executor = PlaceholderExecutor { log_level: Some(handle.clone()) };
+        let result = executor.execute("alter  SYSTEM SET log_level = 'debug';").await.unwrap();
+        assert_eq!(result.tag, "ALTER SYSTEM");
+        assert_eq!(handle.with_current(|filter| filter.to_string()).unwrap(), "debug");
+        executor.execute("ALTER SYSTEM SET log_level TO warn").await.unwrap();
+        assert_eq!(handle.with_current(|filter| filter.to_string()).unwrap(), "warn");
+
+        let code = |sql: &'static str| {
+            let executor = &executor;
+            async move { executor.execute(sql).await.unwrap_err().downcast_ref::<ExecError>().unwrap().code.clone() }
+        };
+        assert_eq!(code("ALTER SYSTEM SET wal.sync = 'off'").await, "42704");
+        assert_eq!(code("ALTER SYSTEM SET log_level = 'loud'").await, "22023");
+        assert_eq!(code("ALTER SYSTEM RESET ALL").await, "42601");
+        assert!(executor.execute("SELECT 1").await.is_ok());
+    }
+
+    #[test]
+    fn parses_alter_system_set() {
+        let parse = |sql| parse_alter_system_set(sql);
+        assert_eq!(parse("ALTER SYSTEM SET Log_Level = 'it''s'"), Some(("log_level".into(), "it's".into())));
+        assert_eq!(parse("alter system set log_level to debug;"), Some(("log_level".into(), "debug".into())));
+        assert_eq!(parse("ALTER SYSTEM SET log_level"), None);
+        assert_eq!(parse("ALTER TABLE t SET x = 1"), None);
+    }
+
+    #[tokio::test]
+    async fn server_returns_after_shutdown_signal() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let auth = Arc::new(AuthConfig { users: Default::default(), hba: Default::default(), tls: None, cert_auth: None, rate_limit: Default::default() });
+        let auth = Arc::new(SharedAuthConfig::new(auth).unwrap());
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(listener, auth, Default::default(), None, shutdown.clone()));
+
+        // A client that never finishes its startup packet must not hold up shutdown.
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!server.is_finished());
+
+        shutdown.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await.expect("server did not stop");
+        result.unwrap().unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err(), "listener should be closed");
+    }
 } 
\ No newline at end of file